@@ -1,15 +1,29 @@
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, Event},
+    cursor::SetCursorStyle,
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+    },
     execute,
 };
-use futures::StreamExt;
+use futures::{StreamExt, stream::Fuse};
 use ratatui::prelude::*;
-use std::{io::stdout, path::PathBuf};
+use std::{
+    io::stdout,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use crate::utils::{absolute_path, expand_path, plain_text};
 
 use crate::{
     Widget,
-    screens::{AlphaScreen, EditorScreen},
-    state::{EditorEvent, Screen, State},
+    screens::{AlphaScreen, EditorScreen, Screen as _},
+    state::{
+        AutoEvent, EditorEvent, Mode, Screen, StartupTimes, State, remote::Remote, session,
+        startup::FIRST_RENDER,
+    },
 };
 
 #[derive(Debug)]
@@ -20,27 +34,131 @@ pub struct App {
     // Screens
     editor: EditorScreen,
     alpha: AlphaScreen,
+
+    /// Time of the last terminal event, for the `CursorHold` idle timer
+    last_input: Instant,
+    /// Whether `CursorHold` already fired since the last terminal event
+    hold_fired: bool,
+    /// Whether the terminal currently reports mouse events
+    mouse_captured: bool,
 }
 
 impl App {
-    pub fn new(path: PathBuf) -> Self {
+    /// Open a file or folder, or the text piped to the standard input, then the panes of
+    /// a session file. As a pager, the files are read-only.
+    pub fn new(
+        path: PathBuf,
+        stdin: Option<String>,
+        pager: bool,
+        session: Option<PathBuf>,
+        startup: StartupTimes,
+    ) -> Self {
+        // The root folder is either the given folder, or the parent folder of the given file,
+        // or the current folder for remote files
+        let path = expand_path(&path.to_string_lossy());
+        let remote = path
+            .to_str()
+            .is_some_and(|path| Remote::parse(path).is_some());
+        let path = match remote {
+            true => path,
+            false => absolute_path(&path),
+        };
+        let root = match (remote, path.is_dir()) {
+            (true, _) => absolute_path(Path::new(".")),
+            (false, true) => path.clone(),
+            (false, false) => path.parent().map(PathBuf::from).unwrap_or_default(),
+        };
+
+        let mut state = State::new(root, startup);
+        state.pager = pager;
+        state.startup.mark("init the state");
+        let mut editor = EditorScreen::new();
+        editor.source_startup_scripts(&mut state);
+        state.startup.mark("source the startup scripts");
+        if let Some(text) = stdin {
+            editor.open_page("stdin", &plain_text(&text), &mut state);
+            state.screen = Screen::Editor;
+            state.startup.mark("open the standard input");
+        } else if !path.is_dir() {
+            editor.open_path(&path, &mut state);
+            state.screen = Screen::Editor;
+            state.startup.mark("open the file");
+        }
+        if let Some(path) = session {
+            match session::read(&path) {
+                Ok(session) => {
+                    if editor.restore_session(&session, &mut state) {
+                        state.screen = Screen::Editor;
+                    }
+                }
+                Err(err) => state.error(format!("Failed to read {}: {}", path.display(), err)),
+            }
+            state.startup.mark("restore the session");
+        }
+        if pager && state.screen == Screen::Editor {
+            editor.close_tree();
+        } else if path
+            .file_name()
+            .is_some_and(|name| name == "COMMIT_EDITMSG")
+        {
+            editor.edit_commit_message(&mut state);
+        }
+        editor.fire(AutoEvent::VimEnter, None, &mut state);
+        state.startup.mark("VimEnter autocommands");
+
         Self {
-            state: State::new(path),
-            editor: EditorScreen::new(),
+            state,
+            editor,
             alpha: AlphaScreen::new(),
+            last_input: Instant::now(),
+            hold_fired: false,
+            mouse_captured: false,
         }
     }
 
-    /// Run the event loop until exit
-    pub async fn run(&mut self) -> std::io::Result<()> {
-        execute!(stdout(), EnableMouseCapture)?;
+    /// Run the event loop until exit, returning the exit status
+    pub async fn run(&mut self) -> std::io::Result<ExitCode> {
         let mut terminal = ratatui::init();
+        // Pasted text is received at once instead of as typed keys
+        execute!(stdout(), EnableBracketedPaste)?;
         while !self.state.exit {
+            self.sync_mouse_capture()?;
+            self.editor.commit_changes(&mut self.state);
+            if self.editor.replaying() {
+                let mut events = vec![];
+                read_available(&mut self.state.events.term_events, &mut events).await;
+                self.editor.resume_replay(events, &mut self.state);
+                continue;
+            }
             terminal.draw(|frame| self.draw(frame))?;
+            self.state.startup.mark(FIRST_RENDER);
             self.handle_events().await;
         }
+        let state = &mut self.state;
+        self.editor.fire(AutoEvent::VimLeave, None, state);
         ratatui::restore();
-        execute!(stdout(), DisableMouseCapture)
+        self.state.save_shada();
+        // Back to the cursor style of the user's terminal
+        execute!(
+            stdout(),
+            DisableMouseCapture,
+            DisableBracketedPaste,
+            SetCursorStyle::DefaultUserShape
+        )?;
+        Ok(ExitCode::from(self.state.exit_status))
+    }
+
+    /// Enable or disable the mouse capture after the `mouse` option changed
+    fn sync_mouse_capture(&mut self) -> std::io::Result<()> {
+        let capture = !self.state.config.mouse.is_empty() && !self.state.mouse_suspended;
+        if capture != self.mouse_captured {
+            match capture {
+                true => execute!(stdout(), EnableMouseCapture)?,
+                false => execute!(stdout(), DisableMouseCapture)?,
+            }
+            self.mouse_captured = capture;
+        }
+        Ok(())
     }
 
     pub fn draw(&self, frame: &mut Frame) {
@@ -51,32 +169,115 @@ impl App {
             Screen::Alpha => self.alpha.render(area, buffer, &self.state),
             Screen::Editor => self.editor.render(area, buffer, &self.state),
         }
+        self.state.config.colors.degrade_buffer(buffer);
 
         let position = self.state.cursor_pos.get();
         frame.set_cursor_position(position);
     }
 
     pub async fn handle_events(&mut self) {
+        let updatetime = Duration::from_millis(self.state.config.updatetime as u64);
+        let hold = tokio::time::sleep_until((self.last_input + updatetime).into());
+        let hold_pending = !self.hold_fired && self.state.screen == Screen::Editor;
+
         let events = &mut self.state.events;
         tokio::select! {
             Some(Ok(event)) = events.term_events.next() => {
-                self.handle_term_event(event);
+                let mut burst = vec![event];
+                read_available(&mut events.term_events, &mut burst).await;
+                self.last_input = Instant::now();
+                self.hold_fired = false;
+                self.handle_term_events(burst);
             }
             Some(event) = events.editor_events.recv() => {
                 self.handle_editor_event(event).await;
             }
+            _ = hold, if hold_pending => {
+                self.hold_fired = true;
+                self.editor.cursor_hold(&mut self.state);
+            }
         }
     }
 
     async fn handle_editor_event(&mut self, event: EditorEvent) {
         match event {
-            EditorEvent::FolderLoaded { id, files, folders } => {
-                self.state.filesystem.init_folder(id, files, folders);
+            EditorEvent::SearchCounted {
+                file,
+                at,
+                current,
+                total,
+            } => {
+                // Discard outdated counts
+                if let Some(count) = &mut self.state.search_count
+                    && count.file == file
+                    && count.at == at
+                {
+                    count.result = Some((current, total));
+                }
             }
+            event => self.editor.handle_editor_event(event, &mut self.state),
         }
     }
 
+    /// Handle a burst of terminal events received at once.
+    /// IMEs commit composed text as a burst of typed chars, which is inserted at once.
+    fn handle_term_events(&mut self, events: Vec<Event>) {
+        let mut text = String::new();
+        for event in events {
+            match typed_char(&event) {
+                Some(c) if self.state.mode == Mode::Insert => text.push(c),
+                _ => {
+                    self.flush_typed_text(&mut text);
+                    self.handle_term_event(event);
+                }
+            }
+        }
+        self.flush_typed_text(&mut text);
+    }
+
+    /// Handle the chars of a burst, a single one as a regular key
+    fn flush_typed_text(&mut self, text: &mut String) {
+        match text.chars().count() {
+            0 => {}
+            1 => {
+                let key_event = KeyEvent::new(KeyCode::Char(text.remove(0)), KeyModifiers::NONE);
+                self.handle_term_event(Event::Key(key_event));
+            }
+            _ => match self.state.screen {
+                Screen::Editor => self.editor.handle_typed_text(text, &mut self.state),
+                Screen::Alpha => {}
+            },
+        }
+        text.clear();
+    }
+
+    /// Delegate terminal events to the current screen,
+    /// which delegates them based on focus / hitboxes
     fn handle_term_event(&mut self, event: Event) {
-        // TODO: delegate to screens, which will delegate based on focus / hitboxes
+        match self.state.screen {
+            Screen::Alpha => self.alpha.handle(event, &mut self.state),
+            Screen::Editor => self.editor.handle(event, &mut self.state),
+        }
+    }
+}
+
+/// Read the terminal events already available, without waiting for more. They are read
+/// from the event stream, as mixing it with the blocking reads of crossterm loses events.
+async fn read_available(term_events: &mut Fuse<EventStream>, events: &mut Vec<Event>) {
+    while let Ok(Some(Ok(event))) = tokio::time::timeout(Duration::ZERO, term_events.next()).await {
+        events.push(event);
+    }
+}
+
+/// Char typed without a modifier other than shift
+fn typed_char(event: &Event) -> Option<char> {
+    match event {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) if (*modifiers - KeyModifiers::SHIFT).is_empty() => Some(*c),
+        _ => None,
     }
 }
@@ -1,8 +1,12 @@
-use ratatui::layout::Position;
 use ropey::Rope;
 
+use crate::{
+    position::{last_line, line_len, to_char, to_line_col},
+    state::Edit,
+};
+
 /// Cursor with position, relative to the parent element
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Cursor {
     pub x: usize,
     pub y: usize,
@@ -14,21 +18,14 @@ pub struct Cursor {
 }
 
 impl Cursor {
-    pub fn position(&self) -> Position {
-        Position {
-            x: self.x as u16,
-            y: self.y as u16,
-        }
-    }
-
     /// Insert a char at the current cursor position
-    pub fn insert_char(&mut self, rope: &mut Rope, c: char) {
+    pub fn insert_char(&mut self, rope: &mut Edit, c: char) {
         rope.insert_char(self.cursor_char_index(rope), c);
         self.move_right(rope);
     }
 
     /// Delete the char before the cursor
-    pub fn delete_prev_char(&mut self, rope: &mut Rope) {
+    pub fn delete_prev_char(&mut self, rope: &mut Edit) {
         let index = self.cursor_char_index(rope);
         if index > 0 {
             rope.remove(index - 1..index);
@@ -48,13 +45,32 @@ impl Cursor {
     }
 
     /// Delete the char after the cursor
-    pub fn delete_next_char(&mut self, rope: &mut Rope) {
+    pub fn delete_next_char(&mut self, rope: &mut Edit) {
         let index = self.cursor_char_index(rope);
         if index < rope.len_chars() {
             rope.remove(index..index + 1);
         }
     }
 
+    /// Delete the lines from `start` to `end` included, leaving the cursor on the line after them.
+    /// Returns the deleted lines, ending with a newline.
+    pub fn delete_lines(&mut self, rope: &mut Edit, start: usize, end: usize) -> String {
+        let mut from = rope.line_to_char(start);
+        let to = rope.line_to_char((end + 1).min(rope.len_lines()));
+        let mut text = rope.slice(from..to).to_string();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        // Deleting the last line also deletes the newline before it
+        if to == rope.len_chars() && from > 0 && rope.char(to.max(1) - 1) != '\n' {
+            from -= 1;
+        }
+        rope.remove(from..to);
+        self.set_position(0, start, rope);
+        self.clamp(rope);
+        text
+    }
+
     pub fn move_left(&mut self, rope: &Rope) {
         if self.x > 0 {
             self.x -= 1;
@@ -69,7 +85,7 @@ impl Cursor {
         let last = self.last_valid_line_index(rope);
         if self.x < last {
             self.x += 1;
-        } else if self.y < rope.len_lines() - 1 {
+        } else if self.y < last_line(rope) {
             self.y += 1;
             self.x = 0;
         }
@@ -92,14 +108,14 @@ impl Cursor {
     }
 
     pub fn move_down_n(&mut self, rope: &Rope, n: usize) {
-        if self.y < rope.len_lines() - 1 {
-            self.y = self.y.saturating_add(n).min(rope.len_lines() - 1);
+        if self.y < last_line(rope) {
+            self.y = self.y.saturating_add(n).min(last_line(rope));
             self.move_to_preferred_x(rope);
         }
     }
 
     pub fn move_down(&mut self, rope: &Rope) {
-        if self.y < rope.len_lines() - 1 {
+        if self.y < last_line(rope) {
             self.y += 1;
             self.move_to_preferred_x(rope);
         }
@@ -115,11 +131,42 @@ impl Cursor {
         self.preferred_x = 0;
     }
 
+    /// Move to the first non-blank char of the line
+    pub fn move_first_non_blank(&mut self, rope: &Rope) {
+        let blanks = rope
+            .line(self.y)
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .count();
+        self.x = blanks.min(self.last_valid_line_index(rope));
+        self.preferred_x = self.x;
+    }
+
+    /// Move to the start of the next word (`w`), or of the next WORD (`W`) with `big`
+    pub fn move_word_start(&mut self, rope: &Rope, big: bool) {
+        let index = next_word_start(rope, self.cursor_char_index(rope), big);
+        self.move_to_char(rope, index);
+        self.clamp_to_last_line(rope);
+    }
+
+    /// Move to the end of the word (`e`), or of the next one from its last char
+    pub fn move_word_end(&mut self, rope: &Rope, big: bool) {
+        let index = word_end(rope, self.cursor_char_index(rope), big);
+        self.move_to_char(rope, index);
+        self.clamp_to_last_line(rope);
+    }
+
+    /// Move back to the start of the word (`b`), or of the previous one from its first char
+    pub fn move_word_back(&mut self, rope: &Rope, big: bool) {
+        let index = prev_word_start(rope, self.cursor_char_index(rope), big);
+        self.move_to_char(rope, index);
+    }
+
     /// Set the cursor position (from a click)
     pub fn set_position(&mut self, x: usize, y: usize, rope: &Rope) {
-        let lines = rope.len_lines();
-        if y >= lines {
-            self.y = lines - 1;
+        let last = last_line(rope);
+        if y > last {
+            self.y = last;
             self.move_line_end(rope);
         } else {
             self.y = y;
@@ -128,32 +175,136 @@ impl Cursor {
         }
     }
 
+    /// Move the cursor to a char index in the buffer
+    pub fn move_to_char(&mut self, rope: &Rope, index: usize) {
+        (self.y, self.x) = to_line_col(rope, index);
+        self.preferred_x = self.x;
+    }
+
+    /// Keep the cursor within the buffer bounds,
+    /// after the buffer has been modified from elsewhere.
+    /// The empty line after a final line break stays reachable while inserting.
+    pub fn clamp(&mut self, rope: &Rope) {
+        self.y = self.y.min(rope.len_lines() - 1);
+        self.x = self.x.min(self.last_valid_line_index(rope));
+    }
+
+    /// Leave the empty line after a final line break, which only exists while inserting
+    pub fn clamp_to_last_line(&mut self, rope: &Rope) {
+        if self.y > last_line(rope) {
+            self.y = last_line(rope);
+            self.move_line_end(rope);
+        }
+    }
+
     // ********************************************************************* //
     //                                Helpers                                //
     // ********************************************************************* //
 
     /// Get the char index at the cursor position
-    fn cursor_char_index(&self, rope: &Rope) -> usize {
-        rope.line_to_char(self.y) + self.x
+    pub fn cursor_char_index(&self, rope: &Rope) -> usize {
+        to_char(rope, self.y, self.x)
     }
 
     /// Returns the last "valid" cursor position in the line.
     /// This is the position right before potential \n or \r\n chars.
     fn last_valid_line_index(&self, rope: &Rope) -> usize {
-        let line = rope.line(self.y);
-        let mut length = line.len_chars();
+        line_len(rope.line(self.y))
+    }
 
-        if length > 0 && line.char(length - 1) == '\n' {
-            length -= 1;
-            if length > 0 && line.char(length - 1) == '\r' {
-                length -= 1;
-            }
+    fn move_to_preferred_x(&mut self, rope: &Rope) {
+        self.x = self.preferred_x.min(self.last_valid_line_index(rope));
+    }
+}
+
+/// Kind of a char for word motions: words are runs of letters, digits and `_`, or of
+/// other non-blank chars, and WORDs (`big`) runs of non-blank chars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Blank,
+    Punctuation,
+    Word,
+}
+
+pub fn char_class(c: char, big: bool) -> CharClass {
+    match c {
+        c if c.is_whitespace() => CharClass::Blank,
+        _ if big => CharClass::Word,
+        c if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        _ => CharClass::Punctuation,
+    }
+}
+
+/// Whether a char index is on an empty line, which word motions stop on
+fn is_empty_line(rope: &Rope, index: usize) -> bool {
+    rope.get_char(index) == Some('\n') && (index == 0 || rope.char(index - 1) == '\n')
+}
+
+/// Char index of the start of the next word (`w`), or the end of the buffer
+pub fn next_word_start(rope: &Rope, index: usize, big: bool) -> usize {
+    let len = rope.len_chars();
+    let Some(c) = rope.get_char(index) else {
+        return len;
+    };
+    let class = char_class(c, big);
+    let mut chars = rope.chars_at(index).peekable();
+    let mut i = index;
+    if class != CharClass::Blank {
+        while chars.next_if(|c| char_class(*c, big) == class).is_some() {
+            i += 1;
+        }
+    }
+    while let Some(c) = chars.next_if(|c| char_class(*c, big) == CharClass::Blank) {
+        if i > index && c == '\n' && is_empty_line(rope, i) {
+            break;
         }
+        i += 1;
+    }
+    i
+}
 
-        length
+/// Char index of the end of the word after the cursor char (`e`), or the last char
+pub fn word_end(rope: &Rope, index: usize, big: bool) -> usize {
+    let len = rope.len_chars();
+    let mut chars = rope.chars_at((index + 1).min(len)).peekable();
+    let mut i = index + 1;
+    while chars
+        .next_if(|c| char_class(*c, big) == CharClass::Blank)
+        .is_some()
+    {
+        i += 1;
+    }
+    let Some(class) = chars.next().map(|c| char_class(c, big)) else {
+        return len.saturating_sub(1);
+    };
+    while chars.next_if(|c| char_class(*c, big) == class).is_some() {
+        i += 1;
     }
+    i
+}
 
-    fn move_to_preferred_x(&mut self, rope: &Rope) {
-        self.x = self.preferred_x.min(self.last_valid_line_index(rope));
+/// Char index of the start of the word before the cursor (`b`), or of the buffer
+pub fn prev_word_start(rope: &Rope, index: usize, big: bool) -> usize {
+    let mut chars = rope.chars_at(index.min(rope.len_chars()));
+    let mut i = index;
+    let class = loop {
+        let Some(c) = chars.prev() else {
+            return 0;
+        };
+        i -= 1;
+        let class = char_class(c, big);
+        if class != CharClass::Blank || is_empty_line(rope, i) {
+            break class;
+        }
+    };
+    if class == CharClass::Blank {
+        return i;
+    }
+    while let Some(c) = chars.prev() {
+        if char_class(c, big) != class {
+            break;
+        }
+        i -= 1;
     }
+    i
 }
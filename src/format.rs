@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use unicode_width::UnicodeWidthStr;
+
+/// Comment leaders recognized at the start of lines, longest first
+const COMMENT_LEADERS: &[&str] = &["//!", "///", "//", "--", "#", ";", ">"];
+
+/// Extensions of the files holding prose rather than code
+const PROSE_EXTENSIONS: &[&str] = &["txt", "md", "markdown", "rst", "adoc", "org"];
+
+/// Delimiters aligned at the end of the field before them, like in `a,  b`, rather than
+/// with a space on each side, like in `a = b`
+const TRAILING_DELIMITERS: &[&str] = &[",", ";", ":"];
+
+/// Width used when `textwidth` is zero
+pub const DEFAULT_TEXTWIDTH: usize = 79;
+
+/// Indentation and comment leader that start a line, kept when wrapping it.
+/// The leader of the filetype comments is tried before the builtin ones.
+pub fn line_prefix(line: &str, comment: Option<&str>) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let Some(leader) = comment
+        .iter()
+        .chain(COMMENT_LEADERS)
+        .find(|leader| rest.starts_with(**leader))
+    else {
+        return line[..indent].to_string();
+    };
+    let after = &rest[leader.len()..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+    line[..indent + leader.len() + spaces.min(1)].to_string()
+}
+
+/// Re-wrap lines to at most `width` chars, keeping their indentation and comment leader.
+/// Blank lines and changes of prefix separate the paragraphs.
+pub fn reflow(lines: &[&str], width: usize, comment: Option<&str>) -> Vec<String> {
+    let mut result = vec![];
+    let mut words: Vec<&str> = vec![];
+    let mut prefix = String::new();
+
+    for line in lines {
+        let line_prefix = line_prefix(line, comment);
+        let text = &line[line_prefix.len()..];
+        if text.trim().is_empty() || line_prefix.trim_end() != prefix.trim_end() {
+            wrap_words(&prefix, &words, width, &mut result);
+            words.clear();
+            prefix = line_prefix;
+        }
+        match text.trim().is_empty() {
+            true => result.push(line.trim_end().to_string()),
+            false => words.extend(text.split_whitespace()),
+        }
+    }
+    wrap_words(&prefix, &words, width, &mut result);
+    result
+}
+
+/// Pad the fields of lines separated by a delimiter so that the delimiters line up in the
+/// same columns. The lines without the delimiter are left as is.
+pub fn align(lines: &[&str], delimiter: &str) -> Vec<String> {
+    let trailing = TRAILING_DELIMITERS.contains(&delimiter);
+    // Fields of the lines, the first one keeping the indentation, with the trailing
+    // delimiters at their end
+    let rows = lines
+        .iter()
+        .map(|line| {
+            if delimiter.is_empty() || !line.contains(delimiter) {
+                return None;
+            }
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let fields = line.split(delimiter).collect::<Vec<_>>();
+            let last = fields.len() - 1;
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| {
+                    let indent = if i == 0 { indent } else { "" };
+                    match trailing && i < last {
+                        true => format!("{}{}{}", indent, field.trim(), delimiter),
+                        false => format!("{}{}", indent, field.trim()),
+                    }
+                })
+                .collect::<Vec<_>>();
+            Some(fields)
+        })
+        .collect::<Vec<_>>();
+    let mut widths: Vec<usize> = vec![];
+    for fields in rows.iter().flatten() {
+        for (i, field) in fields.iter().enumerate().take(fields.len() - 1) {
+            match widths.get_mut(i) {
+                Some(width) => *width = (*width).max(field.width()),
+                None => widths.push(field.width()),
+            }
+        }
+    }
+
+    lines
+        .iter()
+        .zip(rows)
+        .map(|(line, fields)| {
+            let Some(fields) = fields else {
+                return line.to_string();
+            };
+            let mut text = String::new();
+            for (i, field) in fields.iter().enumerate() {
+                if i > 0 && !trailing {
+                    text.push_str(delimiter);
+                }
+                if i > 0 && !text.trim().is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(field);
+                if i + 1 < fields.len() {
+                    text.push_str(&" ".repeat(widths[i] - field.width()));
+                    // No space is added before the `|` starting a table row
+                    if !trailing && (i > 0 || !field.trim().is_empty()) {
+                        text.push(' ');
+                    }
+                }
+            }
+            text.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Greedily fill lines with words. Words longer than the width get their own line.
+fn wrap_words(prefix: &str, words: &[&str], width: usize, result: &mut Vec<String>) {
+    let mut line = prefix.to_string();
+    let mut empty = true;
+    for word in words {
+        let len = line.chars().count() + 1 + word.chars().count();
+        if !empty && len > width {
+            result.push(line);
+            line = prefix.to_string();
+            empty = true;
+        }
+        if !empty {
+            line.push(' ');
+        }
+        line.push_str(word);
+        empty = false;
+    }
+    if !empty {
+        result.push(line);
+    }
+}
+
+/// Whether a file holds prose, where all the text is auto-wrapped rather than only comments
+pub fn is_prose(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => PROSE_EXTENSIONS.contains(&ext),
+        None => path.file_name().is_none_or(|name| name == "COMMIT_EDITMSG"),
+    }
+}
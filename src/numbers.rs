@@ -0,0 +1,117 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Item of an ordered list, like `1.` or `2)`, after its indentation
+static ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\s*)(\d+)([.)])(\s|$)").unwrap());
+
+/// Number found in a line, as a char range and its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Number {
+    pub start: usize,
+    pub end: usize,
+    pub value: i64,
+}
+
+/// First decimal number of a line ending at or after a char column, a `-` right before
+/// it making it negative
+pub fn find_number(line: &str, column: usize) -> Option<Number> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut start = (column..chars.len()).find(|&i| chars[i].is_ascii_digit())?;
+    // The cursor may be in the middle of the number
+    while start > 0 && chars[start - 1].is_ascii_digit() {
+        start -= 1;
+    }
+    let end = (start..chars.len())
+        .find(|&i| !chars[i].is_ascii_digit())
+        .unwrap_or(chars.len());
+    if start > 0 && chars[start - 1] == '-' {
+        start -= 1;
+    }
+    let value = chars[start..end].iter().collect::<String>().parse().ok()?;
+    Some(Number { start, end, value })
+}
+
+/// Add to the first number at or after a char column of each line, like `CTRL-A`, or
+/// `delta` more on each line with a number when `progressive`, like `g CTRL-A`
+pub fn add_to_numbers(
+    lines: &[&str],
+    columns: &[usize],
+    delta: i64,
+    progressive: bool,
+) -> Vec<String> {
+    let mut added = 0;
+    lines
+        .iter()
+        .zip(columns)
+        .map(|(line, &column)| {
+            let Some(number) = find_number(line, column) else {
+                return line.to_string();
+            };
+            added = match progressive {
+                true => added + delta,
+                false => delta,
+            };
+            let value = number.value.saturating_add(added).to_string();
+            let mut chars = line.chars().collect::<Vec<_>>();
+            chars.splice(number.start..number.end, value.chars());
+            chars.into_iter().collect()
+        })
+        .collect()
+}
+
+/// Insert an increasing sequence of numbers from 1 at a char column of each line, the
+/// lines without a column being skipped
+pub fn insert_sequence(lines: &[&str], columns: &[Option<usize>]) -> Vec<String> {
+    let mut next = 1;
+    lines
+        .iter()
+        .zip(columns)
+        .map(|(line, column)| {
+            let Some(column) = *column else {
+                return line.to_string();
+            };
+            let mut chars = line.chars().collect::<Vec<_>>();
+            chars.splice(column..column, next.to_string().chars());
+            next += 1;
+            chars.into_iter().collect()
+        })
+        .collect()
+}
+
+/// Renumber the items of the ordered lists of lines, like `1.` or `2)`, consecutively at
+/// each indentation from the first item, or from a start number for the outer list.
+/// Lines that are not items end the lists indented as much as them or more, and less
+/// indented items end the deeper lists.
+pub fn renumber(lines: &[&str], start: Option<i64>) -> Vec<String> {
+    // Next number of the open lists, by indentation
+    let mut lists: Vec<(usize, i64)> = vec![];
+    lines
+        .iter()
+        .map(|line| {
+            let Some(captures) = ITEM.captures(line) else {
+                let indent = line.len() - line.trim_start().len();
+                if !line.trim().is_empty() {
+                    lists.retain(|(open, _)| *open < indent);
+                }
+                return line.to_string();
+            };
+            let indent = captures[1].len();
+            lists.retain(|(open, _)| *open <= indent);
+            if lists.last().is_none_or(|(open, _)| *open < indent) {
+                let first = match (lists.is_empty(), start) {
+                    (true, Some(start)) => start,
+                    _ => captures[2].parse().unwrap_or(1),
+                };
+                lists.push((indent, first));
+            }
+            let Some((_, next)) = lists.last_mut() else {
+                return line.to_string();
+            };
+            let value = *next;
+            *next += 1;
+            let digits = captures.get(2).map_or(0..0, |m| m.range());
+            format!("{}{}{}", &line[..digits.start], value, &line[digits.end..])
+        })
+        .collect()
+}
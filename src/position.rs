@@ -0,0 +1,167 @@
+use ropey::{Rope, RopeSlice};
+use unicode_width::UnicodeWidthChar;
+
+/// Number of chars of a line, without its line break
+pub fn line_len(line: RopeSlice) -> usize {
+    let mut len = line.len_chars();
+    if len > 0 && line.char(len - 1) == '\n' {
+        len -= 1;
+        if len > 0 && line.char(len - 1) == '\r' {
+            len -= 1;
+        }
+    }
+    len
+}
+
+/// Number of lines of a buffer, the empty line after a final line break not counting.
+/// An empty buffer has one empty line.
+pub fn line_count(rope: &Rope) -> usize {
+    let lines = rope.len_lines();
+    match rope.len_chars() > 0 && rope.line(lines - 1).len_chars() == 0 {
+        true => lines - 1,
+        false => lines,
+    }
+}
+
+/// Index of the last line of a buffer, see `line_count`
+pub fn last_line(rope: &Rope) -> usize {
+    line_count(rope) - 1
+}
+
+/// Chars from which a line is too long to be wrapped and highlighted in every frame
+pub const LONG_LINE: usize = 10_000;
+
+/// Whether a buffer has a line of at least `LONG_LINE` chars, like minified code
+pub fn has_long_lines(rope: &Rope) -> bool {
+    rope.lines().any(|line| line.len_chars() >= LONG_LINE)
+}
+
+/// Line and column of a char index, clamped to the end of the buffer
+pub fn to_line_col(rope: &Rope, index: usize) -> (usize, usize) {
+    let index = index.min(rope.len_chars());
+    let line = rope.char_to_line(index);
+    (line, index - rope.line_to_char(line))
+}
+
+/// Char index of a line and column, clamped to the buffer and to the line break
+pub fn to_char(rope: &Rope, line: usize, col: usize) -> usize {
+    let line = line.min(rope.len_lines().saturating_sub(1));
+    rope.line_to_char(line) + col.min(line_len(rope.line(line)))
+}
+
+/// Display cells of a char drawn at a cell, tabs reaching the next tab stop
+pub fn char_width(c: char, cell: usize, tabstop: usize) -> usize {
+    match c {
+        '\t' => tabstop - cell % tabstop,
+        c => c.width().unwrap_or(0),
+    }
+}
+
+/// Display cells before a column of a line, with wide chars and expanded tabs
+pub fn col_to_cell(line: RopeSlice, col: usize, tabstop: usize) -> usize {
+    line.chars()
+        .take(col)
+        .fold(0, |cell, c| cell + char_width(c, cell, tabstop))
+}
+
+/// Column of the char drawn at a display cell of a line, or the end of the line past
+/// its last cell
+pub fn cell_to_col(line: RopeSlice, cell: usize, tabstop: usize) -> usize {
+    let mut current = 0;
+    for (col, c) in line.chars().take(line_len(line)).enumerate() {
+        current += char_width(c, current, tabstop);
+        if current > cell {
+            return col;
+        }
+    }
+    line_len(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pieces of the tested texts: a narrow char, a tab, a wide char, an astral wide
+    /// char and both line breaks
+    const PIECES: &[&str] = &["a", "\t", "中", "😀", "\n", "\r\n"];
+    /// Tab widths of the tested display cells
+    const TABSTOPS: [usize; 3] = [1, 4, 8];
+
+    /// Every text of up to 5 pieces
+    fn texts() -> impl Iterator<Item = Rope> {
+        (0..=5).flat_map(|len| {
+            (0..PIECES.len().pow(len)).map(move |mut n| {
+                let mut text = String::new();
+                for _ in 0..len {
+                    text.push_str(PIECES[n % PIECES.len()]);
+                    n /= PIECES.len();
+                }
+                Rope::from_str(&text)
+            })
+        })
+    }
+
+    #[test]
+    fn char_index_round_trip() {
+        for rope in texts() {
+            for index in 0..=rope.len_chars() {
+                let (line, col) = to_line_col(&rope, index);
+                // The line feed of a CRLF is clamped to its carriage return
+                let crlf =
+                    index > 0 && rope.get_char(index) == Some('\n') && rope.char(index - 1) == '\r';
+                let expected = match crlf {
+                    true => index - 1,
+                    false => index,
+                };
+                assert_eq!(
+                    to_char(&rope, line, col),
+                    expected,
+                    "{:?} at {}",
+                    rope,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn column_round_trip() {
+        for rope in texts() {
+            for (line, tabstop) in rope.lines().flat_map(|line| TABSTOPS.map(|t| (line, t))) {
+                for col in 0..=line_len(line) {
+                    let cell = col_to_cell(line, col, tabstop);
+                    assert_eq!(
+                        cell_to_col(line, cell, tabstop),
+                        col,
+                        "{:?} at {}",
+                        line,
+                        col
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cells_drawn_by_their_column() {
+        for rope in texts() {
+            for (line, tabstop) in rope.lines().flat_map(|line| TABSTOPS.map(|t| (line, t))) {
+                let len = line_len(line);
+                let end = col_to_cell(line, len, tabstop);
+                for cell in 0..end + 2 {
+                    let col = cell_to_col(line, cell, tabstop);
+                    match cell < end {
+                        true => assert!(
+                            (col_to_cell(line, col, tabstop)..col_to_cell(line, col + 1, tabstop))
+                                .contains(&cell),
+                            "{:?} at {}",
+                            line,
+                            cell
+                        ),
+                        false => assert_eq!(col, len, "{:?} at {}", line, cell),
+                    }
+                }
+            }
+        }
+    }
+}
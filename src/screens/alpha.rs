@@ -1,7 +1,15 @@
-use crossterm::event::Event;
-use ratatui::prelude::*;
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use ratatui::{
+    layout::Flex,
+    prelude::*,
+    widgets::{Paragraph, Widget as RatatuiWidget},
+};
 
-use crate::{State, Widget, screens::Screen};
+use crate::{
+    State, Widget,
+    screens::Screen,
+    state::{self, EditorEvent},
+};
 
 /// alpha.nvim home page widget
 #[derive(Debug)]
@@ -15,13 +23,45 @@ impl AlphaScreen {
 
 impl Screen for AlphaScreen {
     fn handle(&mut self, event: Event, state: &mut State) {
-        // TODO
+        let Event::Key(key_event) = event else {
+            return;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+        match key_event.code {
+            KeyCode::Char('e') | KeyCode::Enter => state.screen = state::Screen::Editor,
+            KeyCode::Char('n') => {
+                state.screen = state::Screen::Editor;
+                let command = EditorEvent::Command("enew".to_string());
+                if let Err(err) = state.events.editor_sender.try_send(command) {
+                    log::error!("Failed to send the new file command: {}", err);
+                }
+            }
+            KeyCode::Char('q') => state.exit = true,
+            _ => {}
+        }
     }
 }
 
 impl Widget for AlphaScreen {
-    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
-        // TODO
+    fn render(&self, area: Rect, buf: &mut Buffer, _: &State) {
+        let [middle] = Layout::vertical([Constraint::Length(5)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        Paragraph::new(vec![
+            Line::from("TUI Editor").bold().magenta(),
+            Line::default(),
+            Line::from(vec![
+                Span::from("e").cyan(),
+                Span::from("  Open the editor"),
+            ]),
+            Line::from(vec![Span::from("n").cyan(), Span::from("  New file")]),
+            Line::from(vec![Span::from("q").cyan(), Span::from("  Quit")]),
+        ])
+        .alignment(HorizontalAlignment::Center)
+        .render(middle, buf);
     }
 
     fn contains(&self, _: Position) -> bool {
@@ -1,12 +1,48 @@
-use crossterm::event::Event;
+use std::{collections::VecDeque, path::Path};
+
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use ratatui::prelude::*;
+use ropey::Rope;
 
 use crate::{
     State, Widget,
+    cursor::Cursor,
+    position::{LONG_LINE, has_long_lines, line_count, to_line_col},
     screens::Screen,
-    widgets::{Border, FileTree, Lualine, Panes},
+    state::{
+        AutoEvent, CmdlineLayout, Edit, EditorEvent, File, FileFormat, FileId, FolderId, Jump,
+        KeyPreset, Mode, OptionSource, Pattern, Prompt, Search, Substitute, UndoTree,
+        clipboard::{self, Selection},
+        filetype, git, parse_keys,
+        remote::{self, Remote},
+        session::Session,
+        startup::ROOT_SCAN,
+    },
+    utils::absolute_path,
+    widgets::{
+        Action, Border, Cmdline, CmdlineWindow, CommandPalette, ContextMenu, FileTree, Lualine,
+        MenuItem, PaletteAction, Pane, Panes, Preview, ProjectReplace, READONLY_ERROR, cmdwin,
+        filetree::Entry,
+    },
 };
 
+mod commands;
+mod macros;
+mod repeat;
+mod script;
+mod substitute;
+
+/// Widget that receives the key events
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Tree,
+    #[default]
+    Panes,
+    CmdlineWindow,
+}
+
 /// The file editor screen, with a filetree
 #[derive(Debug)]
 pub struct EditorScreen {
@@ -16,12 +52,50 @@ pub struct EditorScreen {
 
     /// Border between the filetree and the panes
     border: Border,
+    /// Preview of the selected filetree file
+    preview: Preview,
 
     /// File editor panes
     panes: Panes,
 
     /// Lualine at the bottom
     lualine: Lualine,
+
+    /// Popups
+    cmdline: Cmdline,
+    replace: ProjectReplace,
+    palette: CommandPalette,
+    /// Right click menu
+    menu: Option<ContextMenu>,
+
+    /// History editing window, below the panes
+    cmdwin: Option<CmdlineWindow>,
+    /// Last `:s` command, repeated by `:s` without pattern
+    last_substitute: Option<Substitute>,
+    /// Substitution waiting for the confirmation of a replacement, with the `c` flag
+    substitution: Option<substitute::Substitution>,
+    /// Page of the staged changes next to a commit message, closed along with it
+    staged_diff: Option<FileId>,
+    /// View of the focused pane when a search started, previewed with `incsearch`
+    search_view: Option<(Cursor, usize)>,
+
+    focus: Focus,
+    /// Whether autocommands are executing, as they do not trigger other autocommands
+    firing: bool,
+
+    /// Keys typed since the recording of a macro started
+    recorded: Vec<KeyEvent>,
+    /// Keys of the replayed macros left to handle
+    replay: VecDeque<KeyEvent>,
+    /// Terminal events received during a replay, handled after it
+    typed: Vec<Event>,
+    /// Register of the last replayed macro, for `@@`
+    last_replayed: Option<char>,
+    /// Keys of the change being typed, and whether they edited the buffer yet
+    change: Vec<KeyEvent>,
+    change_edits: bool,
+    /// Keys of the last change, repeated by `.`
+    last_change: Vec<KeyEvent>,
 }
 
 impl EditorScreen {
@@ -31,24 +105,956 @@ impl EditorScreen {
             tree_open: true,
             tree_width: 30,
             border: Border::vertical(),
+            preview: Preview::default(),
             panes: Panes::new(),
             lualine: Lualine::new(),
+            cmdline: Cmdline::default(),
+            replace: ProjectReplace::new(),
+            palette: CommandPalette::default(),
+            menu: None,
+            cmdwin: None,
+            last_substitute: None,
+            substitution: None,
+            staged_diff: None,
+            search_view: None,
+            focus: Focus::Tree,
+            firing: false,
+            recorded: vec![],
+            replay: VecDeque::new(),
+            typed: vec![],
+            last_replayed: None,
+            change: vec![],
+            change_edits: false,
+            last_change: vec![],
+        }
+    }
+
+    /// Open a file from its path in the focused pane
+    pub fn open_path(&mut self, path: &Path, state: &mut State) -> Option<FileId> {
+        if let Some(remote) = path.to_str().and_then(Remote::parse) {
+            return Some(self.open_remote(remote, state));
+        }
+        let path = absolute_path(path);
+        match state.filesystem.open_file(&path) {
+            Ok(id) => {
+                self.open_file(id, state);
+                Some(id)
+            }
+            Err(err) => {
+                state.error(format!("Failed to open {}: {}", path.display(), err));
+                None
+            }
+        }
+    }
+
+    /// Open a remote file in the focused pane, downloading it in the background
+    fn open_remote(&mut self, remote: Remote, state: &mut State) -> FileId {
+        let uri = remote.uri();
+        let (id, fetch) = state.filesystem.open_remote(remote.clone());
+        self.open_file(id, state);
+        if fetch {
+            remote::fetch(remote, state.events.editor_sender.clone(), &state.jobs, id);
+            state.info(format!("Fetching {}...", uri));
+        }
+        id
+    }
+
+    /// Open a file with a loaded buffer in the focused pane
+    fn open_file(&mut self, id: FileId, state: &mut State) {
+        if state.pager {
+            state.filesystem.files[id].readonly = true;
+        }
+        // Opening another file is a jump, gone back from with CTRL-O
+        if let Some(jump) = self
+            .panes
+            .focused()
+            .filter(|pane| pane.file != id)
+            .and_then(|pane| pane.jump(state))
+        {
+            state.jumps.push(jump);
+        }
+        self.panes.open(id);
+        self.focus = Focus::Panes;
+        // The basic preset edits without modes
+        if state.config.keypreset == KeyPreset::Basic
+            && state.mode == Mode::Normal
+            && !state.filesystem.files[id].readonly
+        {
+            state.mode = Mode::Insert;
+        }
+        self.enter_buffer(id, state);
+    }
+
+    /// Go to a position of the jump list, opening its buffer in the focused pane
+    fn jump(&mut self, jump: Jump, state: &mut State) {
+        let Some(rope) = state
+            .filesystem
+            .files
+            .get(jump.file)
+            .and_then(|file| file.buffer.as_ref())
+        else {
+            return;
+        };
+        let (line, column) = to_line_col(rope, jump.index.min(rope.len_chars()));
+        if self
+            .panes
+            .focused()
+            .is_some_and(|pane| pane.file != jump.file)
+        {
+            self.panes.open(jump.file);
+            self.enter_buffer(jump.file, state);
+        }
+        self.move_cursor(line, column, state);
+    }
+
+    /// Open the panes of a session from the focused one to the right, with their cursor
+    /// and scroll, focusing the one focused when it was written. The files failing to
+    /// open are skipped. Returns whether a pane was opened.
+    pub fn restore_session(&mut self, session: &Session, state: &mut State) -> bool {
+        let mut opened = 0;
+        let mut focused = None;
+        for (i, saved) in session.panes.iter().enumerate() {
+            if opened > 0 {
+                self.panes.split(None);
+            }
+            let Some(id) = self.open_path(&saved.path, state) else {
+                if opened > 0 {
+                    self.panes.close_focused();
+                }
+                continue;
+            };
+            opened += 1;
+            if let (Some(pane), Some(rope)) = (
+                self.panes.focused_mut(),
+                state.filesystem.files[id].buffer.as_ref(),
+            ) {
+                let mut cursor = Cursor::default();
+                cursor.set_position(saved.col, saved.line, rope);
+                pane.restore_view((cursor, saved.scroll_y));
+            }
+            if i == session.focused {
+                focused = Some(self.panes.focused_index());
+            }
+        }
+        if let Some(index) = focused {
+            self.panes.focus(index);
+        }
+        opened > 0
+    }
+
+    /// Open a read-only buffer displaying generated text in the focused pane
+    pub fn open_page(&mut self, name: &str, text: &str, state: &mut State) {
+        let id = state.filesystem.files.insert(File::page(name, text));
+        self.panes.open(id);
+        self.focus = Focus::Panes;
+    }
+
+    /// Hide the filetree, leaving the panes the whole width
+    pub fn close_tree(&mut self) {
+        self.tree_open = false;
+        self.focus = Focus::Panes;
+    }
+
+    /// Edit a commit message, when run as the editor of git: the tree is hidden, and the
+    /// staged changes shown in a pane on the right
+    pub fn edit_commit_message(&mut self, state: &mut State) {
+        self.close_tree();
+        let Some(diff) = git::staged_diff() else {
+            return;
+        };
+        let mut page = File::page("git diff --cached", &diff);
+        page.filetype = Some("diff");
+        let id = state.filesystem.files.insert(page);
+        self.panes.split_behind(id);
+        self.staged_diff = Some(id);
+    }
+
+    /// Apply the filetype options of a buffer on its first display, adapted to the indentation
+    /// of its contents, then fire `BufEnter`
+    fn enter_buffer(&mut self, id: FileId, state: &mut State) {
+        let file = &mut state.filesystem.files[id];
+        let path = file.path.clone();
+        if file.options.is_none() {
+            let mut options = file
+                .filetype
+                .and_then(|filetype| filetype::defaults(filetype, &state.config.buffer))
+                .unwrap_or_else(|| state.config.buffer.clone());
+            if let Some(rope) = &file.buffer {
+                for name in options.detect_indent(rope) {
+                    file.option_sources.insert(name, OptionSource::Detected);
+                }
+            }
+            file.options = Some(options);
+            self.check_long_lines(id, state);
+            if let Some(filetype) = state.filesystem.files[id].filetype {
+                self.fire(AutoEvent::FileType, Some(Path::new(filetype)), state);
+            }
+        }
+        self.fire(AutoEvent::BufEnter, Some(&path), state);
+    }
+
+    /// Disable the wrapping and highlighting of the buffers with very long lines, which
+    /// would slow down every frame
+    fn check_long_lines(&mut self, id: FileId, state: &mut State) {
+        let file = &mut state.filesystem.files[id];
+        file.long_lines = file.buffer.as_ref().is_some_and(has_long_lines);
+        if file.long_lines {
+            let message = format!(
+                "W: \"{}\" has lines of {} chars or more, wrap and highlighting are disabled",
+                file.name, LONG_LINE
+            );
+            state.error(message);
+        }
+    }
+
+    /// Search a pattern in the focused pane.
+    /// An empty pattern repeats the last search.
+    fn search(&mut self, pattern: &str, backward: bool, state: &mut State) {
+        if !pattern.is_empty() {
+            match Pattern::new(pattern, &state.config) {
+                Ok(pattern) => state.search = Some(Search { pattern, backward }),
+                Err(err) => {
+                    state.error(format!("E383: Invalid search pattern: {}", err));
+                    return;
+                }
+            }
+        } else if let Some(search) = &mut state.search {
+            search.backward = backward;
+        } else {
+            state.error("E35: No previous regular expression");
+            return;
+        }
+
+        if let Some(pane) = self.panes.focused_mut()
+            && !pane.search(state)
+            && let Some(search) = &state.search
+        {
+            let text = format!("E486: Pattern not found: {}", search.pattern.source);
+            state.error(text);
+        }
+    }
+
+    /// Fire the `CursorHold` autocommands after some time without input
+    pub fn cursor_hold(&mut self, state: &mut State) {
+        let event = match state.mode {
+            Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                AutoEvent::CursorHold
+            }
+            Mode::Insert => AutoEvent::CursorHoldI,
+            Mode::Command => return,
+        };
+        let path = match self.focus {
+            Focus::Panes => self
+                .panes
+                .focused()
+                .map(|pane| state.filesystem.files[pane.file].path.clone()),
+            _ => None,
+        };
+        self.fire(event, path.as_deref(), state);
+    }
+
+    /// Handle a request from a child widget
+    fn handle_action(&mut self, action: Action, state: &mut State) {
+        match action {
+            Action::CmdlineWindow(prompt) => {
+                self.close_cmdwin(state);
+                self.cmdwin = Some(CmdlineWindow::new(prompt, state));
+                self.focus = Focus::CmdlineWindow;
+            }
+            Action::Cmdline(text) => {
+                state.open_cmdline(Prompt::Command);
+                self.cmdline.set_text(&text);
+            }
+            Action::Help(topic) => self.help(&topic, state),
+            Action::Command(command) => self.execute_command(&command, state),
+            Action::Record(register) => self.record(register, state),
+            Action::StopRecording => self.stop_recording(state),
+            Action::Replay(register) => self.replay(register, state),
+            Action::Jump(jump) => self.jump(jump, state),
+        }
+    }
+
+    /// Run the chosen context menu entry on the focused widget
+    fn menu_action(&mut self, item: MenuItem, state: &mut State) {
+        match item {
+            MenuItem::Copy => {
+                let Some(text) = self.panes.focused().and_then(|pane| pane.copy_text(state)) else {
+                    return;
+                };
+                state.registers.set('"', text.clone());
+                match clipboard::copy(&text) {
+                    Ok(()) => state.info(format!("{} chars copied", text.chars().count())),
+                    Err(err) => state.error(err),
+                }
+                if state.mode.is_visual() {
+                    state.mode = Mode::Normal;
+                }
+            }
+            MenuItem::Paste => {
+                if let Some(pane) = self.panes.focused()
+                    && let Some(rope) = &state.filesystem.files[pane.file].buffer
+                {
+                    let at = pane.cursor.cursor_char_index(rope);
+                    let sender = state.events.editor_sender.clone();
+                    clipboard::paste(Selection::Clipboard, sender, &state.jobs, pane.file, at);
+                }
+            }
+            MenuItem::GotoDefinition => {
+                if let Some(pane) = self.panes.focused_mut()
+                    && let Err(err) = pane.goto_definition(state)
+                {
+                    state.error(err);
+                }
+            }
+            MenuItem::Open => {
+                if let Some(id) = self.filetree.activate_selected(state) {
+                    match state.filesystem.open(id) {
+                        Ok(()) => self.open_file(id, state),
+                        Err(err) => state.error(format!("Failed to open file: {}", err)),
+                    }
+                }
+            }
+            MenuItem::Rename => {
+                let path = match self.filetree.selected_entry(&state.filesystem) {
+                    Some(Entry::File(id)) => &state.filesystem.files[id].path,
+                    Some(Entry::Folder(id)) => &state.filesystem.folders[id].path,
+                    None => return,
+                };
+                let path = state.filesystem.relative_path(path).display().to_string();
+                self.handle_action(Action::Cmdline(format!("Rename {}", path)), state);
+            }
+            // Submitting the command confirms the deletion
+            MenuItem::Delete => self.handle_action(Action::Cmdline("Delete".to_string()), state),
+        }
+    }
+
+    /// Prompt to move a file dropped onto a filetree folder, with the `:Rename` command
+    /// of keyboard moves so that submitting it confirms the move
+    fn drop_file(&mut self, file: FileId, folder: FolderId, state: &mut State) {
+        let filesystem = &state.filesystem;
+        let path = &filesystem.files[file].path;
+        let folder = &filesystem.folders[folder].path;
+        if path.parent() == Some(folder.as_path()) {
+            return;
+        }
+        let target = folder.join(path.file_name().unwrap_or_default());
+        let target = filesystem.relative_path(&target).display().to_string();
+        self.filetree.select(Entry::File(file), filesystem);
+        self.focus = Focus::Tree;
+        self.handle_action(Action::Cmdline(format!("Rename {}", target)), state);
+    }
+
+    /// Close the command-line window, if open
+    fn close_cmdwin(&mut self, state: &mut State) {
+        if let Some(cmdwin) = self.cmdwin.take() {
+            cmdwin.close(state);
+        }
+        if self.focus == Focus::CmdlineWindow {
+            self.focus = match self.panes.is_empty() {
+                true => Focus::Tree,
+                false => Focus::Panes,
+            };
+        }
+    }
+
+    fn handle_cmdwin_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+        let Some(cmdwin) = &mut self.cmdwin else {
+            return;
+        };
+        if state.mode == Mode::Normal {
+            match key_event.code {
+                // Execute the line under the cursor
+                KeyCode::Enter => {
+                    let prompt = cmdwin.prompt;
+                    let text = cmdwin.current_line(state);
+                    self.close_cmdwin(state);
+                    state.history.push(prompt, &text);
+                    match prompt {
+                        Prompt::Command => self.execute_command(&text, state),
+                        Prompt::Search { backward } => self.search(&text, backward, state),
+                        Prompt::Input => self.submit_input(&text, state),
+                    }
+                    return;
+                }
+                KeyCode::Esc => {
+                    self.close_cmdwin(state);
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if let Some(action) = cmdwin.handle_key_event(key_event, state) {
+            self.handle_action(action, state);
+        }
+    }
+
+    /// Notify the listeners of the changes made to the buffers since the last commit
+    pub fn commit_changes(&mut self, state: &mut State) {
+        for (id, file) in state.filesystem.files.iter_mut() {
+            let Some(transaction) = file.commit(state.mode == Mode::Insert) else {
+                continue;
+            };
+            if let Some(rope) = &file.buffer {
+                self.panes.follow_changes(id, &transaction, rope);
+            }
+            state.jumps.follow(id, &transaction);
+        }
+    }
+
+    /// Handle the internal events that target this screen
+    pub fn handle_editor_event(&mut self, event: EditorEvent, state: &mut State) {
+        match event {
+            EditorEvent::FolderLoaded { id, files, folders } => {
+                // Refreshes keep the selected entry under the cursor
+                let selected = self.filetree.selected_entry(&state.filesystem);
+                state.filesystem.init_folder(id, files, folders);
+                if let Some(entry) = selected {
+                    self.filetree.select(entry, &state.filesystem);
+                }
+                if id == state.filesystem.root {
+                    state.startup.mark(ROOT_SCAN);
+                }
+            }
+            EditorEvent::ProjectSearch { id, results } => self.replace.add_results(id, results),
+            EditorEvent::Command(command) => self.execute_command(&command, state),
+            EditorEvent::ShellFinished {
+                command,
+                success,
+                output,
+            } => {
+                // The command may have modified the files of unchanged buffers
+                for id in state.filesystem.open_buffers.clone() {
+                    let file = &mut state.filesystem.files[id];
+                    if !file.modified
+                        && !file.path.as_os_str().is_empty()
+                        && file.remote.is_none()
+                        && file.changed_on_disk()
+                        && let Err(err) = file.load()
+                    {
+                        log::error!("Failed to reload {}: {}", file.path.display(), err);
+                    }
+                }
+                self.panes.clamp_cursors(state);
+
+                let mut last_line = output.lines().rfind(|line| !line.trim().is_empty());
+                // Longer outputs are displayed in a scratch buffer
+                if output
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .count()
+                    > 1
+                {
+                    let mut file = File::scratch(&output);
+                    file.name = format!("!{}", command);
+                    let id = state.filesystem.files.insert(file);
+                    self.panes.open(id);
+                    self.focus = Focus::Panes;
+                    last_line = None;
+                }
+                match (success, last_line) {
+                    (true, Some(line)) => state.info(line.trim()),
+                    (true, None) => state.info(format!(":!{}", command)),
+                    (false, Some(line)) => state.error(format!("shell returned: {}", line.trim())),
+                    (false, None) => state.error(format!("shell returned an error: {}", command)),
+                }
+            }
+            EditorEvent::RemoteLoaded { file: id, text } => {
+                let Some(file) = state.filesystem.files.get_mut(id) else {
+                    return;
+                };
+                let uri = file.path.display().to_string();
+                match text {
+                    Ok(text) => {
+                        let rope = Rope::from_str(&text);
+                        if let Some(options) = &mut file.options {
+                            for name in options.detect_indent(&rope) {
+                                file.option_sources.insert(name, OptionSource::Detected);
+                            }
+                        }
+                        file.fileformat = FileFormat::detect(&rope);
+                        let lines = line_count(&rope);
+                        file.buffer = Some(rope);
+                        file.undo = UndoTree::default();
+                        file.readonly = false;
+                        file.modified = false;
+                        self.panes.clamp_cursors(state);
+                        state.info(format!("\"{}\" {} lines", uri, lines));
+                        self.check_long_lines(id, state);
+                    }
+                    Err(err) => state.error(format!("Failed to fetch {}: {}", uri, err)),
+                }
+            }
+            EditorEvent::RemoteWritten {
+                file: id,
+                text,
+                result,
+            } => {
+                let Some(file) = state.filesystem.files.get_mut(id) else {
+                    return;
+                };
+                let path = file.path.clone();
+                match result {
+                    Ok(()) => {
+                        // The buffer may have been edited during the upload
+                        if file.text() == text {
+                            file.modified = false;
+                            file.undo.mark_saved();
+                        }
+                        file.readonly = false;
+                        let lines = text.lines().count();
+                        state.info(format!(
+                            "\"{}\" {}L, {}B written",
+                            path.display(),
+                            lines,
+                            text.len()
+                        ));
+                        self.fire(AutoEvent::BufWritePost, Some(&path), state);
+                    }
+                    // Keep the buffer as is until the host can be reached again
+                    Err(err) => {
+                        file.readonly = true;
+                        state.error(format!(
+                            "Failed to write {}: {}, the buffer is read-only until :w succeeds or :w {{file}} writes a local copy",
+                            path.display(),
+                            err
+                        ));
+                    }
+                }
+            }
+            EditorEvent::ManPage { page, text } => match text {
+                Ok(text) => self.open_page(&format!("man {}", page), &text, state),
+                Err(err) => state.error(err),
+            },
+            EditorEvent::ReadOutput { file, line, text } => match text {
+                Ok(text) => self.insert_below(file, line, &text, state),
+                Err(err) => state.error(err),
+            },
+            EditorEvent::Paste { file, at, text } => {
+                let text = match text {
+                    Ok(text) => text,
+                    Err(err) => return state.error(err),
+                };
+                let Some(buffer) = state.filesystem.files.get_mut(file) else {
+                    return;
+                };
+                if buffer.readonly {
+                    return state.error(READONLY_ERROR);
+                }
+                let Some(rope) = buffer.buffer.as_mut() else {
+                    return;
+                };
+                let rope = &mut Edit::new(rope, &mut buffer.changes);
+                // The buffer may have changed while the selection was read
+                let at = at.min(rope.len_chars());
+                rope.insert(at, &text);
+                buffer.modified = !buffer.scratch;
+
+                let end = at + text.chars().count();
+                if let Some(pane) = self.panes.focused_mut()
+                    && pane.file == file
+                {
+                    pane.cursor.move_to_char(rope, end);
+                }
+                self.panes.clamp_cursors(state);
+            }
+            EditorEvent::GitStatus(status) => state.git = status,
+            EditorEvent::MakeFinished {
+                command,
+                success,
+                output,
+                diagnostics,
+                jump,
+            } => {
+                let summary = diagnostics.workspace_counts().summary();
+                let first = diagnostics
+                    .first()
+                    .map(|(path, diagnostic)| (path.to_path_buf(), diagnostic.clone()));
+                state.diagnostics = diagnostics;
+                match first {
+                    Some((path, diagnostic)) if jump => {
+                        if self.open_path(&path, state).is_some() {
+                            self.goto(diagnostic.line, diagnostic.column, state);
+                        }
+                        state.info(format!(
+                            "({}) {}: {}",
+                            summary,
+                            diagnostic.severity.name(),
+                            diagnostic.message
+                        ));
+                    }
+                    Some(_) => state.info(format!("{}: {}", command, summary)),
+                    None if success => state.info(format!("{}: {}", command, summary)),
+                    None => {
+                        let last_line = output.lines().rfind(|line| !line.trim().is_empty());
+                        state.error(format!("{}: {}", command, last_line.unwrap_or("failed")));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+        match state.mode {
+            // The answers to the confirmations of `:s///c` come first
+            _ if self.substitution.is_some() => self.handle_substitution_key(key_event, state),
+            // The cmdline receives all the keys until it closes
+            Mode::Command => self.handle_cmdline_key_event(key_event, state),
+            Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock | Mode::Insert => {
+                self.handle_editing_key_event(key_event, state)
+            }
+        }
+        // The next keys read the marks shifted by the edits of this one
+        self.commit_changes(state);
+        state.pending_keys = match (self.focus, self.panes.focused()) {
+            (Focus::Panes, Some(pane)) if state.mode != Mode::Command => pane.pending_keys(),
+            _ => String::new(),
+        };
+    }
+
+    fn handle_cmdline_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+        let prompt = state.prompt;
+        let incsearch = matches!(prompt, Prompt::Search { .. })
+            && state.config.incsearch
+            && self.focus == Focus::Panes;
+        if incsearch && self.search_view.is_none() {
+            self.search_view = self.panes.focused().map(Pane::view);
+        }
+        let submitted = self.cmdline.handle_key_event(key_event, state);
+        if let Prompt::Search { backward } = prompt {
+            self.preview_search(backward, state);
+        }
+        if let Some(text) = submitted {
+            match prompt {
+                Prompt::Command => self.execute_command(&text, state),
+                Prompt::Search { backward } => self.search(&text, backward, state),
+                Prompt::Input => self.submit_input(&text, state),
+            }
+        }
+    }
+
+    /// Move the focused pane to the first match of the search being typed, from where
+    /// the search started, and back there once the cmdline closes
+    fn preview_search(&mut self, backward: bool, state: &mut State) {
+        let Some(view) = self.search_view.clone() else {
+            return;
+        };
+        let Some(pane) = self.panes.focused_mut() else {
+            return;
+        };
+        pane.restore_view(view);
+        if state.mode != Mode::Command {
+            self.search_view = None;
+            state.incsearch = None;
+            return;
+        }
+        // Invalid patterns are reported once submitted
+        let text = self.cmdline.text();
+        state.incsearch = match text.is_empty() {
+            true => None,
+            false => Pattern::new(&text, &state.config).ok(),
+        };
+        if let Some(pattern) = &state.incsearch {
+            pane.preview_search(pattern, backward, state);
+        }
+    }
+
+    /// Handle a key event outside of command mode
+    fn handle_editing_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+        if let Some(menu) = &mut self.menu {
+            match key_event.code {
+                KeyCode::Esc => self.menu = None,
+                KeyCode::Enter => {
+                    let item = menu.selected();
+                    self.menu = None;
+                    self.menu_action(item, state);
+                }
+                _ => menu.handle_key_event(key_event),
+            }
+            return;
+        }
+
+        if self.replace.open {
+            if let Some((path, line, col)) = self.replace.handle_key_event(key_event, state) {
+                self.replace.open = false;
+                if let Some(id) = self.open_path(&path, state)
+                    && let Some(rope) = &state.filesystem.files[id].buffer
+                    && let Some(pane) = self.panes.focused_mut()
+                {
+                    pane.cursor.set_position(col, line, rope);
+                }
+            }
+            // Replacements may have modified open buffers
+            self.panes.clamp_cursors(state);
+            return;
+        }
+
+        if self.palette.open {
+            match self.palette.handle_key_event(key_event) {
+                Some(PaletteAction::Command(command)) => self.execute_command(&command, state),
+                Some(PaletteAction::Keys(keys)) => {
+                    if !self.panes.is_empty() {
+                        self.focus = Focus::Panes;
+                    }
+                    // Typed before the keys left to replay, as if they came first
+                    for key_event in parse_keys(&keys).into_iter().rev() {
+                        self.replay.push_front(key_event);
+                    }
+                }
+                Some(PaletteAction::Edit(text)) => {
+                    self.handle_action(Action::Cmdline(text), state);
+                }
+                None => {}
+            }
+            return;
+        }
+
+        // Keys inserted literally after CTRL-V skip the mappings and the global keys
+        let literal =
+            self.focus == Focus::Panes && self.panes.focused().is_some_and(Pane::inserting_literal);
+        if literal {
+            self.handle_pane_key_event(key_event, state);
+            return;
+        }
+
+        // User mappings take precedence over the default keys, the visual ones applying to
+        // the linewise and blockwise selections too
+        let mode = match (self.focus, state.mode) {
+            (Focus::Tree, _) => Mode::Normal,
+            (_, Mode::VisualLine | Mode::VisualBlock) => Mode::Visual,
+            (_, mode) => mode,
+        };
+        if let Some(command) = state.keymaps.get(mode, key_event) {
+            let command = command.to_string();
+            self.execute_command(&command, state);
+            return;
+        }
+
+        // Temporarily hand the mouse over to the terminal
+        if key_event.code == KeyCode::F(2) {
+            state.mouse_suspended = !state.mouse_suspended;
+            match state.mouse_suspended {
+                true => state.info("Mouse capture suspended, press F2 to resume"),
+                false => state.info("Mouse capture resumed"),
+            }
+            return;
+        }
+
+        // Focus switching
+        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            match key_event.code {
+                KeyCode::Char('h') if self.tree_open => {
+                    self.focus = Focus::Tree;
+                    return;
+                }
+                KeyCode::Char('l') if !self.panes.is_empty() => {
+                    self.focus = Focus::Panes;
+                    return;
+                }
+                KeyCode::Char('p') if mode == Mode::Normal => {
+                    self.palette.open(state);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        match self.focus {
+            Focus::Tree => {
+                if let Some(id) = self.filetree.handle_key_event(key_event, state) {
+                    match state.filesystem.open(id) {
+                        Ok(()) => self.open_file(id, state),
+                        Err(err) => state.error(format!("Failed to open file: {}", err)),
+                    }
+                }
+            }
+            Focus::Panes => {
+                let repeat = state.mode == Mode::Normal
+                    && key_event.code == KeyCode::Char('.')
+                    && !self.panes.focused().is_some_and(Pane::is_pending);
+                match repeat {
+                    true => self.repeat_change(state),
+                    false => self.handle_pane_key_event(key_event, state),
+                }
+            }
+            Focus::CmdlineWindow => self.handle_cmdwin_key_event(key_event, state),
+        }
+    }
+
+    fn handle_mouse_event(&mut self, mouse_event: MouseEvent, state: &mut State) {
+        // Popups capture the input
+        if state.mode == Mode::Command || !state.config.mouse_enabled(state.mode) {
+            return;
+        }
+        if self.replace.open {
+            self.replace.handle_mouse_event(mouse_event, state);
+            return;
+        }
+        if self.palette.open {
+            return;
+        }
+        let position = Position::new(mouse_event.column, mouse_event.row);
+        let click = matches!(mouse_event.kind, MouseEventKind::Down(_));
+
+        if let Some(cmdwin) = &mut self.cmdwin
+            && cmdwin.contains(position)
+        {
+            cmdwin.handle_mouse_event(mouse_event, state);
+            return;
+        }
+
+        if let Some(menu) = &mut self.menu {
+            let item = menu.hover(position);
+            match mouse_event.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    self.menu = None;
+                    if let Some(item) = item {
+                        self.menu_action(item, state);
+                    }
+                    return;
+                }
+                // A right click elsewhere opens a new menu
+                MouseEventKind::Down(_) if item.is_none() => self.menu = None,
+                _ => return,
+            }
+        }
+        let right_click = mouse_event.kind == MouseEventKind::Down(MouseButton::Right);
+
+        if self.tree_open && self.filetree.contains(position) {
+            if click {
+                self.focus = Focus::Tree;
+            }
+            if let Some(id) = self.filetree.handle_mouse_event(mouse_event, state) {
+                match state.filesystem.open(id) {
+                    Ok(()) => self.open_file(id, state),
+                    Err(err) => state.error(format!("Failed to open file: {}", err)),
+                }
+            }
+            if let Some((file, folder)) = self.filetree.take_drop() {
+                self.drop_file(file, folder, state);
+            }
+            if right_click && self.filetree.selected_entry(&state.filesystem).is_some() {
+                self.menu = Some(ContextMenu::new(MenuItem::TREE, position));
+            }
+        } else if self.panes.contains(position) || !click {
+            if let MouseEventKind::Up(_) = mouse_event.kind {
+                self.filetree.cancel_drag();
+            }
+            if click && !self.panes.is_empty() {
+                self.focus = Focus::Panes;
+            }
+            self.panes.handle_mouse_event(mouse_event, state);
+            if right_click && !self.panes.is_empty() {
+                self.menu = Some(ContextMenu::new(MenuItem::PANE, position));
+            }
+        }
+    }
+
+    /// Insert chars typed in a burst, like IME commits, as a single edit.
+    /// They are handled as keys if they may be mapped or complete pending keys.
+    pub fn handle_typed_text(&mut self, text: &str, state: &mut State) {
+        let mapped = text.chars().any(|c| {
+            let key_event = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            state.keymaps.get(Mode::Insert, key_event).is_some()
+        });
+        if state.mode == Mode::Insert
+            && self.focus == Focus::Panes
+            && self.menu.is_none()
+            && !self.replace.open
+            && !self.palette.open
+            && !mapped
+            && let Some(pane) = self.panes.focused_mut()
+            && !pane.is_pending()
+        {
+            pane.type_text(text, state);
+            self.extend_change(text);
+            if state.recording.is_some() {
+                let keys = text
+                    .chars()
+                    .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+                self.recorded.extend(keys);
+            }
+            return;
+        }
+        for c in text.chars() {
+            let key_event = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            self.handle(Event::Key(key_event), state);
+        }
+    }
+
+    /// Insert bracketed pasted text in the cmdline or the focused pane
+    fn handle_paste(&mut self, text: &str, state: &mut State) {
+        if state.mode == Mode::Command {
+            self.cmdline.paste(text);
+        } else if self.focus == Focus::Panes
+            && self.menu.is_none()
+            && !self.replace.open
+            && !self.palette.open
+            && let Some(pane) = self.panes.focused_mut()
+        {
+            pane.insert_text(text, state);
+            if state.mode == Mode::Insert {
+                self.extend_change(text);
+            }
+        }
+    }
+
+    /// Place the terminal cursor for the focused widget
+    fn place_cursor(&self, state: &State) {
+        match self.focus {
+            Focus::Tree => {
+                state.set_cursor_style(state.config.cursor.get(Mode::Normal));
+                state.cursor_pos.set(self.filetree.cursor_position());
+            }
+            Focus::Panes => {
+                if let Some(pane) = self.panes.focused() {
+                    state.set_cursor_style(state.config.cursor.get(state.mode));
+                    state.cursor_pos.set(pane.cursor_position());
+                }
+            }
+            Focus::CmdlineWindow => {
+                if let Some(cmdwin) = &self.cmdwin {
+                    state.set_cursor_style(state.config.cursor.get(state.mode));
+                    state.cursor_pos.set(cmdwin.cursor_position());
+                }
+            }
         }
     }
 }
 
 impl Screen for EditorScreen {
     fn handle(&mut self, event: Event, state: &mut State) {
-        // TODO:
+        match event {
+            // it's important to check that the event is a key press event as
+            // crossterm also emits key release and repeat events on Windows.
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                if state.recording.is_some() {
+                    self.recorded.push(key_event);
+                }
+                self.handle_key_event(key_event, state);
+                self.run_replay(state);
+            }
+            Event::Mouse(mouse_event) => self.handle_mouse_event(mouse_event, state),
+            Event::Paste(text) => self.handle_paste(&text, state),
+            _ => {}
+        }
     }
 }
 
 impl Widget for EditorScreen {
     fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
-        let [main, lualine] =
-            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(area);
+        let bottom_height = match state.config.cmdline {
+            CmdlineLayout::Float => 0,
+            CmdlineLayout::Bottom => 1,
+        };
+        let [main, lualine, bottom] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(bottom_height),
+        ])
+        .areas(area);
 
-        match self.tree_open {
+        let panes = match self.tree_open {
             true => {
                 let [tree, border, panes] = Layout::horizontal([
                     Constraint::Length(self.tree_width),
@@ -59,12 +1065,62 @@ impl Widget for EditorScreen {
 
                 self.filetree.render(tree, buf, state);
                 self.border.render(border, buf, state);
+                panes
+            }
+            false => main,
+        };
+
+        match &self.cmdwin {
+            Some(cmdwin) => {
+                let [panes, window] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(cmdwin::HEIGHT + 1)])
+                        .areas(panes);
                 self.panes.render(panes, buf, state);
+                cmdwin.render(window, buf, state);
             }
-            false => self.panes.render(main, buf, state),
+            None => self.panes.render(panes, buf, state),
+        }
+        // The selected file is previewed over the right half of the panes
+        if self.tree_open
+            && self.focus == Focus::Tree
+            && let Some(path) = self.filetree.preview_path(&state.filesystem)
+        {
+            let [_, preview] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Percentage(50)]).areas(panes);
+            let tabstop = state.config.buffer.tabstop;
+            self.preview.render(path, preview, buf, tabstop);
+        } else {
+            self.preview.hide();
         }
 
         self.lualine.render(lualine, buf, state);
+        self.place_cursor(state);
+
+        // Popups are rendered on top, and place the cursor in their input
+        if let Some(menu) = &self.menu {
+            menu.render(main, buf, state);
+        }
+        if self.replace.open {
+            state.set_cursor_style(state.config.cursor.get(Mode::Insert));
+            self.replace.render(main, buf, state);
+        }
+        if self.palette.open {
+            state.set_cursor_style(state.config.cursor.get(Mode::Insert));
+            self.palette.render(main, buf, state);
+        }
+        match state.config.cmdline {
+            CmdlineLayout::Float if state.mode == Mode::Command => {
+                state.set_cursor_style(state.config.cursor.get(Mode::Command));
+                self.cmdline.render(main, buf, state);
+            }
+            CmdlineLayout::Float => {}
+            CmdlineLayout::Bottom => {
+                if state.mode == Mode::Command {
+                    state.set_cursor_style(state.config.cursor.get(Mode::Command));
+                }
+                self.cmdline.render(bottom, buf, state);
+            }
+        }
     }
 
     /// Always true when the screen is active
@@ -0,0 +1,1692 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, ErrorKind, Write},
+    path::Path,
+    process::Stdio,
+};
+
+use ropey::Rope;
+
+use crate::{
+    State, format, numbers,
+    position::line_count,
+    state::{
+        AutoAction, AutoEvent, BufferOptions, Config, Edit, EditorEvent, File, FileFormat, FileId,
+        LineOperation, Mode, OptionSource, SortFlags, Value, WindowOptions,
+        commands::{LineRange, RangeContext, accepts_range, parse_range, split_chain, split_name},
+        data,
+        diagnostics::{Diagnostic, Diagnostics, Severity},
+        diff, digraphs, filetype, health, help, is_register,
+        remote::{self, Remote},
+        session,
+    },
+    utils::{absolute_path, expand_path, plain_text},
+    widgets::{Action, READONLY_ERROR, UndoMotion, filetree::Entry},
+};
+
+use super::{EditorScreen, Focus};
+
+/// Lines around a line whose text without line break satisfies a predicate, None if the
+/// line does not
+fn lines_around(rope: &Rope, line: usize, keep: impl Fn(&str) -> bool) -> Option<LineRange> {
+    let keep = |line: usize| {
+        let text = rope.line(line).to_string();
+        keep(text.trim_end_matches(['\r', '\n']))
+    };
+    if !keep(line) {
+        return None;
+    }
+    let start = (0..line).rev().find(|&i| !keep(i)).map_or(0, |i| i + 1);
+    let end = (line + 1..line_count(rope))
+        .find(|&i| !keep(i))
+        .map_or(line_count(rope) - 1, |i| i - 1);
+    Some(LineRange { start, end })
+}
+
+/// Whether a file exists and cannot be written
+fn is_readonly(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// Run a write to a file, which is made writable for the time of the write when it is
+/// read-only
+fn writable<T>(path: &Path, write: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    let Some(permissions) = fs::metadata(path)
+        .map(|metadata| metadata.permissions())
+        .ok()
+        .filter(|permissions| permissions.readonly())
+    else {
+        return write();
+    };
+    let mut writable = permissions.clone();
+    // The original permissions are restored right after the write
+    #[allow(clippy::permissions_set_readonly_false)]
+    writable.set_readonly(false);
+    fs::set_permissions(path, writable)?;
+    let result = write();
+    fs::set_permissions(path, permissions)?;
+    result
+}
+
+/// Message of a written file: its name, and its numbers of lines and bytes
+fn written_message(id: FileId, path: &Path, new: bool, state: &State) -> String {
+    let lines = state.filesystem.files[id]
+        .buffer
+        .as_ref()
+        .map_or(0, line_count);
+    let bytes = fs::metadata(path).map_or(0, |metadata| metadata.len());
+    format!(
+        "\"{}\" {}{}L, {}B written",
+        state.filesystem.relative_path(path).display(),
+        if new { "[New] " } else { "" },
+        lines,
+        bytes
+    )
+}
+
+/// Split the `++p` flag, which creates the missing directories, from the file argument
+fn parse_create_flag(args: &str) -> (bool, &str) {
+    match args.strip_prefix("++p") {
+        Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+            (true, rest.trim_start())
+        }
+        _ => (false, args),
+    }
+}
+
+fn bang(force: bool) -> &'static str {
+    if force { "!" } else { "" }
+}
+
+/// Arguments of `:set`, separated by spaces unless escaped with a backslash like in
+/// `makeprg=cargo\ build`
+fn split_set_args(args: &str) -> Vec<String> {
+    let mut split = vec![];
+    let mut current = String::new();
+    let mut chars = args.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|c| c.is_whitespace()) => {
+                current.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    split.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        split.push(current);
+    }
+    split
+}
+
+/// Text of an `:echo` argument, without its quotes
+fn unquote(text: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| text.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(text)
+}
+
+/// 1-based line and optional column of a `:goto` argument: `42`, `42 5` or `42:5`
+fn parse_position(args: &str) -> Option<(usize, usize)> {
+    let mut parts = args.split([' ', ':']).filter(|part| !part.is_empty());
+    let line = parts.next().map_or(Ok(1), str::parse).ok()?;
+    let column = parts.next().map_or(Ok(1), str::parse).ok()?;
+    parts.next().is_none().then_some((line, column))
+}
+
+/// Maximum nesting of user commands expanding to other user commands
+const MAX_DEPTH: usize = 32;
+
+impl EditorScreen {
+    /// Execute a command line submitted from the cmdline or bound to a key,
+    /// made of `|` separated commands
+    pub(super) fn execute_command(&mut self, line: &str, state: &mut State) {
+        state.message = None;
+        self.execute_chain(line, 0, state);
+    }
+
+    /// Execute the commands of a chain until one fails
+    pub(super) fn execute_chain(&mut self, line: &str, depth: usize, state: &mut State) {
+        if depth > MAX_DEPTH {
+            state.error("E169: Command too recursive");
+            return;
+        }
+        for command in split_chain(line) {
+            self.execute_single(&command, depth, state);
+            if state.message.as_ref().is_some_and(|message| message.error) {
+                return;
+            }
+        }
+    }
+
+    fn execute_single(&mut self, command: &str, depth: usize, state: &mut State) {
+        let command = command.trim().trim_start_matches(':').trim_start();
+        let (range, command) = match self.parse_range(command, state) {
+            Ok((range, command)) => (range, command.trim_start()),
+            Err(err) => {
+                state.error(err);
+                return;
+            }
+        };
+        if range.is_some() && command.starts_with('!') {
+            state.error("E481: No range allowed");
+            return;
+        }
+        if let Some(shell) = command.strip_prefix('!') {
+            self.shell(shell.trim(), state);
+            return;
+        }
+        let (name, rest) = split_name(command);
+        let force = rest.starts_with('!');
+        let args = rest.strip_prefix('!').unwrap_or(rest).trim();
+        if name.is_empty() {
+            match range {
+                _ if !rest.is_empty() => {
+                    state.error(format!("E492: Not an editor command: {}", command));
+                }
+                // A bare range jumps to its last line
+                Some(range) => self.goto(range.end, 0, state),
+                None => {}
+            }
+            return;
+        }
+        let name = match state.commands.resolve(name) {
+            Ok(name) => name,
+            Err(err) => {
+                state.error(err);
+                return;
+            }
+        };
+
+        if range.is_some() && !accepts_range(&name) {
+            state.error("E481: No range allowed");
+            return;
+        }
+
+        match name.as_str() {
+            "delete" => match args.chars().collect::<Vec<_>>()[..] {
+                [] => self.delete_lines(range, '"', state),
+                [register] if is_register(register) => self.delete_lines(range, register, state),
+                _ => state.error(format!("E488: Trailing characters: {}", args)),
+            },
+            "substitute" => self.substitute(range, args, state),
+            "sort" => match SortFlags::parse(args, force) {
+                Ok(flags) => self.reorder_lines(range, LineOperation::Sort(flags), state),
+                Err(err) => state.error(err),
+            },
+            "uniq" if matches!(args, "" | "i") => {
+                let operation = LineOperation::Uniq {
+                    ignorecase: args == "i",
+                };
+                self.reorder_lines(range, operation, state);
+            }
+            "reverse" if args.is_empty() => {
+                self.reorder_lines(range, LineOperation::Reverse, state);
+            }
+            "uniq" | "reverse" => state.error(format!("E488: Trailing characters: {}", args)),
+            "Align" if args.is_empty() => state.error("E471: Argument required"),
+            "Align" => self.align(range, args, state),
+            "Renumber" => self.renumber(range, args, state),
+            "Apply" => self.apply_hunk(force, state),
+            "jq" => self.jq(args, state),
+            // `:r!cmd` reads the output of a command like `:r !cmd`
+            "read" => self.read(range, rest.trim(), state),
+            "goto" => match parse_position(args) {
+                Some((line, column)) => {
+                    let line = range.map_or(line, |range| range.end + 1);
+                    self.goto(line.saturating_sub(1), column.saturating_sub(1), state);
+                }
+                None => state.error(format!("E474: Invalid argument: {}", args)),
+            },
+            "write" if range.is_some() || args.starts_with(">>") => {
+                self.write_part(range, args, force, state);
+            }
+            "write" => {
+                self.write(&name, args, force, state);
+            }
+            "quit" => self.quit(force, state),
+            "make" => self.make(args, !force, state),
+            "wq" | "xit" => {
+                if self.write(&name, args, force, state) {
+                    self.quit(force, state);
+                }
+            }
+            "wall" => {
+                self.write_all(state);
+            }
+            "qall" => self.quit_all(force, state),
+            // Exit with an error status, discarding the changes
+            "cquit" => {
+                let status = match args {
+                    "" => Ok(1),
+                    args => args.parse::<u8>(),
+                };
+                match status {
+                    Ok(status) => {
+                        state.exit_status = status;
+                        state.exit = true;
+                    }
+                    Err(_) => state.error(format!("E474: Invalid argument: {}", args)),
+                }
+            }
+            "wqall" | "xall" => {
+                if self.write_all(state) {
+                    self.quit_all(force, state);
+                }
+            }
+            "vsplit" if args.is_empty() => self.panes.split(None),
+            "vsplit" => {
+                let path = absolute_path(&expand_path(args));
+                match state.filesystem.open_file(&path) {
+                    Ok(id) => {
+                        self.panes.split(Some(id));
+                        self.focus = Focus::Panes;
+                        self.enter_buffer(id, state);
+                    }
+                    Err(err) => state.error(format!("Failed to open {}: {}", path.display(), err)),
+                }
+            }
+            "edit" | "source" if args.is_empty() => state.error("E471: Argument required"),
+            "edit" => {
+                self.open_path(&expand_path(args), state);
+            }
+            "source" => self.source(&expand_path(args), state),
+            "call" if args.is_empty() => state.error("E471: Argument required"),
+            "call" => self.call(args, depth, state),
+            "Replace" => self.replace.open(args, state),
+            "enew" => {
+                let id = state.filesystem.files.insert(File::unnamed());
+                match state.filesystem.open(id) {
+                    Ok(()) => self.open_file(id, state),
+                    Err(err) => state.error(format!("Failed to open file: {}", err)),
+                }
+            }
+            "mksession" => self.mksession(args, force, state),
+            "saveas" if args.is_empty() => state.error("E471: Argument required"),
+            "saveas" => self.save_as(args, force, state),
+            "file" if args.is_empty() => self.file_info(state),
+            "file" => self.name_buffer(args, state),
+            "Rename" if args.is_empty() => state.error("E471: Argument required"),
+            "Rename" => self.rename(&expand_path(args), state),
+            "Delete" => self.delete(force, state),
+            "workspace" => self.workspace(args, state),
+            "checkhealth" => {
+                let report = health::report(state);
+                self.open_page("health", &report, state);
+            }
+            "help" => self.help(args, state),
+            "digraphs" => self.open_page("digraphs", &digraphs::listing(), state),
+            "startuptime" => {
+                let report = state.startup.report();
+                self.open_page("startuptime", &report, state);
+            }
+            "jobs" => self.jobs(args, state),
+            "undo" if args.is_empty() => self.undo(UndoMotion::Undo, state),
+            "undo" => match args.parse() {
+                Ok(revision) => self.undo(UndoMotion::To(revision), state),
+                Err(_) => state.error(format!("E474: Invalid argument: {}", args)),
+            },
+            "redo" => self.undo(UndoMotion::Redo, state),
+            "Man" if args.is_empty() => state.error("E471: Argument required"),
+            "Man" => self.man(args, state),
+            "echo" => state.info(unquote(args)),
+            "echoerr" => state.error(unquote(args)),
+            "autocmd" if force => {
+                if let Err(err) = state.autocmds.clear((!args.is_empty()).then_some(args)) {
+                    state.error(err);
+                }
+            }
+            "autocmd" => {
+                if let Err(err) = state.autocmds.define(args) {
+                    state.error(err);
+                }
+            }
+            "set" | "setlocal" => {
+                for arg in split_set_args(args) {
+                    match self.set_option(&arg, name == "setlocal", state) {
+                        Ok(Some(message)) => state.info(message),
+                        Ok(None) => {}
+                        Err(err) => {
+                            state.error(err);
+                            break;
+                        }
+                    }
+                }
+            }
+            "command" if args.is_empty() => {
+                let names = state.commands.user.keys().cloned().collect::<Vec<_>>();
+                match names.is_empty() {
+                    true => state.info("No user-defined commands found"),
+                    false => state.info(names.join("  ")),
+                }
+            }
+            "command" => {
+                if let Err(err) = state.commands.define(args, force) {
+                    state.error(err);
+                }
+            }
+            "delcommand" => {
+                if state.commands.user.remove(args).is_none() {
+                    state.error(format!("E184: No such user-defined command: {}", args));
+                }
+            }
+            "map" | "noremap" => self.map(&[Mode::Normal, Mode::Visual], args, state),
+            "nmap" | "nnoremap" => self.map(&[Mode::Normal], args, state),
+            "vmap" | "vnoremap" => self.map(&[Mode::Visual], args, state),
+            "imap" | "inoremap" => self.map(&[Mode::Insert], args, state),
+            "cabbrev" | "cnoreabbrev" if args.is_empty() => {
+                let abbreviations = &state.commands.abbreviations;
+                let text = abbreviations
+                    .iter()
+                    .map(|(lhs, rhs)| format!("{} {}", lhs, rhs))
+                    .collect::<Vec<_>>();
+                match text.is_empty() {
+                    true => state.info("No abbreviation found"),
+                    false => state.info(text.join("  ")),
+                }
+            }
+            "cabbrev" | "cnoreabbrev" => {
+                if let Err(err) = state.commands.abbreviate(args) {
+                    state.error(err);
+                }
+            }
+            "cunabbrev" => {
+                if state.commands.abbreviations.remove(args).is_none() {
+                    state.error(format!("E24: No such abbreviation: {}", args));
+                }
+            }
+            _ => match state.commands.expand(&name, args) {
+                Some(expanded) => self.execute_chain(&expanded, depth + 1, state),
+                None => state.error(format!("E492: Not an editor command: {}", command)),
+            },
+        }
+    }
+
+    /// File or folder targeted by the file commands:
+    /// the filetree selection when it is focused, or else the buffer of the focused pane
+    fn target_entry(&self, state: &State) -> Option<Entry> {
+        match self.focus {
+            Focus::Tree => self.filetree.selected_entry(&state.filesystem),
+            _ => self.panes.focused().map(|pane| Entry::File(pane.file)),
+        }
+    }
+
+    /// Rename or move the targeted file or folder, to a path relative to the root folder
+    fn rename(&mut self, path: &Path, state: &mut State) {
+        let root = &state.filesystem.folders[state.filesystem.root].path;
+        let path = absolute_path(&root.join(path));
+        let result = match self.target_entry(state) {
+            Some(Entry::File(id)) if state.filesystem.files[id].path.as_os_str().is_empty() => {
+                Err(io::Error::new(ErrorKind::InvalidInput, "No file name"))
+            }
+            Some(Entry::File(id)) => state.filesystem.rename_file(id, &path),
+            Some(Entry::Folder(id)) => state.filesystem.rename_folder(id, &path),
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                let name = state.filesystem.relative_path(&path).display().to_string();
+                state.info(format!("Renamed to {}", name));
+            }
+            Err(err) => state.error(format!("Failed to rename to {}: {}", path.display(), err)),
+        }
+    }
+
+    /// Add or remove an extra root folder of the workspace, or list the roots
+    fn workspace(&mut self, args: &str, state: &mut State) {
+        let (action, path) = args.split_once(' ').unwrap_or((args, ""));
+        let path = absolute_path(&expand_path(path.trim()));
+        match action {
+            "" => {
+                let roots = state
+                    .filesystem
+                    .workspace()
+                    .map(|root| state.filesystem.folders[root].path.display().to_string())
+                    .collect::<Vec<_>>();
+                state.info(roots.join("  "));
+            }
+            "add" | "remove" if path.as_os_str().is_empty() => {
+                state.error("E471: Argument required");
+            }
+            "add" if !path.is_dir() => {
+                state.error(format!("{} is not a directory", path.display()));
+            }
+            "add" => match state.filesystem.add_root(path.clone()) {
+                Some(id) => {
+                    state.filesystem.load_folder(
+                        state.events.editor_sender.clone(),
+                        &state.jobs,
+                        id,
+                    );
+                    state.info(format!("Added {} to the workspace", path.display()));
+                }
+                None => state.error(format!("{} is already in the workspace", path.display())),
+            },
+            "remove" => match state.filesystem.remove_root(&path) {
+                true => state.info(format!("Removed {} from the workspace", path.display())),
+                false => state.error(format!("{} is not an extra root", path.display())),
+            },
+            _ => state.error(format!("E474: Invalid argument: {}", args)),
+        }
+    }
+
+    /// Delete the targeted file, or folder with its contents when forced
+    fn delete(&mut self, force: bool, state: &mut State) {
+        let (path, result) = match self.target_entry(state) {
+            Some(Entry::File(id)) => {
+                let path = state.filesystem.files[id].path.clone();
+                (path, state.filesystem.delete_file(id))
+            }
+            Some(Entry::Folder(id)) if state.filesystem.is_root(id) => {
+                state.error("Cannot delete a root folder");
+                return;
+            }
+            Some(Entry::Folder(id)) => {
+                let path = state.filesystem.folders[id].path.clone();
+                (path, state.filesystem.delete_folder(id, force))
+            }
+            None => return,
+        };
+        let name = state.filesystem.relative_path(&path).display().to_string();
+        match result {
+            Ok(()) => state.info(format!("Deleted {}", name)),
+            Err(err) if err.kind() == ErrorKind::DirectoryNotEmpty => state.error(format!(
+                "{} is not empty, add ! to delete its contents",
+                name
+            )),
+            Err(err) => state.error(format!("Failed to delete {}: {}", name, err)),
+        }
+    }
+
+    /// Parse the range at the start of a command,
+    /// resolved against the buffer of the focused pane
+    fn parse_range<'a>(
+        &self,
+        command: &'a str,
+        state: &State,
+    ) -> Result<(Option<LineRange>, &'a str), String> {
+        let pane = self.panes.focused();
+        let file = pane.and_then(|pane| state.filesystem.files.get(pane.file));
+        let empty = Rope::new();
+        let no_marks = BTreeMap::new();
+        let cx = RangeContext {
+            rope: file.and_then(|file| file.buffer.as_ref()).unwrap_or(&empty),
+            current: pane.map_or(0, |pane| pane.cursor.y),
+            marks: file.map_or(&no_marks, |file| &file.marks),
+        };
+        parse_range(command, &cx)
+    }
+
+    /// Apply a `:set` argument. Buffer options are set for the focused buffer and as the
+    /// default of the files without a known filetype, and queried from the focused buffer
+    /// with their origin. With `:setlocal`, buffer and window options are only set for the
+    /// focused buffer and pane, and `{option}<` restores their global value.
+    fn set_option(
+        &mut self,
+        arg: &str,
+        local: bool,
+        state: &mut State,
+    ) -> Result<Option<String>, String> {
+        let file = self
+            .panes
+            .focused()
+            .and_then(|pane| state.filesystem.files.get_mut(pane.file));
+        if let Some(name) = arg.strip_suffix('?')
+            && matches!(name, "filetype" | "ft")
+        {
+            let filetype = file.and_then(|file| file.filetype).unwrap_or_default();
+            return Ok(Some(format!("  {}={}", name, filetype)));
+        }
+        if let Some(value) = arg
+            .strip_prefix("filetype=")
+            .or_else(|| arg.strip_prefix("ft="))
+        {
+            let Some(file) = file else {
+                return Ok(None);
+            };
+            let (Some(name), Some(options)) = (
+                filetype::known(value),
+                filetype::defaults(value, &state.config.buffer),
+            ) else {
+                return Err(format!("E474: Invalid argument: {}", arg));
+            };
+            file.filetype = Some(name);
+            file.options = Some(options);
+            file.option_sources.clear();
+            self.fire(AutoEvent::FileType, Some(Path::new(value)), state);
+            return Ok(None);
+        }
+        // The line breaks are a property of the file rather than of its filetype
+        if let Some(name) = arg.strip_suffix('?')
+            && matches!(name, "fileformat" | "ff")
+        {
+            let format = file.map(|file| file.fileformat).unwrap_or_default();
+            return Ok(Some(format!("  {}={}", name, format.name())));
+        }
+        if let Some(value) = arg
+            .strip_prefix("fileformat=")
+            .or_else(|| arg.strip_prefix("ff="))
+        {
+            let format = FileFormat::parse(value)
+                .ok_or_else(|| format!("E474: Invalid argument: {}", arg))?;
+            if let Some(file) = file
+                && file.fileformat != format
+            {
+                file.fileformat = format;
+                file.modified = !file.scratch;
+            }
+            return Ok(None);
+        }
+        // Window options apply to the focused pane, which stops overriding them with `:set`
+        if let Some(name) = WindowOptions::option_name(arg)
+            && let Some(pane) = self.panes.focused_mut()
+        {
+            let message = state.config.set_local(&mut pane.local, arg)?;
+            if !local
+                && let Some(value) = pane.local.get_mut(name).and_then(Option::take)
+                && let Some(option) = state.config.window.get_mut(name)
+            {
+                *option = value;
+            }
+            return Ok(message);
+        }
+        let Some(name) = BufferOptions::option_name(arg) else {
+            return state.config.set(arg);
+        };
+        let Some(file) = file else {
+            return state.config.buffer.set(arg);
+        };
+        let options = file
+            .options
+            .get_or_insert_with(|| state.config.buffer.clone());
+        if arg.ends_with('<') {
+            options.copy_option(name, &state.config.buffer);
+            file.option_sources.insert(name, OptionSource::Global);
+            return Ok(None);
+        }
+        match options.set(arg)? {
+            Some(message) => Ok(Some(format!(
+                "{} ({})",
+                message,
+                file.option_source(name).name()
+            ))),
+            None if local => {
+                file.option_sources.insert(name, OptionSource::Local);
+                Ok(None)
+            }
+            None => {
+                state.config.buffer.set(arg)?;
+                file.option_sources.insert(name, OptionSource::Global);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Jump to a 0-based line and column in the focused pane, recording the position
+    /// jumped from in the jump list
+    pub(super) fn goto(&mut self, line: usize, column: usize, state: &mut State) {
+        if let Some(jump) = self.panes.focused().and_then(|pane| pane.jump(state)) {
+            state.jumps.push(jump);
+        }
+        self.move_cursor(line, column, state);
+    }
+
+    /// Move the cursor of the focused pane to a 0-based line and column, centered in the view
+    pub(super) fn move_cursor(&mut self, line: usize, column: usize, state: &mut State) {
+        let Some(pane) = self.panes.focused_mut() else {
+            return;
+        };
+        let Some(rope) = &state.filesystem.files[pane.file].buffer else {
+            return;
+        };
+        pane.cursor.set_position(column, line, rope);
+        pane.open_folds();
+        pane.center_cursor(rope, state);
+    }
+
+    /// Delete the lines of a range, or the cursor line, in the focused pane, into a register
+    fn delete_lines(&mut self, range: Option<LineRange>, register: char, state: &mut State) {
+        let Some(pane) = self.panes.focused_mut() else {
+            return;
+        };
+        let file = &mut state.filesystem.files[pane.file];
+        let Some(rope) = &mut file.buffer else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        let range = range.unwrap_or(LineRange {
+            start: pane.cursor.y,
+            end: pane.cursor.y,
+        });
+
+        let text = pane.cursor.delete_lines(rope, range.start, range.end);
+        file.modified = !file.scratch;
+        state.set_register(register, text);
+    }
+
+    /// Replace the lines of a range of the focused buffer by a function of their text, the
+    /// range defaulting to lines found from the cursor line. The cursor goes to the first
+    /// line of the range.
+    fn replace_lines(
+        &mut self,
+        range: Option<LineRange>,
+        default: impl FnOnce(&Rope, usize) -> Result<LineRange, String>,
+        f: impl FnOnce(&[&str]) -> Vec<String>,
+        state: &mut State,
+    ) {
+        let Some(pane) = self.panes.focused_mut() else {
+            return;
+        };
+        let file = &mut state.filesystem.files[pane.file];
+        if file.readonly {
+            state.error(READONLY_ERROR);
+            return;
+        }
+        let Some(rope) = &mut file.buffer else {
+            return;
+        };
+        let range = match range.map_or_else(|| default(rope, pane.cursor.y), Ok) {
+            Ok(range) => range,
+            Err(err) => {
+                state.error(err);
+                return;
+            }
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+
+        if rope.replace_lines(range.start, range.end, f) {
+            file.modified = !file.scratch;
+        }
+        pane.cursor.set_position(0, range.start, rope);
+        pane.cursor.clamp(rope);
+    }
+
+    /// Sort, deduplicate or reverse the lines of a range, the whole buffer by default
+    fn reorder_lines(
+        &mut self,
+        range: Option<LineRange>,
+        operation: LineOperation,
+        state: &mut State,
+    ) {
+        let buffer = |rope: &Rope, _| {
+            Ok(LineRange {
+                start: 0,
+                end: line_count(rope) - 1,
+            })
+        };
+        let reorder = |lines: &[&str]| {
+            let lines = operation.apply(lines.to_vec());
+            lines.into_iter().map(str::to_string).collect()
+        };
+        self.replace_lines(range, buffer, reorder, state);
+    }
+
+    /// Align a delimiter on the lines of a range, by default on the lines around the
+    /// cursor containing it
+    fn align(&mut self, range: Option<LineRange>, delimiter: &str, state: &mut State) {
+        let around = |rope: &Rope, line| {
+            lines_around(rope, line, |text| text.contains(delimiter))
+                .ok_or_else(|| format!("E486: Pattern not found: {}", delimiter))
+        };
+        self.replace_lines(
+            range,
+            around,
+            |lines| format::align(lines, delimiter),
+            state,
+        );
+    }
+
+    /// Renumber the ordered lists of a range, by default of the paragraph around the
+    /// cursor, from the number of their first items or from a start number
+    fn renumber(&mut self, range: Option<LineRange>, args: &str, state: &mut State) {
+        let start = match args {
+            "" => None,
+            args => match args.parse() {
+                Ok(start) => Some(start),
+                Err(_) => {
+                    state.error(format!("E474: Invalid argument: {}", args));
+                    return;
+                }
+            },
+        };
+        let paragraph = |rope: &Rope, line| {
+            lines_around(rope, line, |text| !text.trim().is_empty())
+                .ok_or_else(|| "E486: No list found".to_string())
+        };
+        self.replace_lines(
+            range,
+            paragraph,
+            |lines| numbers::renumber(lines, start),
+            state,
+        );
+    }
+
+    /// Execute the startup script and the plugins, if there are some
+    pub fn source_startup_scripts(&mut self, state: &mut State) {
+        if let Some(dir) = Config::dir() {
+            let script = dir.join("init.vim");
+            if script.is_file() {
+                self.source(&script, state);
+            }
+
+            // Plugins are sourced in alphabetical order
+            let mut plugins = fs::read_dir(dir.join("plugins"))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext == "vim" || ext == "tes")
+                })
+                .collect::<Vec<_>>();
+            plugins.sort();
+            for plugin in plugins {
+                self.source(&plugin, state);
+            }
+        }
+    }
+
+    /// Execute the autocommands of an event, for the given file
+    pub fn fire(&mut self, event: AutoEvent, path: Option<&Path>, state: &mut State) {
+        // The data files are checked after writing them, before the autocommands
+        if event == AutoEvent::BufWritePost
+            && let Some(path) = path
+        {
+            self.validate(path, state);
+        }
+        if self.firing {
+            return;
+        }
+        self.firing = true;
+        for action in state.autocmds.matching(event, path) {
+            match action {
+                AutoAction::Command(command) => self.execute_chain(&command, 0, state),
+                AutoAction::Function(function) => {
+                    let path = path.map(|path| path.display().to_string());
+                    let args = vec![
+                        Value::Str(event.name().to_string()),
+                        Value::Str(path.unwrap_or_default()),
+                    ];
+                    self.call_function(&function, args, state);
+                }
+            }
+        }
+        self.firing = false;
+    }
+
+    /// Execute the lines of a script as ex commands.
+    /// Empty lines and `"` comments are skipped. `.tes` files are run as scripts instead.
+    fn source(&mut self, path: &Path, state: &mut State) {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                state.error(format!("E484: Can't open file {}", path.display()));
+                return;
+            }
+            Err(err) => {
+                state.error(format!("Failed to read {}: {}", path.display(), err));
+                return;
+            }
+        };
+        if path.extension().is_some_and(|ext| ext == "tes") {
+            self.source_script(path, &text, state);
+            return;
+        }
+
+        let mut first_error = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('"') {
+                continue;
+            }
+            let location = format!("{}:{}", path.display(), index + 1);
+            let previous = state.keymaps.source.replace(location);
+            self.execute_command(line, state);
+            state.keymaps.source = previous;
+            if let Some(message) = &state.message
+                && message.error
+                && first_error.is_none()
+            {
+                let text = format!("{}:{}: {}", path.display(), index + 1, message.text);
+                first_error = Some(text);
+            }
+        }
+        if let Some(text) = first_error {
+            state.error(text);
+        }
+    }
+
+    /// Define a mapping, or list the keybindings starting with the single argument
+    fn map(&mut self, modes: &[Mode], args: &str, state: &mut State) {
+        if args.contains(char::is_whitespace) {
+            if let Err(err) = state.keymaps.define(modes, args) {
+                state.error(err);
+            }
+            return;
+        }
+        let listing = state.keymaps.listing(modes, args);
+        if listing.is_empty() {
+            state.info("No mapping found");
+            return;
+        }
+        self.open_page("map", &listing, state);
+    }
+
+    /// Run a shell command in the root folder, in the background
+    fn shell(&mut self, command: &str, state: &mut State) {
+        if command.is_empty() {
+            state.error("E471: Argument required");
+            return;
+        }
+        let root = state.filesystem.folders[state.filesystem.root].path.clone();
+        let sender = state.events.editor_sender.clone();
+        let command = command.to_string();
+        state.info(format!(":!{}", command));
+
+        let name = format!("!{}", command);
+        state.jobs.spawn(name, |_| async move {
+            let result = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .current_dir(root)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await;
+            let (success, output) = match result {
+                Ok(output) => {
+                    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                    text.push_str(&String::from_utf8_lossy(&output.stderr));
+                    (output.status.success(), text)
+                }
+                Err(err) => (false, err.to_string()),
+            };
+            let event = EditorEvent::ShellFinished {
+                command,
+                success,
+                output,
+            };
+            if let Err(err) = sender.send(event).await {
+                log::error!("Failed to send shell command event: {}", err);
+            }
+        });
+    }
+
+    /// Apply the hunk under the cursor of a diff to the files of the root folder with
+    /// `git apply` in the background, or revert it with `reverse`
+    fn apply_hunk(&mut self, reverse: bool, state: &mut State) {
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let Some(rope) = &state.filesystem.files[pane.file].buffer else {
+            return;
+        };
+        let patch = match diff::hunk_patch(rope, pane.cursor.y) {
+            Ok(patch) => patch,
+            Err(err) => return state.error(err),
+        };
+        let root = state.filesystem.folders[state.filesystem.root].path.clone();
+        let sender = state.events.editor_sender.clone();
+        let command = match reverse {
+            true => "git apply --recount -R",
+            false => "git apply --recount",
+        };
+
+        state.jobs.spawn_blocking(command, move |_| {
+            let apply = || -> io::Result<std::process::Output> {
+                let mut child = std::process::Command::new("git")
+                    .args(command.split(' ').skip(1))
+                    .current_dir(root)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(patch.as_bytes())?;
+                }
+                child.wait_with_output()
+            };
+            let (success, output) = match apply() {
+                Ok(output) => {
+                    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                    text.push_str(&String::from_utf8_lossy(&output.stderr));
+                    (output.status.success(), text)
+                }
+                Err(err) => (false, err.to_string()),
+            };
+            let event = EditorEvent::ShellFinished {
+                command: command.to_string(),
+                success,
+                output,
+            };
+            if let Err(err) = sender.blocking_send(event) {
+                log::error!("Failed to send hunk applied event: {}", err);
+            }
+        });
+    }
+
+    /// Jump to the key at a path of the data file of the focused pane, like `.a.b[2]`
+    fn jq(&mut self, args: &str, state: &mut State) {
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let file = &state.filesystem.files[pane.file];
+        let path = match data::parse_path(args) {
+            Ok(path) => path,
+            Err(err) => return state.error(err),
+        };
+        let Some(result) = file
+            .filetype
+            .and_then(|filetype| data::parse(filetype, &file.text()))
+        else {
+            return state.error("Not a JSON, YAML or TOML file");
+        };
+        let root = match result {
+            Ok(root) => root,
+            Err(err) => {
+                let text = format!("{}:{}: {}", err.line + 1, err.column + 1, err.message);
+                return state.error(text);
+            }
+        };
+        match root.find(&path) {
+            Some(node) => {
+                let (line, column) = (node.line, node.column);
+                self.goto(line, column, state);
+            }
+            None => state.error(format!("No such key: {}", args)),
+        }
+    }
+
+    /// Check the syntax of a data file after writing it, its parse error replacing its
+    /// diagnostics
+    fn validate(&mut self, path: &Path, state: &mut State) {
+        let Some(file) = state
+            .filesystem
+            .find_file(path)
+            .map(|id| &state.filesystem.files[id])
+        else {
+            return;
+        };
+        let Some(result) = file
+            .filetype
+            .and_then(|filetype| data::parse(filetype, &file.text()))
+        else {
+            return;
+        };
+        let diagnostics = match result {
+            Ok(_) => vec![],
+            Err(err) => {
+                let text = format!(
+                    "{}:{}:{}: {}",
+                    state.filesystem.relative_path(path).display(),
+                    err.line + 1,
+                    err.column + 1,
+                    err.message
+                );
+                state.error(text);
+                vec![Diagnostic {
+                    line: err.line,
+                    column: err.column,
+                    severity: Severity::Error,
+                    message: err.message,
+                }]
+            }
+        };
+        state.diagnostics.set(path, diagnostics);
+    }
+
+    /// Run `makeprg` with arguments in the root folder, in the background, reading the
+    /// diagnostics of its output and jumping to the first one when `jump` is set
+    fn make(&mut self, args: &str, jump: bool, state: &mut State) {
+        let root = state.filesystem.folders[state.filesystem.root].path.clone();
+        let sender = state.events.editor_sender.clone();
+        let command = match args.is_empty() {
+            true => state.config.makeprg.clone(),
+            false => format!("{} {}", state.config.makeprg, args),
+        };
+        state.info(format!(":!{}", command));
+
+        state
+            .jobs
+            .spawn(format!("make: {}", command), |_| async move {
+                let result = tokio::process::Command::new("sh")
+                    .arg("-c")
+                    .arg(&command)
+                    .current_dir(&root)
+                    .stdin(Stdio::null())
+                    .kill_on_drop(true)
+                    .output()
+                    .await;
+                let (success, output) = match result {
+                    Ok(output) => {
+                        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                        text.push_str(&String::from_utf8_lossy(&output.stderr));
+                        (output.status.success(), text)
+                    }
+                    Err(err) => (false, err.to_string()),
+                };
+                let diagnostics = Diagnostics::parse(&output, &root);
+                let event = EditorEvent::MakeFinished {
+                    command,
+                    success,
+                    output,
+                    diagnostics,
+                    jump,
+                };
+                if let Err(err) = sender.send(event).await {
+                    log::error!("Failed to send make event: {}", err);
+                }
+            });
+    }
+
+    /// Insert a file, or the output of a `!` shell command, below the cursor line or the
+    /// last line of the range
+    fn read(&mut self, range: Option<LineRange>, args: &str, state: &mut State) {
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let (id, line) = (pane.file, range.map_or(pane.cursor.y, |range| range.end));
+        if args.is_empty() {
+            state.error("E32: No file name");
+            return;
+        }
+        let Some(command) = args.strip_prefix('!') else {
+            let path = absolute_path(&expand_path(args));
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    self.insert_below(id, line, &text, state);
+                    state.info(format!(
+                        "\"{}\" {}L, {}B",
+                        state.filesystem.relative_path(&path).display(),
+                        text.lines().count(),
+                        text.len()
+                    ));
+                }
+                Err(err) => state.error(format!("E484: Can't open file {}: {}", args, err)),
+            }
+            return;
+        };
+        let root = state.filesystem.folders[state.filesystem.root].path.clone();
+        let sender = state.events.editor_sender.clone();
+        let command = command.trim().to_string();
+        state.info(format!(":r !{}", command));
+
+        let name = format!("read !{}", command);
+        state.jobs.spawn(name, |_| async move {
+            let result = tokio::process::Command::new("sh")
+                .arg("-c")
+                .arg(&command)
+                .current_dir(root)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await;
+            let text = match result {
+                Ok(output) if output.status.success() => {
+                    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+                }
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    let reason = stderr.lines().rfind(|line| !line.trim().is_empty());
+                    Err(format!(
+                        "shell returned: {}",
+                        reason.unwrap_or(&command).trim()
+                    ))
+                }
+                Err(err) => Err(err.to_string()),
+            };
+            let event = EditorEvent::ReadOutput {
+                file: id,
+                line,
+                text,
+            };
+            if let Err(err) = sender.send(event).await {
+                log::error!("Failed to send read command event: {}", err);
+            }
+        });
+    }
+
+    /// Insert lines below a line of a buffer in one edit, moving the cursor of the focused
+    /// pane to the first of them
+    pub(super) fn insert_below(&mut self, id: FileId, line: usize, text: &str, state: &mut State) {
+        let Some(file) = state.filesystem.files.get_mut(id) else {
+            return;
+        };
+        if file.readonly {
+            state.error(READONLY_ERROR);
+            return;
+        }
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        if text.is_empty() {
+            return;
+        }
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        // The buffer may have changed while the text was read
+        let line = (line + 1).min(rope.len_lines());
+        let at = rope.line_to_char(line);
+        let mut text = text.to_string();
+        // The last line may have no line break to insert after
+        if at == rope.len_chars() && at > 0 && rope.char(at - 1) != '\n' {
+            text.insert(0, '\n');
+        } else if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        rope.insert(at, &text);
+        file.modified = !file.scratch;
+
+        if let Some(pane) = self.panes.focused_mut()
+            && pane.file == id
+        {
+            pane.cursor.move_to_char(rope, rope.line_to_char(line));
+        }
+        self.panes.clamp_cursors(state);
+    }
+
+    /// Open the builtin help at a topic, reusing the help buffer
+    pub(super) fn help(&mut self, topic: &str, state: &mut State) {
+        let line = match topic {
+            "" => Some(0),
+            topic => help::find_tag(topic),
+        };
+        let Some(line) = line else {
+            state.error(format!("E149: Sorry, no help for {}", topic));
+            return;
+        };
+        let existing = state
+            .filesystem
+            .files
+            .iter()
+            .find(|(_, file)| file.readonly && file.name == "help")
+            .map(|(id, _)| id);
+        let id = existing.unwrap_or_else(|| {
+            state
+                .filesystem
+                .files
+                .insert(File::page("help", help::HELP))
+        });
+        self.panes.open(id);
+        self.focus = Focus::Panes;
+        if let Some(pane) = self.panes.focused_mut()
+            && let Some(rope) = &state.filesystem.files[id].buffer
+        {
+            pane.cursor.set_position(0, line, rope);
+        }
+    }
+
+    /// Move through the undo tree of the buffer of the focused pane
+    fn undo(&mut self, motion: UndoMotion, state: &mut State) {
+        if let Some(pane) = self.panes.focused_mut() {
+            pane.undo(motion, state);
+        }
+    }
+
+    /// List the background jobs, or kill one with `:jobs kill {id}`
+    fn jobs(&mut self, args: &str, state: &mut State) {
+        let id = match args.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => {
+                let listing = state.jobs.listing();
+                self.open_page("jobs", &listing, state);
+                return;
+            }
+            ["kill", id] => id,
+            _ => {
+                state.error(format!("E474: Invalid argument: {}", args));
+                return;
+            }
+        };
+        match id
+            .parse()
+            .map_err(|_| format!("E900: Invalid job id: {}", id))
+        {
+            Ok(id) => match state.jobs.kill(id) {
+                Ok(()) => state.info(format!("Killed job {}", id)),
+                Err(err) => state.error(err),
+            },
+            Err(err) => state.error(err),
+        }
+    }
+
+    /// Render a man page in the background, to open it in a buffer
+    fn man(&mut self, page: &str, state: &mut State) {
+        let sender = state.events.editor_sender.clone();
+        let page = page.to_string();
+        state.jobs.spawn(format!("man {}", page), |_| async move {
+            let result = tokio::process::Command::new("man")
+                .args(page.split_whitespace())
+                .env("MANPAGER", "cat")
+                .env("MANWIDTH", "80")
+                .env("GROFF_NO_SGR", "1")
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await;
+            let text = match result {
+                Ok(output) if output.status.success() => {
+                    Ok(plain_text(&String::from_utf8_lossy(&output.stdout)))
+                }
+                Ok(_) => Err(format!("No manual entry for {}", page)),
+                Err(err) => Err(format!("Failed to run man: {}", err)),
+            };
+            if let Err(err) = sender.send(EditorEvent::ManPage { page, text }).await {
+                log::error!("Failed to send man page event: {}", err);
+            }
+        });
+    }
+
+    /// Write the buffer of the focused pane, or a copy of it to the file argument.
+    /// A buffer without a name is bound to the file it is written to.
+    /// Returns whether the write succeeded.
+    fn write(&mut self, command: &str, args: &str, force: bool, state: &mut State) -> bool {
+        let Some(pane) = self.panes.focused() else {
+            state.error("E32: No file name");
+            return false;
+        };
+        let id = pane.file;
+        let (create, name) = parse_create_flag(args);
+        let file = &state.filesystem.files[id];
+        // Remote buffers are uploaded in the background
+        if name.is_empty()
+            && let Some(remote) = file.remote.clone()
+        {
+            let text = file.text();
+            let message = format!("Writing {}...", file.path.display());
+            remote::upload(
+                remote,
+                state.events.editor_sender.clone(),
+                &state.jobs,
+                id,
+                text,
+            );
+            state.info(message);
+            return false;
+        }
+        if Remote::parse(name).is_some() {
+            state.error("Only remote buffers can be written to a remote file");
+            return false;
+        }
+        let path = match name.is_empty() {
+            true => file.path.clone(),
+            false => absolute_path(&expand_path(name)),
+        };
+        // Ask for the name of new buffers
+        if path.as_os_str().is_empty() {
+            let text = format!("{}{} ", command, bang(force));
+            self.handle_action(Action::Cmdline(text), state);
+            state.info("E32: No file name, enter one to write the buffer");
+            return false;
+        }
+        if path != file.path && path.exists() && !force {
+            state.error("E13: File exists (add ! to override)");
+            return false;
+        }
+        let command = format!("{}{}", command, bang(force));
+        if !self.ensure_parent(&path, create, &command, name, state) {
+            return false;
+        }
+
+        let file = &state.filesystem.files[id];
+        if !force && path == file.path && file.changed_on_disk() {
+            state.error("W12: The file has been changed since reading it (add ! to override)");
+            return false;
+        }
+        if !force && is_readonly(&path) {
+            state.error("E45: The file is read-only (add ! to override)");
+            return false;
+        }
+
+        if state.filesystem.files[id].path.as_os_str().is_empty() {
+            state.filesystem.set_file_path(id, &path);
+        }
+        let new = !path.exists();
+        let file = &mut state.filesystem.files[id];
+        let result = writable(&path, || match path == file.path {
+            true => file.write(),
+            false => file.write_to(&path),
+        });
+        match result {
+            Ok(()) => {
+                let message = written_message(id, &path, new, state);
+                state.info(message);
+                self.fire(AutoEvent::BufWritePost, Some(&path), state);
+                true
+            }
+            Err(err) => {
+                state.error(format!("Failed to write {}: {}", path.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Write a range of lines of the focused buffer to a file, or append them to it with
+    /// `>>`, the buffer staying bound to its file. Returns whether the write succeeded.
+    fn write_part(
+        &mut self,
+        range: Option<LineRange>,
+        args: &str,
+        force: bool,
+        state: &mut State,
+    ) -> bool {
+        let Some(pane) = self.panes.focused() else {
+            state.error("E32: No file name");
+            return false;
+        };
+        let id = pane.file;
+        let (append, args) = match args.strip_prefix(">>") {
+            Some(args) => (true, args.trim_start()),
+            None => (false, args),
+        };
+        let (create, name) = parse_create_flag(args);
+        let file = &state.filesystem.files[id];
+        if Remote::parse(name).is_some() || name.is_empty() && file.remote.is_some() {
+            state.error("Only whole buffers can be written to a remote file");
+            return false;
+        }
+        let path = match name.is_empty() {
+            true => file.path.clone(),
+            false => absolute_path(&expand_path(name)),
+        };
+        if path.as_os_str().is_empty() {
+            state.error("E32: No file name");
+            return false;
+        }
+        let exists = path.exists();
+        if append && !exists && !force {
+            state.error("E212: Cannot append to a missing file (add ! to create it)");
+            return false;
+        }
+        if !append && exists && !force {
+            match path == file.path {
+                true => state.error("E140: Use ! to write partial buffer"),
+                false => state.error("E13: File exists (add ! to override)"),
+            }
+            return false;
+        }
+        if !force && is_readonly(&path) {
+            state.error("E45: The file is read-only (add ! to override)");
+            return false;
+        }
+        let command = format!(
+            "{}write{} {}",
+            range.map_or(String::new(), |range| format!(
+                "{},{}",
+                range.start + 1,
+                range.end + 1
+            )),
+            bang(force),
+            if append { ">>" } else { "" }
+        );
+        if !self.ensure_parent(&path, create, command.trim_end(), name, state) {
+            return false;
+        }
+
+        let file = &state.filesystem.files[id];
+        let lines = match range {
+            Some(range) => range.start..range.end + 1,
+            None => 0..file.buffer.as_ref().map_or(0, line_count),
+        };
+        let count = lines.len();
+        match writable(&path, || file.write_lines(&path, lines, append)) {
+            Ok(bytes) => {
+                // Writing over the file of the buffer is not a change made by another program
+                if path == file.path {
+                    state.filesystem.files[id].mtime = state.filesystem.files[id].disk_mtime();
+                }
+                let action = if append { "appended" } else { "written" };
+                let message = format!(
+                    "\"{}\" {}{}L, {}B {}",
+                    state.filesystem.relative_path(&path).display(),
+                    if exists { "" } else { "[New] " },
+                    count,
+                    bytes,
+                    action
+                );
+                state.info(message);
+                true
+            }
+            Err(err) => {
+                state.error(format!("Failed to write {}: {}", path.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Write the buffer of the focused pane to a new path, and bind the buffer to it.
+    /// Existing files are only overwritten when forced.
+    fn save_as(&mut self, args: &str, force: bool, state: &mut State) {
+        let (create, name) = parse_create_flag(args);
+        if name.is_empty() {
+            state.error("E471: Argument required");
+            return;
+        }
+        if Remote::parse(name).is_some() {
+            state.error("Only remote buffers can be written to a remote file");
+            return;
+        }
+        let path = absolute_path(&expand_path(name));
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let id = pane.file;
+        if path.exists() && !force {
+            state.error("E13: File exists (add ! to override)");
+            return;
+        }
+        if !self.ensure_parent(
+            &path,
+            create,
+            &format!("saveas{}", bang(force)),
+            name,
+            state,
+        ) {
+            return;
+        }
+        let new = !path.exists();
+        let file = &state.filesystem.files[id];
+        if let Err(err) = writable(&path, || file.write_to(&path)) {
+            state.error(format!("Failed to write {}: {}", path.display(), err));
+            return;
+        }
+        state.filesystem.set_file_path(id, &path);
+        let file = &mut state.filesystem.files[id];
+        file.modified = false;
+        file.undo.mark_saved();
+        file.mtime = file.disk_mtime();
+        let message = written_message(id, &path, new, state);
+        state.info(message);
+        self.fire(AutoEvent::BufWritePost, Some(&path), state);
+    }
+
+    /// Write the panes and their files to a session file, restored with `-S`.
+    /// An existing file is only overwritten when forced.
+    fn mksession(&mut self, args: &str, force: bool, state: &mut State) {
+        let name = match args {
+            "" => session::DEFAULT_FILE,
+            args => args,
+        };
+        let path = expand_path(name);
+        if path.exists() && !force {
+            state.error(format!("E189: \"{}\" exists (add ! to override)", name));
+            return;
+        }
+        let session = self.panes.session(state);
+        match fs::write(&path, session.format()) {
+            Ok(()) => state.info(format!("\"{}\" session written", name)),
+            Err(err) => state.error(format!(
+                "E190: Cannot open \"{}\" for writing: {}",
+                name, err
+            )),
+        }
+    }
+
+    /// Bind the buffer of the focused pane to a new path, without writing it
+    fn name_buffer(&mut self, args: &str, state: &mut State) {
+        let (create, name) = parse_create_flag(args);
+        if name.is_empty() {
+            state.error("E471: Argument required");
+            return;
+        }
+        let path = absolute_path(&expand_path(name));
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let id = pane.file;
+        if !self.ensure_parent(&path, create, "file", name, state) {
+            return;
+        }
+        state.filesystem.set_file_path(id, &path);
+        state.filesystem.files[id].modified = !state.filesystem.files[id].scratch;
+        self.file_info(state);
+    }
+
+    /// Display the name, state and size of the focused buffer
+    fn file_info(&self, state: &mut State) {
+        let Some(file) = self
+            .panes
+            .focused()
+            .map(|pane| &state.filesystem.files[pane.file])
+        else {
+            return;
+        };
+        let name = match file.path.as_os_str().is_empty() {
+            true => "[No Name]".to_string(),
+            false => state
+                .filesystem
+                .relative_path(&file.path)
+                .display()
+                .to_string(),
+        };
+        let lines = file.buffer.as_ref().map_or(0, line_count);
+        let modified = if file.modified { " [Modified]" } else { "" };
+        state.info(format!("\"{}\"{} {} lines", name, modified, lines));
+    }
+
+    /// Make sure the parent directory of a path exists before writing to it.
+    /// Missing directories are created when `create` is set, or else the command is
+    /// proposed again with `++p` in the cmdline, submitting it confirms the creation.
+    fn ensure_parent(
+        &mut self,
+        path: &Path,
+        create: bool,
+        command: &str,
+        args: &str,
+        state: &mut State,
+    ) -> bool {
+        let Some(parent) = path.parent().filter(|parent| !parent.exists()) else {
+            return true;
+        };
+        if !create {
+            let text = format!("{} ++p {}", command, args).trim_end().to_string();
+            self.handle_action(Action::Cmdline(text), state);
+            let dir = state.filesystem.relative_path(parent).display().to_string();
+            state.info(format!(
+                "Directory {} does not exist, submit to create it",
+                dir
+            ));
+            return false;
+        }
+        match fs::create_dir_all(parent) {
+            Ok(()) => true,
+            Err(err) => {
+                state.error(format!("Failed to create {}: {}", parent.display(), err));
+                false
+            }
+        }
+    }
+
+    /// Write all the modified buffers.
+    /// Returns whether all writes succeeded, remote buffers being still uploaded.
+    fn write_all(&mut self, state: &mut State) -> bool {
+        let mut ids = state
+            .filesystem
+            .open_buffers
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        ids.retain(|id| state.filesystem.files[*id].modified);
+        let mut uploading = false;
+        for id in ids {
+            let file = &mut state.filesystem.files[id];
+            if let Some(remote) = file.remote.clone() {
+                let text = file.text();
+                remote::upload(
+                    remote,
+                    state.events.editor_sender.clone(),
+                    &state.jobs,
+                    id,
+                    text,
+                );
+                uploading = true;
+                continue;
+            }
+            if let Err(err) = file.write() {
+                let text = format!("Failed to write {}: {}", file.path.display(), err);
+                state.error(text);
+                return false;
+            }
+            let path = file.path.clone();
+            self.fire(AutoEvent::BufWritePost, Some(&path), state);
+        }
+        if uploading {
+            state.info("Writing the remote buffers...");
+        }
+        !uploading
+    }
+
+    /// Close the focused pane, or exit the editor when it is the last one.
+    /// In the command-line window, close the window instead.
+    fn quit(&mut self, force: bool, state: &mut State) {
+        if self.focus == Focus::CmdlineWindow {
+            self.close_cmdwin(state);
+            return;
+        }
+        // The staged changes are not left alone once the commit message is closed
+        let staged_diff = self.staged_diff.filter(|diff| {
+            self.panes.count_file(*diff) == 1
+                && self.panes.focused().is_some_and(|pane| pane.file != *diff)
+        });
+        if self.panes.len() <= 1 || (self.panes.len() == 2 && staged_diff.is_some()) {
+            self.quit_all(force, state);
+            return;
+        }
+        // The buffer can be closed if it is displayed in another pane
+        if let Some(pane) = self.panes.focused()
+            && state.filesystem.files[pane.file].modified
+            && self.panes.count_file(pane.file) == 1
+            && !force
+        {
+            state.error("E37: No write since last change (add ! to override)");
+            return;
+        }
+        self.panes.close_focused();
+    }
+
+    /// Exit the editor, unless there are unsaved changes
+    fn quit_all(&mut self, force: bool, state: &mut State) {
+        let modified = state
+            .filesystem
+            .open_buffers
+            .iter()
+            .map(|id| &state.filesystem.files[*id])
+            .find(|file| file.modified);
+        if let Some(file) = modified
+            && !force
+        {
+            let text = format!(
+                "E162: No write since last change for buffer \"{}\"",
+                file.name
+            );
+            state.error(text);
+            return;
+        }
+        state.exit = true;
+    }
+}
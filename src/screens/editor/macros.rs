@@ -0,0 +1,104 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    State,
+    screens::Screen,
+    state::{key_notation, parse_keys},
+};
+
+use super::EditorScreen;
+
+/// Replayed keys handled between two checks of the interrupt key
+const REPLAY_BATCH: usize = 256;
+
+impl EditorScreen {
+    /// Start recording the typed keys into a register, shown in the lualine
+    pub(super) fn record(&mut self, register: char, state: &mut State) {
+        self.recorded.clear();
+        state.recording = Some(register);
+    }
+
+    /// Store the recorded keys in their register, without the `q` that stopped the recording
+    pub(super) fn stop_recording(&mut self, state: &mut State) {
+        let Some(register) = state.recording.take() else {
+            return;
+        };
+        self.recorded.pop();
+        let keys = self
+            .recorded
+            .drain(..)
+            .map(key_notation)
+            .collect::<String>();
+        state.registers.set(register, keys);
+        state.message = None;
+    }
+
+    /// Queue the keys of a register before the keys left to replay, so that the macros
+    /// replayed by a macro run in place
+    pub(super) fn replay(&mut self, register: char, state: &mut State) {
+        let register = match register {
+            '@' => match self.last_replayed {
+                Some(register) => register,
+                None => return state.error("E748: No previously used register"),
+            },
+            register => register,
+        };
+        let keys = match state.register(register) {
+            Ok(keys) => parse_keys(&keys),
+            Err(err) => return state.error(err),
+        };
+        self.last_replayed = Some(register);
+        for key in keys.into_iter().rev() {
+            self.replay.push_front(key);
+        }
+    }
+
+    /// Whether replayed keys are left to handle, the main loop resuming the replay
+    /// without rendering the intermediate states
+    pub fn replaying(&self) -> bool {
+        !self.replay.is_empty()
+    }
+
+    /// Handle the next batch of replayed keys after the terminal events received since
+    /// the last one. CTRL-C interrupts endless macros, the other events being handled
+    /// once the replay ends.
+    pub fn resume_replay(&mut self, events: Vec<Event>, state: &mut State) {
+        for event in events {
+            match event {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                }) => {
+                    self.replay.clear();
+                    state.error("Interrupted");
+                }
+                event => self.typed.push(event),
+            }
+        }
+        self.run_replay(state);
+    }
+
+    /// Handle a batch of the replayed keys in a row, and the events typed during the
+    /// replay once it ends
+    pub(super) fn run_replay(&mut self, state: &mut State) {
+        for _ in 0..REPLAY_BATCH {
+            let Some(key_event) = self.replay.pop_front() else {
+                break;
+            };
+            if state.exit {
+                self.replay.clear();
+                break;
+            }
+            self.handle_key_event(key_event, state);
+        }
+        // The typed events may start another replay, which the next ones wait for
+        let mut typed = std::mem::take(&mut self.typed).into_iter();
+        while !self.replaying()
+            && let Some(event) = typed.next()
+        {
+            self.handle(event, state);
+        }
+        self.typed.extend(typed);
+    }
+}
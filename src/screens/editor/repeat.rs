@@ -0,0 +1,81 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{State, state::Mode, widgets::Pane};
+
+use super::EditorScreen;
+
+impl EditorScreen {
+    /// Handle a key of the focused pane, recording the keys of the changes for `.`: a
+    /// change starts with a normal mode command and lasts until the pane is back in
+    /// normal mode with no pending keys, like `dw`, `ci(foo<Esc>` or `vjd`
+    pub(super) fn handle_pane_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let file = pane.file;
+        if state.mode == Mode::Normal && !pane.is_pending() {
+            self.change.clear();
+            self.change_edits = false;
+        }
+        self.change.push(key_event);
+
+        let edits = |state: &State| {
+            state
+                .filesystem
+                .files
+                .get(file)
+                .map_or(0, |file| file.changes.changes.len())
+        };
+        let before = edits(state);
+        if let Some(action) = self.panes.handle_key_event(key_event, state) {
+            self.handle_action(action, state);
+        }
+        self.change_edits |= edits(state) != before;
+
+        let done =
+            state.mode == Mode::Normal && !self.panes.focused().is_some_and(Pane::is_pending);
+        if done {
+            if self.change_edits && !is_undo(&self.change) {
+                self.last_change = std::mem::take(&mut self.change);
+            }
+            self.change.clear();
+        }
+    }
+
+    /// Record text typed or pasted into the pane as part of the current change
+    pub(super) fn extend_change(&mut self, text: &str) {
+        if self.change.is_empty() {
+            return;
+        }
+        let keys = text
+            .chars()
+            .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        self.change.extend(keys);
+        self.change_edits = true;
+    }
+
+    /// Repeat the last change with `.`, its keys being handled before the keys left to
+    /// replay, so that `.` works in macros
+    pub(super) fn repeat_change(&mut self, state: &mut State) {
+        if self.last_change.is_empty() {
+            return state.error("No change to repeat");
+        }
+        for key in self.last_change.iter().rev() {
+            self.replay.push_front(*key);
+        }
+    }
+}
+
+/// Whether the keys of a change undo or redo the previous ones, which is no change to
+/// repeat
+fn is_undo(keys: &[KeyEvent]) -> bool {
+    let ctrl = |key: &KeyEvent| key.modifiers.contains(KeyModifiers::CONTROL);
+    match keys {
+        [key, ..] if key.code == KeyCode::Char('u') && !ctrl(key) => true,
+        [key, ..] if key.code == KeyCode::Char('r') && ctrl(key) => true,
+        [g, key, ..] => {
+            g.code == KeyCode::Char('g') && matches!(key.code, KeyCode::Char('-' | '+'))
+        }
+        _ => false,
+    }
+}
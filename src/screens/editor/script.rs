@@ -0,0 +1,290 @@
+use std::path::Path;
+
+use ropey::Rope;
+
+use crate::{
+    State,
+    state::{Edit, Host, Mode, Prompt, Script, Value},
+    widgets::READONLY_ERROR,
+};
+
+use super::EditorScreen;
+
+/// Editor functions available to the scripts
+const FUNCTIONS: &[&str] = &[
+    "echo",
+    "error",
+    "execute",
+    "buffer_path",
+    "line_count",
+    "get_line",
+    "set_line",
+    "append_line",
+    "delete_line",
+    "cursor",
+    "set_cursor",
+    "map",
+    "command",
+    "autocmd",
+    "prompt",
+];
+
+/// Gives the scripts access to the editor while they run
+struct EditorHost<'a> {
+    editor: &'a mut EditorScreen,
+    state: &'a mut State,
+    /// Nesting of the command that started the script
+    depth: usize,
+}
+
+impl EditorHost<'_> {
+    /// Buffer of the focused pane
+    fn rope(&self) -> Result<&Rope, String> {
+        self.editor
+            .panes
+            .focused()
+            .and_then(|pane| self.state.filesystem.files[pane.file].buffer.as_ref())
+            .ok_or_else(|| "No buffer".to_string())
+    }
+
+    /// Edit the buffer of the focused pane, then keep the cursors inside the buffer
+    fn edit(&mut self, edit: impl FnOnce(&mut Edit) -> Result<(), String>) -> Result<(), String> {
+        let Some(pane) = self.editor.panes.focused() else {
+            return Err("No buffer".to_string());
+        };
+        let file = &mut self.state.filesystem.files[pane.file];
+        if file.readonly {
+            return Err(READONLY_ERROR.to_string());
+        }
+        let Some(rope) = &mut file.buffer else {
+            return Err("No buffer".to_string());
+        };
+        edit(&mut Edit::new(rope, &mut file.changes))?;
+        file.modified = !file.scratch;
+        self.editor.panes.clamp_cursors(self.state);
+        Ok(())
+    }
+
+    /// Name of a script function passed as a callback
+    fn function(&self, value: &Value) -> Result<String, String> {
+        let name = match value {
+            Value::Fn(name) | Value::Str(name) => name,
+            value => return Err(format!("Expected a function, got {}", value.type_name())),
+        };
+        match self.state.scripts.defines(name) {
+            true => Ok(name.clone()),
+            false => Err(format!("Unknown function: {}", name)),
+        }
+    }
+}
+
+/// Index of a 1-based line number
+fn line_index(rope: &Rope, line: i64) -> Result<usize, String> {
+    match usize::try_from(line) {
+        Ok(line @ 1..) if line <= rope.len_lines() => Ok(line - 1),
+        _ => Err(format!("Invalid line number: {}", line)),
+    }
+}
+
+/// Char range of a line, without its line ending
+fn line_chars(rope: &Rope, index: usize) -> (usize, usize) {
+    let start = rope.line_to_char(index);
+    let line = rope.line(index);
+    let mut len = line.len_chars();
+    while len > 0 && matches!(line.char(len - 1), '\n' | '\r') {
+        len -= 1;
+    }
+    (start, start + len)
+}
+
+impl Host for EditorHost<'_> {
+    fn call(&mut self, name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+        let result = match (name, args) {
+            ("echo", [value]) => {
+                self.state.info(value.to_string());
+                Ok(Value::Unit)
+            }
+            ("error", [value]) => {
+                self.state.error(value.to_string());
+                Ok(Value::Unit)
+            }
+            ("execute", [Value::Str(line)]) => {
+                self.state.message = None;
+                self.editor.execute_chain(line, self.depth + 1, self.state);
+                match &self.state.message {
+                    Some(message) if message.error => Err(message.text.clone()),
+                    _ => Ok(Value::Unit),
+                }
+            }
+            ("buffer_path", []) => {
+                let path = self
+                    .editor
+                    .panes
+                    .focused()
+                    .map(|pane| self.state.filesystem.files[pane.file].path.clone());
+                Ok(Value::Str(path.unwrap_or_default().display().to_string()))
+            }
+            ("line_count", []) => self.rope().map(|rope| Value::Int(rope.len_lines() as i64)),
+            ("get_line", [Value::Int(line)]) => self.rope().and_then(|rope| {
+                let (start, end) = line_chars(rope, line_index(rope, *line)?);
+                Ok(Value::Str(rope.slice(start..end).to_string()))
+            }),
+            ("set_line", [Value::Int(line), Value::Str(text)]) => self
+                .edit(|rope| {
+                    let (start, end) = line_chars(rope, line_index(rope, *line)?);
+                    rope.remove(start..end);
+                    rope.insert(start, text);
+                    Ok(())
+                })
+                .map(|()| Value::Unit),
+            // Insert a line after the given one, 0 inserting it at the top
+            ("append_line", [Value::Int(line), Value::Str(text)]) => self
+                .edit(|rope| {
+                    let index = match line {
+                        0 => 0,
+                        line => line_index(rope, *line)? + 1,
+                    };
+                    if index < rope.len_lines() {
+                        rope.insert(rope.line_to_char(index), &format!("{}\n", text));
+                    } else {
+                        rope.insert(rope.len_chars(), &format!("\n{}", text));
+                    }
+                    Ok(())
+                })
+                .map(|()| Value::Unit),
+            ("delete_line", [Value::Int(line)]) => self
+                .edit(|rope| {
+                    let index = line_index(rope, *line)?;
+                    let mut start = rope.line_to_char(index);
+                    let end = rope.line_to_char((index + 1).min(rope.len_lines()));
+                    // Deleting the last line also deletes the newline before it
+                    if end == rope.len_chars() && start > 0 && rope.char(end.max(1) - 1) != '\n' {
+                        start -= 1;
+                    }
+                    rope.remove(start..end);
+                    Ok(())
+                })
+                .map(|()| Value::Unit),
+            ("cursor", []) => match self.editor.panes.focused() {
+                Some(pane) => Ok(Value::Array(vec![
+                    Value::Int(pane.cursor.y as i64 + 1),
+                    Value::Int(pane.cursor.x as i64 + 1),
+                ])),
+                None => Err("No buffer".to_string()),
+            },
+            ("set_cursor", [Value::Int(line), Value::Int(col)]) => {
+                let position = self.rope().and_then(|rope| {
+                    let index = line_index(rope, *line)?;
+                    let (start, end) = line_chars(rope, index);
+                    let col = usize::try_from(col.saturating_sub(1)).unwrap_or_default();
+                    Ok((col.min(end - start), index))
+                });
+                match (position, self.editor.panes.focused_mut()) {
+                    (Ok((col, line)), Some(pane)) => {
+                        if let Some(rope) = &self.state.filesystem.files[pane.file].buffer {
+                            pane.cursor.set_position(col, line, rope);
+                        }
+                        Ok(Value::Unit)
+                    }
+                    (Err(err), _) => Err(err),
+                    (_, None) => Err("No buffer".to_string()),
+                }
+            }
+            // Modes are given by their letters, such as "nv" for normal and visual
+            ("map", [Value::Str(modes), Value::Str(lhs), callback]) => {
+                let modes = modes
+                    .chars()
+                    .map(|c| match c {
+                        'n' => Ok(Mode::Normal),
+                        'v' => Ok(Mode::Visual),
+                        'i' => Ok(Mode::Insert),
+                        c => Err(format!("Invalid mode: {}", c)),
+                    })
+                    .collect::<Result<Vec<_>, _>>();
+                modes.and_then(|modes| {
+                    let function = self.function(callback)?;
+                    let args = format!("{} :call {}()<CR>", lhs, function);
+                    self.state.keymaps.define(&modes, &args)?;
+                    Ok(Value::Unit)
+                })
+            }
+            // The command arguments are passed as a string
+            ("command", [Value::Str(name), callback]) => {
+                self.function(callback).and_then(|function| {
+                    let args = format!("{} call {}(<q-args>)", name, function);
+                    self.state.commands.define(&args, true)?;
+                    Ok(Value::Unit)
+                })
+            }
+            // The callback receives the event name and the file path
+            ("autocmd", [Value::Str(events), Value::Str(pattern), callback]) => {
+                self.function(callback).and_then(|function| {
+                    self.state
+                        .autocmds
+                        .define_function(events, pattern, &function)?;
+                    Ok(Value::Unit)
+                })
+            }
+            // The callback receives the submitted text
+            ("prompt", [Value::Str(label), callback]) => self.function(callback).map(|function| {
+                self.state.scripts.prompt = Some((label.clone(), function));
+                self.editor.cmdline.set_text("");
+                self.state.open_cmdline(Prompt::Input);
+                Value::Unit
+            }),
+            (name, args) if FUNCTIONS.contains(&name) => {
+                let types = args.iter().map(Value::type_name).collect::<Vec<_>>();
+                Err(format!(
+                    "Invalid arguments for {}: ({})",
+                    name,
+                    types.join(", ")
+                ))
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+impl EditorScreen {
+    /// Run a script, showing its error in the lualine
+    fn run_script(&mut self, script: Script, depth: usize, state: &mut State) {
+        let mut host = EditorHost {
+            editor: self,
+            state,
+            depth,
+        };
+        if let Err(err) = script.run(&mut host) {
+            state.error(err);
+        }
+    }
+
+    /// Evaluate the script statements of `:call`
+    pub(super) fn call(&mut self, text: &str, depth: usize, state: &mut State) {
+        match state.scripts.load("call", text) {
+            Ok(script) => self.run_script(script, depth, state),
+            Err(err) => state.error(err),
+        }
+    }
+
+    /// Call a script function from an editor callback
+    pub(super) fn call_function(&mut self, function: &str, args: Vec<Value>, state: &mut State) {
+        let script = state.scripts.call(function, args);
+        self.run_script(script, 0, state);
+    }
+
+    /// Load a `.tes` script, defining its functions and running its top level statements
+    pub(super) fn source_script(&mut self, path: &Path, text: &str, state: &mut State) {
+        match state.scripts.load(&path.display().to_string(), text) {
+            Ok(script) => self.run_script(script, 0, state),
+            Err(err) => state.error(err),
+        }
+    }
+
+    /// Pass the text submitted to a script prompt to its callback
+    pub(super) fn submit_input(&mut self, text: &str, state: &mut State) {
+        if let Some((_, function)) = state.scripts.prompt.take() {
+            self.call_function(&function, vec![Value::Str(text.to_string())], state);
+        }
+    }
+}
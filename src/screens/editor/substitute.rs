@@ -0,0 +1,336 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ropey::Rope;
+
+use crate::{
+    State,
+    position::{line_count, line_len},
+    state::{
+        Edit, FileId, Pattern, Search, Substitute, SubstituteFlags, commands::LineRange, expand,
+    },
+    widgets::READONLY_ERROR,
+};
+
+use super::EditorScreen;
+
+/// Substitution going through the matches of a line range
+#[derive(Debug, Clone)]
+pub(super) struct Substitution {
+    file: FileId,
+    pattern: Pattern,
+    replacement: String,
+    global: bool,
+    count_only: bool,
+    /// Line and char column to search the next match from
+    line: usize,
+    column: usize,
+    /// Last line of the range, moved by the line breaks of the replacements
+    end: usize,
+    /// Column where an empty match is skipped, right after the previous match
+    after_match: Option<usize>,
+    /// Whether a match was found, even if skipped
+    found: bool,
+    /// Number of matches replaced or counted, and of their lines
+    count: usize,
+    lines: usize,
+    /// Line the last replacement ends on
+    last_line: Option<usize>,
+}
+
+/// Match of a substitution, as a line and a char range in the line
+#[derive(Debug, Clone, Copy)]
+struct Match {
+    line: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Substitution {
+    /// Next match in the range, starting from the current position
+    fn next_match(&self, rope: &Rope) -> Option<Match> {
+        let mut column = self.column;
+        let mut line = self.line;
+        while line <= self.end.min(line_count(rope).saturating_sub(1)) {
+            let text = line_text(rope, line);
+            let len = text.chars().count();
+            while column <= len {
+                let byte = text
+                    .char_indices()
+                    .nth(column)
+                    .map_or(text.len(), |(i, _)| i);
+                let Some(m) = self.pattern.regex.find_at(&text, byte) else {
+                    break;
+                };
+                let start = text[..m.start()].chars().count();
+                // An empty match right after the previous one would replace it again
+                if m.is_empty() && self.after_match == Some(start) && line == self.line {
+                    column = start + 1;
+                    continue;
+                }
+                let end = start + m.as_str().chars().count();
+                return Some(Match { line, start, end });
+            }
+            line += 1;
+            column = 0;
+        }
+        None
+    }
+
+    /// Replace a match, or only count it with the `n` flag, then search after it
+    fn replace(&mut self, m: Match, rope: &mut Edit) {
+        let line_start = rope.line_to_char(m.line);
+        let mut after = line_start + m.end;
+        if !self.count_only {
+            let text = line_text(rope, m.line);
+            let byte = text
+                .char_indices()
+                .nth(m.start)
+                .map_or(text.len(), |(i, _)| i);
+            let replacement = match self.pattern.regex.captures_at(&text, byte) {
+                Some(captures) => expand(&self.replacement, &captures, !self.pattern.literal),
+                None => String::new(),
+            };
+            rope.remove(line_start + m.start..line_start + m.end);
+            rope.insert(line_start + m.start, &replacement);
+            after = line_start + m.start + replacement.chars().count();
+            self.end += replacement.matches('\n').count();
+        }
+        self.found = true;
+        self.count += 1;
+        if self.last_line != Some(m.line) {
+            self.lines += 1;
+        }
+        self.last_line = Some(rope.char_to_line(after.min(rope.len_chars())));
+        self.skip_to(after, m.start == m.end, rope);
+    }
+
+    /// Skip a match without replacing it
+    fn skip(&mut self, m: Match, rope: &Rope) {
+        self.found = true;
+        let after = rope.line_to_char(m.line) + m.end;
+        self.skip_to(after, m.start == m.end, rope);
+    }
+
+    /// Search the next match from a char index, on the next line without the `g` flag
+    fn skip_to(&mut self, after: usize, empty: bool, rope: &Rope) {
+        let line = rope.char_to_line(after.min(rope.len_chars()));
+        let column = after - rope.line_to_char(line);
+        match self.global {
+            true => {
+                self.line = line;
+                // The char after an empty match is kept
+                self.column = column + empty as usize;
+                self.after_match = (!empty).then_some(column);
+            }
+            false => {
+                self.line = line + 1;
+                self.column = 0;
+                self.after_match = None;
+            }
+        }
+    }
+
+    /// Summary of the substitution, like `3 substitutions on 2 lines`
+    fn report(&self) -> String {
+        let plural = |count: usize, one: &str, many: &str| match count {
+            1 => format!("1 {}", one),
+            count => format!("{} {}", count, many),
+        };
+        let name = match self.count_only {
+            true => plural(self.count, "match", "matches"),
+            false => plural(self.count, "substitution", "substitutions"),
+        };
+        format!("{} on {}", name, plural(self.lines, "line", "lines"))
+    }
+}
+
+/// Text of a line without its line break, which `$` matches before
+fn line_text(rope: &Rope, line: usize) -> String {
+    let line = rope.line(line);
+    line.slice(..line_len(line)).to_string()
+}
+
+impl EditorScreen {
+    /// Execute `:s` on a range of the focused buffer, the current line by default.
+    /// Without arguments, the last substitution is repeated.
+    pub(super) fn substitute(&mut self, range: Option<LineRange>, args: &str, state: &mut State) {
+        // Flags alone repeat the last substitution with them
+        let substitute = if args.is_empty() || args.starts_with(char::is_alphabetic) {
+            let Some(last) = &self.last_substitute else {
+                return state.error("E35: No previous regular expression");
+            };
+            match SubstituteFlags::parse(args) {
+                Ok(flags) => Substitute {
+                    flags,
+                    ..last.clone()
+                },
+                Err(err) => return state.error(err),
+            }
+        } else {
+            match Substitute::parse(args) {
+                Ok(substitute) => substitute,
+                Err(err) => return state.error(err),
+            }
+        };
+
+        // An empty pattern is the last search
+        let source = match substitute.pattern.as_str() {
+            "" => match &state.search {
+                Some(search) => search.pattern.source.clone(),
+                None => return state.error("E35: No previous regular expression"),
+            },
+            pattern => pattern.to_string(),
+        };
+        let pattern = match Pattern::new(&substitute.pattern_source(&source), &state.config) {
+            Ok(pattern) => pattern,
+            Err(err) => return state.error(format!("E383: Invalid search pattern: {}", err)),
+        };
+        self.last_substitute = Some(Substitute {
+            pattern: source,
+            ..substitute.clone()
+        });
+        // The matches are highlighted, and `n` goes on searching them
+        state.search = Some(Search {
+            pattern: pattern.clone(),
+            backward: false,
+        });
+
+        let Some(pane) = self.panes.focused() else {
+            return;
+        };
+        let file = &state.filesystem.files[pane.file];
+        if file.readonly && !substitute.flags.count {
+            return state.error(READONLY_ERROR);
+        }
+        let range = range.unwrap_or(LineRange {
+            start: pane.cursor.y,
+            end: pane.cursor.y,
+        });
+        let substitution = Substitution {
+            file: pane.file,
+            pattern,
+            replacement: substitute.replacement,
+            global: substitute.flags.global,
+            count_only: substitute.flags.count,
+            line: range.start,
+            column: 0,
+            end: range.end,
+            after_match: None,
+            found: false,
+            count: 0,
+            lines: 0,
+            last_line: None,
+        };
+        let quiet = substitute.flags.quiet;
+        match substitute.flags.confirm && !substitute.flags.count {
+            true => {
+                self.substitution = Some(substitution);
+                self.confirm_next(quiet, state);
+            }
+            false => self.substitute_all(substitution, quiet, state),
+        }
+    }
+
+    /// Replace all the matches of a substitution at once
+    fn substitute_all(&mut self, mut substitution: Substitution, quiet: bool, state: &mut State) {
+        let file = &mut state.filesystem.files[substitution.file];
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        while let Some(m) = substitution.next_match(rope) {
+            substitution.replace(m, rope);
+        }
+        if substitution.count > 0 && !substitution.count_only {
+            file.modified = !file.scratch;
+        }
+        self.finish_substitution(substitution, quiet, state);
+    }
+
+    /// Move the cursor to the next match to confirm, or finish the substitution
+    fn confirm_next(&mut self, quiet: bool, state: &mut State) {
+        let Some(substitution) = self.substitution.take() else {
+            return;
+        };
+        let m = state.filesystem.files[substitution.file]
+            .buffer
+            .as_ref()
+            .and_then(|rope| Some((substitution.next_match(rope)?, rope)));
+        match m {
+            Some((m, rope)) => {
+                if let Some(pane) = self.panes.focused_mut() {
+                    let index = rope.line_to_char(m.line) + m.start;
+                    pane.cursor.move_to_char(rope, index);
+                }
+                let text = format!("replace with {} (y/n/a/q/l)?", substitution.replacement);
+                state.info(text);
+                self.substitution = Some(Substitution {
+                    found: true,
+                    ..substitution
+                });
+            }
+            None => self.finish_substitution(substitution, quiet, state),
+        }
+    }
+
+    /// Handle the answer to the confirmation of a replacement, with the `c` flag: `y` to
+    /// replace the match, `l` to replace it and stop, `n` to skip it, `a` to replace all
+    /// the matches left, and `q` or `<Esc>` to stop
+    pub(super) fn handle_substitution_key(&mut self, key_event: KeyEvent, state: &mut State) {
+        let Some(mut substitution) = self.substitution.take() else {
+            return;
+        };
+        let file = &mut state.filesystem.files[substitution.file];
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        let Some(m) = substitution.next_match(rope) else {
+            return self.finish_substitution(substitution, false, state);
+        };
+        match key_event.code {
+            KeyCode::Char('y') => substitution.replace(m, rope),
+            KeyCode::Char('l') => {
+                substitution.replace(m, rope);
+                file.modified = !file.scratch;
+                return self.finish_substitution(substitution, false, state);
+            }
+            KeyCode::Char('n') => substitution.skip(m, rope),
+            KeyCode::Char('a') => return self.substitute_all(substitution, false, state),
+            KeyCode::Char('q') | KeyCode::Esc => {
+                return self.finish_substitution(substitution, false, state);
+            }
+            _ => {
+                self.substitution = Some(substitution);
+                return;
+            }
+        }
+        if substitution.count > 0 {
+            file.modified = !file.scratch;
+        }
+        self.substitution = Some(substitution);
+        self.confirm_next(false, state);
+    }
+
+    /// Report the number of replacements, the cursor going to the last replaced line
+    fn finish_substitution(&mut self, substitution: Substitution, quiet: bool, state: &mut State) {
+        if substitution.count == 0 {
+            match quiet || substitution.found {
+                true => state.message = None,
+                false => {
+                    let text = format!("E486: Pattern not found: {}", substitution.pattern.source);
+                    state.error(text);
+                }
+            }
+            return;
+        }
+        if let Some(pane) = self.panes.focused_mut()
+            && let Some(line) = substitution.last_line.filter(|_| !substitution.count_only)
+            && let Some(rope) = &state.filesystem.files[substitution.file].buffer
+        {
+            pane.cursor
+                .move_to_char(rope, rope.line_to_char(line.min(rope.len_lines() - 1)));
+            pane.cursor.move_first_non_blank(rope);
+        }
+        state.info(substitution.report());
+    }
+}
@@ -1,17 +1,70 @@
 use std::{cell::Cell, io::stdout, path::PathBuf};
 
-pub use config::Config;
+pub use autocmds::{AutoAction, AutoEvent, Autocmds};
+use clipboard::Selection;
+pub use colors::{ColorSupport, NON_TEXT};
+pub use commands::Commands;
+pub use config::{CmdlineLayout, Config, KeyPreset, SignColumn, WindowOptions};
 use crossterm::{cursor::SetCursorStyle, execute};
+use diagnostics::Diagnostics;
 pub use events::{EditorEvent, Events};
-pub use filesystem::{File, FileId, FileSystem, Folder, FolderId};
-use ratatui::layout::Position;
+pub use filesystem::{
+    Edit, File, FileFormat, FileId, FileSystem, Folder, FolderId, Transaction, UndoTree,
+    is_link_cycle,
+};
+pub use filetype::{BufferOptions, OptionSource};
+use git::GitStatus;
+pub use history::History;
+pub use icons::{IconSet, Icons};
+pub use jobs::{JobHandle, Jobs};
+pub use jumps::{Jump, JumpList};
+pub use keymap::{Keymaps, key_notation, parse_keys};
+use ratatui::{layout::Position, style::Stylize, text::Span};
+pub use registers::{Registers, is_register};
+use ropey::Rope;
+pub use script::{Host, Script, Scripts, Value};
+pub use search::{Pattern, Search, SearchCount};
+pub use sort::{LineOperation, SortFlags};
+pub use startup::StartupTimes;
+pub use substitute::{Substitute, SubstituteFlags, expand};
 
+mod autocmds;
+pub mod clipboard;
+mod colors;
+pub mod commands;
 mod config;
+pub mod data;
+pub mod diagnostics;
+pub mod diff;
+pub mod digraphs;
 mod events;
 mod filesystem;
+pub mod filetype;
+pub mod folds;
+pub mod git;
+pub mod grep;
+pub mod health;
+pub mod help;
+mod history;
+mod icons;
+mod jobs;
+mod jumps;
+mod keymap;
+mod registers;
+pub mod remote;
+mod script;
+mod search;
+pub mod session;
+mod shada;
+mod sort;
+pub mod startup;
+mod substitute;
+
+/// Buffers larger than this many chars have their search matches counted in the background
+const ASYNC_COUNT_THRESHOLD: usize = 1 << 20;
 
 /// Currently displayed screen
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Screen {
     /// Home page
     #[default]
@@ -21,23 +74,105 @@ pub enum Screen {
 }
 
 /// Editor mode
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     #[default]
     Normal,
     Insert,
     Visual,
+    /// Visual mode selecting whole lines, started with `V`
+    VisualLine,
+    /// Visual mode selecting a rectangular block, started with `CTRL-V`
+    VisualBlock,
     /// Cmdline widget open
     Command,
 }
 
+impl Mode {
+    /// Whether a visual selection is shown, charwise, linewise or blockwise
+    pub fn is_visual(&self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine | Self::VisualBlock)
+    }
+}
+
+/// Kind of input expected by the open cmdline
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// Ex command, opened with `:`
+    #[default]
+    Command,
+    /// Buffer search, opened with `/` or `?`
+    Search { backward: bool },
+    /// Text requested by a script with `prompt()`
+    Input,
+}
+
+/// Message displayed in the lualine
+#[derive(Debug)]
+pub struct Message {
+    pub text: String,
+    pub error: bool,
+}
+
+impl Message {
+    pub fn span(&self) -> Span<'_> {
+        match self.error {
+            true => Span::raw(&self.text).red(),
+            false => Span::raw(&self.text),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct State {
     pub screen: Screen,
     pub mode: Mode,
+    /// Mode to restore when the cmdline closes
+    pub previous_mode: Mode,
     pub events: Events,
+    /// Background tasks, listed by `:jobs`
+    pub jobs: Jobs,
     pub filesystem: FileSystem,
     pub config: Config,
+    /// Last info or error message
+    pub message: Option<Message>,
+    /// Kind of the cmdline input, when in command mode
+    pub prompt: Prompt,
+    /// Last buffer search
+    pub search: Option<Search>,
+    /// Pattern being typed, highlighted instead of the last search with `incsearch`
+    pub incsearch: Option<Pattern>,
+    /// Match count of the last search, shown in the lualine
+    pub search_count: Option<SearchCount>,
+    /// Keys typed so far of the pending command of the focused pane, shown by `showcmd`
+    pub pending_keys: String,
+    /// Register the typed keys are recorded into, from `q{reg}` to `q`
+    pub recording: Option<char>,
+    /// Cmdline histories, persisted in the state file
+    pub history: History,
+    /// Mouse capture temporarily disabled, to select text with the terminal
+    pub mouse_suspended: bool,
+    /// Running as a pager with `-R`: the files open read-only, and Space, b and q page
+    /// and quit in normal mode
+    pub pager: bool,
+    /// Commands defined with `:command`
+    pub commands: Commands,
+    /// Keys bound with `:map`
+    pub keymaps: Keymaps,
+    /// Commands bound to editor events with `:autocmd`
+    pub autocmds: Autocmds,
+    /// Functions of the loaded `.tes` scripts
+    pub scripts: Scripts,
+    /// Text registers
+    pub registers: Registers,
+    /// Positions before the large jumps, gone back to with `CTRL-O`
+    pub jumps: JumpList,
+    /// Branch of the repository of the root folder, if any
+    pub git: Option<GitStatus>,
+    /// Diagnostics of the last `:make`
+    pub diagnostics: Diagnostics,
+    /// Timestamps of the startup phases, for `--startuptime` and `:startuptime`
+    pub startup: StartupTimes,
 
     /// Cursor position determined at rendering time by the widgets
     /// This variable is read after rendering to update the cursor position
@@ -46,31 +181,186 @@ pub struct State {
     /// This variable is read at rendering time for the widget that owns
     /// the focus to decide whether the cursor style needs to be changed
     pub cursor_style: Cell<SetCursorStyle>,
+    /// Cursor of the focused pane, determined at rendering time by the panes
+    /// and read by the lualine
+    pub active_cursor: Cell<Option<ActiveCursor>>,
 
     pub exit: bool,
+    /// Exit status of the editor, non-zero after `:cquit` for the programs that run it
+    /// to abort, like git for a commit message
+    pub exit_status: u8,
+}
+
+/// Buffer and cursor position of the focused pane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveCursor {
+    pub file: FileId,
+    pub line: usize,
+    pub column: usize,
 }
 
 impl State {
-    pub fn new(root_path: PathBuf) -> Self {
+    pub fn new(root_path: PathBuf, startup: StartupTimes) -> Self {
         let screen = Screen::default();
         let mode = Mode::default();
         let events = Events::new();
         let filesystem = FileSystem::new(root_path);
         let config = Config::default();
+        let jobs = Jobs::default();
 
         // Load the root folder asynchronously
-        filesystem.load_folder(events.editor_sender.clone(), filesystem.root);
+        filesystem.load_folder(events.editor_sender.clone(), &jobs, filesystem.root);
+        let root = filesystem.folders[filesystem.root].path.clone();
+        git::watch(root, events.editor_sender.clone(), &jobs);
 
         Self {
             screen,
             mode,
+            previous_mode: mode,
             events,
+            jobs,
             filesystem,
             config,
+            message: None,
+            prompt: Prompt::default(),
+            search: None,
+            incsearch: None,
+            search_count: None,
+            pending_keys: String::new(),
+            recording: None,
+            history: shada::load(),
+            mouse_suspended: false,
+            pager: false,
+            commands: Commands::default(),
+            keymaps: Keymaps::default(),
+            autocmds: Autocmds::default(),
+            scripts: Scripts::default(),
+            registers: Registers::default(),
+            jumps: JumpList::default(),
+            git: None,
+            diagnostics: Diagnostics::default(),
+            startup,
             cursor_pos: Cell::new(Position::default()),
-            cursor_style: Cell::new(SetCursorStyle::SteadyBlock),
+            // Unknown until the first style is set
+            cursor_style: Cell::new(SetCursorStyle::DefaultUserShape),
+            active_cursor: Cell::new(None),
             exit: false,
+            exit_status: 0,
+        }
+    }
+
+    /// Persist the editor state for the next sessions
+    pub fn save_shada(&self) {
+        if let Err(err) = shada::save(&self.history) {
+            log::error!("Failed to write state file: {}", err);
+        }
+    }
+
+    /// Open the cmdline with the given prompt, entering command mode
+    pub fn open_cmdline(&mut self, prompt: Prompt) {
+        if self.mode != Mode::Command {
+            self.previous_mode = self.mode;
         }
+        self.mode = Mode::Command;
+        self.prompt = prompt;
+    }
+
+    /// Contents of a register, including the read-only and clipboard ones
+    pub fn register(&self, name: char) -> Result<String, String> {
+        let text = match name {
+            '+' => return clipboard::read(Selection::Clipboard),
+            '*' => return clipboard::read(Selection::Primary),
+            '/' => self
+                .search
+                .as_ref()
+                .map(|search| search.pattern.source.clone()),
+            ':' => self.history.commands.last().cloned(),
+            name => self.registers.get(name).map(str::to_string),
+        };
+        text.ok_or_else(|| format!("E353: Nothing in register {}", name))
+    }
+
+    /// Store deleted text in a register and in the unnamed one, except for the blackhole `_`
+    pub fn set_register(&mut self, name: char, text: String) {
+        match name {
+            '_' => return,
+            '+' | '*' => {
+                if let Err(err) = clipboard::copy(&text) {
+                    self.error(err);
+                }
+            }
+            '/' | ':' | '"' => {}
+            name => self.registers.set(name, text.clone()),
+        }
+        let text = match name.is_ascii_uppercase() {
+            true => self
+                .registers
+                .get(name.to_ascii_lowercase())
+                .unwrap_or_default()
+                .to_string(),
+            false => text,
+        };
+        self.registers.set('"', text);
+    }
+
+    /// Leave command mode, back to the mode the cmdline was opened from
+    pub fn close_cmdline(&mut self) {
+        if self.mode == Mode::Command {
+            self.mode = self.previous_mode;
+        }
+    }
+
+    /// Count the matches of the last search in a buffer, for the cursor at char index `at`.
+    /// Large buffers are counted in the background.
+    pub fn count_search(&mut self, file: FileId, at: usize) {
+        let (Some(search), Some(rope)) = (&self.search, &self.filesystem.files[file].buffer) else {
+            return;
+        };
+
+        let result = if rope.len_chars() <= ASYNC_COUNT_THRESHOLD {
+            Some(search.pattern.count(rope, at))
+        } else {
+            let pattern = search.pattern.clone();
+            let rope = rope.clone();
+            let sender = self.events.editor_sender.clone();
+            self.jobs.spawn_blocking("count search matches", move |_| {
+                let (current, total) = pattern.count(&rope, at);
+                let event = EditorEvent::SearchCounted {
+                    file,
+                    at,
+                    current,
+                    total,
+                };
+                if let Err(err) = sender.blocking_send(event) {
+                    log::error!("Failed to send search count event: {}", err);
+                }
+            });
+            None
+        };
+        self.search_count = Some(SearchCount { file, at, result });
+    }
+
+    /// Display an info message
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            error: false,
+        });
+    }
+
+    /// Display an error message
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            error: true,
+        });
+    }
+
+    /// Buffer and cursor of the focused pane, as of the last rendering of the panes
+    pub fn active_buffer(&self) -> Option<(&Rope, ActiveCursor)> {
+        let cursor = self.active_cursor.get()?;
+        let rope = self.filesystem.files.get(cursor.file)?.buffer.as_ref()?;
+        Some((rope, cursor))
     }
 
     /// Change the cursor style.
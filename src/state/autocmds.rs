@@ -0,0 +1,176 @@
+use std::path::Path;
+
+/// Editor events that trigger autocommands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoEvent {
+    /// After the startup script and plugins are sourced
+    VimEnter,
+    /// Before exiting
+    VimLeave,
+    /// After a buffer is displayed in a pane
+    BufEnter,
+    /// Before the first `BufEnter` of a buffer with a known filetype.
+    /// The pattern matches the filetype name, like `rust` or `python`.
+    FileType,
+    /// After a buffer is written
+    BufWritePost,
+    /// After `updatetime` milliseconds without input in normal mode
+    CursorHold,
+    /// After `updatetime` milliseconds without input in insert mode
+    CursorHoldI,
+}
+
+impl AutoEvent {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vimenter" => Some(Self::VimEnter),
+            "vimleave" => Some(Self::VimLeave),
+            "bufenter" => Some(Self::BufEnter),
+            "filetype" => Some(Self::FileType),
+            "bufwritepost" => Some(Self::BufWritePost),
+            "cursorhold" => Some(Self::CursorHold),
+            "cursorholdi" => Some(Self::CursorHoldI),
+            _ => None,
+        }
+    }
+
+    /// Name passed to the script callbacks
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::VimEnter => "VimEnter",
+            Self::VimLeave => "VimLeave",
+            Self::BufEnter => "BufEnter",
+            Self::FileType => "FileType",
+            Self::BufWritePost => "BufWritePost",
+            Self::CursorHold => "CursorHold",
+            Self::CursorHoldI => "CursorHoldI",
+        }
+    }
+}
+
+/// What runs when an autocommand fires
+#[derive(Debug, Clone)]
+pub enum AutoAction {
+    /// Ex command line
+    Command(String),
+    /// Script function, called with the event name and the file path,
+    /// or the filetype name for `FileType`
+    Function(String),
+}
+
+/// Command executed when an event fires for a matching file
+#[derive(Debug, Clone)]
+pub struct Autocmd {
+    pub event: AutoEvent,
+    /// File name glob, `*` for all files
+    pub pattern: String,
+    pub action: AutoAction,
+}
+
+/// Autocommands defined with `:autocmd`
+#[derive(Debug, Default)]
+pub struct Autocmds {
+    pub cmds: Vec<Autocmd>,
+}
+
+impl Autocmds {
+    /// Define an autocommand from the `:autocmd` arguments (`{events} {pattern} {command}`).
+    /// Several events can be separated by commas.
+    pub fn define(&mut self, args: &str) -> Result<(), String> {
+        let mut parts = args.splitn(3, char::is_whitespace);
+        let (Some(events), Some(pattern), Some(command)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err("E471: Argument required".to_string());
+        };
+        self.push(
+            events,
+            pattern,
+            AutoAction::Command(command.trim().to_string()),
+        )
+    }
+
+    /// Call a script function when one of the comma separated events fires
+    pub fn define_function(
+        &mut self,
+        events: &str,
+        pattern: &str,
+        function: &str,
+    ) -> Result<(), String> {
+        self.push(events, pattern, AutoAction::Function(function.to_string()))
+    }
+
+    fn push(&mut self, events: &str, pattern: &str, action: AutoAction) -> Result<(), String> {
+        let events = events
+            .split(',')
+            .map(|name| {
+                AutoEvent::parse(name).ok_or_else(|| format!("E216: No such event: {}", name))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for event in events {
+            self.cmds.push(Autocmd {
+                event,
+                pattern: pattern.to_string(),
+                action: action.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove the autocommands of an event, or all of them (`:autocmd!`)
+    pub fn clear(&mut self, event: Option<&str>) -> Result<(), String> {
+        match event {
+            Some(name) => {
+                let event = AutoEvent::parse(name)
+                    .ok_or_else(|| format!("E216: No such event: {}", name))?;
+                self.cmds.retain(|cmd| cmd.event != event);
+            }
+            None => self.cmds.clear(),
+        }
+        Ok(())
+    }
+
+    /// Actions to run for an event on a file
+    pub fn matching(&self, event: AutoEvent, path: Option<&Path>) -> Vec<AutoAction> {
+        self.cmds
+            .iter()
+            .filter(|cmd| cmd.event == event && matches_path(&cmd.pattern, path))
+            .map(|cmd| cmd.action.clone())
+            .collect()
+    }
+}
+
+/// Match a glob against the file name, or the whole path if it contains a `/`
+fn matches_path(pattern: &str, path: Option<&Path>) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    let Some(path) = path else {
+        return false;
+    };
+    let text = match pattern.contains('/') {
+        true => path.to_string_lossy(),
+        false => path.file_name().unwrap_or_default().to_string_lossy(),
+    };
+    glob(pattern, &text)
+}
+
+/// Glob matching with `*` wildcards
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
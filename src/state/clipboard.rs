@@ -0,0 +1,118 @@
+use std::{
+    env,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use tokio::sync::mpsc::Sender;
+
+use super::{EditorEvent, FileId, Jobs};
+
+/// System selections that can be pasted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Text selected with the mouse, pasted with a middle click
+    Primary,
+    /// Text explicitly copied
+    Clipboard,
+}
+
+impl Selection {
+    /// Commands printing the selection, with the display server they need
+    fn read_commands(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            Self::Primary => &[
+                (
+                    "WAYLAND_DISPLAY",
+                    &["wl-paste", "--primary", "--no-newline"],
+                ),
+                ("DISPLAY", &["xclip", "-o", "-selection", "primary"]),
+                ("DISPLAY", &["xsel", "-o", "-p"]),
+            ],
+            Self::Clipboard => &[
+                ("WAYLAND_DISPLAY", &["wl-paste", "--no-newline"]),
+                ("DISPLAY", &["xclip", "-o", "-selection", "clipboard"]),
+                ("DISPLAY", &["xsel", "-o", "-b"]),
+            ],
+        }
+    }
+}
+
+/// Commands reading the text to copy to the clipboard from their input
+const COPY_COMMANDS: &[(&str, &[&str])] = &[
+    ("WAYLAND_DISPLAY", &["wl-copy"]),
+    ("DISPLAY", &["xclip", "-i", "-selection", "clipboard"]),
+    ("DISPLAY", &["xsel", "-i", "-b"]),
+];
+
+const NO_PROVIDER: &str = "No clipboard provider found (wl-clipboard, xclip or xsel)";
+
+/// Read a selection in the background,
+/// then send it to be pasted at the `at` char index of the file
+pub fn paste(
+    selection: Selection,
+    sender: Sender<EditorEvent>,
+    jobs: &Jobs,
+    file: FileId,
+    at: usize,
+) {
+    jobs.spawn_blocking("paste selection", move |_| {
+        let text = read(selection);
+        let event = EditorEvent::Paste { file, at, text };
+        if let Err(err) = sender.blocking_send(event) {
+            log::error!("Failed to send paste event: {}", err);
+        }
+    });
+}
+
+/// Read the text of a selection
+pub fn read(selection: Selection) -> Result<String, String> {
+    for (display, command) in selection.read_commands() {
+        if env::var_os(display).is_none() {
+            continue;
+        }
+        let output = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(_) => return Err("The selection is empty".to_string()),
+            // Try the next tool
+            Err(_) => {}
+        }
+    }
+    Err(NO_PROVIDER.to_string())
+}
+
+/// Copy text to the clipboard.
+/// The tools fork to serve the selection, so this does not block.
+pub fn copy(text: &str) -> Result<(), String> {
+    for (display, command) in COPY_COMMANDS {
+        if env::var_os(display).is_none() {
+            continue;
+        }
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        return match child.wait() {
+            Ok(status) if status.success() => Ok(()),
+            _ => Err(format!("{} failed", command[0])),
+        };
+    }
+    Err(NO_PROVIDER.to_string())
+}
@@ -0,0 +1,332 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::utils::expand_path;
+
+pub use range::{LineRange, RangeContext, parse_range};
+
+mod range;
+
+/// Builtin ex commands, by priority when abbreviated,
+/// with the minimum number of chars they can be abbreviated to (`w[rite]`)
+const BUILTIN_COMMANDS: &[(&str, usize)] = &[
+    ("edit", 1),
+    ("quit", 1),
+    ("write", 1),
+    ("wq", 2),
+    ("xit", 1),
+    ("wall", 2),
+    ("qall", 2),
+    ("wqall", 3),
+    ("xall", 2),
+    ("cquit", 2),
+    ("vsplit", 2),
+    ("delete", 1),
+    ("substitute", 1),
+    ("read", 1),
+    ("set", 2),
+    ("setlocal", 4),
+    ("source", 2),
+    ("call", 3),
+    ("command", 3),
+    ("delcommand", 4),
+    ("map", 3),
+    ("noremap", 2),
+    ("nmap", 2),
+    ("nnoremap", 2),
+    ("vmap", 2),
+    ("vnoremap", 2),
+    ("imap", 2),
+    ("inoremap", 3),
+    ("cabbrev", 2),
+    ("cnoreabbrev", 6),
+    ("cunabbrev", 4),
+    ("autocmd", 2),
+    ("checkhealth", 3),
+    ("startuptime", 4),
+    ("echo", 2),
+    ("echoerr", 5),
+    ("Replace", 1),
+    ("Rename", 3),
+    ("Delete", 3),
+    ("Apply", 3),
+    ("help", 1),
+    ("Man", 3),
+    ("goto", 2),
+    ("digraphs", 3),
+    ("saveas", 3),
+    ("mksession", 3),
+    ("file", 1),
+    ("enew", 3),
+    ("workspace", 4),
+    ("jobs", 4),
+    ("undo", 1),
+    ("redo", 3),
+    ("make", 3),
+    ("jq", 2),
+    ("sort", 3),
+    ("uniq", 3),
+    ("reverse", 3),
+    ("Align", 2),
+    ("Renumber", 3),
+];
+
+/// Names of the builtin commands
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTIN_COMMANDS.iter().map(|(name, _)| *name)
+}
+
+/// Whether a builtin command accepts a line range
+pub fn accepts_range(name: &str) -> bool {
+    matches!(
+        name,
+        "delete"
+            | "goto"
+            | "write"
+            | "read"
+            | "substitute"
+            | "sort"
+            | "uniq"
+            | "reverse"
+            | "Align"
+            | "Renumber"
+    )
+}
+
+/// Whether a possibly abbreviated command takes a path argument, completed with `<Tab>`
+pub fn completes_path(name: &str) -> bool {
+    let name = resolve_builtin(name.trim_end_matches('!'));
+    matches!(
+        name,
+        Some(
+            "edit"
+                | "write"
+                | "wq"
+                | "xit"
+                | "vsplit"
+                | "source"
+                | "saveas"
+                | "file"
+                | "Rename"
+                | "workspace"
+        )
+    )
+}
+
+/// Complete a typed path, relative to `base` unless absolute, with the entries of its
+/// folder, sorted with the folders ending with `/`. The typed folder is kept as is,
+/// with its `~` and variables unexpanded.
+pub fn complete_path(typed: &str, base: &Path) -> Vec<String> {
+    let (folder, prefix) = match typed.rfind('/') {
+        Some(index) => typed.split_at(index + 1),
+        None => ("", typed),
+    };
+    let dir = match folder.is_empty() {
+        true => base.join("."),
+        false => base.join(expand_path(folder)),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut paths = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Hidden entries are only completed when their dot is typed
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", folder, name, slash))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+/// Builtin command matching a possibly abbreviated name
+fn resolve_builtin(name: &str) -> Option<&'static str> {
+    BUILTIN_COMMANDS
+        .iter()
+        .find(|(full, _)| *full == name)
+        .or_else(|| {
+            BUILTIN_COMMANDS
+                .iter()
+                .find(|(full, min)| name.len() >= *min && full.starts_with(name))
+        })
+        .map(|(full, _)| *full)
+}
+
+/// Command defined with `:command`
+#[derive(Debug, Clone)]
+pub struct UserCommand {
+    /// Command line executed in place of the user command, in which `<args>` is replaced
+    /// by the command arguments, and `<q-args>` by them as a quoted string
+    pub replacement: String,
+}
+
+/// Registry of the user defined commands and cmdline abbreviations
+#[derive(Debug, Default)]
+pub struct Commands {
+    pub user: BTreeMap<String, UserCommand>,
+    /// Words replaced while typing in the cmdline, defined with `:cabbrev`
+    pub abbreviations: BTreeMap<String, String>,
+}
+
+impl Commands {
+    /// Full name of a builtin or user command from a possibly abbreviated name.
+    /// Exact names come first, then builtin abbreviations, then unique prefixes
+    /// of user commands.
+    pub fn resolve(&self, name: &str) -> Result<String, String> {
+        if self.user.contains_key(name) {
+            return Ok(name.to_string());
+        }
+        if let Some(full) = resolve_builtin(name) {
+            return Ok(full.to_string());
+        }
+        let mut matches = self.user.keys().filter(|full| full.starts_with(name));
+        match (matches.next(), matches.next()) {
+            (Some(full), None) => Ok(full.clone()),
+            (Some(_), Some(_)) => Err(format!(
+                "E464: Ambiguous use of user-defined command: {}",
+                name
+            )),
+            (None, _) => Err(format!("E492: Not an editor command: {}", name)),
+        }
+    }
+
+    /// Define a user command from the `:command` arguments (`Name replacement`).
+    /// An existing command is only replaced with `force` (`:command!`).
+    pub fn define(&mut self, args: &str, force: bool) -> Result<(), String> {
+        let Some((name, replacement)) = args.split_once(char::is_whitespace) else {
+            return Err("E471: Argument required".to_string());
+        };
+        if !name.starts_with(|c: char| c.is_ascii_uppercase())
+            || !name.chars().all(|c| c.is_ascii_alphanumeric())
+            || BUILTIN_COMMANDS.iter().any(|(full, _)| *full == name)
+        {
+            let text = "E183: User defined commands must start with an uppercase letter";
+            return Err(format!("{}: {}", text, name));
+        }
+        if self.user.contains_key(name) && !force {
+            return Err(format!(
+                "E174: Command already exists: add ! to replace it: {}",
+                name
+            ));
+        }
+        let replacement = replacement.trim();
+        let replacement = replacement.strip_prefix(':').unwrap_or(replacement);
+        self.user.insert(
+            name.to_string(),
+            UserCommand {
+                replacement: replacement.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Define a cmdline abbreviation from the `:cabbrev` arguments (`lhs rhs`)
+    pub fn abbreviate(&mut self, args: &str) -> Result<(), String> {
+        let Some((lhs, rhs)) = args.split_once(char::is_whitespace) else {
+            return Err("E471: Argument required".to_string());
+        };
+        if !lhs.chars().all(is_keyword) {
+            return Err(format!("E474: Invalid argument: {}", lhs));
+        }
+        self.abbreviations
+            .insert(lhs.to_string(), rhs.trim().to_string());
+        Ok(())
+    }
+
+    /// Expand a user command with its arguments
+    pub fn expand(&self, name: &str, args: &str) -> Option<String> {
+        let command = self.user.get(name)?;
+        let quoted = format!("\"{}\"", args.replace('\\', "\\\\").replace('"', "\\\""));
+        Some(
+            command
+                .replacement
+                .replace("<q-args>", &quoted)
+                .replace("<args>", args),
+        )
+    }
+
+    /// Complete a command name prefix with the builtin and user commands
+    pub fn complete(&self, prefix: &str) -> Vec<String> {
+        let mut names = BUILTIN_COMMANDS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(self.user.keys().map(String::as_str))
+            .filter(|name| name.starts_with(prefix))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+/// Split a command line into its `|` separated commands.
+/// `\|` escapes the separator. Commands that take the rest of the line as argument
+/// (`:!`, `:command`, `:call` and mappings) end the chain.
+pub fn split_chain(line: &str) -> Vec<String> {
+    let mut commands = vec![];
+    let mut current = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                current.push('|');
+                chars.next();
+            }
+            '|' if !takes_rest(&current) => {
+                commands.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    commands.push(current);
+    commands
+}
+
+/// Whether a command uses `|` in its argument
+fn takes_rest(command: &str) -> bool {
+    let command = command.trim_start().trim_start_matches(':');
+    if command.starts_with('!') {
+        return true;
+    }
+    let (name, _) = split_name(command);
+    matches!(
+        resolve_builtin(name),
+        Some(
+            "command"
+                | "map"
+                | "noremap"
+                | "nmap"
+                | "nnoremap"
+                | "vmap"
+                | "vnoremap"
+                | "imap"
+                | "inoremap"
+                | "cabbrev"
+                | "cnoreabbrev"
+                | "autocmd"
+                | "call"
+        )
+    )
+}
+
+/// Split a command into its name, and the rest of the command
+/// starting with the bang and the arguments
+pub fn split_name(command: &str) -> (&str, &str) {
+    // User commands can contain digits
+    let user = command.starts_with(|c: char| c.is_ascii_uppercase());
+    let end = command
+        .find(|c: char| !(c.is_ascii_alphabetic() || user && c.is_ascii_digit()))
+        .unwrap_or(command.len());
+    command.split_at(end)
+}
+
+/// Chars that can form an abbreviation
+pub fn is_keyword(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use ropey::Rope;
+
+use crate::position::line_count;
+
+/// Inclusive span of 0-based lines targeted by an ex command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Buffer state the addresses of a range are resolved against
+#[derive(Debug, Clone, Copy)]
+pub struct RangeContext<'a> {
+    pub rope: &'a Rope,
+    /// 0-based line of the cursor
+    pub current: usize,
+    /// Char indices of the buffer marks
+    pub marks: &'a BTreeMap<char, usize>,
+}
+
+impl RangeContext<'_> {
+    /// Number of lines, not counting the empty line after a final newline
+    fn last_line(&self) -> usize {
+        line_count(self.rope)
+    }
+
+    fn mark_line(&self, mark: char) -> Result<usize, String> {
+        let index = *self.marks.get(&mark).ok_or("E20: Mark not set")?;
+        Ok(self.rope.char_to_line(index.min(self.rope.len_chars())) + 1)
+    }
+}
+
+/// Parse the range at the start of a command: `%`, `.`, `$`, numbers, marks (`'a`, `'<`),
+/// offsets (`+3`, `-`), separated by `,` or `;` (which moves the current line).
+/// Returns the range if one was given, and the rest of the command.
+pub fn parse_range<'a>(
+    command: &'a str,
+    cx: &RangeContext,
+) -> Result<(Option<LineRange>, &'a str), String> {
+    if let Some(rest) = command.strip_prefix('%') {
+        let range = LineRange {
+            start: 0,
+            end: cx.last_line() - 1,
+        };
+        return Ok((Some(range), rest));
+    }
+
+    // Addresses are 1-based while parsing, as in the command
+    let mut current = cx.current as isize + 1;
+    let mut addresses = vec![];
+    let mut rest = command;
+    loop {
+        let (address, after) = parse_address(rest, current, cx)?;
+        rest = after;
+        if let Some(after) = rest.strip_prefix(',') {
+            addresses.push(address.unwrap_or(current));
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(';') {
+            current = address.unwrap_or(current);
+            addresses.push(current);
+            rest = after;
+        } else {
+            match address {
+                Some(address) => addresses.push(address),
+                // Trailing separator
+                None if !addresses.is_empty() => addresses.push(current),
+                None => {}
+            }
+            break;
+        }
+    }
+
+    // Only the last two addresses are used
+    let (start, end) = match addresses.as_slice() {
+        [] => return Ok((None, rest)),
+        [line] => (*line, *line),
+        [.., start, end] => (*start, *end),
+    };
+    let last = cx.last_line() as isize;
+    // Lines jumped to with a bare range are clamped, as in `:999`
+    let (start, end) = match rest.trim().is_empty() {
+        true => (start.clamp(1, last), end.clamp(1, last)),
+        false => (start, end),
+    };
+    if start < 0 || end < 0 || start > last || end > last {
+        return Err("E16: Invalid range".to_string());
+    }
+    let (start, end) = (start.min(end), start.max(end));
+    let range = LineRange {
+        start: (start.max(1) - 1) as usize,
+        end: (end.max(1) - 1) as usize,
+    };
+    Ok((Some(range), rest))
+}
+
+/// Parse a single address with its offsets
+fn parse_address<'a>(
+    text: &'a str,
+    current: isize,
+    cx: &RangeContext,
+) -> Result<(Option<isize>, &'a str), String> {
+    let mut rest = text;
+    let (digits, after) = split_digits(rest);
+    let mut line = if !digits.is_empty() {
+        rest = after;
+        Some(digits.parse().map_err(|_| "E16: Invalid range")?)
+    } else if let Some(after) = rest.strip_prefix('.') {
+        rest = after;
+        Some(current)
+    } else if let Some(after) = rest.strip_prefix('$') {
+        rest = after;
+        Some(cx.last_line() as isize)
+    } else if let Some(after) = rest.strip_prefix('\'') {
+        let mut chars = after.chars();
+        let mark = chars.next().ok_or("E20: Mark not set")?;
+        rest = chars.as_str();
+        Some(cx.mark_line(mark)? as isize)
+    } else {
+        None
+    };
+
+    while let Some(sign) = rest.chars().next().filter(|c| matches!(c, '+' | '-')) {
+        let (digits, after) = split_digits(&rest[1..]);
+        let offset = match digits.is_empty() {
+            true => 1,
+            false => digits.parse::<isize>().map_err(|_| "E16: Invalid range")?,
+        };
+        let base = line.unwrap_or(current);
+        line = Some(match sign {
+            '+' => base + offset,
+            _ => base - offset,
+        });
+        rest = after;
+    }
+    Ok((line, rest))
+}
+
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
@@ -1,11 +1,524 @@
+use std::path::PathBuf;
+
+use crossterm::cursor::SetCursorStyle;
+
+use super::{BufferOptions, ColorSupport, IconSet, Mode};
+
+/// Default of the `guicursor` option, matching the default `CursorStyles`
+const GUICURSOR: &str = "n-v:block,i-c:ver25";
+
+/// Cursor style of each mode, set with the `guicursor` option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorStyles {
+    pub normal: SetCursorStyle,
+    pub visual: SetCursorStyle,
+    pub insert: SetCursorStyle,
+    pub command: SetCursorStyle,
+}
+
+impl CursorStyles {
+    /// Parse comma separated `{modes}:{shape}[-blinkon{ms}]` entries, where the modes
+    /// are `n`, `v`, `i` and `c` separated by `-`, and the shape is `block`, `ver{n}` for
+    /// a bar or `hor{n}` for an underline. Cursors blink unless `blinkon0` or no
+    /// `blinkon` is given, and unlisted modes keep a steady block.
+    pub fn parse(value: &str) -> Option<Self> {
+        let block = SetCursorStyle::SteadyBlock;
+        let mut styles = Self {
+            normal: block,
+            visual: block,
+            insert: block,
+            command: block,
+        };
+        for entry in value.split(',').filter(|entry| !entry.is_empty()) {
+            let (modes, attributes) = entry.split_once(':')?;
+            let mut attributes = attributes.split('-');
+            let shape = attributes.next()?;
+            let blink = attributes
+                .map(|attribute| attribute.strip_prefix("blinkon"))
+                .collect::<Option<Vec<_>>>()?
+                .last()
+                .is_some_and(|ms| *ms != "0");
+            let style = match (shape, blink) {
+                ("block", false) => SetCursorStyle::SteadyBlock,
+                ("block", true) => SetCursorStyle::BlinkingBlock,
+                (shape, false) if shape.starts_with("ver") => SetCursorStyle::SteadyBar,
+                (shape, true) if shape.starts_with("ver") => SetCursorStyle::BlinkingBar,
+                (shape, false) if shape.starts_with("hor") => SetCursorStyle::SteadyUnderScore,
+                (shape, true) if shape.starts_with("hor") => SetCursorStyle::BlinkingUnderScore,
+                _ => return None,
+            };
+            for mode in modes.split('-') {
+                match mode {
+                    "n" => styles.normal = style,
+                    "v" => styles.visual = style,
+                    "i" => styles.insert = style,
+                    "c" => styles.command = style,
+                    _ => return None,
+                }
+            }
+        }
+        Some(styles)
+    }
+
+    /// Style of the cursor in a mode
+    pub fn get(&self, mode: Mode) -> SetCursorStyle {
+        match mode {
+            Mode::Normal => self.normal,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => self.visual,
+            Mode::Insert => self.insert,
+            Mode::Command => self.command,
+        }
+    }
+}
+
+/// Placement of the cmdline, selected with the `cmdline` option
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CmdlineLayout {
+    /// Floating box in the middle of the screen
+    #[default]
+    Float,
+    /// Classic line under the lualine, which also displays the messages
+    Bottom,
+}
+
+impl CmdlineLayout {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "float" => Some(Self::Float),
+            "bottom" => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Float => "float",
+            Self::Bottom => "bottom",
+        }
+    }
+}
+
+/// Keys of the panes, selected with the `keypreset` option
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPreset {
+    /// Modal vim keys
+    #[default]
+    Vim,
+    /// Shortcuts of common editors on top of the vim keys, files opening in insert mode
+    Basic,
+}
+
+impl KeyPreset {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "vim" => Some(Self::Vim),
+            "basic" => Some(Self::Basic),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Vim => "vim",
+            Self::Basic => "basic",
+        }
+    }
+}
+
+/// Display of the sign column of the gutter, selected with the `signcolumn` option
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SignColumn {
+    /// Only when the buffer has signs
+    #[default]
+    Auto,
+    Yes,
+    No,
+}
+
+impl SignColumn {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "yes" => Some(Self::Yes),
+            "no" => Some(Self::No),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Yes => "yes",
+            Self::No => "no",
+        }
+    }
+}
+
+/// Options of the panes, set for all of them by `:set` and overridden in a pane by
+/// `:setlocal`, whose overrides hold `Some` values
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WindowOptions<T = bool> {
+    /// Show the line numbers in the gutter
+    pub number: T,
+    /// Show the line numbers relative to the cursor line, which shows its own number
+    /// with `number`
+    pub relativenumber: T,
+    /// Highlight the cursor line
+    pub cursorline: T,
+    /// Wrap the lines longer than the pane over several rows instead of truncating them
+    pub wrap: T,
+}
+
+impl<T> WindowOptions<T> {
+    /// Option by name or abbreviation
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut T> {
+        match name {
+            "number" | "nu" => Some(&mut self.number),
+            "relativenumber" | "rnu" => Some(&mut self.relativenumber),
+            "cursorline" | "cul" => Some(&mut self.cursorline),
+            "wrap" => Some(&mut self.wrap),
+            _ => None,
+        }
+    }
+}
+
+impl WindowOptions {
+    /// Name of the window option targeted by a `:set` argument
+    pub fn option_name(arg: &str) -> Option<&str> {
+        let name = arg.trim_end_matches(['?', '!', '<']);
+        let mut options = Self::default();
+        let name = match name.strip_prefix("inv").or_else(|| name.strip_prefix("no")) {
+            Some(stripped) if options.get_mut(stripped).is_some() => stripped,
+            _ => name,
+        };
+        options.get_mut(name).map(|_| name)
+    }
+
+    /// Options of a pane, with its local overrides
+    pub fn with(self, local: &WindowOptions<Option<bool>>) -> Self {
+        Self {
+            number: local.number.unwrap_or(self.number),
+            relativenumber: local.relativenumber.unwrap_or(self.relativenumber),
+            cursorline: local.cursorline.unwrap_or(self.cursorline),
+            wrap: local.wrap.unwrap_or(self.wrap),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     /// Minimum number of lines between the cursor and the top/bottom of the screen.
     pub cursor_margin_y: usize,
+    /// Number of lines scrolled per mouse wheel tick
+    pub scroll_tick: usize,
+    /// Allow scrolling until the last line is at the top of the screen
+    pub scrollpasteof: bool,
+    /// Char of the gutter rows after the end of the buffer, set with `fillchars=eob:c`.
+    /// None to leave them empty.
+    pub eob: Option<char>,
+    /// Minimum cells of the line numbers, with the space after them
+    pub numberwidth: usize,
+    /// Cells of the fold column on the left of the gutter
+    pub foldcolumn: usize,
+    /// Display of the sign column, between the fold column and the line numbers
+    pub signcolumn: SignColumn,
+
+    /// Case insensitive searches
+    pub ignorecase: bool,
+    /// Case sensitive searches when the pattern contains uppercase chars,
+    /// if `ignorecase` is set
+    pub smartcase: bool,
+    /// Patterns are regexes by default, or literal strings when unset
+    pub magic: bool,
+
+    /// Milliseconds without input before the `CursorHold` event fires
+    pub updatetime: usize,
+    /// Maximum line width when formatting with `gq`, 79 when zero
+    pub textwidth: usize,
+    /// Keep the indentation of the previous line on new lines,
+    /// with an extra level after an opening bracket
+    pub autoindent: bool,
+    /// Insert typed text as is, without autoindent, auto-wrap and expanded tabs,
+    /// for terminals without bracketed paste
+    pub paste: bool,
+    /// Buffer options of the files without a known filetype, also set by `:set`
+    pub buffer: BufferOptions,
+    /// Options of the panes without local overrides
+    pub window: WindowOptions,
+
+    /// Glyphs displayed by the widgets
+    pub icons: IconSet,
+    /// Colors the terminal can display, detected at startup
+    pub colors: ColorSupport,
+    /// Modes in which the mouse is captured: `n`ormal, `v`isual, `i`nsert,
+    /// `c`ommand or `a`ll. Empty to leave the mouse to the terminal.
+    pub mouse: String,
+    /// Paste the primary selection on middle click
+    pub middlepaste: bool,
+    /// Highlight the matches of the last search
+    pub hlsearch: bool,
+    /// Jump to the first match and highlight the matches while typing a search
+    pub incsearch: bool,
+    /// Show the keys of the pending command on the right of the lualine
+    pub showcmd: bool,
+    /// Placement of the cmdline
+    pub cmdline: CmdlineLayout,
+    /// Keys of the panes
+    pub keypreset: KeyPreset,
+    /// Shell command run by `:make`, whose output is read for diagnostics
+    pub makeprg: String,
+    /// Value of the `guicursor` option
+    pub guicursor: String,
+    /// Cursor styles parsed from `guicursor`
+    pub cursor: CursorStyles,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { cursor_margin_y: 5 }
+        Self {
+            cursor_margin_y: 5,
+            scroll_tick: 3,
+            scrollpasteof: false,
+            eob: Some('~'),
+            numberwidth: 5,
+            foldcolumn: 0,
+            signcolumn: SignColumn::default(),
+            ignorecase: false,
+            smartcase: false,
+            magic: true,
+            middlepaste: true,
+            hlsearch: true,
+            incsearch: true,
+            showcmd: true,
+            updatetime: 4000,
+            textwidth: 0,
+            autoindent: true,
+            paste: false,
+            buffer: BufferOptions::default(),
+            window: WindowOptions {
+                number: true,
+                relativenumber: true,
+                cursorline: false,
+                wrap: false,
+            },
+            icons: IconSet::default(),
+            colors: ColorSupport::detect(),
+            mouse: "a".to_string(),
+            cmdline: CmdlineLayout::default(),
+            keypreset: KeyPreset::default(),
+            makeprg: "make".to_string(),
+            guicursor: GUICURSOR.to_string(),
+            cursor: CursorStyles {
+                normal: SetCursorStyle::SteadyBlock,
+                visual: SetCursorStyle::SteadyBlock,
+                insert: SetCursorStyle::SteadyBar,
+                command: SetCursorStyle::SteadyBar,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Configuration directory, in the XDG config directory.
+    /// It holds the `init.vim` startup script, executed as ex commands before the first render,
+    /// and the `plugins` folder, whose `.vim` and `.tes` scripts are executed after it.
+    pub fn dir() -> Option<PathBuf> {
+        let dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("tui-editor"))
+    }
+
+    /// Apply a single `:set` argument (`opt`, `noopt`, `opt!`, `invopt`, `opt=value`, `opt?`).
+    /// Returns a message to display when querying an option.
+    pub fn set(&mut self, arg: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            let invalid = || format!("E474: Invalid argument: {}", arg);
+            match name {
+                "icons" => {
+                    self.icons = IconSet::parse(value).ok_or_else(invalid)?;
+                    return Ok(None);
+                }
+                "colors" => {
+                    self.colors = ColorSupport::parse(value).ok_or_else(invalid)?;
+                    return Ok(None);
+                }
+                "cmdline" => {
+                    self.cmdline = CmdlineLayout::parse(value).ok_or_else(invalid)?;
+                    return Ok(None);
+                }
+                "keypreset" | "kp" => {
+                    self.keypreset = KeyPreset::parse(value).ok_or_else(invalid)?;
+                    return Ok(None);
+                }
+                "guicursor" | "gcr" => {
+                    self.cursor = CursorStyles::parse(value).ok_or_else(invalid)?;
+                    self.guicursor = value.to_string();
+                    return Ok(None);
+                }
+                "signcolumn" | "scl" => {
+                    self.signcolumn = SignColumn::parse(value).ok_or_else(invalid)?;
+                    return Ok(None);
+                }
+                "numberwidth" | "nuw" if value == "0" => {
+                    return Err(format!("E487: Argument must be positive: {}", arg));
+                }
+                "numberwidth" | "nuw" if value.parse().is_ok_and(|n: usize| n > 20) => {
+                    return Err(invalid());
+                }
+                "foldcolumn" | "fdc" if value.parse().is_ok_and(|n: usize| n > 12) => {
+                    return Err(invalid());
+                }
+                "mouse" if value.chars().all(|c| "nvica".contains(c)) => {
+                    self.mouse = value.to_string();
+                    return Ok(None);
+                }
+                "mouse" => return Err(invalid()),
+                "makeprg" => {
+                    self.makeprg = value.to_string();
+                    return Ok(None);
+                }
+                "fillchars" | "fcs" => {
+                    let fill = value.strip_prefix("eob:").ok_or_else(invalid)?;
+                    let mut chars = fill.chars();
+                    self.eob = match (chars.next(), chars.next()) {
+                        (None, _) | (Some(' '), None) => None,
+                        (Some(c), None) => Some(c),
+                        _ => return Err(invalid()),
+                    };
+                    return Ok(None);
+                }
+                _ => {}
+            }
+            let option = self.number_option(name)?;
+            *option = value
+                .parse()
+                .map_err(|_| format!("E521: Number required after =: {}", arg))?;
+            return Ok(None);
+        }
+        if let Some(name) = arg.strip_suffix('?') {
+            if let Some(value) = self.string_option(name) {
+                return Ok(Some(format!("  {}={}", name, value)));
+            }
+            if let Ok(value) = self.number_option(name) {
+                return Ok(Some(format!("  {}={}", name, value)));
+            }
+            let value = *self.bool_option(name)?;
+            return Ok(Some(format!("{}{}", if value { "  " } else { "no" }, name)));
+        }
+        if let Some(name) = arg.strip_suffix('!').or_else(|| arg.strip_prefix("inv")) {
+            let option = self.bool_option(name)?;
+            *option = !*option;
+            return Ok(None);
+        }
+        if let Ok(option) = self.bool_option(arg) {
+            *option = true;
+            return Ok(None);
+        }
+        if let Some(name) = arg.strip_prefix("no") {
+            *self.bool_option(name)? = false;
+            return Ok(None);
+        }
+        if let Ok(value) = self.number_option(arg) {
+            return Ok(Some(format!("  {}={}", arg, value)));
+        }
+        if let Some(value) = self.string_option(arg) {
+            return Ok(Some(format!("  {}={}", arg, value)));
+        }
+        Err(format!("E518: Unknown option: {}", arg))
+    }
+
+    /// Apply a single `:setlocal` argument to the overrides of a pane (`opt`, `noopt`,
+    /// `opt!`, `invopt`, `opt?`, and `opt<` to follow `:set` again).
+    /// Returns a message to display when querying an option.
+    pub fn set_local(
+        &self,
+        local: &mut WindowOptions<Option<bool>>,
+        arg: &str,
+    ) -> Result<Option<String>, String> {
+        let mut options = self.window.with(local);
+        // Only the window options have local values
+        let unknown = |name: &str| format!("E518: Unknown option: {}", name);
+        if let Some((name, _)) = arg.split_once('=') {
+            return Err(match options.get_mut(name) {
+                Some(_) => format!("E474: Invalid argument: {}", arg),
+                None => unknown(name),
+            });
+        }
+        if let Some(name) = arg.strip_suffix('?') {
+            let value = *options.get_mut(name).ok_or_else(|| unknown(name))?;
+            return Ok(Some(format!("{}{}", if value { "  " } else { "no" }, name)));
+        }
+        if let Some(name) = arg.strip_suffix('<') {
+            *local.get_mut(name).ok_or_else(|| unknown(name))? = None;
+            return Ok(None);
+        }
+        let (name, value) = match arg.strip_suffix('!').or_else(|| arg.strip_prefix("inv")) {
+            Some(name) => (name, !*options.get_mut(name).ok_or_else(|| unknown(name))?),
+            None => match arg.strip_prefix("no") {
+                Some(name) if options.get_mut(arg).is_none() => (name, false),
+                _ => (arg, true),
+            },
+        };
+        *local.get_mut(name).ok_or_else(|| unknown(name))? = Some(value);
+        Ok(None)
+    }
+
+    fn bool_option(&mut self, name: &str) -> Result<&mut bool, String> {
+        match name {
+            "ignorecase" | "ic" => Ok(&mut self.ignorecase),
+            "smartcase" | "scs" => Ok(&mut self.smartcase),
+            "magic" => Ok(&mut self.magic),
+            "middlepaste" | "mp" => Ok(&mut self.middlepaste),
+            "hlsearch" | "hls" => Ok(&mut self.hlsearch),
+            "incsearch" | "is" => Ok(&mut self.incsearch),
+            "showcmd" | "sc" => Ok(&mut self.showcmd),
+            "scrollpasteof" | "spe" => Ok(&mut self.scrollpasteof),
+            "autoindent" | "ai" => Ok(&mut self.autoindent),
+            "paste" => Ok(&mut self.paste),
+            name => self
+                .window
+                .get_mut(name)
+                .ok_or_else(|| format!("E518: Unknown option: {}", name)),
+        }
+    }
+
+    /// Whether mouse events are handled in a mode
+    pub fn mouse_enabled(&self, mode: Mode) -> bool {
+        let flag = match mode {
+            Mode::Normal => 'n',
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => 'v',
+            Mode::Insert => 'i',
+            Mode::Command => 'c',
+        };
+        self.mouse.contains(['a', flag])
+    }
+
+    fn string_option(&self, name: &str) -> Option<String> {
+        match name {
+            "icons" => Some(self.icons.name().to_string()),
+            "colors" => Some(self.colors.name().to_string()),
+            "mouse" => Some(self.mouse.clone()),
+            "makeprg" => Some(self.makeprg.clone()),
+            "cmdline" => Some(self.cmdline.name().to_string()),
+            "keypreset" | "kp" => Some(self.keypreset.name().to_string()),
+            "signcolumn" | "scl" => Some(self.signcolumn.name().to_string()),
+            "guicursor" | "gcr" => Some(self.guicursor.clone()),
+            "fillchars" | "fcs" => Some(format!("eob:{}", self.eob.unwrap_or(' '))),
+            _ => None,
+        }
+    }
+
+    fn number_option(&mut self, name: &str) -> Result<&mut usize, String> {
+        match name {
+            "updatetime" | "ut" => Ok(&mut self.updatetime),
+            "textwidth" | "tw" => Ok(&mut self.textwidth),
+            "scrolloff" | "so" => Ok(&mut self.cursor_margin_y),
+            "scrolltick" => Ok(&mut self.scroll_tick),
+            "numberwidth" | "nuw" => Ok(&mut self.numberwidth),
+            "foldcolumn" | "fdc" => Ok(&mut self.foldcolumn),
+            _ => Err(format!("E518: Unknown option: {}", name)),
+        }
     }
 }
@@ -0,0 +1,216 @@
+use std::fmt;
+
+mod json;
+mod toml;
+mod yaml;
+
+/// Key of a node in its parent: the name of an object entry, or the index of an array item
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Root,
+    Name(String),
+    Index(usize),
+}
+
+/// Value of a data file, with the lines it spans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub key: Key,
+    /// 0-based line and char column of the key, or of the value in arrays
+    pub line: usize,
+    pub column: usize,
+    /// Last line of the value
+    pub end_line: usize,
+    pub children: Vec<Node>,
+}
+
+/// Syntax error of a data file, at a 0-based line and char column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Root => Ok(()),
+            Self::Name(name) => write!(f, ".{}", name),
+            Self::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+impl Node {
+    fn new(key: Key, line: usize, column: usize) -> Self {
+        Self {
+            key,
+            line,
+            column,
+            end_line: line,
+            children: vec![],
+        }
+    }
+
+    fn child(&self, key: &Key) -> Option<&Node> {
+        self.children.iter().find(|child| child.key == *key)
+    }
+
+    /// Node at a path of keys below this one
+    pub fn find(&self, path: &[Key]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, key| node.child(key))
+    }
+
+    /// Line ranges of the nodes spanning several lines below this one, parents first
+    pub fn folds(&self, folds: &mut Vec<(usize, usize)>) {
+        if self.end_line > self.line && self.key != Key::Root {
+            folds.push((self.line, self.end_line));
+        }
+        for child in &self.children {
+            child.folds(folds);
+        }
+    }
+}
+
+/// Parse the text of a data file, JSON, YAML or TOML, None for the other filetypes
+pub fn parse(filetype: &str, text: &str) -> Option<Result<Node, ParseError>> {
+    match filetype {
+        "json" => Some(json::parse(text)),
+        "yaml" => Some(yaml::parse(text)),
+        "toml" => Some(toml::parse(text)),
+        _ => None,
+    }
+}
+
+/// Parse a path of keys like jq, as `.name`, `.items[2].id` or `."a key"`, `.` being the
+/// document itself
+pub fn parse_path(text: &str) -> Result<Vec<Key>, String> {
+    let invalid = || format!("Invalid path: {}", text);
+    let mut chars = text.trim().chars().peekable();
+    let mut path = vec![];
+    if chars.peek().is_none() {
+        return Err(invalid());
+    }
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => match chars.peek() {
+                Some('"') => {
+                    chars.next();
+                    path.push(Key::Name(quoted(&mut chars).ok_or_else(invalid)?));
+                }
+                Some('[') | None => {}
+                Some(_) => {
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek()
+                        && !matches!(c, '.' | '[')
+                    {
+                        name.push(c);
+                        chars.next();
+                    }
+                    path.push(Key::Name(name));
+                }
+            },
+            '[' => {
+                let key = match chars.peek() {
+                    Some('"') => {
+                        chars.next();
+                        Key::Name(quoted(&mut chars).ok_or_else(invalid)?)
+                    }
+                    _ => {
+                        let mut digits = String::new();
+                        while let Some(&c) = chars.peek()
+                            && c.is_ascii_digit()
+                        {
+                            digits.push(c);
+                            chars.next();
+                        }
+                        Key::Index(digits.parse().map_err(|_| invalid())?)
+                    }
+                };
+                if chars.next() != Some(']') {
+                    return Err(invalid());
+                }
+                path.push(key);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(path)
+}
+
+/// Rest of a double quoted string in a path, after its opening quote
+fn quoted(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(text),
+            '\\' => text.push(chars.next()?),
+            c => text.push(c),
+        }
+    }
+}
+
+/// Char reader of the JSON and TOML parsers, following the line and column
+struct Scanner {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Scanner {
+    fn new(text: &str) -> Self {
+        Self {
+            chars: text.chars().collect(),
+            pos: 0,
+            line: 0,
+            column: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 0;
+            }
+            _ => self.column += 1,
+        }
+        Some(c)
+    }
+
+    /// Consume a char if it is the next one
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.bump();
+        }
+        found
+    }
+
+    /// Error at the next char, which is missing at the end of the text
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let message = message.into();
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: match self.peek() {
+                Some(_) => message,
+                None => format!("{} at the end of the file", message),
+            },
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use super::{Key, Node, ParseError, Scanner};
+
+/// Parse a JSON document, strictly: no comments nor trailing commas
+pub(super) fn parse(text: &str) -> Result<Node, ParseError> {
+    let mut s = Scanner::new(text);
+    skip_whitespace(&mut s);
+    let (line, column) = (s.line, s.column);
+    let root = value(&mut s, Key::Root, line, column)?;
+    skip_whitespace(&mut s);
+    match s.peek() {
+        Some(_) => Err(s.error("unexpected characters after the document")),
+        None => Ok(root),
+    }
+}
+
+fn skip_whitespace(s: &mut Scanner) {
+    while s
+        .peek()
+        .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+    {
+        s.bump();
+    }
+}
+
+/// Parse a value, whose key starts at a line and column
+fn value(s: &mut Scanner, key: Key, line: usize, column: usize) -> Result<Node, ParseError> {
+    let mut node = Node::new(key, line, column);
+    match s.peek() {
+        Some('{') => {
+            s.bump();
+            skip_whitespace(s);
+            if !s.eat('}') {
+                loop {
+                    skip_whitespace(s);
+                    let (line, column) = (s.line, s.column);
+                    if s.peek() != Some('"') {
+                        return Err(s.error("expected a string key"));
+                    }
+                    let name = string(s)?;
+                    skip_whitespace(s);
+                    if !s.eat(':') {
+                        return Err(s.error("expected ':' after the key"));
+                    }
+                    skip_whitespace(s);
+                    node.children.push(value(s, Key::Name(name), line, column)?);
+                    skip_whitespace(s);
+                    match s.peek() {
+                        Some(',') => s.bump(),
+                        Some('}') => {
+                            s.bump();
+                            break;
+                        }
+                        _ => return Err(s.error("expected ',' or '}'")),
+                    };
+                }
+            }
+        }
+        Some('[') => {
+            s.bump();
+            skip_whitespace(s);
+            if !s.eat(']') {
+                loop {
+                    skip_whitespace(s);
+                    let key = Key::Index(node.children.len());
+                    let (line, column) = (s.line, s.column);
+                    node.children.push(value(s, key, line, column)?);
+                    skip_whitespace(s);
+                    match s.peek() {
+                        Some(',') => s.bump(),
+                        Some(']') => {
+                            s.bump();
+                            break;
+                        }
+                        _ => return Err(s.error("expected ',' or ']'")),
+                    };
+                }
+            }
+        }
+        Some('"') => {
+            string(s)?;
+        }
+        Some('-' | '0'..='9') => number(s)?,
+        Some(c) if c.is_alphabetic() => {
+            let start = (s.line, s.column);
+            let mut word = String::new();
+            while let Some(c) = s.peek().filter(|c| c.is_alphanumeric()) {
+                word.push(c);
+                s.bump();
+            }
+            if !matches!(word.as_str(), "true" | "false" | "null") {
+                return Err(ParseError {
+                    line: start.0,
+                    column: start.1,
+                    message: format!("unexpected '{}'", word),
+                });
+            }
+        }
+        Some(c) => return Err(s.error(format!("unexpected '{}'", c))),
+        None => return Err(s.error("expected a value")),
+    }
+    node.end_line = s.line;
+    Ok(node)
+}
+
+/// Parse a string, returning its unescaped text
+fn string(s: &mut Scanner) -> Result<String, ParseError> {
+    s.bump();
+    let mut text = String::new();
+    loop {
+        match s.peek() {
+            Some('"') => {
+                s.bump();
+                return Ok(text);
+            }
+            Some('\\') => {
+                s.bump();
+                match s.peek() {
+                    Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('u') => {
+                        s.bump();
+                        let mut hex = String::new();
+                        for _ in 0..4 {
+                            match s.peek().filter(char::is_ascii_hexdigit) {
+                                Some(c) => hex.push(c),
+                                None => return Err(s.error("expected 4 hex digits after \\u")),
+                            }
+                            s.bump();
+                        }
+                        let code = u32::from_str_radix(&hex, 16).unwrap_or_default();
+                        text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        continue;
+                    }
+                    _ => return Err(s.error("invalid escape sequence")),
+                }
+                s.bump();
+            }
+            Some('\n') | None => return Err(s.error("unterminated string")),
+            Some(c) if c.is_control() && c != '\t' => {
+                return Err(s.error("control character in a string"));
+            }
+            Some(c) => {
+                text.push(c);
+                s.bump();
+            }
+        }
+    }
+}
+
+/// Parse a number, like `-1.5e3`
+fn number(s: &mut Scanner) -> Result<(), ParseError> {
+    let digits = |s: &mut Scanner| {
+        let mut count = 0;
+        while s.peek().is_some_and(|c| c.is_ascii_digit()) {
+            s.bump();
+            count += 1;
+        }
+        count
+    };
+    s.eat('-');
+    match s.peek() {
+        Some('0') => {
+            s.bump();
+        }
+        Some('1'..='9') => {
+            digits(s);
+        }
+        _ => return Err(s.error("expected a digit")),
+    }
+    if s.eat('.') && digits(s) == 0 {
+        return Err(s.error("expected a digit after the decimal point"));
+    }
+    if s.eat('e') || s.eat('E') {
+        if !s.eat('+') {
+            s.eat('-');
+        }
+        if digits(s) == 0 {
+            return Err(s.error("expected a digit in the exponent"));
+        }
+    }
+    Ok(())
+}
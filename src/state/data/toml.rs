@@ -0,0 +1,349 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use regex::Regex;
+
+use super::{Key, Node, ParseError, Scanner};
+
+/// Numbers, booleans, and dates and times
+static SCALAR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"^(true|false|[+-]?(inf|nan)|[+-]?\d[\d_]*(\.\d[\d_]*)?([eE][+-]?\d[\d_]*)?",
+        r"|0x[\da-fA-F_]+|0o[0-7_]+|0b[01_]+",
+        r"|\d{4}-\d{2}-\d{2}([Tt ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?([Zz]|[+-]\d{2}:\d{2})?)?",
+        r"|\d{2}:\d{2}:\d{2}(\.\d+)?)$"
+    ))
+    .unwrap()
+});
+
+/// Parse a TOML document, its tables being nodes whether they have a header or come
+/// from dotted keys
+pub(super) fn parse(text: &str) -> Result<Node, ParseError> {
+    let mut s = Scanner::new(text);
+    let mut root = Node::new(Key::Root, 0, 0);
+    // Path of the current table, with the indices of the arrays of tables
+    let mut table = vec![];
+    let mut headers = HashSet::new();
+    loop {
+        skip_blank(&mut s);
+        if s.peek().is_none() {
+            break;
+        }
+        let (line, column) = (s.line, s.column);
+        if s.eat('[') {
+            let array = s.eat('[');
+            skip_spaces(&mut s);
+            let (parents, last) = key(&mut s)?;
+            skip_spaces(&mut s);
+            if !s.eat(']') || (array && !s.eat(']')) {
+                return Err(s.error("expected ']' after the table name"));
+            }
+            end_of_line(&mut s)?;
+
+            let mut path = vec![];
+            let mut node = &mut root;
+            for name in &parents {
+                path.push(Key::Name(name.clone()));
+                node = entry(node, Key::Name(name.clone()), line, column);
+                // The parent is the last table of an array of tables
+                if let Some(item) = node.children.last()
+                    && matches!(item.key, Key::Index(_))
+                {
+                    path.push(item.key.clone());
+                    let index = node.children.len() - 1;
+                    node = &mut node.children[index];
+                }
+            }
+            path.push(Key::Name(last.clone()));
+            let exists = node.child(&Key::Name(last.clone())).is_some();
+            let node = entry(node, Key::Name(last.clone()), line, column);
+            match array {
+                true => {
+                    let key = Key::Index(node.children.len());
+                    path.push(key.clone());
+                    node.children.push(Node::new(key, line, column));
+                }
+                false => {
+                    if !headers.insert(path.clone()) {
+                        let mut name = parents;
+                        name.push(last);
+                        return Err(ParseError {
+                            line,
+                            column,
+                            message: format!("duplicate table [{}]", name.join(".")),
+                        });
+                    }
+                    // Tables first defined by dotted keys start at their header
+                    if exists {
+                        node.line = line;
+                        node.column = column;
+                    }
+                }
+            }
+            table = path;
+        } else {
+            let node = table.iter().fold(&mut root, |node, key| {
+                entry(node, key.clone(), line, column)
+            });
+            key_value(&mut s, node)?;
+            node.end_line = node.end_line.max(s.line);
+            end_of_line(&mut s)?;
+        }
+    }
+    Ok(root)
+}
+
+/// Child of a table, added when missing
+fn entry(node: &mut Node, key: Key, line: usize, column: usize) -> &mut Node {
+    match node.children.iter().position(|child| child.key == key) {
+        Some(index) => &mut node.children[index],
+        None => {
+            node.children.push(Node::new(key, line, column));
+            let index = node.children.len() - 1;
+            &mut node.children[index]
+        }
+    }
+}
+
+/// Skip the spaces, line breaks and comments between the statements
+fn skip_blank(s: &mut Scanner) {
+    loop {
+        match s.peek() {
+            Some(' ' | '\t' | '\n' | '\r') => {
+                s.bump();
+            }
+            Some('#') => {
+                while s.peek().is_some_and(|c| c != '\n') {
+                    s.bump();
+                }
+            }
+            _ => return,
+        }
+    }
+}
+
+fn skip_spaces(s: &mut Scanner) {
+    while s.peek().is_some_and(|c| matches!(c, ' ' | '\t')) {
+        s.bump();
+    }
+}
+
+/// Skip the comment ending a statement, before the line break
+fn end_of_line(s: &mut Scanner) -> Result<(), ParseError> {
+    skip_spaces(s);
+    if s.peek() == Some('#') {
+        while s.peek().is_some_and(|c| c != '\n') {
+            s.bump();
+        }
+    }
+    s.eat('\r');
+    match s.peek() {
+        Some('\n') | None => Ok(()),
+        Some(_) => Err(s.error("expected a line break after the statement")),
+    }
+}
+
+/// Parse a key, dotted like `a."b c".d`, as its parent tables and its last part
+fn key(s: &mut Scanner) -> Result<(Vec<String>, String), ParseError> {
+    let mut keys = vec![];
+    loop {
+        let name = match s.peek() {
+            Some('"') => basic_string(s)?,
+            Some('\'') => literal_string(s)?,
+            _ => {
+                let mut name = String::new();
+                while let Some(c) = s
+                    .peek()
+                    .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+                {
+                    name.push(c);
+                    s.bump();
+                }
+                if name.is_empty() {
+                    return Err(s.error("expected a key"));
+                }
+                name
+            }
+        };
+        skip_spaces(s);
+        if !s.eat('.') {
+            return Ok((keys, name));
+        }
+        keys.push(name);
+        skip_spaces(s);
+    }
+}
+
+/// Parse a `key = value` statement into a table
+fn key_value(s: &mut Scanner, table: &mut Node) -> Result<(), ParseError> {
+    let (line, column) = (s.line, s.column);
+    let (parents, last) = key(s)?;
+    skip_spaces(s);
+    if !s.eat('=') {
+        return Err(s.error("expected '=' after the key"));
+    }
+    skip_spaces(s);
+
+    let path = parents
+        .iter()
+        .map(|name| Key::Name(name.clone()))
+        .chain([Key::Name(last.clone())])
+        .collect::<Vec<_>>();
+    if table.find(&path).is_some() {
+        let mut name = parents;
+        name.push(last);
+        return Err(ParseError {
+            line,
+            column,
+            message: format!("duplicate key '{}'", name.join(".")),
+        });
+    }
+    let value = value(s, Key::Name(last), line, column)?;
+    // The tables of dotted keys span their values
+    let mut node = table;
+    for name in parents {
+        node = entry(node, Key::Name(name), line, column);
+        node.end_line = node.end_line.max(value.end_line);
+    }
+    node.children.push(value);
+    Ok(())
+}
+
+/// Parse a value, whose key starts at a line and column
+fn value(s: &mut Scanner, key: Key, line: usize, column: usize) -> Result<Node, ParseError> {
+    let mut node = Node::new(key, line, column);
+    match s.peek() {
+        Some('"') => {
+            basic_string(s)?;
+        }
+        Some('\'') => {
+            literal_string(s)?;
+        }
+        Some('[') => {
+            s.bump();
+            loop {
+                skip_blank(s);
+                if s.eat(']') {
+                    break;
+                }
+                let key = Key::Index(node.children.len());
+                let (line, column) = (s.line, s.column);
+                node.children.push(value(s, key, line, column)?);
+                skip_blank(s);
+                if s.eat(']') {
+                    break;
+                }
+                if !s.eat(',') {
+                    return Err(s.error("expected ',' or ']'"));
+                }
+            }
+        }
+        Some('{') => {
+            s.bump();
+            skip_spaces(s);
+            if !s.eat('}') {
+                loop {
+                    skip_spaces(s);
+                    key_value(s, &mut node)?;
+                    skip_spaces(s);
+                    if s.eat('}') {
+                        break;
+                    }
+                    if !s.eat(',') {
+                        return Err(s.error("expected ',' or '}'"));
+                    }
+                }
+            }
+        }
+        _ => {
+            let start = (s.line, s.column);
+            let mut word = String::new();
+            while let Some(c) = s
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '.' | ':'))
+            {
+                word.push(c);
+                s.bump();
+                // Dates and times may be separated by a space
+                if word.len() == 10
+                    && s.peek() == Some(' ')
+                    && s.chars.get(s.pos + 1).is_some_and(char::is_ascii_digit)
+                {
+                    word.push(' ');
+                    s.bump();
+                }
+            }
+            if !SCALAR.is_match(&word) {
+                return Err(ParseError {
+                    line: start.0,
+                    column: start.1,
+                    message: match word.is_empty() {
+                        true => "expected a value".to_string(),
+                        false => format!("invalid value '{}'", word),
+                    },
+                });
+            }
+        }
+    }
+    node.end_line = s.line;
+    Ok(node)
+}
+
+/// Parse a `"basic"` or `"""multi-line"""` string, returning its text with the escapes
+/// left as is
+fn basic_string(s: &mut Scanner) -> Result<String, ParseError> {
+    let multiline = s.starts_with("\"\"\"");
+    let quotes = if multiline { 3 } else { 1 };
+    for _ in 0..quotes {
+        s.bump();
+    }
+    let mut text = String::new();
+    loop {
+        match s.peek() {
+            Some('"') if !multiline || s.starts_with("\"\"\"") => {
+                for _ in 0..quotes {
+                    s.bump();
+                }
+                return Ok(text);
+            }
+            Some('\\') => {
+                s.bump();
+                match s.bump() {
+                    Some(c) => text.push(c),
+                    None => return Err(s.error("unterminated string")),
+                }
+            }
+            Some('\n') if !multiline => return Err(s.error("unterminated string")),
+            None => return Err(s.error("unterminated string")),
+            Some(c) => {
+                text.push(c);
+                s.bump();
+            }
+        }
+    }
+}
+
+/// Parse a `'literal'` or `'''multi-line'''` string
+fn literal_string(s: &mut Scanner) -> Result<String, ParseError> {
+    let multiline = s.starts_with("'''");
+    let quotes = if multiline { 3 } else { 1 };
+    for _ in 0..quotes {
+        s.bump();
+    }
+    let mut text = String::new();
+    loop {
+        match s.peek() {
+            Some('\'') if !multiline || s.starts_with("'''") => {
+                for _ in 0..quotes {
+                    s.bump();
+                }
+                return Ok(text);
+            }
+            Some('\n') if !multiline => return Err(s.error("unterminated string")),
+            None => return Err(s.error("unterminated string")),
+            Some(c) => {
+                text.push(c);
+                s.bump();
+            }
+        }
+    }
+}
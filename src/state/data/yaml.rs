@@ -0,0 +1,415 @@
+use super::{Key, Node, ParseError};
+
+/// Line of the block structure of a YAML document, without its comment
+#[derive(Debug, Clone, Copy)]
+struct Content<'a> {
+    line: usize,
+    indent: usize,
+    text: &'a str,
+}
+
+/// Parser of the block mappings and sequences of the first document of a YAML file.
+/// Flow collections and multi-line scalars are checked but kept as single values.
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    /// Next line to read
+    pos: usize,
+}
+
+/// Parse a YAML document
+pub(super) fn parse(text: &str) -> Result<Node, ParseError> {
+    let mut parser = Parser {
+        lines: text.lines().collect(),
+        pos: 0,
+    };
+    // Directives and the document start come before the content
+    while let Some(line) = parser.lines.get(parser.pos)
+        && (line.starts_with('%') || is_marker(line, "---") || is_blank(line))
+    {
+        parser.pos += 1;
+        if is_marker(line, "---") {
+            break;
+        }
+    }
+    let mut root = Node::new(Key::Root, parser.pos, 0);
+    parser.block(&mut root, None, false)?;
+    match parser.peek()? {
+        Some(content) => Err(ParseError {
+            line: content.line,
+            column: content.indent,
+            message: "bad indentation".to_string(),
+        }),
+        None => Ok(root),
+    }
+}
+
+/// Whether a line is a document marker, like `---` or `...`
+fn is_marker(line: &str, marker: &str) -> bool {
+    line == marker
+        || line
+            .strip_prefix(marker)
+            .is_some_and(|rest| rest.starts_with(' '))
+}
+
+fn is_blank(line: &str) -> bool {
+    let text = line.trim_start();
+    text.is_empty() || text.starts_with('#')
+}
+
+/// Text of a line up to its comment, a `#` after a space out of quotes
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '#') if previous.is_whitespace() => return text[..i].trim_end(),
+            (None, '"' | '\'') if previous.is_whitespace() || ":[{,".contains(previous) => {
+                quote = Some(c);
+            }
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    text.trim_end()
+}
+
+/// Length in bytes of the key of a mapping entry, up to its `:`
+fn key_len(text: &str) -> Option<usize> {
+    if let Some(quote @ ('"' | '\'')) = text.chars().next() {
+        let end = text[1..].find(quote)? + 1;
+        return text[end + 1..].starts_with(':').then_some(end + 1);
+    }
+    if text.starts_with(['[', '{', '|', '>']) {
+        return None;
+    }
+    text.char_indices()
+        .find(|&(i, c)| c == ':' && text[i + 1..].chars().next().is_none_or(char::is_whitespace))
+        .map(|(i, _)| i)
+}
+
+/// Name of a key, without its quotes
+fn key_name(key: &str) -> String {
+    match key.chars().next() {
+        Some('"' | '\'') if key.len() > 1 => key[1..key.len() - 1].to_string(),
+        _ => key.trim_end().to_string(),
+    }
+}
+
+/// Whether a line is a sequence entry, like `- item`
+fn is_entry(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+impl<'a> Parser<'a> {
+    fn error(line: usize, column: usize, message: &str) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    /// Next line of content, skipping the blank lines and the comments, None at the end
+    /// of the first document
+    fn peek(&mut self) -> Result<Option<Content<'a>>, ParseError> {
+        while let Some(&line) = self.lines.get(self.pos) {
+            if is_marker(line, "---") || is_marker(line, "...") {
+                return Ok(None);
+            }
+            if is_blank(line) {
+                self.pos += 1;
+                continue;
+            }
+            let text = line.trim_start_matches(' ');
+            let indent = line.len() - text.len();
+            if text.starts_with('\t') {
+                return Err(Self::error(
+                    self.pos,
+                    indent,
+                    "tabs are not allowed in the indentation",
+                ));
+            }
+            return Ok(Some(Content {
+                line: self.pos,
+                indent,
+                text: strip_comment(text),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Parse the block of a node, on the next lines when they are indented more than its
+    /// parent. A mapping value may also be a sequence at the indentation of its key.
+    fn block(
+        &mut self,
+        node: &mut Node,
+        parent: Option<usize>,
+        mapping_value: bool,
+    ) -> Result<(), ParseError> {
+        let Some(content) = self.peek()? else {
+            return Ok(());
+        };
+        match parent {
+            Some(parent) if content.indent < parent => return Ok(()),
+            Some(parent)
+                if content.indent == parent && !(mapping_value && is_entry(content.text)) =>
+            {
+                return Ok(());
+            }
+            _ => {}
+        }
+        match is_entry(content.text) {
+            true => self.sequence(node, content.indent),
+            false => self.mapping(node, content.indent, None),
+        }
+    }
+
+    /// Parse the entries of a sequence at an indentation
+    fn sequence(&mut self, node: &mut Node, indent: usize) -> Result<(), ParseError> {
+        while let Some(content) = self.peek()? {
+            if content.indent < indent {
+                break;
+            }
+            if content.indent > indent {
+                let text = "bad indentation of a sequence entry";
+                return Err(Self::error(content.line, content.indent, text));
+            }
+            if !is_entry(content.text) {
+                break;
+            }
+            self.pos += 1;
+            let mut item = Node::new(Key::Index(node.children.len()), content.line, indent);
+            let rest = content.text[1..].trim_start();
+            let column = indent + content.text.len() - rest.len();
+            match key_len(rest) {
+                // A mapping starting on the line of the entry
+                Some(_) if !rest.is_empty() => {
+                    let first = Content {
+                        line: content.line,
+                        indent: column,
+                        text: rest,
+                    };
+                    self.mapping(&mut item, column, Some(first))?;
+                }
+                _ => self.value(&mut item, rest, content.line, column, indent, false)?,
+            }
+            node.end_line = node.end_line.max(item.end_line);
+            node.children.push(item);
+        }
+        Ok(())
+    }
+
+    /// Parse the entries of a mapping at an indentation, the first one being already read
+    /// after a sequence entry
+    fn mapping(
+        &mut self,
+        node: &mut Node,
+        indent: usize,
+        mut first: Option<Content<'a>>,
+    ) -> Result<(), ParseError> {
+        loop {
+            let content = match first.take() {
+                Some(content) => content,
+                None => {
+                    let Some(content) = self.peek()? else {
+                        break;
+                    };
+                    if content.indent < indent {
+                        break;
+                    }
+                    if content.indent > indent {
+                        let text = "bad indentation of a mapping entry";
+                        return Err(Self::error(content.line, content.indent, text));
+                    }
+                    if is_entry(content.text) {
+                        let text = "expected a mapping entry, found a sequence entry";
+                        return Err(Self::error(content.line, content.indent, text));
+                    }
+                    self.pos += 1;
+                    content
+                }
+            };
+            let Some(len) = key_len(content.text) else {
+                let text = "expected ':' after the key";
+                return Err(Self::error(content.line, content.indent, text));
+            };
+            let name = key_name(&content.text[..len]);
+            if node.child(&Key::Name(name.clone())).is_some() {
+                let text = format!("duplicate key '{}'", name);
+                return Err(Self::error(content.line, content.indent, &text));
+            }
+            let mut entry = Node::new(Key::Name(name), content.line, content.indent);
+            let rest = content.text[len + 1..].trim_start();
+            let column = content.indent + content.text.len() - rest.len();
+            self.value(&mut entry, rest, content.line, column, content.indent, true)?;
+            node.end_line = node.end_line.max(entry.end_line);
+            node.children.push(entry);
+        }
+        Ok(())
+    }
+
+    /// Parse the value of an entry after its key or `-`, at a line and column, and on the
+    /// next lines indented more than the entry
+    fn value(
+        &mut self,
+        node: &mut Node,
+        text: &str,
+        line: usize,
+        column: usize,
+        indent: usize,
+        mapping_value: bool,
+    ) -> Result<(), ParseError> {
+        // Anchors and tags come before the value
+        let mut text = text;
+        let mut column = column;
+        while text.starts_with(['&', '!']) {
+            let rest = text
+                .find(char::is_whitespace)
+                .map_or("", |i| text[i..].trim_start());
+            column += text.len() - rest.len();
+            text = rest;
+        }
+
+        match text.chars().next() {
+            None => {
+                return self.block(node, Some(indent), mapping_value);
+            }
+            Some('|' | '>') => {
+                // Block scalars go on until a line indented as much as the entry
+                while let Some(line) = self.lines.get(self.pos) {
+                    let text = line.trim_start_matches(' ');
+                    if !text.trim().is_empty() && line.len() - text.len() <= indent {
+                        break;
+                    }
+                    if !text.trim().is_empty() {
+                        node.end_line = self.pos;
+                    }
+                    self.pos += 1;
+                }
+                return Ok(());
+            }
+            Some(quote @ ('"' | '\'')) => self.quoted(node, text, quote, line, column)?,
+            Some(open @ ('[' | '{')) => self.flow(node, text, open, line, column)?,
+            Some(_) => {
+                if let Some(i) = text
+                    .find(": ")
+                    .or(text.ends_with(':').then(|| text.len() - 1))
+                {
+                    let position = column + text[..i].chars().count();
+                    let text = "mapping values are not allowed here";
+                    return Err(Self::error(line, position, text));
+                }
+                // Plain scalars go on over the lines indented more than the entry
+                while let Some(content) = self.peek()?
+                    && content.indent > indent
+                {
+                    if key_len(content.text).is_some() {
+                        let text = "mapping values are not allowed here";
+                        return Err(Self::error(content.line, content.indent, text));
+                    }
+                    node.end_line = content.line;
+                    self.pos += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a quoted scalar, which may go on over the next lines
+    fn quoted(
+        &mut self,
+        node: &mut Node,
+        text: &str,
+        quote: char,
+        line: usize,
+        column: usize,
+    ) -> Result<(), ParseError> {
+        let mut rest = &text[1..];
+        let mut current = line;
+        loop {
+            let mut chars = rest.char_indices();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                match (quote, c) {
+                    ('"', '\\') => {
+                        chars.next();
+                    }
+                    ('\'', '\'') if rest[i + 1..].starts_with('\'') => {
+                        chars.next();
+                    }
+                    _ if c == quote => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(end) = end {
+                if !strip_comment(&rest[end..]).is_empty() {
+                    let text = "unexpected characters after the quoted scalar";
+                    return Err(Self::error(current, 0, text));
+                }
+                node.end_line = current;
+                return Ok(());
+            }
+            current += 1;
+            self.pos = current + 1;
+            match self.lines.get(current) {
+                Some(line) if !is_marker(line, "---") && !is_marker(line, "...") => rest = line,
+                _ => return Err(Self::error(line, column, "unterminated quoted scalar")),
+            }
+        }
+    }
+
+    /// Check that the brackets of a flow collection are balanced, over the next lines
+    fn flow(
+        &mut self,
+        node: &mut Node,
+        text: &str,
+        open: char,
+        line: usize,
+        column: usize,
+    ) -> Result<(), ParseError> {
+        let mut depth = 0usize;
+        let mut quote = None;
+        let mut current = line;
+        let mut rest = text;
+        loop {
+            let mut escaped = false;
+            for c in rest.chars() {
+                match (quote, c) {
+                    (Some('"'), '\\') if !escaped => {
+                        escaped = true;
+                        continue;
+                    }
+                    (Some(q), c) if c == q && !escaped => quote = None,
+                    (Some(_), _) => {}
+                    (None, '"' | '\'') => quote = Some(c),
+                    (None, '[' | '{') => depth += 1,
+                    (None, ']' | '}') => {
+                        depth = depth.saturating_sub(1);
+                        if depth == 0 {
+                            node.end_line = current;
+                            return Ok(());
+                        }
+                    }
+                    _ => {}
+                }
+                escaped = false;
+            }
+            current += 1;
+            self.pos = current + 1;
+            match self.lines.get(current) {
+                Some(line) if !is_marker(line, "---") && !is_marker(line, "...") => {
+                    rest = strip_comment(line);
+                }
+                _ => {
+                    let close = if open == '[' { ']' } else { '}' };
+                    let text = format!("unclosed flow collection, expected '{}'", close);
+                    return Err(Self::error(line, column, &text));
+                }
+            }
+        }
+    }
+}
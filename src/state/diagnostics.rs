@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use regex::Regex;
+
+use crate::utils::absolute_path;
+
+/// `file:line:col: error: message` lines of gcc, clang and most linters
+static LOCATED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^([^:\s][^:]*):(\d+):(?:(\d+):)?\s*(fatal error|error|warning|note|info)(?:\[[^\]]*\])?:\s*(.*)$",
+    )
+    .unwrap()
+});
+/// `error[E0425]: message` headers of rustc, whose location follows on a `-->` line
+static HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(error|warning|note|info)(?:\[[^\]]*\])?:\s*(.*)$").unwrap());
+static ARROW: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*--> ([^:]+):(\d+):(\d+)$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "note",
+        }
+    }
+
+    fn parse(text: &str) -> Self {
+        match text {
+            "error" | "fatal error" => Self::Error,
+            "warning" => Self::Warning,
+            _ => Self::Info,
+        }
+    }
+}
+
+/// Message of a compiler or linter about a position of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 0-based line and column
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Number of diagnostics of each severity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub infos: usize,
+}
+
+impl Counts {
+    pub fn total(&self) -> usize {
+        self.errors + self.warnings + self.infos
+    }
+
+    /// Non-zero counts, like `2 errors, 1 warning`
+    pub fn summary(&self) -> String {
+        let plural = |count: usize, name: &str| match count {
+            1 => format!("1 {}", name),
+            count => format!("{} {}s", count, name),
+        };
+        let parts = [
+            (self.errors, "error"),
+            (self.warnings, "warning"),
+            (self.infos, "note"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, name)| plural(count, name))
+        .collect::<Vec<_>>();
+        match parts.is_empty() {
+            true => "no diagnostics".to_string(),
+            false => parts.join(", "),
+        }
+    }
+
+    fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Info => self.infos += 1,
+        }
+    }
+}
+
+/// Diagnostics of the workspace files, by absolute path, replaced by each `:make` and
+/// by the checks of the data files on write
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    files: BTreeMap<PathBuf, Vec<Diagnostic>>,
+}
+
+impl Diagnostics {
+    /// Read the diagnostics of a compiler output, with paths relative to a folder
+    pub fn parse(output: &str, folder: &Path) -> Self {
+        let mut diagnostics = Self::default();
+        let mut header = None;
+        for line in output.lines() {
+            let line = line.trim_end();
+            if let Some(captures) = LOCATED.captures(line) {
+                let number = |i: usize| {
+                    captures
+                        .get(i)
+                        .and_then(|m| m.as_str().parse::<usize>().ok())
+                        .map_or(0, |n| n.saturating_sub(1))
+                };
+                let diagnostic = Diagnostic {
+                    line: number(2),
+                    column: number(3),
+                    severity: Severity::parse(&captures[4]),
+                    message: captures[5].to_string(),
+                };
+                diagnostics.push(folder, &captures[1], diagnostic);
+                header = None;
+            } else if let Some(captures) = HEADER.captures(line) {
+                header = Some((Severity::parse(&captures[1]), captures[2].to_string()));
+            } else if let Some(captures) = ARROW.captures(line)
+                && let Some((severity, message)) = header.take()
+            {
+                let number = |i: usize| {
+                    captures[i]
+                        .parse::<usize>()
+                        .map_or(0, |n| n.saturating_sub(1))
+                };
+                let diagnostic = Diagnostic {
+                    line: number(2),
+                    column: number(3),
+                    severity,
+                    message,
+                };
+                diagnostics.push(folder, &captures[1], diagnostic);
+            }
+        }
+        diagnostics
+    }
+
+    fn push(&mut self, folder: &Path, path: &str, diagnostic: Diagnostic) {
+        let path = absolute_path(&folder.join(path));
+        self.files.entry(path).or_default().push(diagnostic);
+    }
+
+    /// Replace the diagnostics of a file
+    pub fn set(&mut self, path: &Path, diagnostics: Vec<Diagnostic>) {
+        match diagnostics.is_empty() {
+            true => self.files.remove(path),
+            false => self.files.insert(path.to_path_buf(), diagnostics),
+        };
+    }
+
+    /// Most severe diagnostic, the first one of its severity in path order
+    pub fn first(&self) -> Option<(&Path, &Diagnostic)> {
+        self.files
+            .iter()
+            .flat_map(|(path, diagnostics)| diagnostics.iter().map(move |d| (path.as_path(), d)))
+            .min_by_key(|(_, diagnostic)| diagnostic.severity)
+    }
+
+    /// Diagnostic counts of a file
+    pub fn counts(&self, path: &Path) -> Counts {
+        let mut counts = Counts::default();
+        for diagnostic in self.files.get(path).into_iter().flatten() {
+            counts.add(diagnostic.severity);
+        }
+        counts
+    }
+
+    /// Diagnostic counts of all the files
+    pub fn workspace_counts(&self) -> Counts {
+        let mut counts = Counts::default();
+        for diagnostic in self.files.values().flatten() {
+            counts.add(diagnostic.severity);
+        }
+        counts
+    }
+}
@@ -0,0 +1,76 @@
+use ropey::Rope;
+
+use crate::position::line_count;
+
+/// Text of a line without its line break
+fn line(rope: &Rope, index: usize) -> String {
+    rope.line(index)
+        .to_string()
+        .trim_end_matches(['\n', '\r'])
+        .to_string()
+}
+
+/// Whether a line is a hunk header, like `@@ -1,3 +1,4 @@`
+fn is_hunk_header(text: &str) -> bool {
+    text.starts_with("@@ ")
+}
+
+/// Whether a line is the `--- a/path` line of a file header, followed by `+++ b/path`
+fn is_file_header(rope: &Rope, index: usize) -> bool {
+    line(rope, index).starts_with("--- ")
+        && index + 1 < line_count(rope)
+        && line(rope, index + 1).starts_with("+++ ")
+}
+
+/// Line of the next hunk header after a line, or of the previous one before it
+pub fn next_hunk(rope: &Rope, from: usize, forward: bool) -> Option<usize> {
+    let header = |index: &usize| is_hunk_header(&line(rope, *index));
+    match forward {
+        true => (from + 1..line_count(rope)).find(header),
+        false => (0..from).rev().find(header),
+    }
+}
+
+/// Patch of the hunk under a line, with the header of its file, to be applied on its own
+pub fn hunk_patch(rope: &Rope, at: usize) -> Result<String, String> {
+    let not_found = || "No hunk under the cursor".to_string();
+    let lines = line_count(rope);
+    // The hunk header is above the line, in the same file
+    let start = (0..=at.min(lines.saturating_sub(1)))
+        .rev()
+        .take_while(|index| *index == at || !is_file_header(rope, *index + 1))
+        .find(|index| is_hunk_header(&line(rope, *index)))
+        .ok_or_else(not_found)?;
+    let end = (start + 1..lines)
+        .find(|index| {
+            let text = line(rope, *index);
+            !text.starts_with([' ', '+', '-', '\\']) || is_file_header(rope, *index)
+        })
+        .unwrap_or(lines);
+    if at >= end {
+        return Err(not_found());
+    }
+
+    // The file header is made of the `---` and `+++` lines, after the `diff` line and the
+    // mode lines of git
+    let plus = (0..start)
+        .rev()
+        .find(|index| *index > 0 && is_file_header(rope, *index - 1))
+        .ok_or("No file header above the hunk")?;
+    let minus = plus - 1;
+    let first = (0..minus)
+        .rev()
+        .take_while(|index| {
+            let text = line(rope, *index);
+            !is_hunk_header(&text) && !text.starts_with([' ', '+', '-'])
+        })
+        .find(|index| line(rope, *index).starts_with("diff "))
+        .unwrap_or(minus);
+
+    let mut patch = String::new();
+    for index in (first..=plus).chain(start..end) {
+        patch.push_str(&line(rope, index));
+        patch.push('\n');
+    }
+    Ok(patch)
+}
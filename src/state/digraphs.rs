@@ -0,0 +1,499 @@
+/// RFC1345 digraphs, typed with `CTRL-K {char1} {char2}` in insert mode.
+/// Accented letters follow the letter with `!` grave, `'` acute, `>` circumflex, `?` tilde,
+/// `:` diaeresis, `,` cedilla, `;` ogonek, `<` caron, `-` macron, `(` breve, `.` dot,
+/// `0` ring and `"` double acute. Greek letters are followed by `*`, cyrillic ones by `=`.
+const DIGRAPHS: &[(&str, char)] = &[
+    ("a!", 'à'),
+    ("a'", 'á'),
+    ("a>", 'â'),
+    ("a?", 'ã'),
+    ("a-", 'ā'),
+    ("a(", 'ă'),
+    ("a.", 'ȧ'),
+    ("a:", 'ä'),
+    ("a0", 'å'),
+    ("a<", 'ǎ'),
+    ("a;", 'ą'),
+    ("c'", 'ć'),
+    ("c>", 'ĉ'),
+    ("c.", 'ċ'),
+    ("c<", 'č'),
+    ("c,", 'ç'),
+    ("d<", 'ď'),
+    ("e!", 'è'),
+    ("e'", 'é'),
+    ("e>", 'ê'),
+    ("e-", 'ē'),
+    ("e(", 'ĕ'),
+    ("e.", 'ė'),
+    ("e:", 'ë'),
+    ("e<", 'ě'),
+    ("e,", 'ȩ'),
+    ("e;", 'ę'),
+    ("g'", 'ǵ'),
+    ("g>", 'ĝ'),
+    ("g(", 'ğ'),
+    ("g.", 'ġ'),
+    ("g<", 'ǧ'),
+    ("g,", 'ģ'),
+    ("h>", 'ĥ'),
+    ("h<", 'ȟ'),
+    ("i!", 'ì'),
+    ("i'", 'í'),
+    ("i>", 'î'),
+    ("i?", 'ĩ'),
+    ("i-", 'ī'),
+    ("i(", 'ĭ'),
+    ("i:", 'ï'),
+    ("i<", 'ǐ'),
+    ("i;", 'į'),
+    ("j>", 'ĵ'),
+    ("j<", 'ǰ'),
+    ("k<", 'ǩ'),
+    ("k,", 'ķ'),
+    ("l'", 'ĺ'),
+    ("l<", 'ľ'),
+    ("l,", 'ļ'),
+    ("n!", 'ǹ'),
+    ("n'", 'ń'),
+    ("n?", 'ñ'),
+    ("n<", 'ň'),
+    ("n,", 'ņ'),
+    ("o!", 'ò'),
+    ("o'", 'ó'),
+    ("o>", 'ô'),
+    ("o?", 'õ'),
+    ("o-", 'ō'),
+    ("o(", 'ŏ'),
+    ("o.", 'ȯ'),
+    ("o:", 'ö'),
+    ("o\"", 'ő'),
+    ("o<", 'ǒ'),
+    ("o;", 'ǫ'),
+    ("r'", 'ŕ'),
+    ("r<", 'ř'),
+    ("r,", 'ŗ'),
+    ("s'", 'ś'),
+    ("s>", 'ŝ'),
+    ("s<", 'š'),
+    ("s,", 'ş'),
+    ("t<", 'ť'),
+    ("t,", 'ţ'),
+    ("u!", 'ù'),
+    ("u'", 'ú'),
+    ("u>", 'û'),
+    ("u?", 'ũ'),
+    ("u-", 'ū'),
+    ("u(", 'ŭ'),
+    ("u:", 'ü'),
+    ("u0", 'ů'),
+    ("u\"", 'ű'),
+    ("u<", 'ǔ'),
+    ("u;", 'ų'),
+    ("w>", 'ŵ'),
+    ("y'", 'ý'),
+    ("y>", 'ŷ'),
+    ("y-", 'ȳ'),
+    ("y:", 'ÿ'),
+    ("z'", 'ź'),
+    ("z.", 'ż'),
+    ("z<", 'ž'),
+    ("A!", 'À'),
+    ("A'", 'Á'),
+    ("A>", 'Â'),
+    ("A?", 'Ã'),
+    ("A-", 'Ā'),
+    ("A(", 'Ă'),
+    ("A.", 'Ȧ'),
+    ("A:", 'Ä'),
+    ("A0", 'Å'),
+    ("A<", 'Ǎ'),
+    ("A;", 'Ą'),
+    ("C'", 'Ć'),
+    ("C>", 'Ĉ'),
+    ("C.", 'Ċ'),
+    ("C<", 'Č'),
+    ("C,", 'Ç'),
+    ("D<", 'Ď'),
+    ("E!", 'È'),
+    ("E'", 'É'),
+    ("E>", 'Ê'),
+    ("E-", 'Ē'),
+    ("E(", 'Ĕ'),
+    ("E.", 'Ė'),
+    ("E:", 'Ë'),
+    ("E<", 'Ě'),
+    ("E,", 'Ȩ'),
+    ("E;", 'Ę'),
+    ("G'", 'Ǵ'),
+    ("G>", 'Ĝ'),
+    ("G(", 'Ğ'),
+    ("G.", 'Ġ'),
+    ("G<", 'Ǧ'),
+    ("G,", 'Ģ'),
+    ("H>", 'Ĥ'),
+    ("H<", 'Ȟ'),
+    ("I!", 'Ì'),
+    ("I'", 'Í'),
+    ("I>", 'Î'),
+    ("I?", 'Ĩ'),
+    ("I-", 'Ī'),
+    ("I(", 'Ĭ'),
+    ("I.", 'İ'),
+    ("I:", 'Ï'),
+    ("I<", 'Ǐ'),
+    ("I;", 'Į'),
+    ("J>", 'Ĵ'),
+    ("K<", 'Ǩ'),
+    ("K,", 'Ķ'),
+    ("L'", 'Ĺ'),
+    ("L<", 'Ľ'),
+    ("L,", 'Ļ'),
+    ("N!", 'Ǹ'),
+    ("N'", 'Ń'),
+    ("N?", 'Ñ'),
+    ("N<", 'Ň'),
+    ("N,", 'Ņ'),
+    ("O!", 'Ò'),
+    ("O'", 'Ó'),
+    ("O>", 'Ô'),
+    ("O?", 'Õ'),
+    ("O-", 'Ō'),
+    ("O(", 'Ŏ'),
+    ("O.", 'Ȯ'),
+    ("O:", 'Ö'),
+    ("O\"", 'Ő'),
+    ("O<", 'Ǒ'),
+    ("O;", 'Ǫ'),
+    ("R'", 'Ŕ'),
+    ("R<", 'Ř'),
+    ("R,", 'Ŗ'),
+    ("S'", 'Ś'),
+    ("S>", 'Ŝ'),
+    ("S<", 'Š'),
+    ("S,", 'Ş'),
+    ("T<", 'Ť'),
+    ("T,", 'Ţ'),
+    ("U!", 'Ù'),
+    ("U'", 'Ú'),
+    ("U>", 'Û'),
+    ("U?", 'Ũ'),
+    ("U-", 'Ū'),
+    ("U(", 'Ŭ'),
+    ("U:", 'Ü'),
+    ("U0", 'Ů'),
+    ("U\"", 'Ű'),
+    ("U<", 'Ǔ'),
+    ("U;", 'Ų'),
+    ("W>", 'Ŵ'),
+    ("Y'", 'Ý'),
+    ("Y>", 'Ŷ'),
+    ("Y-", 'Ȳ'),
+    ("Y:", 'Ÿ'),
+    ("Z'", 'Ź'),
+    ("Z.", 'Ż'),
+    ("Z<", 'Ž'),
+    ("A*", 'Α'),
+    ("a*", 'α'),
+    ("B*", 'Β'),
+    ("b*", 'β'),
+    ("G*", 'Γ'),
+    ("g*", 'γ'),
+    ("D*", 'Δ'),
+    ("d*", 'δ'),
+    ("E*", 'Ε'),
+    ("e*", 'ε'),
+    ("Z*", 'Ζ'),
+    ("z*", 'ζ'),
+    ("Y*", 'Η'),
+    ("y*", 'η'),
+    ("H*", 'Θ'),
+    ("h*", 'θ'),
+    ("I*", 'Ι'),
+    ("i*", 'ι'),
+    ("K*", 'Κ'),
+    ("k*", 'κ'),
+    ("L*", 'Λ'),
+    ("l*", 'λ'),
+    ("M*", 'Μ'),
+    ("m*", 'μ'),
+    ("N*", 'Ν'),
+    ("n*", 'ν'),
+    ("C*", 'Ξ'),
+    ("c*", 'ξ'),
+    ("O*", 'Ο'),
+    ("o*", 'ο'),
+    ("P*", 'Π'),
+    ("p*", 'π'),
+    ("R*", 'Ρ'),
+    ("r*", 'ρ'),
+    ("S*", 'Σ'),
+    ("s*", 'σ'),
+    ("T*", 'Τ'),
+    ("t*", 'τ'),
+    ("U*", 'Υ'),
+    ("u*", 'υ'),
+    ("F*", 'Φ'),
+    ("f*", 'φ'),
+    ("X*", 'Χ'),
+    ("x*", 'χ'),
+    ("Q*", 'Ψ'),
+    ("q*", 'ψ'),
+    ("W*", 'Ω'),
+    ("w*", 'ω'),
+    ("*s", 'ς'),
+    ("A=", 'А'),
+    ("a=", 'а'),
+    ("B=", 'Б'),
+    ("b=", 'б'),
+    ("V=", 'В'),
+    ("v=", 'в'),
+    ("G=", 'Г'),
+    ("g=", 'г'),
+    ("D=", 'Д'),
+    ("d=", 'д'),
+    ("E=", 'Е'),
+    ("e=", 'е'),
+    ("Z%", 'Ж'),
+    ("z%", 'ж'),
+    ("Z=", 'З'),
+    ("z=", 'з'),
+    ("I=", 'И'),
+    ("i=", 'и'),
+    ("J=", 'Й'),
+    ("j=", 'й'),
+    ("K=", 'К'),
+    ("k=", 'к'),
+    ("L=", 'Л'),
+    ("l=", 'л'),
+    ("M=", 'М'),
+    ("m=", 'м'),
+    ("N=", 'Н'),
+    ("n=", 'н'),
+    ("O=", 'О'),
+    ("o=", 'о'),
+    ("P=", 'П'),
+    ("p=", 'п'),
+    ("R=", 'Р'),
+    ("r=", 'р'),
+    ("S=", 'С'),
+    ("s=", 'с'),
+    ("T=", 'Т'),
+    ("t=", 'т'),
+    ("U=", 'У'),
+    ("u=", 'у'),
+    ("F=", 'Ф'),
+    ("f=", 'ф'),
+    ("H=", 'Х'),
+    ("h=", 'х'),
+    ("C=", 'Ц'),
+    ("c=", 'ц'),
+    ("C%", 'Ч'),
+    ("c%", 'ч'),
+    ("S%", 'Ш'),
+    ("s%", 'ш'),
+    ("Sc", 'Щ'),
+    ("sc", 'щ'),
+    ("Y=", 'Ы'),
+    ("y=", 'ы'),
+    ("JE", 'Э'),
+    ("je", 'э'),
+    ("JU", 'Ю'),
+    ("ju", 'ю'),
+    ("JA", 'Я'),
+    ("ja", 'я'),
+    ("IO", 'Ё'),
+    ("io", 'ё'),
+    ("=\"", 'Ъ'),
+    ("='", 'ъ'),
+    ("%\"", 'Ь'),
+    ("%'", 'ь'),
+    ("NS", '\u{a0}'),
+    ("!I", '¡'),
+    ("Ct", '¢'),
+    ("Pd", '£'),
+    ("Cu", '¤'),
+    ("Ye", '¥'),
+    ("BB", '¦'),
+    ("SE", '§'),
+    ("':", '¨'),
+    ("Co", '©'),
+    ("-a", 'ª'),
+    ("<<", '«'),
+    ("NO", '¬'),
+    ("--", '\u{ad}'),
+    ("Rg", '®'),
+    ("'m", '¯'),
+    ("DG", '°'),
+    ("+-", '±'),
+    ("2S", '²'),
+    ("3S", '³'),
+    ("''", '´'),
+    ("My", 'µ'),
+    ("PI", '¶'),
+    (".M", '·'),
+    ("',", '¸'),
+    ("1S", '¹'),
+    ("-o", 'º'),
+    (">>", '»'),
+    ("14", '¼'),
+    ("12", '½'),
+    ("34", '¾'),
+    ("?I", '¿'),
+    ("AE", 'Æ'),
+    ("D-", 'Đ'),
+    ("*X", '×'),
+    ("O/", 'Ø'),
+    ("TH", 'Þ'),
+    ("ss", 'ß'),
+    ("ae", 'æ'),
+    ("d-", 'đ'),
+    ("-:", '÷'),
+    ("o/", 'ø'),
+    ("th", 'þ'),
+    ("OE", 'Œ'),
+    ("oe", 'œ'),
+    ("IJ", 'Ĳ'),
+    ("ij", 'ĳ'),
+    ("L/", 'Ł'),
+    ("l/", 'ł'),
+    ("D/", 'Ð'),
+    ("d/", 'ð'),
+    ("-N", '–'),
+    ("-M", '—'),
+    ("'6", '‘'),
+    ("'9", '’'),
+    (".9", '‚'),
+    ("\"6", '“'),
+    ("\"9", '”'),
+    (":9", '„'),
+    ("/-", '†'),
+    ("/=", '‡'),
+    ("oo", '•'),
+    (",.", '…'),
+    ("%0", '‰'),
+    ("1'", '′'),
+    ("2'", '″'),
+    ("<1", '‹'),
+    (">1", '›'),
+    ("Eu", '€'),
+    ("=e", '€'),
+    ("oC", '℃'),
+    ("No", '№'),
+    ("TM", '™'),
+    ("Om", 'Ω'),
+    ("<-", '←'),
+    ("-!", '↑'),
+    ("->", '→'),
+    ("-v", '↓'),
+    ("<>", '↔'),
+    ("UD", '↕'),
+    ("<=", '⇐'),
+    ("=>", '⇒'),
+    ("==", '⇔'),
+    ("FA", '∀'),
+    ("dP", '∂'),
+    ("TE", '∃'),
+    ("/0", '∅'),
+    ("DE", '∆'),
+    ("NB", '∇'),
+    ("(-", '∈'),
+    ("-)", '∋'),
+    ("*P", '∏'),
+    ("+Z", '∑'),
+    ("-2", '−'),
+    ("*-", '∗'),
+    ("Ob", '∘'),
+    ("Sb", '∙'),
+    ("RT", '√'),
+    ("0(", '∝'),
+    ("00", '∞'),
+    ("-L", '∟'),
+    ("-V", '∠'),
+    ("PP", '∥'),
+    ("AN", '∧'),
+    ("OR", '∨'),
+    ("(U", '∩'),
+    (")U", '∪'),
+    ("In", '∫'),
+    ("DI", '∬'),
+    ("Io", '∮'),
+    (".:", '∴'),
+    (":.", '∵'),
+    ("?1", '∼'),
+    ("?=", '≅'),
+    ("?2", '≈'),
+    ("!=", '≠'),
+    ("=3", '≡'),
+    ("=<", '≤'),
+    (">=", '≥'),
+    ("(C", '⊂'),
+    (")C", '⊃'),
+    ("(_", '⊆'),
+    (")_", '⊇'),
+    ("0.", '⊙'),
+    ("-T", '⊥'),
+    ("OK", '✓'),
+    ("XX", '✗'),
+    ("cH", '♥'),
+    ("cS", '♠'),
+    ("cD", '♦'),
+    ("cC", '♣'),
+    ("Md", '♩'),
+    ("M8", '♪'),
+    ("M2", '♫'),
+    ("*1", '☆'),
+    ("*2", '★'),
+    ("SU", '☼'),
+    ("0S", '⁰'),
+    ("4S", '⁴'),
+    ("5S", '⁵'),
+    ("6S", '⁶'),
+    ("7S", '⁷'),
+    ("8S", '⁸'),
+    ("9S", '⁹'),
+    ("+S", '⁺'),
+    ("-S", '⁻'),
+    ("nS", 'ⁿ'),
+    ("0s", '₀'),
+    ("1s", '₁'),
+    ("2s", '₂'),
+    ("3s", '₃'),
+    ("4s", '₄'),
+    ("5s", '₅'),
+    ("6s", '₆'),
+    ("7s", '₇'),
+    ("8s", '₈'),
+    ("9s", '₉'),
+    ("18", '⅛'),
+    ("38", '⅜'),
+    ("58", '⅝'),
+    ("78", '⅞'),
+    ("13", '⅓'),
+    ("23", '⅔'),
+];
+
+/// Char of a digraph, whose chars may also be typed in reverse order
+pub fn lookup(first: char, second: char) -> Option<char> {
+    let find = |a: char, b: char| {
+        DIGRAPHS.iter().find_map(|(keys, c)| {
+            let mut chars = keys.chars();
+            (chars.next() == Some(a) && chars.next() == Some(b)).then_some(*c)
+        })
+    };
+    find(first, second).or_else(|| find(second, first))
+}
+
+/// Table of all the digraphs, for `:digraphs`
+pub fn listing() -> String {
+    DIGRAPHS
+        .chunks(4)
+        .map(|row| {
+            row.iter()
+                .map(|(keys, c)| format!("{} {}  {:>5}", keys, c, *c as u32))
+                .collect::<Vec<_>>()
+                .join("    ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -2,7 +2,9 @@ use crossterm::event::EventStream;
 use futures::{StreamExt, stream::Fuse};
 use tokio::sync::mpsc::{Receiver, Sender};
 
-use super::{File, Folder, FolderId};
+use super::{
+    File, FileId, Folder, FolderId, diagnostics::Diagnostics, git::GitStatus, grep::FileMatches,
+};
 
 /// Internal editor events,
 /// for background running tasks to make their
@@ -14,6 +16,64 @@ pub enum EditorEvent {
         files: Vec<File>,
         folders: Vec<Folder>,
     },
+    /// Background search match count of a large buffer
+    SearchCounted {
+        file: FileId,
+        at: usize,
+        current: usize,
+        total: usize,
+    },
+    /// Results of a project search on files without an open buffer
+    ProjectSearch {
+        id: usize,
+        results: Vec<FileMatches>,
+    },
+    /// Completion of a `:!` shell command
+    ShellFinished {
+        command: String,
+        success: bool,
+        output: String,
+    },
+    /// Rendered `:Man` page
+    ManPage {
+        page: String,
+        text: Result<String, String>,
+    },
+    /// Ex command requested by another screen, executed by the editor
+    Command(String),
+    /// Contents of a remote file, downloaded over SFTP
+    RemoteLoaded {
+        file: FileId,
+        text: Result<String, String>,
+    },
+    /// Completion of the upload of a buffer text to its remote file
+    RemoteWritten {
+        file: FileId,
+        text: String,
+        result: Result<(), String>,
+    },
+    /// Output of a `:read !` command, to insert below a line of a buffer
+    ReadOutput {
+        file: FileId,
+        line: usize,
+        text: Result<String, String>,
+    },
+    /// Selection to paste at a char index of a buffer
+    Paste {
+        file: FileId,
+        at: usize,
+        text: Result<String, String>,
+    },
+    /// Branch and upstream distance of the repository of the root folder
+    GitStatus(Option<GitStatus>),
+    /// Completion of a `:make` command, with the diagnostics read from its output
+    MakeFinished {
+        command: String,
+        success: bool,
+        output: String,
+        diagnostics: Diagnostics,
+        jump: bool,
+    },
 }
 
 /// Event channel listeners
@@ -1,31 +1,82 @@
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
+    io,
     path::{Path, PathBuf},
 };
 
-use ropey::Rope;
+use ratatui::prelude::*;
 use slotmap::{SlotMap, new_key_type};
 
 mod file;
 mod folder;
+mod transaction;
+mod undo;
 
-pub use file::File;
-pub use folder::Folder;
+pub use file::{File, FileFormat};
+pub use folder::{Folder, is_hidden};
 use tokio::sync::mpsc::Sender;
+pub use transaction::{Edit, Transaction};
+pub use undo::UndoTree;
 
-use super::EditorEvent;
+use super::{EditorEvent, Icons, Jobs, remote::Remote};
 
 new_key_type! {
     pub struct FileId;
     pub struct FolderId;
 }
 
+/// Target of a symlinked file or folder
+#[derive(Debug, Clone)]
+pub struct Symlink {
+    /// Target as written in the link
+    pub target: PathBuf,
+    /// Canonical path of the target, or None if the link is broken
+    pub resolved: Option<PathBuf>,
+}
+
+impl Symlink {
+    /// Target of the path if it is a symlink
+    pub fn read(path: &Path) -> Option<Self> {
+        let target = std::fs::read_link(path).ok()?;
+        Some(Self {
+            target,
+            resolved: path.canonicalize().ok(),
+        })
+    }
+
+    /// Filetree spans after the name of the link, with its target in red when broken
+    pub fn spans(&self, icons: &Icons) -> [Span<'_>; 2] {
+        let target = Span::raw(self.target.to_string_lossy());
+        [
+            Span::raw(icons.link).gray(),
+            match self.resolved {
+                Some(_) => target.gray(),
+                None => target.red(),
+            },
+        ]
+    }
+}
+
+/// Whether a path is a symlink to one of its ancestor folders,
+/// whose contents would be infinitely nested
+pub fn is_link_cycle(path: &Path) -> bool {
+    if !path.is_symlink() {
+        return false;
+    }
+    match (path.canonicalize(), path.parent().map(Path::canonicalize)) {
+        (Ok(target), Some(Ok(parent))) => parent.starts_with(target),
+        _ => false,
+    }
+}
+
 /// All loaded files and folders
 #[derive(Debug)]
 pub struct FileSystem {
     /// Root folder
     pub root: FolderId,
+    /// Extra root folders of the workspace, added with `:workspace add`
+    pub roots: Vec<FolderId>,
     pub folders: SlotMap<FolderId, Folder>,
     pub files: SlotMap<FileId, File>,
     /// Shortcut to know which files currently contain open buffers.
@@ -48,6 +99,7 @@ impl FileSystem {
 
         Self {
             root,
+            roots: vec![],
             folders,
             files: SlotMap::with_key(),
             open_buffers: HashSet::new(),
@@ -57,20 +109,28 @@ impl FileSystem {
     }
 
     /// Load the contents of a folder asynchronously in the background
-    pub fn load_folder(&self, sender: Sender<EditorEvent>, id: FolderId) {
+    pub fn load_folder(&self, sender: Sender<EditorEvent>, jobs: &Jobs, id: FolderId) {
         let path = self.folders[id].path.clone();
-        tokio::spawn(async move {
+        let name = format!("load {}", path.display());
+        jobs.spawn(name, |job| async move {
             let mut files: Vec<File> = vec![];
             let mut folders: Vec<Folder> = vec![];
 
             match tokio::fs::read_dir(&path).await {
                 Ok(mut entries) => {
                     while let Ok(Some(entry)) = entries.next_entry().await {
+                        job.advance();
                         let path = entry.path();
+                        let link = Symlink::read(&path);
+                        // Symlinks are listed as their target, files when broken
                         if path.is_dir() {
-                            folders.push(Folder::new(path));
+                            let mut folder = Folder::new(path);
+                            folder.link = link;
+                            folders.push(folder);
                         } else {
-                            files.push(File::new(path));
+                            let mut file = File::new(path);
+                            file.link = link;
+                            files.push(file);
                         }
                     }
 
@@ -91,26 +151,384 @@ impl FileSystem {
         });
     }
 
-    /// Initialize the contents of a folder that is being opened for the first time.
+    /// Reload the contents of every loaded folder, to show the changes made outside
+    /// the editor
+    pub fn refresh(&self, sender: Sender<EditorEvent>, jobs: &Jobs) {
+        for (id, folder) in &self.folders {
+            if folder.init {
+                self.load_folder(sender.clone(), jobs, id);
+            }
+        }
+    }
+
+    /// Set the contents of a folder that is opened for the first time or refreshed.
+    /// Children that still exist keep their id, so that their buffers, open state and
+    /// loaded contents are preserved, and the removed ones are dropped.
     pub fn init_folder(&mut self, id: FolderId, files: Vec<File>, folders: Vec<Folder>) {
-        // Avoid overwriting existing children
-        if self.folders[id].init {
+        // The folder may have been removed by the refresh of its parent while loading
+        if !self.folders.contains_key(id) {
             return;
         }
+        let mut known_files = std::mem::take(&mut self.folders[id].child_files)
+            .into_iter()
+            .map(|file| (self.files[file].path.clone(), file))
+            .collect::<HashMap<_, _>>();
+        let mut known_folders = std::mem::take(&mut self.folders[id].child_folders)
+            .into_iter()
+            .map(|folder| (self.folders[folder].path.clone(), folder))
+            .collect::<HashMap<_, _>>();
+
+        // Files that were opened before their folder was loaded keep their id
         let file_ids = files
             .into_iter()
-            .map(|file| self.files.insert(file))
+            .map(|file| {
+                known_files
+                    .remove(&file.path)
+                    .or_else(|| self.file_paths.remove(&file.path))
+                    .unwrap_or_else(|| self.files.insert(file))
+            })
             .collect::<Vec<_>>();
         let folder_ids = folders
             .into_iter()
-            .map(|folder| self.folders.insert(folder))
+            .map(|folder| {
+                known_folders
+                    .remove(&folder.path)
+                    .unwrap_or_else(|| self.folders.insert(folder))
+            })
             .collect::<Vec<_>>();
+        for (_, file) in known_files {
+            self.forget_file(file);
+        }
+        for (_, folder) in known_folders {
+            self.forget_folder(folder);
+        }
 
         self.folders[id].child_files = file_ids;
         self.folders[id].child_folders = folder_ids;
         self.folders[id].init = true;
         self.folder_paths.insert(self.folders[id].path.clone(), id);
     }
+
+    /// Find the id of a known file from its path.
+    /// Canonical paths also find the symlinks of the filetree that point to them, so
+    /// that a file has a single buffer.
+    pub fn find_file(&self, path: &Path) -> Option<FileId> {
+        if let Some(id) = self.file_paths.get(path) {
+            return Some(*id);
+        }
+        let parent = path.parent()?;
+        if let Some(folder) = self.folder_paths.get(parent)
+            && let Some(id) = self.folders[*folder]
+                .child_files
+                .iter()
+                .find(|id| self.files[**id].path == path)
+        {
+            return Some(*id);
+        }
+        self.files.iter().find_map(|(id, file)| {
+            let link = file.link.as_ref()?;
+            (link.resolved.as_deref() == Some(path)).then_some(id)
+        })
+    }
+
+    /// Open a buffer for the file at the given path, loading it if needed.
+    pub fn open_file(&mut self, path: &Path) -> io::Result<FileId> {
+        let id = match self.find_file(path) {
+            Some(id) => id,
+            None => {
+                let id = self.files.insert(File::new(path.to_path_buf()));
+                self.file_paths.insert(path.to_path_buf(), id);
+                id
+            }
+        };
+        self.open(id)?;
+        Ok(id)
+    }
+
+    /// Open a buffer for a remote file.
+    /// Returns its id, and whether its contents have to be downloaded: when it is new,
+    /// or when an earlier download failed.
+    pub fn open_remote(&mut self, remote: Remote) -> (FileId, bool) {
+        let path = PathBuf::from(remote.uri());
+        let (id, fetch) = match self.file_paths.get(&path) {
+            Some(id) => (*id, self.files[*id].readonly && !self.files[*id].modified),
+            None => {
+                let id = self.files.insert(File::remote(remote));
+                self.file_paths.insert(path, id);
+                (id, true)
+            }
+        };
+        self.open_buffers.insert(id);
+        (id, fetch)
+    }
+
+    /// Open a buffer for a known file, loading it if needed.
+    pub fn open(&mut self, id: FileId) -> io::Result<()> {
+        if self.files[id].buffer.is_none() {
+            self.files[id].load()?;
+        }
+        self.open_buffers.insert(id);
+        Ok(())
+    }
+
+    /// Rename or move a file on disk, keeping its buffer
+    pub fn rename_file(&mut self, id: FileId, path: &Path) -> io::Result<()> {
+        let from = self.files[id].path.clone();
+        std::fs::rename(&from, path)?;
+        self.path_moved(&from, path);
+        Ok(())
+    }
+
+    /// Rename or move a folder on disk, updating the paths of its loaded contents
+    pub fn rename_folder(&mut self, id: FolderId, path: &Path) -> io::Result<()> {
+        let from = self.folders[id].path.clone();
+        std::fs::rename(&from, path)?;
+        self.path_moved(&from, path);
+        Ok(())
+    }
+
+    /// Update the buffers and tree indexes after a file or folder was moved on disk,
+    /// by the editor or externally, so that buffers are written to their new location.
+    /// A file replaced by the move keeps its open buffer, as modified.
+    pub fn path_moved(&mut self, from: &Path, to: &Path) {
+        if let Some(id) = self.find_file(from) {
+            self.set_file_path(id, to);
+        } else if let Some(id) = self.find_folder(from) {
+            self.detach_folder(id);
+            self.move_folder(id, to.to_path_buf());
+            self.attach_folder(id);
+        }
+
+        // Buffers of the moved folder that were opened outside the loaded tree
+        let outside = self
+            .file_paths
+            .iter()
+            .filter(|(path, _)| path.starts_with(from) && path.as_path() != from)
+            .map(|(path, id)| (path.clone(), *id))
+            .collect::<Vec<_>>();
+        for (path, id) in outside {
+            self.file_paths.remove(&path);
+            let moved = to.join(path.strip_prefix(from).unwrap_or(&path));
+            self.files[id].set_path(moved.clone());
+            self.file_paths.insert(moved, id);
+        }
+    }
+
+    /// Bind a file and its buffer to a new path, without touching the disk.
+    /// A file known at that path keeps its open buffer, as modified.
+    pub fn set_file_path(&mut self, id: FileId, path: &Path) {
+        if let Some(replaced) = self.find_file(path)
+            && replaced != id
+        {
+            self.detach_file(replaced);
+            match self.open_buffers.contains(&replaced) {
+                true => self.files[replaced].modified = !self.files[replaced].scratch,
+                false => {
+                    self.files.remove(replaced);
+                }
+            }
+        }
+        self.detach_file(id);
+        self.files[id].set_path(path.to_path_buf());
+        self.files[id].scratch = false;
+        // A remote buffer written to a local path becomes a local file
+        if self.files[id].remote.take().is_some() {
+            self.files[id].readonly = false;
+        }
+        self.attach_file(id);
+    }
+
+    /// Delete a file from disk.
+    /// An open buffer is kept, as modified since it no longer matches the disk.
+    pub fn delete_file(&mut self, id: FileId) -> io::Result<()> {
+        std::fs::remove_file(&self.files[id].path)?;
+        self.detach_file(id);
+        self.forget_file(id);
+        Ok(())
+    }
+
+    /// Delete a folder from disk, with its contents when `recursive`
+    pub fn delete_folder(&mut self, id: FolderId, recursive: bool) -> io::Result<()> {
+        let path = &self.folders[id].path;
+        match recursive {
+            true => std::fs::remove_dir_all(path)?,
+            false => std::fs::remove_dir(path)?,
+        }
+        self.detach_folder(id);
+        self.forget_folder(id);
+        Ok(())
+    }
+
+    /// Remove a file from the tree indexes
+    fn detach_file(&mut self, id: FileId) {
+        for folder in self.folders.values_mut() {
+            folder.child_files.retain(|child| *child != id);
+        }
+        self.file_paths.retain(|_, file| *file != id);
+    }
+
+    /// Insert a file in the children of its loaded parent folder, or in the outside files
+    fn attach_file(&mut self, id: FileId) {
+        let path = self.files[id].path.clone();
+        let parent = path
+            .parent()
+            .and_then(|parent| self.folder_paths.get(parent));
+        match parent {
+            Some(&parent) => {
+                let mut children = std::mem::take(&mut self.folders[parent].child_files);
+                let index = children.partition_point(|child| {
+                    compare_names(&self.files[*child].path, &path) == Ordering::Less
+                });
+                children.insert(index, id);
+                self.folders[parent].child_files = children;
+            }
+            None => {
+                self.file_paths.insert(path, id);
+            }
+        }
+    }
+
+    /// Drop a file that no longer exists, unless its buffer is open
+    fn forget_file(&mut self, id: FileId) {
+        match self.open_buffers.contains(&id) {
+            true => {
+                let file = &mut self.files[id];
+                file.modified = !file.scratch;
+                self.file_paths.insert(file.path.clone(), id);
+            }
+            false => {
+                self.files.remove(id);
+            }
+        }
+    }
+
+    fn detach_folder(&mut self, id: FolderId) {
+        for folder in self.folders.values_mut() {
+            folder.child_folders.retain(|child| *child != id);
+        }
+    }
+
+    /// Insert a folder in the children of its loaded parent folder
+    fn attach_folder(&mut self, id: FolderId) {
+        let path = self.folders[id].path.clone();
+        let parent = path
+            .parent()
+            .and_then(|parent| self.folder_paths.get(parent));
+        if let Some(&parent) = parent {
+            let mut children = std::mem::take(&mut self.folders[parent].child_folders);
+            let index = children.partition_point(|child| {
+                compare_names(&self.folders[*child].path, &path) == Ordering::Less
+            });
+            children.insert(index, id);
+            self.folders[parent].child_folders = children;
+        }
+    }
+
+    /// Loaded folder at a path, opened or not
+    fn find_folder(&self, path: &Path) -> Option<FolderId> {
+        if let Some(id) = self.folder_paths.get(path) {
+            return Some(*id);
+        }
+        let parent = self.folder_paths.get(path.parent()?)?;
+        self.folders[*parent]
+            .child_folders
+            .iter()
+            .find(|id| self.folders[**id].path == path)
+            .copied()
+    }
+
+    /// Update the paths of a folder and its loaded descendants
+    fn move_folder(&mut self, id: FolderId, path: PathBuf) {
+        let old = std::mem::replace(&mut self.folders[id].path, path.clone());
+        self.folders[id].name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if self.folder_paths.get(&old) == Some(&id) {
+            self.folder_paths.remove(&old);
+            self.folder_paths.insert(path.clone(), id);
+        }
+
+        for file in self.folders[id].child_files.clone() {
+            let name = self.files[file].path.file_name().unwrap_or_default();
+            let new_path = path.join(name);
+            self.files[file].set_path(new_path);
+        }
+        for folder in self.folders[id].child_folders.clone() {
+            let name = self.folders[folder].path.file_name().unwrap_or_default();
+            let new_path = path.join(name);
+            self.move_folder(folder, new_path);
+        }
+    }
+
+    /// Drop a deleted folder and its descendants, except the files with an open buffer
+    fn forget_folder(&mut self, id: FolderId) {
+        let Some(folder) = self.folders.remove(id) else {
+            return;
+        };
+        if self.folder_paths.get(&folder.path) == Some(&id) {
+            self.folder_paths.remove(&folder.path);
+        }
+        for file in folder.child_files {
+            self.forget_file(file);
+        }
+        for child in folder.child_folders {
+            self.forget_folder(child);
+        }
+    }
+
+    /// Root folder, followed by the extra roots of the workspace
+    pub fn workspace(&self) -> impl Iterator<Item = FolderId> + '_ {
+        std::iter::once(self.root).chain(self.roots.iter().copied())
+    }
+
+    /// Whether a folder is a root of the workspace
+    pub fn is_root(&self, id: FolderId) -> bool {
+        self.workspace().any(|root| root == id)
+    }
+
+    /// Add an extra root folder to the workspace, open in the filetree.
+    /// Returns None if it already is a root.
+    pub fn add_root(&mut self, path: PathBuf) -> Option<FolderId> {
+        if self.workspace().any(|root| self.folders[root].path == path) {
+            return None;
+        }
+        let mut folder = Folder::new(path);
+        folder.open = true;
+        let id = self.folders.insert(folder);
+        self.roots.push(id);
+        Some(id)
+    }
+
+    /// Remove an extra root folder from the workspace, keeping its open buffers.
+    /// Returns whether it was a root.
+    pub fn remove_root(&mut self, path: &Path) -> bool {
+        let Some(index) = self
+            .roots
+            .iter()
+            .position(|id| self.folders[*id].path == path)
+        else {
+            return false;
+        };
+        let id = self.roots.remove(index);
+        self.forget_folder(id);
+        true
+    }
+
+    /// Path of a file relative to the root folder, for display purposes.
+    /// Paths in the extra roots start with the name of their root.
+    pub fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        if let Ok(relative) = path.strip_prefix(&self.folders[self.root].path) {
+            return relative;
+        }
+        self.roots
+            .iter()
+            .map(|id| &self.folders[*id].path)
+            .filter(|root| path.starts_with(root))
+            .find_map(|root| path.strip_prefix(root.parent()?).ok())
+            .unwrap_or(path)
+    }
 }
 
 fn compare_names(a: &Path, b: &Path) -> Ordering {
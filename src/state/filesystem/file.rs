@@ -1,9 +1,73 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufReader, BufWriter, ErrorKind, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 use devicons::FileIcon;
 use hex_color::HexColor;
 use ratatui::prelude::*;
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
+
+use super::{Edit, Symlink, Transaction, UndoTree};
+use crate::{
+    position::line_len,
+    state::{
+        BufferOptions, Icons,
+        filetype::{self, OptionSource},
+        remote::Remote,
+    },
+};
+
+/// Line breaks of a file, detected when loading it and applied when writing it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    #[default]
+    Unix,
+    /// Lines ending with `\r\n`
+    Dos,
+}
+
+impl FileFormat {
+    /// Format of a text whose line breaks all end with `\r\n`, Unix otherwise
+    pub fn detect(rope: &Rope) -> Self {
+        // The last line has no line break
+        let lines = rope.len_lines() - 1;
+        let dos = (0..lines).all(|index| {
+            let line = rope.line(index);
+            line.len_chars() >= 2 && line.char(line.len_chars() - 2) == '\r'
+        });
+        match lines > 0 && dos {
+            true => Self::Dos,
+            false => Self::Unix,
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "unix" => Some(Self::Unix),
+            "dos" => Some(Self::Dos),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unix => "unix",
+            Self::Dos => "dos",
+        }
+    }
+
+    fn line_break(self) -> &'static str {
+        match self {
+            Self::Unix => "\n",
+            Self::Dos => "\r\n",
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Devicon {
@@ -36,6 +100,36 @@ pub struct File {
     pub path: PathBuf,
     pub name: String,
     pub buffer: Option<Rope>,
+    /// Whether the buffer has unsaved changes
+    pub modified: bool,
+    /// Line breaks written to the file
+    pub fileformat: FileFormat,
+    /// Modification time of the file when it was last read or written, to detect the
+    /// changes made by other programs
+    pub mtime: Option<SystemTime>,
+    /// Changes made to the buffer that the listeners were not notified of yet
+    pub changes: Transaction,
+    /// Undo tree of the buffer
+    pub undo: UndoTree,
+    /// Char indices of the marks set in the buffer
+    pub marks: BTreeMap<char, usize>,
+    /// Whether the buffer cannot be edited, like help pages
+    pub readonly: bool,
+    /// Throwaway buffer, like command outputs, never marked modified
+    pub scratch: bool,
+    /// Whether the buffer had lines too long to be wrapped and highlighted when it was
+    /// first displayed
+    pub long_lines: bool,
+    /// Language detected from the file name
+    pub filetype: Option<&'static str>,
+    /// Indentation and comment settings, applied on the first display of the buffer
+    pub options: Option<BufferOptions>,
+    /// Origins of the buffer options not set from the filetype or the global ones
+    pub option_sources: BTreeMap<&'static str, OptionSource>,
+    /// Host and path of a file edited over SFTP, whose path is its URI
+    pub remote: Option<Remote>,
+    /// Target of a symlinked file
+    pub link: Option<Symlink>,
     icon: Devicon,
 }
 
@@ -49,19 +143,221 @@ impl File {
             .to_string();
 
         Self {
+            filetype: filetype::detect(&path),
             path,
             name,
             icon,
             buffer: None,
+            modified: false,
+            fileformat: FileFormat::default(),
+            mtime: None,
+            changes: Transaction::default(),
+            undo: UndoTree::default(),
+            marks: BTreeMap::new(),
+            readonly: false,
+            scratch: false,
+            long_lines: false,
+            options: None,
+            option_sources: BTreeMap::new(),
+            remote: None,
+            link: None,
+        }
+    }
+
+    /// Point the file to a new path, after it was renamed or moved
+    pub fn set_path(&mut self, path: PathBuf) {
+        let renamed = Self::new(path);
+        self.path = renamed.path;
+        self.name = renamed.name;
+        self.icon = renamed.icon;
+        self.filetype = renamed.filetype;
+    }
+
+    /// Empty buffer without a name, bound to a file on its first write
+    pub fn unnamed() -> Self {
+        let mut file = Self::new(PathBuf::new());
+        file.buffer = Some(Rope::new());
+        file
+    }
+
+    /// Throwaway buffer that is not backed by a file on disk, and whose changes are not
+    /// reported when quitting
+    pub fn scratch(text: &str) -> Self {
+        let mut file = Self::new(PathBuf::new());
+        file.buffer = Some(Rope::from_str(text));
+        file.scratch = true;
+        file
+    }
+
+    /// Buffer of a remote file, read-only until its contents are downloaded
+    pub fn remote(remote: Remote) -> Self {
+        let mut file = Self::new(PathBuf::from(remote.uri()));
+        file.buffer = Some(Rope::new());
+        file.readonly = true;
+        file.remote = Some(remote);
+        file
+    }
+
+    /// Read-only buffer displaying generated text
+    pub fn page(name: &str, text: &str) -> Self {
+        let mut file = Self::scratch(text);
+        file.name = name.to_string();
+        file.readonly = true;
+        file
+    }
+
+    /// Load the file contents into a buffer.
+    /// A missing file results in an empty buffer, created on write. Reloading a buffer
+    /// keeps its undo history, the new text being a single revision.
+    pub fn load(&mut self) -> io::Result<()> {
+        let rope = match fs::File::open(&self.path) {
+            Ok(file) => Rope::from_reader(BufReader::new(file))?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Rope::new(),
+            Err(err) => return Err(err),
+        };
+        self.fileformat = FileFormat::detect(&rope);
+        self.mtime = self.disk_mtime();
+        self.modified = false;
+        match &mut self.buffer {
+            Some(buffer) => {
+                Edit::new(buffer, &mut self.changes).replace_text(&rope);
+                self.undo.record(&mut self.changes);
+                self.undo.mark_saved();
+            }
+            None => {
+                self.buffer = Some(rope);
+                self.changes = Transaction::default();
+                self.undo = UndoTree::default();
+            }
         }
+        Ok(())
     }
 
-    /// Returns a ratatui line to display the file
-    pub fn line(&self, depth: usize) -> Line<'_> {
-        Line::from(vec![
-            Span::raw("  ".repeat(depth + 1)),
-            self.icon.span(),
-            Span::raw(&self.name),
-        ])
+    /// Origin of the value of a buffer option
+    pub fn option_source(&self, name: &str) -> OptionSource {
+        match self.option_sources.get(name) {
+            Some(source) => *source,
+            None if self.filetype.is_some() && filetype::defines(name) => OptionSource::Filetype,
+            None => OptionSource::Global,
+        }
+    }
+
+    /// Take the changes made to the buffer since the last commit, moving the marks along
+    /// the text they were set on. The changes are recorded in the undo history, where
+    /// insert sessions stay open to be undone at once.
+    pub fn commit(&mut self, insert: bool) -> Option<Transaction> {
+        self.undo.record(&mut self.changes);
+        if !insert {
+            self.undo.seal();
+        }
+        if self.changes.is_empty() {
+            return None;
+        }
+        let transaction = std::mem::take(&mut self.changes);
+        for index in self.marks.values_mut() {
+            *index = transaction.map(*index);
+        }
+        Some(transaction)
+    }
+
+    /// Write the buffer contents to disk
+    pub fn write(&mut self) -> io::Result<()> {
+        if self.path.as_os_str().is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "No file name"));
+        }
+        self.write_to(&self.path)?;
+        self.modified = false;
+        self.undo.mark_saved();
+        self.mtime = self.disk_mtime();
+        Ok(())
+    }
+
+    /// Whether the file was modified by another program since it was last read or written
+    pub fn changed_on_disk(&self) -> bool {
+        self.mtime
+            .is_some_and(|mtime| self.disk_mtime().is_some_and(|time| time != mtime))
+    }
+
+    /// Modification time of the file on disk, if it exists
+    pub fn disk_mtime(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Write the buffer contents to another path, the buffer staying bound to its file
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let lines = self.buffer.as_ref().map_or(0, |buffer| buffer.len_lines());
+        self.write_lines(path, 0..lines, false).map(|_| ())
+    }
+
+    /// Write a range of lines of the buffer to a path, or append them to it.
+    /// Returns the number of bytes written.
+    pub fn write_lines(&self, path: &Path, lines: Range<usize>, append: bool) -> io::Result<usize> {
+        let Some(buffer) = &self.buffer else {
+            return Ok(0);
+        };
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        let mut writer = BufWriter::new(file);
+        let mut bytes = 0;
+        for line in buffer.lines().skip(lines.start).take(lines.len()) {
+            let (content, line_break) = self.split_line_break(line);
+            for chunk in content.chunks() {
+                writer.write_all(chunk.as_bytes())?;
+            }
+            writer.write_all(line_break.as_bytes())?;
+            bytes += content.len_bytes() + line_break.len();
+        }
+        writer.flush()?;
+        Ok(bytes)
+    }
+
+    /// Contents of the buffer as written to its file, with the line breaks of its format
+    pub fn text(&self) -> String {
+        let Some(buffer) = &self.buffer else {
+            return String::new();
+        };
+        let mut text = String::with_capacity(buffer.len_bytes());
+        for line in buffer.lines() {
+            let (content, line_break) = self.split_line_break(line);
+            text.extend(content.chunks());
+            text.push_str(line_break);
+        }
+        text
+    }
+
+    /// Line without its line break, and the line break to write after it
+    fn split_line_break<'a>(&self, line: RopeSlice<'a>) -> (RopeSlice<'a>, &'static str) {
+        let len = line_len(line);
+        match len == line.len_chars() {
+            true => (line, ""),
+            false => (line.slice(..len), self.fileformat.line_break()),
+        }
+    }
+
+    /// Returns a ratatui line to display the file.
+    /// Files with an `open` buffer are bold, and marked when they have unsaved changes.
+    pub fn line(&self, depth: usize, open: bool, icons: &Icons) -> Line<'_> {
+        let icon = match icons.devicons {
+            true => self.icon.span(),
+            false => Span::raw(icons.file),
+        };
+        let name = match open {
+            true => Span::raw(&self.name).bold(),
+            false => Span::raw(&self.name),
+        };
+        let mut spans = vec![Span::raw("  ".repeat(depth + 1)), icon, name];
+        if let Some(link) = &self.link {
+            spans.extend(link.spans(icons));
+        }
+        if self.modified {
+            spans.push(Span::raw(icons.modified).yellow());
+        }
+        Line::from(spans)
     }
 }
@@ -2,7 +2,8 @@ use std::path::PathBuf;
 
 use ratatui::prelude::*;
 
-use super::{FileId, FolderId};
+use super::{FileId, FolderId, Symlink};
+use crate::state::Icons;
 
 #[derive(Debug)]
 pub struct Folder {
@@ -16,6 +17,14 @@ pub struct Folder {
     pub open: bool,
     /// Whether the folder has already been loaded once
     pub init: bool,
+    /// Target of a symlinked folder
+    pub link: Option<Symlink>,
+}
+
+/// Whether a folder with this name is hidden from the filetree and project searches
+pub fn is_hidden(name: &str) -> bool {
+    // TODO: have a way to config this ?
+    matches!(name, ".git" | ".venv" | "__pycache__")
 }
 
 impl Folder {
@@ -32,24 +41,29 @@ impl Folder {
             child_folders: vec![],
             open: false,
             init: false,
+            link: None,
         }
     }
 
     pub fn hidden(&self) -> bool {
-        match &self.name as &str {
-            // TODO: have a way to config this ?
-            ".git" | ".venv" | "__pycache__" => true,
-            _ => false,
-        }
+        is_hidden(&self.name)
     }
 
     /// Returns a ratatui line to display the folder
-    pub fn line(&self, depth: usize) -> Line<'_> {
-        Line::from(vec![
+    pub fn line(&self, depth: usize, icons: &Icons) -> Line<'_> {
+        let (chevron, icon) = match self.open {
+            true => (icons.chevron_open, icons.folder_open),
+            false => (icons.chevron_closed, icons.folder_closed),
+        };
+        let mut spans = vec![
             Span::raw("  ".repeat(depth)),
-            Span::raw(if self.open { " " } else { " " }).gray(),
-            Span::raw(if self.open { " " } else { " " }).blue(),
+            Span::raw(chevron).gray(),
+            Span::raw(icon).blue(),
             Span::raw(&self.name).blue(),
-        ])
+        ];
+        if let Some(link) = &self.link {
+            spans.extend(link.spans(icons));
+        }
+        Line::from(spans)
     }
 }
@@ -0,0 +1,169 @@
+use std::ops::{Deref, Range};
+
+use ropey::Rope;
+
+use crate::position::{last_line, line_len};
+
+/// Replacement of the `removed` text at a char index by the `inserted` text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub at: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Change {
+    /// Char index after the change of a char index before it.
+    /// Indices within the removed text move to its start.
+    pub fn map(&self, index: usize) -> usize {
+        let removed = self.removed.chars().count();
+        match index {
+            index if index < self.at => index,
+            index if index < self.at + removed => self.at,
+            index => index - removed + self.inserted.chars().count(),
+        }
+    }
+}
+
+/// Changes made to a buffer, in the order they were applied
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    pub changes: Vec<Change>,
+    /// Changes already in the undo history, like the ones made by undoing
+    pub(super) recorded: usize,
+    /// Indices of the changes starting a new undo group
+    pub(super) breaks: Vec<usize>,
+}
+
+impl Transaction {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Char index after all the changes of a char index before them
+    pub fn map(&self, index: usize) -> usize {
+        self.changes
+            .iter()
+            .fold(index, |index, change| change.map(index))
+    }
+
+    /// Undo the changes on the text they were applied to
+    pub fn revert(&self, rope: &mut Rope) {
+        for change in self.changes.iter().rev() {
+            rope.remove(change.at..change.at + change.inserted.chars().count());
+            rope.insert(change.at, &change.removed);
+        }
+    }
+}
+
+/// Access to the text of a buffer through which all its modifications are made,
+/// recording them in a transaction
+#[derive(Debug)]
+pub struct Edit<'a> {
+    pub(super) rope: &'a mut Rope,
+    pub(super) transaction: &'a mut Transaction,
+}
+
+impl<'a> Edit<'a> {
+    pub fn new(rope: &'a mut Rope, transaction: &'a mut Transaction) -> Self {
+        Self { rope, transaction }
+    }
+
+    /// Insert text at a char index
+    pub fn insert(&mut self, at: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.rope.insert(at, text);
+        self.transaction.changes.push(Change {
+            at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        });
+    }
+
+    /// Start a new undo group with the next changes, like `CTRL-G u` in insert mode
+    pub fn break_undo(&mut self) {
+        self.transaction.breaks.push(self.transaction.changes.len());
+    }
+
+    pub fn insert_char(&mut self, at: usize, c: char) {
+        self.insert(at, c.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Remove a char range
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let removed = self.rope.slice(range.clone()).to_string();
+        self.rope.remove(range.clone());
+        self.transaction.changes.push(Change {
+            at: range.start,
+            removed,
+            inserted: String::new(),
+        });
+    }
+
+    /// Replace the lines from `start` to `end` included by a function of their text
+    /// without line breaks, in a single change keeping the line break of the last one.
+    /// Returns whether they changed.
+    pub fn replace_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        f: impl FnOnce(&[&str]) -> Vec<String>,
+    ) -> bool {
+        let end = end.min(last_line(self.rope));
+        let texts = (start..=end)
+            .map(|i| self.rope.line(i).to_string())
+            .collect::<Vec<_>>();
+        let eol = match texts[0].ends_with("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+        let lines = texts
+            .iter()
+            .map(|line| line.trim_end_matches(['\r', '\n']))
+            .collect::<Vec<_>>();
+        let replaced = f(&lines);
+        if replaced == lines {
+            return false;
+        }
+        let from = self.rope.line_to_char(start);
+        let to = self.rope.line_to_char(end) + line_len(self.rope.line(end));
+        self.remove(from..to);
+        self.insert(from, &replaced.join(eol));
+        true
+    }
+
+    /// Replace the whole text by another one, changing only the chars between their
+    /// common start and end so that the positions around them stay in place
+    pub fn replace_text(&mut self, text: &Rope) {
+        let (old, new) = (self.rope.len_chars(), text.len_chars());
+        let prefix = self
+            .rope
+            .chars()
+            .zip(text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = self
+            .rope
+            .chars_at(old)
+            .reversed()
+            .zip(text.chars_at(new).reversed())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .min(old.min(new) - prefix);
+        self.remove(prefix..old - suffix);
+        self.insert(prefix, &text.slice(prefix..new - suffix).to_string());
+    }
+}
+
+impl Deref for Edit<'_> {
+    type Target = Rope;
+
+    fn deref(&self) -> &Rope {
+        self.rope
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+use super::transaction::{Change, Edit, Transaction};
+
+/// Typing pause after which an insert session continues in a new undo group
+const PAUSE: Duration = Duration::from_secs(3);
+
+/// Group of changes undone and redone at once, like a normal mode command or an
+/// insert session
+#[derive(Debug)]
+struct Revision {
+    parent: usize,
+    changes: Vec<Change>,
+    /// Child revision reapplied by `CTRL-R`: the last one created or undone
+    redo: Option<usize>,
+    time: Instant,
+}
+
+/// Undo tree of a buffer. Revisions are numbered in creation order, 0 being the text
+/// as loaded, and editing after an undo starts a new branch: `u` and `CTRL-R` move
+/// along the branch, `g-` and `g+` through every revision in time order.
+#[derive(Debug)]
+pub struct UndoTree {
+    revisions: Vec<Revision>,
+    current: usize,
+    /// Revision whose text was last written
+    saved: usize,
+    /// Whether the next changes join the current revision, during insert sessions
+    open: bool,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self {
+            revisions: vec![Revision {
+                parent: 0,
+                changes: vec![],
+                redo: None,
+                time: Instant::now(),
+            }],
+            current: 0,
+            saved: 0,
+            open: false,
+        }
+    }
+}
+
+impl UndoTree {
+    /// Record the changes of a transaction made since the last call, in the current
+    /// revision while it is open, and in new ones after the undo breaks
+    pub fn record(&mut self, transaction: &mut Transaction) {
+        let mut start = transaction.recorded;
+        for at in std::mem::take(&mut transaction.breaks) {
+            let at = at.max(start);
+            self.push(&transaction.changes[start..at]);
+            self.open = false;
+            start = at;
+        }
+        self.push(&transaction.changes[start..]);
+        transaction.recorded = transaction.changes.len();
+    }
+
+    /// Close the current revision, the next changes starting a new one
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    /// Remember that the current text was written
+    pub fn mark_saved(&mut self) {
+        self.saved = self.current;
+        self.open = false;
+    }
+
+    /// Whether the current text is the written one
+    pub fn is_saved(&self) -> bool {
+        self.current == self.saved
+    }
+
+    fn push(&mut self, changes: &[Change]) {
+        if changes.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let revision = &mut self.revisions[self.current];
+        if self.open
+            && self.current != 0
+            && self.current != self.saved
+            && now - revision.time < PAUSE
+        {
+            revision.changes.extend_from_slice(changes);
+            revision.time = now;
+            return;
+        }
+        self.revisions.push(Revision {
+            parent: self.current,
+            changes: changes.to_vec(),
+            redo: None,
+            time: now,
+        });
+        let id = self.revisions.len() - 1;
+        self.revisions[self.current].redo = Some(id);
+        self.current = id;
+        self.open = true;
+    }
+}
+
+impl Edit<'_> {
+    /// Revert the current revision of the undo tree.
+    /// Returns the char index of its first change, and a message describing it.
+    pub fn undo(&mut self, tree: &mut UndoTree) -> Result<(usize, String), String> {
+        tree.record(self.transaction);
+        if tree.current == 0 {
+            return Err("Already at oldest change".to_string());
+        }
+        let undone = tree.current;
+        let at = self.revert(tree);
+        Ok((at, describe(tree, "before", undone)))
+    }
+
+    /// Reapply the last undone revision, or the last one created after the current one
+    pub fn redo(&mut self, tree: &mut UndoTree) -> Result<(usize, String), String> {
+        tree.record(self.transaction);
+        let redone = tree.revisions[tree.current]
+            .redo
+            .ok_or("Already at newest change")?;
+        let at = self.reapply(tree, redone);
+        Ok((at, describe(tree, "after", redone)))
+    }
+
+    /// Move to the revision created before the current one (`g-`) or after it (`g+`),
+    /// even on another branch, undoing up to their common ancestor and redoing from there
+    pub fn undo_in_time(
+        &mut self,
+        tree: &mut UndoTree,
+        later: bool,
+    ) -> Result<(usize, String), String> {
+        tree.record(self.transaction);
+        let target = match later {
+            true => Some(tree.current + 1).filter(|id| *id < tree.revisions.len()),
+            false => tree.current.checked_sub(1),
+        };
+        let target = target.ok_or(match later {
+            true => "Already at newest change",
+            false => "Already at oldest change",
+        })?;
+        self.goto(tree, target)
+    }
+
+    /// Move to a revision by number, like `:undo {N}`
+    pub fn goto(&mut self, tree: &mut UndoTree, target: usize) -> Result<(usize, String), String> {
+        if target >= tree.revisions.len() {
+            return Err(format!("E830: Undo number {} not found", target));
+        }
+        tree.record(self.transaction);
+        let mut path = vec![];
+        let mut id = target;
+        while id != 0 {
+            path.push(id);
+            id = tree.revisions[id].parent;
+        }
+        let mut at = tree.revisions[target]
+            .changes
+            .first()
+            .map_or(0, |change| change.at);
+        while tree.current != 0 && !path.contains(&tree.current) {
+            at = self.revert(tree);
+        }
+        if let Some(position) = path.iter().position(|id| *id == tree.current) {
+            path.truncate(position);
+        }
+        for id in path.into_iter().rev() {
+            tree.revisions[tree.current].redo = Some(id);
+            at = self.reapply(tree, id);
+        }
+        let message = match target {
+            0 => "Original text".to_string(),
+            target => describe(tree, "after", target),
+        };
+        Ok((at, message))
+    }
+
+    /// Revert the current revision and move to its parent
+    fn revert(&mut self, tree: &mut UndoTree) -> usize {
+        let revision = &tree.revisions[tree.current];
+        for change in revision.changes.iter().rev() {
+            self.remove(change.at..change.at + change.inserted.chars().count());
+            self.insert(change.at, &change.removed);
+        }
+        let at = revision.changes.first().map_or(0, |change| change.at);
+        let parent = revision.parent;
+        tree.revisions[parent].redo = Some(tree.current);
+        tree.current = parent;
+        tree.open = false;
+        self.transaction.recorded = self.transaction.changes.len();
+        at
+    }
+
+    /// Reapply a child revision of the current one and move to it
+    fn reapply(&mut self, tree: &mut UndoTree, id: usize) -> usize {
+        let changes = &tree.revisions[id].changes;
+        for change in changes {
+            self.remove(change.at..change.at + change.removed.chars().count());
+            self.insert(change.at, &change.inserted);
+        }
+        let at = changes.first().map_or(0, |change| change.at);
+        let at = changes.iter().skip(1).fold(at, |at, change| change.map(at));
+        tree.current = id;
+        tree.open = false;
+        self.transaction.recorded = self.transaction.changes.len();
+        at
+    }
+}
+
+/// Message of an undo or redo, like vim's `before #3  5 seconds ago`
+fn describe(tree: &UndoTree, relation: &str, id: usize) -> String {
+    let seconds = tree.revisions[id].time.elapsed().as_secs();
+    let ago = match seconds {
+        1 => "1 second ago".to_string(),
+        seconds if seconds < 100 => format!("{} seconds ago", seconds),
+        seconds => format!("{} minutes ago", seconds / 60),
+    };
+    format!("{} #{}  {}", relation, id, ago)
+}
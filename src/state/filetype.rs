@@ -0,0 +1,308 @@
+use std::path::Path;
+
+use ropey::Rope;
+
+/// Lines read to detect the indentation of a buffer
+const INDENT_SAMPLE_LINES: usize = 1000;
+
+/// Indentation and comment settings of a buffer, defaulting to the ones of its filetype
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferOptions {
+    /// Display cells between two tab stops
+    pub tabstop: usize,
+    /// Columns of an indentation level, for `>>`, `<<`, `<Tab>` and auto-indent
+    pub shiftwidth: usize,
+    /// Indent with spaces rather than tabs
+    pub expandtab: bool,
+    /// Template of a commented line, `%s` standing for the text. Empty when the
+    /// filetype has no comments.
+    pub commentstring: String,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        Self {
+            tabstop: 8,
+            shiftwidth: 4,
+            expandtab: true,
+            commentstring: "# %s".to_string(),
+        }
+    }
+}
+
+/// Known filetypes, as (name, extensions or file names, shiftwidth, expandtab, commentstring)
+const FILETYPES: &[(&str, &[&str], usize, bool, &str)] = &[
+    ("rust", &["rs"], 4, true, "// %s"),
+    ("c", &["c", "h"], 4, true, "// %s"),
+    ("cpp", &["cpp", "cc", "cxx", "hpp", "hh"], 4, true, "// %s"),
+    ("java", &["java"], 4, true, "// %s"),
+    ("go", &["go"], 4, false, "// %s"),
+    ("javascript", &["js", "mjs", "cjs", "jsx"], 2, true, "// %s"),
+    ("typescript", &["ts", "mts", "tsx"], 2, true, "// %s"),
+    ("python", &["py", "pyi"], 4, true, "# %s"),
+    ("lua", &["lua"], 2, true, "-- %s"),
+    (
+        "sh",
+        &["sh", "bash", "zsh", ".bashrc", ".zshrc", ".profile"],
+        2,
+        true,
+        "# %s",
+    ),
+    ("vim", &["vim", ".vimrc"], 2, true, "\" %s"),
+    (
+        "make",
+        &["mk", "Makefile", "makefile", "GNUmakefile"],
+        8,
+        false,
+        "# %s",
+    ),
+    ("dockerfile", &["Dockerfile"], 4, true, "# %s"),
+    ("toml", &["toml"], 4, true, "# %s"),
+    ("yaml", &["yml", "yaml"], 2, true, "# %s"),
+    ("json", &["json"], 2, true, ""),
+    (
+        "html",
+        &["html", "htm", "xml", "svg"],
+        2,
+        true,
+        "<!-- %s -->",
+    ),
+    ("css", &["css", "scss"], 2, true, "/* %s */"),
+    ("markdown", &["md", "markdown"], 2, true, "<!-- %s -->"),
+    ("text", &["txt"], 4, true, ""),
+    ("gitcommit", &["COMMIT_EDITMSG"], 4, true, "# %s"),
+    ("diff", &["diff", "patch"], 4, true, "# %s"),
+];
+
+/// Filetype of a path, from its file name or else its extension
+pub fn detect(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    FILETYPES
+        .iter()
+        .find(|(_, names, ..)| names.contains(&name))
+        .or_else(|| {
+            let extension = extension?.to_ascii_lowercase();
+            FILETYPES
+                .iter()
+                .find(|(_, names, ..)| names.contains(&extension.as_str()))
+        })
+        .map(|(filetype, ..)| *filetype)
+}
+
+/// Name of a known filetype
+pub fn known(name: &str) -> Option<&'static str> {
+    FILETYPES
+        .iter()
+        .find(|(filetype, ..)| *filetype == name)
+        .map(|(filetype, ..)| *filetype)
+}
+
+/// Default options of a filetype, the ones it does not define following the global ones
+pub fn defaults(name: &str, global: &BufferOptions) -> Option<BufferOptions> {
+    FILETYPES
+        .iter()
+        .find(|(filetype, ..)| *filetype == name)
+        .map(
+            |(_, _, shiftwidth, expandtab, commentstring)| BufferOptions {
+                shiftwidth: *shiftwidth,
+                expandtab: *expandtab,
+                commentstring: commentstring.to_string(),
+                ..global.clone()
+            },
+        )
+}
+
+/// Whether the filetypes have their own default for a buffer option
+pub fn defines(name: &str) -> bool {
+    matches!(name, "shiftwidth" | "expandtab" | "commentstring")
+}
+
+/// Origin of the value of a buffer option, shown when querying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionSource {
+    /// Global value, set with `:set` or for the files without a known filetype
+    Global,
+    /// Default of the filetype
+    Filetype,
+    /// Adopted from the contents of the buffer
+    Detected,
+    /// Set with `:setlocal`
+    Local,
+}
+
+impl OptionSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Filetype => "filetype",
+            Self::Detected => "detected",
+            Self::Local => "local",
+        }
+    }
+}
+
+impl BufferOptions {
+    /// Whether a `:set` argument targets a buffer option
+    pub fn is_option(arg: &str) -> bool {
+        Self::option_name(arg).is_some()
+    }
+
+    /// Full name of the buffer option targeted by a `:set` argument
+    pub fn option_name(arg: &str) -> Option<&'static str> {
+        let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+        let name = name.trim_end_matches(['?', '!', '<']);
+        let name = match name.strip_prefix("inv").or_else(|| name.strip_prefix("no")) {
+            Some(stripped) if full_name(stripped).is_some() => stripped,
+            _ => name,
+        };
+        full_name(name)
+    }
+
+    /// Copy the value of an option from other options
+    pub fn copy_option(&mut self, name: &str, from: &Self) {
+        match full_name(name) {
+            Some("tabstop") => self.tabstop = from.tabstop,
+            Some("shiftwidth") => self.shiftwidth = from.shiftwidth,
+            Some("expandtab") => self.expandtab = from.expandtab,
+            Some("commentstring") => self.commentstring = from.commentstring.clone(),
+            _ => {}
+        }
+    }
+
+    /// Apply a single `:set` argument to the buffer options, with the syntax of
+    /// [`Config::set`](super::Config::set). The filetype is handled by the caller.
+    pub fn set(&mut self, arg: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            match name {
+                "tabstop" | "ts" => {
+                    self.tabstop = value
+                        .parse()
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or_else(|| format!("E487: Argument must be positive: {}", arg))?;
+                }
+                "shiftwidth" | "sw" => {
+                    self.shiftwidth = value
+                        .parse()
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or_else(|| format!("E487: Argument must be positive: {}", arg))?;
+                }
+                "commentstring" | "cms" if value.is_empty() || value.contains("%s") => {
+                    self.commentstring = value.to_string();
+                }
+                "commentstring" | "cms" => {
+                    return Err("E537: 'commentstring' must be empty or contain %s".to_string());
+                }
+                _ => return Err(format!("E474: Invalid argument: {}", arg)),
+            }
+            return Ok(None);
+        }
+
+        let name = arg.trim_end_matches(['?', '!']);
+        match name {
+            "tabstop" | "ts" => return Ok(Some(format!("  {}={}", name, self.tabstop))),
+            "shiftwidth" | "sw" => return Ok(Some(format!("  {}={}", name, self.shiftwidth))),
+            "commentstring" | "cms" => {
+                return Ok(Some(format!("  {}={}", name, self.commentstring)));
+            }
+            _ => {}
+        }
+        if let Some(name) = arg.strip_suffix('?') {
+            let prefix = if self.expandtab { "  " } else { "no" };
+            return Ok(Some(format!("{}{}", prefix, name)));
+        }
+        self.expandtab = match arg {
+            "expandtab" | "et" => true,
+            "noexpandtab" | "noet" => false,
+            "expandtab!" | "et!" | "invexpandtab" | "invet" => !self.expandtab,
+            _ => return Err(format!("E474: Invalid argument: {}", arg)),
+        };
+        Ok(None)
+    }
+
+    /// Adopt the indentation style of the buffer contents: tabs or spaces, and the most
+    /// common width of the space indents. Buffers without indented lines are left as is.
+    /// Returns the names of the adopted options.
+    pub fn detect_indent(&mut self, rope: &Rope) -> &'static [&'static str] {
+        let (mut tabs, mut spaces) = (0, 0);
+        let mut widths = [0usize; 9];
+        let mut previous = 0;
+        for line in rope.lines().take(INDENT_SAMPLE_LINES) {
+            let mut chars = line.chars().peekable();
+            if chars.peek() == Some(&'\t') {
+                tabs += 1;
+                continue;
+            }
+            let indent = chars.by_ref().take_while(|c| *c == ' ').count();
+            let Some(first) = line.chars().nth(indent).filter(|c| !c.is_whitespace()) else {
+                continue;
+            };
+            // Continuation lines of block comments are aligned rather than indented
+            if first == '*' {
+                continue;
+            }
+            if indent > 0 {
+                spaces += 1;
+            }
+            if let Some(width) = indent.checked_sub(previous)
+                && (2..widths.len()).contains(&width)
+            {
+                widths[width] += 1;
+            }
+            previous = indent;
+        }
+
+        if tabs > spaces {
+            self.expandtab = false;
+            &["expandtab"]
+        } else if spaces > 0
+            && let Some((width, _)) = widths
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .max_by_key(|(width, count)| (**count, usize::MAX - width))
+        {
+            self.expandtab = true;
+            self.shiftwidth = width;
+            &["expandtab", "shiftwidth"]
+        } else {
+            &[]
+        }
+    }
+
+    /// Whitespace of an indentation level
+    pub fn indent_unit(&self) -> String {
+        match self.expandtab {
+            true => " ".repeat(self.shiftwidth),
+            false => "\t".to_string(),
+        }
+    }
+
+    /// Text before and after `%s` in the comment string, trimmed.
+    /// None when the filetype has no comments.
+    pub fn comment_parts(&self) -> Option<(&str, &str)> {
+        let (leader, trailer) = self.commentstring.split_once("%s")?;
+        let leader = leader.trim();
+        (!leader.is_empty()).then_some((leader, trailer.trim()))
+    }
+
+    /// Comment leader of the lines, when comments end with the line
+    pub fn line_comment(&self) -> Option<&str> {
+        self.comment_parts()
+            .filter(|(_, trailer)| trailer.is_empty())
+            .map(|(leader, _)| leader)
+    }
+}
+
+/// Full name of a buffer option or of its abbreviation
+fn full_name(name: &str) -> Option<&'static str> {
+    match name {
+        "tabstop" | "ts" => Some("tabstop"),
+        "shiftwidth" | "sw" => Some("shiftwidth"),
+        "expandtab" | "et" => Some("expandtab"),
+        "commentstring" | "cms" => Some("commentstring"),
+        _ => None,
+    }
+}
@@ -0,0 +1,61 @@
+use std::cmp::Reverse;
+
+use ropey::Rope;
+
+use crate::position::{col_to_cell, line_count, line_len};
+
+use super::data;
+
+/// Foldable line ranges of a buffer, sorted with the outer folds first: the nodes of the
+/// data files, or else the blocks of lines indented more than the line above them
+pub fn folds(rope: &Rope, filetype: Option<&str>, tabstop: usize) -> Vec<(usize, usize)> {
+    if let Some(Ok(root)) = filetype.and_then(|filetype| data::parse(filetype, &rope.to_string())) {
+        let mut folds = vec![];
+        root.folds(&mut folds);
+        return folds;
+    }
+    indent_folds(rope, tabstop)
+}
+
+/// Folds of the lines followed by lines indented more, up to the last of them or to the
+/// closing bracket after them
+fn indent_folds(rope: &Rope, tabstop: usize) -> Vec<(usize, usize)> {
+    let mut folds = vec![];
+    // Lines whose fold is still open, with their indentation
+    let mut open: Vec<(usize, usize)> = vec![];
+    let mut last = 0;
+    for index in 0..line_count(rope) {
+        let line = rope.line(index);
+        let Some(column) = line
+            .chars()
+            .take(line_len(line))
+            .position(|c| !c.is_whitespace())
+        else {
+            continue;
+        };
+        let indent = col_to_cell(line, column, tabstop);
+        // The closing bracket of a block ends its fold
+        let closing = line
+            .get_char(column)
+            .is_some_and(|c| matches!(c, '}' | ']' | ')'));
+        while let Some(&(start, open_indent)) = open.last().filter(|(_, open)| *open >= indent) {
+            open.pop();
+            let end = match closing && open_indent == indent && last > start {
+                true => index,
+                false => last,
+            };
+            if end > start {
+                folds.push((start, end));
+            }
+        }
+        open.push((index, indent));
+        last = index;
+    }
+    for (start, _) in open {
+        if last > start {
+            folds.push((start, last));
+        }
+    }
+    folds.sort_by_key(|&(start, end)| (start, Reverse(end)));
+    folds
+}
@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::{process::Command, sync::mpsc::Sender};
+
+use super::{EditorEvent, Jobs};
+
+/// Delay between two checks of the repository files
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Branch checked out in the repository of the root folder, and its distance to its
+/// upstream branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitStatus {
+    /// Branch name, or the short hash of a detached HEAD
+    pub branch: String,
+    /// Commits not pushed to the upstream branch
+    pub ahead: usize,
+    /// Commits of the upstream branch not merged yet
+    pub behind: usize,
+}
+
+/// Watch the repository containing a folder in the background, sending its status when
+/// it changes: when HEAD moves to another branch or commit, and after fetches
+pub fn watch(root: PathBuf, sender: Sender<EditorEvent>, jobs: &Jobs) {
+    let Some(git_dir) = find_git_dir(&root) else {
+        return;
+    };
+    jobs.spawn("git status", |job| async move {
+        let mut last = None;
+        loop {
+            let stamp = stamp(&git_dir);
+            if last.as_ref() != Some(&stamp) {
+                last = Some(stamp);
+                job.advance();
+                let status = status(&root, &git_dir).await;
+                if sender.send(EditorEvent::GitStatus(status)).await.is_err() {
+                    return;
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// Changes staged for the next commit, from `git diff --cached` run in the current
+/// folder, as git does for the editor of the commit message
+pub fn staged_diff() -> Option<String> {
+    let output = std::process::Command::new("git")
+        .args(["diff", "--cached", "--no-color", "--no-ext-diff"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    (output.status.success() && !text.trim().is_empty()).then(|| text.into_owned())
+}
+
+/// `.git` folder of the repository containing a folder. A `.git` file, in worktrees and
+/// submodules, points to it with a `gitdir:` line.
+fn find_git_dir(folder: &Path) -> Option<PathBuf> {
+    folder.ancestors().find_map(|dir| {
+        let git = dir.join(".git");
+        if git.is_dir() {
+            return Some(git);
+        }
+        let text = fs::read_to_string(&git).ok()?;
+        let target = text.strip_prefix("gitdir:")?.trim();
+        Some(dir.join(target))
+    })
+}
+
+/// Contents of HEAD and modification times of the files updated by commits, checkouts
+/// and fetches, which change along with the status
+fn stamp(git_dir: &Path) -> (String, Vec<Option<SystemTime>>) {
+    let head = fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+    let mut files = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+    files.push(git_dir.join("FETCH_HEAD"));
+    if let Some(reference) = head.trim().strip_prefix("ref: ") {
+        files.push(git_dir.join(reference));
+    }
+    let mtimes = files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect();
+    (head, mtimes)
+}
+
+/// Branch of HEAD, read from the `.git` folder, with its ahead and behind counts from
+/// `git rev-list`, which are 0 without upstream branch or git command
+async fn status(root: &Path, git_dir: &Path) -> Option<GitStatus> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let branch = match head.strip_prefix("ref: ") {
+        Some(reference) => reference
+            .strip_prefix("refs/heads/")
+            .unwrap_or(reference)
+            .to_string(),
+        None => head.chars().take(7).collect(),
+    };
+    let output = Command::new("git")
+        .args(["rev-list", "--left-right", "--count", "HEAD...@{upstream}"])
+        .current_dir(root)
+        .kill_on_drop(true)
+        .output()
+        .await;
+    let (ahead, behind) = match output {
+        Ok(output) if output.status.success() => {
+            let text = String::from_utf8_lossy(&output.stdout);
+            let mut counts = text
+                .split_whitespace()
+                .map(|count| count.parse().unwrap_or(0));
+            (counts.next().unwrap_or(0), counts.next().unwrap_or(0))
+        }
+        _ => (0, 0),
+    };
+    Some(GitStatus {
+        branch,
+        ahead,
+        behind,
+    })
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ropey::Rope;
+use tokio::sync::mpsc::Sender;
+
+use super::{
+    Edit, EditorEvent, FileSystem, JobHandle, Jobs,
+    filesystem::{is_hidden, is_link_cycle},
+};
+
+/// Files larger than this are skipped by project searches
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Single pattern occurrence in a file
+#[derive(Debug, Clone)]
+pub struct Match {
+    /// Line index in the file
+    pub line: usize,
+    /// Char range of the match in the line
+    pub start: usize,
+    pub end: usize,
+    /// Line contents, without the line ending
+    pub text: String,
+    /// Whether the match is selected for replacement
+    pub selected: bool,
+}
+
+/// All occurrences of a pattern in a single file
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<Match>,
+}
+
+/// Search a pattern in a single line
+fn search_line(index: usize, line: &str, pattern: &str, matches: &mut Vec<Match>) {
+    let text = line.trim_end_matches(['\n', '\r']);
+    for (byte, _) in text.match_indices(pattern) {
+        let start = text[..byte].chars().count();
+        matches.push(Match {
+            line: index,
+            start,
+            end: start + pattern.chars().count(),
+            text: text.to_string(),
+            selected: true,
+        });
+    }
+}
+
+/// Search a pattern in an open buffer
+pub fn search_rope(rope: &Rope, pattern: &str) -> Vec<Match> {
+    let mut matches = vec![];
+    for (index, line) in rope.lines().enumerate() {
+        search_line(index, &line.to_string(), pattern, &mut matches);
+    }
+    matches
+}
+
+/// Search a pattern in a file contents
+fn search_text(text: &str, pattern: &str) -> Vec<Match> {
+    let mut matches = vec![];
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        search_line(index, line, pattern, &mut matches);
+    }
+    matches
+}
+
+/// Recursively search a pattern in all files on disk under `path`
+fn search_folder(
+    path: &Path,
+    pattern: &str,
+    exclude: &HashSet<PathBuf>,
+    results: &mut Vec<FileMatches>,
+    job: &JobHandle,
+) {
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    let mut entries = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for path in entries {
+        // Killed searches send the results found so far
+        if job.cancelled() {
+            return;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !is_hidden(&name) && !is_link_cycle(&path) {
+                search_folder(&path, pattern, exclude, results, job);
+            }
+            continue;
+        }
+        job.advance();
+        if exclude.contains(&path) {
+            continue;
+        }
+        if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_FILE_SIZE) {
+            continue;
+        }
+        // Binary files are skipped as they are not valid UTF-8
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let matches = search_text(&text, pattern);
+        if !matches.is_empty() {
+            results.push(FileMatches { path, matches });
+        }
+    }
+}
+
+/// Search a pattern in the whole project, including the extra roots of the workspace.
+/// Open buffers are searched immediately because their contents may differ from disk,
+/// while the other files are searched in the background.
+/// Their results are sent with an `EditorEvent::ProjectSearch` event with the given id.
+pub fn search_project(
+    filesystem: &FileSystem,
+    sender: Sender<EditorEvent>,
+    jobs: &Jobs,
+    pattern: &str,
+    id: usize,
+) -> Vec<FileMatches> {
+    let mut exclude = HashSet::new();
+    let mut results = vec![];
+    for file_id in &filesystem.open_buffers {
+        let file = &filesystem.files[*file_id];
+        let Some(rope) = &file.buffer else {
+            continue;
+        };
+        exclude.insert(file.path.clone());
+        let matches = search_rope(rope, pattern);
+        if !matches.is_empty() {
+            results.push(FileMatches {
+                path: file.path.clone(),
+                matches,
+            });
+        }
+    }
+
+    let roots = filesystem
+        .workspace()
+        .map(|root| filesystem.folders[root].path.clone())
+        .collect::<Vec<_>>();
+    let pattern = pattern.to_string();
+    jobs.spawn_blocking(format!("grep {}", pattern), move |job| {
+        let mut results = vec![];
+        for root in &roots {
+            search_folder(root, &pattern, &exclude, &mut results, &job);
+        }
+        if let Err(err) = sender.blocking_send(EditorEvent::ProjectSearch { id, results }) {
+            log::error!("Failed to send project search event: {}", err);
+        }
+    });
+
+    results
+}
+
+/// Replace the selected matches of a line, with matches sorted by start column.
+/// Returns None if the line contents changed since the search.
+fn replace_line(
+    line: &str,
+    matches: &[&Match],
+    pattern: &str,
+    replacement: &str,
+) -> Option<String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut result = String::with_capacity(line.len());
+    let mut last = 0;
+    for m in matches {
+        let found = chars.get(m.start..m.end)?.iter().collect::<String>();
+        if found != pattern {
+            return None;
+        }
+        result.extend(&chars[last..m.start]);
+        result.push_str(replacement);
+        last = m.end;
+    }
+    result.extend(&chars[last..]);
+    Some(result)
+}
+
+/// Selected matches of a file, grouped by line in descending order
+fn selected_lines(matches: &[Match]) -> Vec<Vec<&Match>> {
+    let mut lines: Vec<Vec<&Match>> = vec![];
+    for m in matches.iter().filter(|m| m.selected) {
+        match lines.last_mut() {
+            Some(line) if line[0].line == m.line => line.push(m),
+            _ => lines.push(vec![m]),
+        }
+    }
+    lines.reverse();
+    lines
+}
+
+/// Apply the selected replacements of a file to its open buffer.
+/// Returns the number of replaced matches.
+fn replace_in_rope(rope: &mut Edit, file: &FileMatches, pattern: &str, replacement: &str) -> usize {
+    let mut count = 0;
+    for matches in selected_lines(&file.matches) {
+        let index = matches[0].line;
+        if index >= rope.len_lines() {
+            continue;
+        }
+        let line = rope.line(index).to_string();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let Some(replaced) = replace_line(content, &matches, pattern, replacement) else {
+            continue;
+        };
+        let start = rope.line_to_char(index);
+        rope.remove(start..start + content.chars().count());
+        rope.insert(start, &replaced);
+        count += matches.len();
+    }
+    count
+}
+
+/// Apply the selected replacements of a file directly on disk.
+/// Returns the number of replaced matches.
+fn replace_on_disk(file: &FileMatches, pattern: &str, replacement: &str) -> io::Result<usize> {
+    let text = fs::read_to_string(&file.path)?;
+    let mut lines = text
+        .split_inclusive('\n')
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let mut count = 0;
+    for matches in selected_lines(&file.matches) {
+        let Some(line) = lines.get_mut(matches[0].line) else {
+            continue;
+        };
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = line[content.len()..].to_string();
+        let Some(replaced) = replace_line(content, &matches, pattern, replacement) else {
+            continue;
+        };
+        *line = replaced + &ending;
+        count += matches.len();
+    }
+    if count > 0 {
+        fs::write(&file.path, lines.concat())?;
+    }
+    Ok(count)
+}
+
+/// Apply all selected replacements in one operation.
+/// Files with an open buffer are edited in memory, the others on disk.
+/// A file that cannot be read or written does not stop the others.
+/// Returns the number of modified files and replaced matches, and the failed paths.
+pub fn replace_project(
+    filesystem: &mut FileSystem,
+    results: &[FileMatches],
+    pattern: &str,
+    replacement: &str,
+) -> (usize, usize, Vec<PathBuf>) {
+    let mut files = 0;
+    let mut total = 0;
+    let mut failed = vec![];
+    for file in results {
+        let buffer = filesystem
+            .find_file(&file.path)
+            .filter(|id| filesystem.files[*id].buffer.is_some());
+
+        let count = match buffer {
+            Some(id) => {
+                let file_state = &mut filesystem.files[id];
+                let Some(rope) = &mut file_state.buffer else {
+                    continue;
+                };
+                let rope = &mut Edit::new(rope, &mut file_state.changes);
+                let count = replace_in_rope(rope, file, pattern, replacement);
+                file_state.modified |= count > 0;
+                count
+            }
+            None => match replace_on_disk(file, pattern, replacement) {
+                Ok(count) => count,
+                Err(err) => {
+                    log::error!("Failed to replace in {}: {}", file.path.display(), err);
+                    failed.push(file.path.clone());
+                    continue;
+                }
+            },
+        };
+        if count > 0 {
+            files += 1;
+            total += count;
+        }
+    }
+    (files, total, failed)
+}
@@ -0,0 +1,180 @@
+use std::{env, fs, path::Path};
+
+use super::{Config, State, commands::split_name};
+use crate::utils::find_executable;
+
+/// Glyphs that only render with a nerd font
+const NERD_FONT_PROBE: &str = "\u{e5ff} \u{f07c} \u{f0219} \u{e7a8} \u{e0b4}";
+
+/// Build the `:checkhealth` report
+pub fn report(state: &State) -> String {
+    let mut report = Report::default();
+    check_terminal(&mut report);
+    check_tools(&mut report);
+    check_config(&mut report, state);
+    report.text
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    text: String,
+}
+
+impl Report {
+    fn section(&mut self, title: &str) {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        self.text.push_str(&"=".repeat(78));
+        self.text.push_str(&format!("\n{}\n\n", title));
+    }
+
+    fn ok(&mut self, text: &str) {
+        self.text.push_str(&format!("- OK {}\n", text));
+    }
+
+    fn warn(&mut self, text: &str) {
+        self.text.push_str(&format!("- WARNING {}\n", text));
+    }
+
+    fn error(&mut self, text: &str) {
+        self.text.push_str(&format!("- ERROR {}\n", text));
+    }
+
+    fn info(&mut self, text: &str) {
+        self.text.push_str(&format!("- {}\n", text));
+    }
+}
+
+fn check_terminal(report: &mut Report) {
+    report.section("Terminal");
+    let term = env::var("TERM").unwrap_or_default();
+    match term.as_str() {
+        "" => report.warn("TERM is not set"),
+        term => report.ok(&format!("TERM: {}", term)),
+    }
+
+    match env::var("COLORTERM").as_deref() {
+        Ok("truecolor" | "24bit") => report.ok("True color is supported (COLORTERM)"),
+        _ if term.contains("256color") => {
+            report.warn("Only 256 colors are advertised: set COLORTERM=truecolor if supported")
+        }
+        _ => report.warn("True color support is not advertised (COLORTERM)"),
+    }
+
+    // Querying the terminal would race with the input event stream
+    let kitty = env::var_os("KITTY_WINDOW_ID").is_some()
+        || ["kitty", "foot", "ghostty"]
+            .iter()
+            .any(|t| term.contains(t))
+        || env::var("TERM_PROGRAM").is_ok_and(|p| p == "WezTerm");
+    match kitty {
+        true => report.ok("Kitty keyboard protocol is likely supported"),
+        false => report.info("Kitty keyboard protocol support was not detected"),
+    }
+
+    report.info(&format!(
+        "Nerd font: check that these glyphs render as icons rather than boxes: {}",
+        NERD_FONT_PROBE
+    ));
+}
+
+fn check_tools(report: &mut Report) {
+    report.section("External tools");
+    let tools: &[(&str, &[&str])] = &[
+        ("git", &["git"]),
+        ("ripgrep", &["rg"]),
+        (
+            "language server",
+            &[
+                "rust-analyzer",
+                "pyright",
+                "typescript-language-server",
+                "clangd",
+            ],
+        ),
+        (
+            "clipboard",
+            &["wl-copy", "xclip", "xsel", "pbcopy", "clip.exe"],
+        ),
+    ];
+    for (tool, executables) in tools {
+        let found = executables
+            .iter()
+            .filter_map(|name| find_executable(name))
+            .collect::<Vec<_>>();
+        match found.as_slice() {
+            [] => report.warn(&format!(
+                "No {} found (tried {})",
+                tool,
+                executables.join(", ")
+            )),
+            found => {
+                let paths = found.iter().map(|p| p.display().to_string());
+                report.ok(&format!(
+                    "{}: {}",
+                    tool,
+                    paths.collect::<Vec<_>>().join(", ")
+                ))
+            }
+        }
+    }
+}
+
+fn check_config(report: &mut Report, state: &State) {
+    report.section("Configuration");
+    let Some(dir) = Config::dir() else {
+        report.warn("The config directory cannot be determined (HOME is not set)");
+        return;
+    };
+
+    let mut scripts = vec![dir.join("init.vim")];
+    if let Ok(entries) = fs::read_dir(dir.join("plugins")) {
+        let mut plugins = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "vim"))
+            .collect::<Vec<_>>();
+        plugins.sort();
+        scripts.extend(plugins);
+    }
+
+    for script in scripts {
+        check_script(report, state, &script);
+    }
+}
+
+/// Verify that the lines of a script are known commands
+fn check_script(report: &mut Report, state: &State, path: &Path) {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) if !path.exists() => {
+            report.info(&format!("{} does not exist", path.display()));
+            return;
+        }
+        Err(err) => {
+            report.error(&format!("{} cannot be read: {}", path.display(), err));
+            return;
+        }
+    };
+
+    let mut errors = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim().trim_start_matches(':');
+        if line.is_empty() || line.starts_with('"') || line.starts_with('!') {
+            continue;
+        }
+        let (name, _) = split_name(line);
+        let result = match name {
+            "" => Err(format!("E492: Not an editor command: {}", line)),
+            name => state.commands.resolve(name),
+        };
+        if let Err(err) = result {
+            report.error(&format!("{}:{}: {}", path.display(), index + 1, err));
+            errors += 1;
+        }
+    }
+    if errors == 0 {
+        report.ok(&format!("{} is valid", path.display()));
+    }
+}
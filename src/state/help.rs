@@ -0,0 +1,564 @@
+/// Cells before the descriptions of the ex commands
+const USAGE_WIDTH: usize = 38;
+
+/// Builtin help text. Tags are written `*tag*` and links to them `|tag|`.
+pub const HELP: &str = r#"*help.txt*                     TUI editor help
+
+Move the cursor on a link between bars and press CTRL-] or <Enter> to jump
+to its tag.
+Use `:help {topic}` to jump to a topic directly.
+
+Contents:
+  |keys|          Keybindings
+  |mouse|         Mouse support
+  |cmdline|       Command line editing
+  |commands|      Ex commands
+  |options|       Options set with |:set|
+  |registers|     Registers
+  |config|        Startup scripts and plugins
+
+==============================================================================
+*keys*                                                   Keybindings
+
+Normal mode:                                             *normal-mode*
+  h j k l        Move the cursor
+  0 $            Move to the start / end of the line
+  gg G           Go to the first / last line                          *G*
+  {count}G       Go to a line, like `12G`, also with `{count}gg`
+  CTRL-D CTRL-U  Scroll down / up half a page, the cursor moving as many lines
+  CTRL-F CTRL-B  Scroll down / up a page, keeping two lines of the previous one
+                 in view. The cursor stays |'scrolloff'| lines away from
+                 the edges.
+  w b e          Move to the next word start / back to the word start /  *word*
+                 to the word end. Words are runs of letters, digits and
+                 `_`, or of other non-blank chars, and empty lines.
+  W B E          Same with WORDs, runs of non-blank chars
+  i a I A        Enter insert mode before / after the cursor, at the line
+                 start / end
+  v              Enter |visual-mode|
+  V              Enter linewise visual mode, selecting whole lines  *linewise*
+  CTRL-V         Enter blockwise visual mode, selecting a rectangle  *blockwise*
+  x              Delete the char under the cursor
+  CTRL-A CTRL-X  Add / subtract 1 to the number under or after the cursor
+  d{motion}      Delete the text of a motion or text object, see  *operator*
+                 |operator-pending|
+  c{motion}      Delete it and enter insert mode
+  y{motion}      Yank it
+  dd cc yy       Delete / change / yank the line                     *yy*
+  p P            Put a register after / before the cursor, or below /  *p*
+                 above the line for lines yanked or deleted whole
+  >> <<          Indent / unindent the line by |'shiftwidth'|
+  gcc            Comment or uncomment the line with |'commentstring'| *gc*
+  "{reg}         Use a register for the next delete, yank or put, like `"_dd`,
+                 see |registers|
+  u CTRL-R       Undo / redo the last change. Each command and each line   *undo*
+                 typed in insert mode is a change, along with the typed
+                 text after a pause, see |i_CTRL-G_u|
+  g- g+          Go to the previous / next text state in time, including
+                 the changes undone then replaced by new ones        *undo-tree*
+  .              Repeat the last change: the keys typed from a normal     *.*
+                 mode command until back in normal mode, like `dw`,
+                 `ciwfoo<Esc>` or `vjd`
+  gqq gqj gqk    Format the current line, with the next or previous line, *gq*
+                 to |'textwidth'|
+  gqip gqap      Format the current paragraph
+  g CTRL-G       Count the lines, words, chars and bytes           *g_CTRL-G*
+                 of the buffer, or of the selection in visual mode
+  ga             Show the codepoint and bytes of the cursor char   *ga*
+  : / ?          Open the |cmdline| for a command or a search
+  n N            Repeat the last search forward / backward
+  ]c [c          Jump to the next / previous hunk of a diff       *]c*
+  m{a-z}         Set a mark at the cursor. Marks move along the edits   *mark*
+                 of their text, and name lines in the ranges of |commands|.
+  '{a-z} `{a-z}  Jump to the line of a mark / to its position, also `'<`
+                 and `'>` for the last visual selection
+  CTRL-O CTRL-I  Go back / forward in the jump list: the positions     *jumps*
+                 before the line jumps, searches, mark jumps and opened
+                 files. <Tab> is the same as CTRL-I.
+  zc zo za       Close / open / toggle the fold under the cursor     *fold*
+                 The JSON, YAML and TOML files fold their objects and
+                 arrays, the other files the lines indented more than
+                 the line above them. A closed fold shows as one line.
+  zM zR          Close / open all the folds
+  q: q/ q?       Open the command-line history window
+  q{reg}         Record the typed keys into a register, until `q`    *q*
+  @{reg} @@      Replay the keys of a register, or the last replayed  *@*
+                 one. The intermediate states of long replays are not
+                 drawn, and CTRL-C interrupts them.
+  CTRL-]         Jump to the tag under the cursor in help buffers
+  CTRL-h CTRL-l  Focus the filetree / the panes
+  CTRL-p         Open the command palette                   *palette*
+                 A fuzzy picker of the keys and commands: Enter runs the
+                 chosen one, Tab types a command in the |cmdline| to add
+                 its arguments.
+  F2             Suspend the mouse capture, see |mouse|
+
+Operator-pending mode, after `d`, `c` or `y`:          *operator-pending*
+  h l 0 ^ $      The chars before / after the cursor, until the line start,
+                 its first non-blank or its end
+  w W            Until the next |word|, or the line end after the last word
+                 of a line. `cw` changes until the end of the word.
+  e E b B        Until the end of the word / back to its start
+  j k            The cursor line and the next / previous one
+  gg G           The lines up to the first / last one
+  iw aw iW aW    The word under the cursor, with the blanks after it for
+                 `aw`. WORDs are runs of non-blank chars.
+  i( a( ib ab    The block between the parentheses around the cursor,
+                 with them for `a(`. Also `i[`, `i{` or `iB`, and `i<`.
+  i" a" i' i`    The quoted string of the line around or after the cursor,
+                 with the quotes and the blanks after it for `a"`
+  ip ap          The paragraph, with the blank lines after it for `ap`
+  <Esc>          Cancel the operator
+
+Visual mode:                                             *visual-mode*
+  h j k l 0 $    Extend the selection, also with the |word| motions
+  gg G {count}G  Extend the selection to the first / last / a line
+  '{a-z} `{a-z}  Extend the selection to a |mark|
+  CTRL-D CTRL-U  Scroll half a page / a page, extending the selection
+  CTRL-F CTRL-B
+  :              Open the |cmdline| with the '<,'> range
+  gq             Format the selected lines
+  gs gr gu       Sort / reverse / deduplicate the selected lines, like |:sort|,
+                 |:reverse| and |:uniq|
+  ga{char}       Align the selected lines on a delimiter char, see |:Align|
+  CTRL-A CTRL-X  Add / subtract 1 to the first number of each selected line
+  g CTRL-A       Same, adding 1 on the first line with a number, 2 on the next
+  g CTRL-X       one and so on, to turn a column of zeros into a sequence
+  d x c          Delete / change the selection
+  y              Yank the selection
+  > <            Indent / unindent the selected lines
+  gc             Comment the selected lines, or uncomment them if they all are
+  p              Replace the selection with the unnamed register, or the one
+                 given with `"{reg}`. The unnamed register receives the
+                 replaced text.
+  P              Replace the selection, keeping the unnamed register
+  v V CTRL-V     Switch to the charwise / linewise / blockwise selection, or
+                 back to normal mode from the same one
+  <Esc>          Back to normal mode
+
+In linewise visual mode, `d` `y` `c` `p` and `P` act on the lines of the selection,
+and the text they yank is put as whole lines by |p|.
+
+In blockwise visual mode, the selection spans the display columns between the
+cursor and the start of the selection, on each of their lines:
+  d x            Delete the block, one line of text per line in the register
+  y              Yank the block
+  I              Insert before the block: the text typed on the first line is
+                 inserted on the other lines when leaving insert mode, except
+                 on the lines ending before the block
+  A              Append after the block, padding the shorter lines with spaces
+  c              Delete the block and insert in its place
+  g#             Insert the numbers from 1 down the block, before it
+  p P            Replace the block with a register, put at its start
+
+Insert mode:                                             *insert-mode*
+  <Esc>          Back to normal mode
+  <Tab>          Indent to the next |'shiftwidth'| column, or insert a tab with
+                 |'noexpandtab'|
+  <Enter>        Open a line with the same indentation, one level deeper after
+                 an opening bracket, see |'autoindent'|
+  CTRL-R {reg}   Insert the contents of a register, see |registers|     *i_CTRL-R*
+  CTRL-V {key}   Insert a key literally, bypassing the mappings: a tab,    *i_CTRL-V*
+                 or a control char for CTRL chords
+  CTRL-V u1234   Insert a codepoint from up to 4 hex digits, or 8 after
+  CTRL-V U1F600  CTRL-V U
+  CTRL-K {a} {b} Insert the RFC1345 digraph of two chars, like `a:` for ä,   *i_CTRL-K*
+                 `e'` for é or `Eu` for €, see |:digraphs|
+  CTRL-G u       Start a new undoable change                        *i_CTRL-G_u*
+  CTRL-X CTRL-F  Complete the path before the cursor, relative to the    *i_CTRL-X_CTRL-F*
+                 folder of the file. CTRL-N and CTRL-P cycle through the
+                 matches, CTRL-E restores the typed text, and CTRL-Y or any
+                 other key accepts the match
+  Lines are wrapped past |'textwidth'| in prose files and comments.
+
+Filetree:                                                *filetree*
+  j k            Move the selection
+  l o <Enter>    Open the file, or toggle the folder
+  h              Close the folder
+  y              Copy the name of the selected entry to the unnamed register
+                 and the clipboard
+  Y              Copy its path relative to the root folder
+  gy             Copy its absolute path
+  P              Toggle the preview of the selected file over the panes: the
+                 first lines of text files, PNG images on terminals supporting
+                 the kitty graphics protocol, and the metadata of other files
+  i              Toggle the details of the selected entry: size, permissions
+                 and modification time
+  R              Refresh the loaded folders, to show the files created or
+                 deleted outside the editor
+  Files with an open buffer are shown in bold, and marked when they have
+  unsaved changes.
+
+==============================================================================
+*mouse*                                                  Mouse support
+
+  Left click     Move the cursor, or open a filetree entry. Clicks in the
+                 gutter jump to the line
+  Shift click    Extend the |visual-mode| selection to the clicked position,
+                 or to the whole clicked line in the gutter
+  Middle click   Paste the primary selection, see |'middlepaste'|
+  Right click    Open the context menu: copy, paste and go to definition in
+                 the panes, open, rename and delete in the filetree
+  Drag and drop  Move a filetree file into the folder it is dropped onto,
+                 confirmed by submitting the prefilled |:Rename| command
+  Wheel          Scroll the pane, filetree or list under the mouse by
+                 |'scrolltick'| lines
+
+==============================================================================
+*cmdline*                                                Command line editing
+
+  <Tab> <S-Tab>  Complete and cycle through command names, and the paths of
+                 the file arguments
+  <Up> <Down>    Browse the history, filtered by the typed prefix
+  CTRL-W         Delete the word before the cursor
+  CTRL-U         Delete the text before the cursor
+  CTRL-R {reg}   Insert the contents of a register, see |registers|
+  <Esc>          Close the cmdline
+
+==============================================================================
+*commands*                                               Ex commands
+
+Commands can be abbreviated, separated by `|`, and some accept a range like
+`%`, `'<,'>`, `.,$` or `3;+2`. Marks follow the text they were set on when
+lines are inserted or deleted above them. File arguments can start with `~` and contain
+`$VAR`, `${VAR}` or `%VAR%` environment variables.
+
+  *:edit*       :e[dit] {file}        Open a file in the focused pane
+                                      A `ssh://[user@]host[:port]/path` file *remote*
+                                      is read and written in the background
+                                      with `sftp`, which needs a key or agent
+                                      login. `/~/path` is relative to the
+                                      home folder. A failed write leaves the
+                                      buffer read-only until a write succeeds
+  *:write*      :w[rite][!] [++p] [file]
+                                      Write the buffer, or a copy of it to
+                                      {file}. Missing directories are created
+                                      with `++p`, or after a confirmation.
+                                      Read-only files, and files changed on
+                                      disk since they were read, are only
+                                      overwritten with `!`
+  *:w_range*    :[range]w[rite][!] [file]
+                                      Write lines to {file}, or over the
+                                      file of the buffer with `!`
+  *:w_a*        :[range]w[rite][!] >> [file]
+                                      Append lines to {file}, or to the file
+                                      of the buffer. `!` creates a missing
+                                      file
+  *:read*       :[range]r[ead] {file} Insert a file below the cursor line, or
+                                      below the last line of the range
+  *:read!*      :[range]r[ead] !{cmd} Insert the output of a shell command
+  *:quit*       :q[uit][!]            Close the pane, or exit
+  *:wq*         :wq :x[it]            Write and close
+  *:wall*       :wa[ll]               Write all the buffers
+  *:enew*       :ene[w]               Edit a new unnamed buffer, whose name
+                                      is asked on the first write
+  *:saveas*     :sav[eas][!] [++p] {file}
+                                      Write the buffer to a new file and
+                                      edit it. `++p` creates the missing
+                                      directories, which is otherwise asked
+  *:file*       :f[ile] [++p] [name]  Rename the buffer without writing it,
+                                      or show its name and size
+  *:qall*       :qa[ll][!]            Exit
+  *:wqall*      :wqa[ll] :xa[ll]      Write all the buffers and exit
+  *:cquit*      :cq[uit] [N]          Exit without writing, with the error
+                                      status N, 1 by default, to abort the
+                                      commit of |git-commit|
+  *:vsplit*     :vs[plit] [file]      Split the pane vertically
+  *:delete*     :[range]d[elete] [x]  Delete lines into a register
+  *:substitute* :[range]s[ubstitute]/{pattern}/{string}/[flags]
+                                      Replace the first match of {pattern} by
+                                      {string} on each line of the range,
+                                      the cursor line by default. In {string},
+                                      `&` is the match and `\1` to `\9` its
+                                      groups, `\r` a line break, `\u` / `\U`
+                                      uppercase the next char / up to `\E`.
+                                      An empty {pattern} is the last search.
+                                      Flags: `g` replace all the matches of
+                                      the lines, `c` confirm each one with y,
+                                      n, a (all), q (quit) or l (last), `n`
+                                      count the matches, `e` no error when
+                                      not found, `i` / `I` ignore / match case
+                :[range]s[ubstitute] [flags]
+                                      Repeat the last substitution
+  *:sort*       :[range]sor[t][!] [flags]
+                                      Sort the lines of the range, the whole
+                                      buffer by default, in reverse with `!`.
+                                      Flags: `u` keep only the first of the
+                                      equal lines, `i` ignore case, `n` sort
+                                      by the first number of the lines
+  *:uniq*       :[range]uni[q] [i]    Remove the lines equal to the one above
+                                      them, ignoring case with `i`
+  *:reverse*    :[range]rev[erse]     Reverse the order of the lines
+  *:Align*      :[range]Al[ign] {delim}
+                                      Pad the fields of the lines separated by
+                                      {delim}, like `=`, `,` or `|`, so that
+                                      the delimiters line up, by default on
+                                      the lines around the cursor containing
+                                      it. `,` `;` and `:` stay after their
+                                      field, the others get a space around.
+  *:Renumber*   :[range]Ren[umber] [N]
+                                      Renumber the ordered list items like
+                                      `1.` or `1)` of the range, by default of
+                                      the paragraph around the cursor, from
+                                      the first item or from [N]. Nested
+                                      lists are numbered on their own.
+  *:undo*       :u[ndo] [N]           Undo a change, or go to the text after
+                                      the change numbered {N}, 0 being the
+                                      text as loaded
+  *:redo*       :red[o]               Redo the last undone change
+  *:goto*       :go[to] {line} [col]  Jump to a line and column, also with
+                                      a bare range like `:42` or `:$`
+  *:set*        :se[t] {option}       Set an option, see |options|
+  *:setlocal*   :setl[ocal] {option}  Set a window or buffer option for the
+                                      focused pane or buffer only, `{option}<`
+                                      restoring the global value
+  *:source*     :so[urce] {file}      Execute a script of ex commands, or a
+                                      .tes |scripts| file
+  *:call*       :cal[l] {code}        Run script code, like `:call f(1)`
+  *:command*    :com[mand][!] {Name} {rep}
+                                      Define a user command
+  *:delcommand* :delc[ommand] {Name}  Delete a user command
+  *:map*        :map {lhs} :{cmd}<CR> Bind a key, also :nmap, :vmap, :imap
+                                      and their noremap variants. Without
+                                      {cmd}, list the keybindings starting
+                                      with {lhs} and where they are defined
+  *:cabbrev*    :ca[bbrev] {lhs} {rhs}
+                                      Define a cmdline abbreviation
+  *:cunabbrev*  :cuna[bbrev] {lhs}    Delete a cmdline abbreviation
+  *:autocmd*    :au[tocmd][!] {event} {pat} {cmd}
+                                      Run a command on an event
+  *:checkhealth* :che[ckhealth]       Report the terminal and tools support
+  *:startuptime* :star[tuptime]       Show the times of the startup phases,
+                                      see |--startuptime|
+  *:echo*       :ec[ho] {text}        Display a message
+  *:Replace*    :R[eplace] [pattern]  Find and replace in the project
+  *:Rename*     :Ren[ame] {path}      Rename the file or filetree entry
+  *:Delete*     :Del[ete][!]          Delete the file or filetree entry
+  *:Apply*      :App[ly][!]           Apply the hunk under the cursor of a
+                                      |diff| to the files of the root folder
+                                      with `git apply`, or revert it with `!`
+  *:workspace*  :work[space] add {dir}
+                                      Add a root folder to the filetree and
+                                      to the project searches
+                :work[space] remove {dir}
+                                      Remove an added root folder
+                :work[space]          List the root folders
+  *:help*       :h[elp] [topic]       Open this help
+  *:Man*        :Man {page}           Open a man page
+  *:digraphs*   :dig[raphs]           List the digraphs
+  *:mksession*  :mks[ession][!] [file]
+                                      Write the panes, their files, cursors
+                                      and scroll to a session file restored
+                                      with |-S|, `Session.tui` by default
+  *:jobs*       :jobs                 List the background jobs, like searches,
+                                      shell commands and transfers, with their
+                                      duration and processed items
+                :jobs kill {id}       Cancel a background job
+  *:!*          :!{cmd}               Run a shell command. Outputs of several
+                                      lines open in a scratch buffer, whose
+                                      changes are never reported as unsaved
+  *:make*       :mak[e][!] [args]     Run |'makeprg'| with arguments in the
+                                      background, and read the errors and
+                                      warnings of its output, counted in the
+                                      lualine. Jumps to the first error,
+                                      unless `!` is given.
+  *:jq*         :jq {path}            Jump to a key of a JSON, YAML or TOML
+                                      file, like `.dependencies.serde` or
+                                      `.items[0]`, see |data-files|
+
+==============================================================================
+*options*                                                Options
+
+  *'ignorecase'* *'ic'*   Case insensitive searches
+  *'smartcase'* *'scs'*   Case sensitive searches with uppercase chars
+  *'magic'*             Patterns are regexes, or literal strings when unset
+  *'hlsearch'* *'hls'*    Highlight the matches of the last search
+  *'incsearch'* *'is'*    Jump to the first match and highlight the matches
+                        while typing a search, back to the start on <Esc>
+  *'showcmd'* *'sc'*      Show the keys of the pending command, like `"ad`, on
+                        the right of the lualine until it completes
+  *'updatetime'* *'ut'*   Milliseconds before the CursorHold event
+  *'scrolloff'* *'so'*    Minimum number of lines kept above and below the cursor
+  *'scrolltick'*        Number of lines scrolled per mouse wheel tick
+  *'textwidth'* *'tw'*    Maximum line width of |gq| and auto-wrap, 0 for 79
+  *'icons'*             Glyphs: nerd, unicode or ascii
+  *'colors'*            Colors: truecolor, 256 or 16
+  *'mouse'*             Modes with mouse support, among n, v, i, c or a
+  *'middlepaste'* *'mp'*  Paste the primary selection on middle click
+  *'cmdline'*           Cmdline layout: float or bottom
+  *'makeprg'*           Command of |:make|, `make` by default. Spaces are
+                        escaped with a backslash, like `cargo\ build`.
+  *'guicursor'* *'gcr'*   Cursor style of each mode, as comma separated
+                        `{modes}:{shape}[-blinkon{ms}]` entries. The modes are
+                        n, v, i and c joined by `-`, and the shape is block,
+                        ver{n} for a bar or hor{n} for an underline. Cursors
+                        only blink with a non-zero blinkon, and unlisted modes
+                        use a steady block (default n-v:block,i-c:ver25). The
+                        terminal cursor is restored on exit.
+  *'scrollpasteof'* *'spe'*
+                        Scroll until the last line is at the top of the
+                        screen, showing |'fillchars'| rows after it
+  *'fillchars'* *'fcs'*   `eob:{char}` marks the gutter rows after the end of
+                        the buffer, `~` by default, nothing when empty
+  *'autoindent'* *'ai'*   Indent the new lines like the previous one
+  *'paste'*             Insert typed text as is, without |'autoindent'|,
+                        auto-wrap or expanded tabs, when pasting in a terminal
+                        without bracketed paste. Shown in the lualine.
+  *'keypreset'* *'kp'*    Keys of the panes: vim, or basic for the shortcuts of
+                        common editors on top of the vim keys, with the files
+                        opening in insert mode, like in the `init.vim` line
+                        `set keypreset=basic`:                 *basic-preset*
+                          CTRL-S CTRL-Q   |:write| / |:quit|
+                          CTRL-Z CTRL-Y   Undo / redo
+                          Shift-arrows    Select from the cursor, Shift-Home
+                                          and Shift-End to the line ends
+                          CTRL-A          Select all
+                          CTRL-C CTRL-X   Copy / cut the selection to the
+                                          clipboard
+                          CTRL-V          Paste the clipboard, over the
+                                          selection
+                        Typed text replaces the selection, and the arrows
+                        leave it. Esc still leaves insert mode for the vim
+                        keys.
+
+Window options, set for all the panes and overridden in a pane with |:setlocal|.
+A split pane keeps the overrides of the pane it was split from:
+  *'number'* *'nu'*       Show the line numbers in the gutter
+  *'relativenumber'* *'rnu'*
+                        Show the line numbers relative to the cursor line,
+                        which shows its own number with 'number'. The line
+                        numbers are hidden without both.
+  *'numberwidth'* *'nuw'* Minimum cells of the line numbers, with the space
+                        after them, from 1 to 20 (default 5)
+  *'foldcolumn'* *'fdc'*  Cells of the fold column on the left of the gutter,
+                        from 0 to 12 (default 0)
+  *'signcolumn'* *'scl'*  Show the sign column between the fold column and
+                        the line numbers: yes, no, or auto to only show it
+                        when the buffer has signs (default auto)
+  *'cursorline'* *'cul'*  Highlight the cursor line
+  *'wrap'*              Wrap the long lines over several rows instead of
+                        truncating them. Buffers with lines of 10000 chars
+                        or more, like minified code, are never wrapped nor
+                        highlighted
+
+Buffer options, set for the focused buffer and for the files without a known
+filetype. Querying them with `:set {option}?` shows where their value comes
+from: global, filetype, detected from the contents, or local:    *filetype*
+  *'filetype'* *'ft'*     Language of the buffer, detected from the file name.
+                        Setting it applies the defaults of the language.
+  *'fileformat'* *'ff'*   Line breaks of the file: unix, or dos when all its
+                        lines end with CR-LF. Line breaks are converted on write.
+  *'tabstop'* *'ts'*      Display cells between two tab stops
+  *'shiftwidth'* *'sw'*   Columns of an indentation level
+  *'expandtab'* *'et'*    Indent with spaces rather than tabs
+  *'commentstring'* *'cms'*
+                        Template of a comment, like `//%s`, used by |gc| and |gq|
+Each language has its own defaults, which can be overridden in the |config|
+with the FileType event: `autocmd FileType python set sw=2`. When a buffer is
+first displayed, 'expandtab' and 'shiftwidth' follow the indentation of its
+lines: tabs or spaces, and the most common indent width.
+
+==============================================================================
+*registers*                                              Registers
+
+Deleted and yanked text goes to the register given with `"{reg}`, and to the
+unnamed register. Yanks without a register also go to `0`.
+  "              Unnamed register, also set by the context menu copy
+  _              Blackhole register: deletes into it keep the other
+                 registers
+  a-z 0-9        Named registers, `A-Z` appends to `a-z`. `0` holds the last
+                 yank
+  + *            Clipboard and primary selection
+  /              Last search pattern
+  :              Last command line
+
+==============================================================================
+*config*                                                 Startup scripts
+
+The `init.vim` script of the config directory ($XDG_CONFIG_HOME/tui-editor or
+~/.config/tui-editor) is executed at startup, then the `.vim` scripts of its
+`plugins` folder. Lines are ex commands, and `"` starts a comment.
+Plugins can also be `.tes` |scripts|.
+
+`tui-editor -R [file]` opens the files read-only, as a pager:        *-R*
+Space and b scroll a page forward and backward, and q quits. Without file,
+the text piped to the standard input is displayed, without its colors and
+overstrikes, as with `export PAGER='tui-editor -R'` or the same `MANPAGER`.
+`tui-editor -` reads the standard input without the pager keys.
+
+`tui-editor -S [session]` restores the panes written by |:mksession|,    *-S*
+from `Session.tui` by default.
+
+`tui-editor --startuptime {file}` writes the times of the   *--startuptime*
+startup phases to a file, in milliseconds since the start: sourcing the
+startup scripts, opening the file, restoring the |-S| session, the first
+render and the listing of the root folder, which runs in the background.
+|:startuptime| shows them.
+
+The `.diff` and `.patch` files, and the staged changes of      *diff*
+|git-commit|, show their added and removed lines in color. |]c| jumps
+between their hunks, and |:Apply| applies the hunk under the cursor.
+
+The JSON, YAML and TOML files are checked on write: a syntax   *data-files*
+error is reported with its position, and counted in the lualine until
+fixed. |:jq| jumps to a key, and |fold| collapses the objects and arrays.
+
+As the editor of git, with `export GIT_EDITOR=tui-editor`, the   *git-commit*
+COMMIT_EDITMSG message is edited next to the staged changes, with guides
+after the 50 columns of the summary and the 72 of the body. `:wq` commits,
+and |:cquit| or an empty message aborts the commit.
+
+Scripts:                                                 *scripts*
+  `.tes` files define functions with `fn name(args) { ... }`, and run their
+  other statements when sourced. Values are ints, strings, bools, arrays
+  and function names, with `let`, `if`/`else`, `while`, `for x in 0..n`,
+  `return`, and `//` comments. The builtins are len, str, int, trim, upper,
+  lower, split, join, contains, replace and push.
+
+  echo(text) error(text)        Display a message
+  execute(cmdline)              Run ex commands
+  buffer_path() line_count()    Focused buffer
+  get_line(n) set_line(n, text) Read or replace line n, counted from 1
+  append_line(n, text)          Insert a line after line n, 0 for the top
+  delete_line(n)                Delete line n
+  cursor() set_cursor(l, c)     Cursor position, as [line, col]
+  map(modes, key, f)            Bind a key to a function, in modes like "nv"
+  command(Name, f)              Define a user command, f gets its arguments
+  autocmd(events, pat, f)       Call f(event, file) on an |:autocmd| event
+  prompt(label, f)              Ask for a text in the cmdline, passed to f
+"#;
+
+/// First line of the description of an ex command, after its usage
+pub fn command_summary(name: &str) -> Option<String> {
+    let tag = format!("*:{}*", name);
+    let mut lines = HELP
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with(&tag));
+    let line = lines.next()?;
+    // Descriptions start after the usage column, on the next line after long usages
+    let summary = match line.get(USAGE_WIDTH..) {
+        Some(text)
+            if line
+                .get(..USAGE_WIDTH)
+                .is_some_and(|usage| usage.ends_with("  "))
+                && !text.starts_with(' ') =>
+        {
+            text
+        }
+        _ => lines.next()?.get(USAGE_WIDTH..)?,
+    };
+    Some(summary.trim().replace('|', ""))
+}
+
+/// Line of the tag matching a topic: an exact tag, a tag starting with the topic,
+/// or else the first line mentioning it
+pub fn find_tag(topic: &str) -> Option<usize> {
+    let exact = format!("*{}*", topic);
+    let prefix = format!("*{}", topic);
+    let lines = HELP.lines().collect::<Vec<_>>();
+    lines
+        .iter()
+        .position(|line| line.contains(&exact))
+        .or_else(|| lines.iter().position(|line| line.contains(&prefix)))
+        .or_else(|| lines.iter().position(|line| line.contains(topic)))
+}
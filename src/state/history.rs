@@ -0,0 +1,59 @@
+use super::Prompt;
+
+/// Maximum number of entries kept per history
+const MAX_ENTRIES: usize = 200;
+
+/// Cmdline histories, one per prompt kind
+#[derive(Debug, Default)]
+pub struct History {
+    pub commands: Vec<String>,
+    pub searches: Vec<String>,
+    /// Script prompt answers, not persisted
+    pub inputs: Vec<String>,
+}
+
+impl History {
+    /// History entries for a prompt kind, from oldest to newest
+    pub fn entries(&self, prompt: Prompt) -> &[String] {
+        match prompt {
+            Prompt::Command => &self.commands,
+            Prompt::Search { .. } => &self.searches,
+            Prompt::Input => &self.inputs,
+        }
+    }
+
+    /// Add an entry as the newest one, removing its older duplicate
+    pub fn push(&mut self, prompt: Prompt, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+        let entries = match prompt {
+            Prompt::Command => &mut self.commands,
+            Prompt::Search { .. } => &mut self.searches,
+            Prompt::Input => &mut self.inputs,
+        };
+        entries.retain(|e| e != entry);
+        entries.push(entry.to_string());
+        if entries.len() > MAX_ENTRIES {
+            entries.remove(0);
+        }
+    }
+
+    /// Find the entry before `index` (or the newest one) that starts with `prefix`
+    pub fn older(&self, prompt: Prompt, index: Option<usize>, prefix: &str) -> Option<usize> {
+        let entries = self.entries(prompt);
+        let end = index.unwrap_or(entries.len());
+        entries[..end].iter().rposition(|e| e.starts_with(prefix))
+    }
+
+    /// Find the entry after `index` that starts with `prefix`
+    pub fn newer(&self, prompt: Prompt, index: usize, prefix: &str) -> Option<usize> {
+        let entries = self.entries(prompt);
+        entries
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, e)| e.starts_with(prefix))
+            .map(|(i, _)| i)
+    }
+}
@@ -0,0 +1,139 @@
+/// Glyph table selected with the `icons` option, depending on the font support
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IconSet {
+    /// Nerd font glyphs and devicons
+    #[default]
+    Nerd,
+    /// Glyphs available in common monospace fonts
+    Unicode,
+    /// Plain ASCII, for the most basic terminals
+    Ascii,
+}
+
+/// Glyphs displayed by the widgets
+#[derive(Debug)]
+pub struct Icons {
+    pub chevron_open: &'static str,
+    pub chevron_closed: &'static str,
+    pub folder_open: &'static str,
+    pub folder_closed: &'static str,
+    /// File icon, when devicons are not used
+    pub file: &'static str,
+    /// Whether files display the devicon of their file type
+    pub devicons: bool,
+    /// Lualine separator at the right of a block
+    pub separator_right: &'static str,
+    /// Lualine separator at the left of a block
+    pub separator_left: &'static str,
+    /// Separator between items of a lualine block
+    pub separator_thin: &'static str,
+    /// Lualine file position
+    pub position: &'static str,
+    /// Lualine git branch, followed by its commits ahead of and behind its upstream
+    pub branch: &'static str,
+    pub ahead: &'static str,
+    pub behind: &'static str,
+    /// Lualine diagnostic counts, by severity
+    pub error: &'static str,
+    pub warning: &'static str,
+    pub info: &'static str,
+    /// Cmdline prompt for ex commands
+    pub cmdline: &'static str,
+    /// Filetree marker of the files with unsaved changes
+    pub modified: &'static str,
+    /// Filetree separator between a symlink and its target
+    pub link: &'static str,
+}
+
+const NERD: Icons = Icons {
+    chevron_open: "\u{f47c} ",
+    chevron_closed: "\u{f460} ",
+    folder_open: "\u{e5fe} ",
+    folder_closed: "\u{e5ff} ",
+    file: "",
+    devicons: true,
+    separator_right: "\u{e0b4}",
+    separator_left: "\u{e0b6}",
+    separator_thin: "\u{e0b3}",
+    position: "\u{e64e}",
+    branch: "\u{e0a0} ",
+    ahead: "\u{f062}",
+    behind: "\u{f063}",
+    error: "\u{f057} ",
+    warning: "\u{f071} ",
+    info: "\u{f05a} ",
+    cmdline: " \u{f054} ",
+    modified: " \u{f111}",
+    link: " \u{f0c1} ",
+};
+
+const UNICODE: Icons = Icons {
+    chevron_open: "▾ ",
+    chevron_closed: "▸ ",
+    folder_open: "▪ ",
+    folder_closed: "▪ ",
+    file: "· ",
+    devicons: false,
+    separator_right: "▌",
+    separator_left: "▐",
+    separator_thin: "│",
+    position: "☰",
+    branch: "⎇ ",
+    ahead: "↑",
+    behind: "↓",
+    error: "✖ ",
+    warning: "⚠ ",
+    info: "ℹ ",
+    cmdline: " ❯ ",
+    modified: " ●",
+    link: " → ",
+};
+
+const ASCII: Icons = Icons {
+    chevron_open: "- ",
+    chevron_closed: "+ ",
+    folder_open: "",
+    folder_closed: "",
+    file: "",
+    devicons: false,
+    separator_right: "",
+    separator_left: "",
+    separator_thin: "|",
+    position: "",
+    branch: "",
+    ahead: "+",
+    behind: "-",
+    error: "E",
+    warning: "W",
+    info: "I",
+    cmdline: " : ",
+    modified: " [+]",
+    link: " -> ",
+};
+
+impl IconSet {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "nerd" => Some(Self::Nerd),
+            "unicode" => Some(Self::Unicode),
+            "ascii" => Some(Self::Ascii),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Nerd => "nerd",
+            Self::Unicode => "unicode",
+            Self::Ascii => "ascii",
+        }
+    }
+
+    pub fn icons(self) -> &'static Icons {
+        match self {
+            Self::Nerd => &NERD,
+            Self::Unicode => &UNICODE,
+            Self::Ascii => &ASCII,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use tokio::task::AbortHandle;
+
+/// Progress and cancellation flag shared between a job and the registry
+#[derive(Debug, Default)]
+struct Progress {
+    cancelled: AtomicBool,
+    /// Items processed so far, like searched files
+    done: AtomicUsize,
+}
+
+/// Access of a background job to its entry in the registry, to report its progress
+/// and notice its cancellation
+#[derive(Debug, Clone, Default)]
+pub struct JobHandle {
+    progress: Arc<Progress>,
+}
+
+impl JobHandle {
+    /// Whether the job was killed and should stop as soon as possible
+    pub fn cancelled(&self) -> bool {
+        self.progress.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Count one more processed item
+    pub fn advance(&self) {
+        self.progress.done.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Background task tracked by the registry
+#[derive(Debug)]
+struct Job {
+    name: String,
+    started: Instant,
+    handle: JobHandle,
+    task: AbortHandle,
+}
+
+/// Registry of the background tasks, which can be listed with `:jobs` and killed.
+/// Clones share the same registry.
+#[derive(Debug, Clone, Default)]
+pub struct Jobs {
+    jobs: Arc<Mutex<BTreeMap<usize, Job>>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl Jobs {
+    /// Run a future in the background
+    pub fn spawn<F>(&self, name: impl Into<String>, job: impl FnOnce(JobHandle) -> F) -> usize
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = JobHandle::default();
+        let task = tokio::spawn(job(handle.clone())).abort_handle();
+        self.insert(name.into(), handle, task)
+    }
+
+    /// Run a blocking function on a thread of the blocking pool.
+    /// It can only be stopped by checking `JobHandle::cancelled`.
+    pub fn spawn_blocking(
+        &self,
+        name: impl Into<String>,
+        job: impl FnOnce(JobHandle) + Send + 'static,
+    ) -> usize {
+        let handle = JobHandle::default();
+        let cloned = handle.clone();
+        let task = tokio::task::spawn_blocking(move || job(cloned)).abort_handle();
+        self.insert(name.into(), handle, task)
+    }
+
+    fn insert(&self, name: String, handle: JobHandle, task: AbortHandle) -> usize {
+        let id = self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = Job {
+            name,
+            started: Instant::now(),
+            handle,
+            task,
+        };
+        let mut jobs = self.jobs.lock().unwrap_or_else(|err| err.into_inner());
+        jobs.retain(|_, job| !job.task.is_finished());
+        jobs.insert(id, job);
+        id
+    }
+
+    /// Cancel a running job. Futures are dropped at their next await point, and
+    /// blocking jobs stop when they notice the cancellation.
+    pub fn kill(&self, id: usize) -> Result<(), String> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|err| err.into_inner());
+        let job = jobs
+            .remove(&id)
+            .filter(|job| !job.task.is_finished())
+            .ok_or_else(|| format!("E900: Invalid job id: {}", id))?;
+        job.handle.progress.cancelled.store(true, Ordering::Relaxed);
+        job.task.abort();
+        Ok(())
+    }
+
+    /// Table of the running jobs, with their id, duration and progress
+    pub fn listing(&self) -> String {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|err| err.into_inner());
+        jobs.retain(|_, job| !job.task.is_finished());
+        let mut lines = vec![format!("{:>4}  {:>8}  {:>8}  Job", "Id", "Time", "Done")];
+        for (id, job) in jobs.iter() {
+            let time = format!("{:.1}s", job.started.elapsed().as_secs_f64());
+            let done = job.handle.progress.done.load(Ordering::Relaxed);
+            lines.push(format!("{:>4}  {:>8}  {:>8}  {}", id, time, done, job.name));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,60 @@
+use super::{FileId, Transaction};
+
+/// Maximum number of positions kept in the jump list, like vim
+const MAX_JUMPS: usize = 100;
+
+/// Position jumped from, as a char index moved along the edits of its buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jump {
+    pub file: FileId,
+    pub index: usize,
+}
+
+/// Positions before the large jumps, like `G`, searches and opening files, gone back to
+/// with `CTRL-O` and forward again with `CTRL-I`
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// Entry of the last `CTRL-O` or `CTRL-I`, the length when none was used since the
+    /// last jump
+    index: usize,
+}
+
+impl JumpList {
+    /// Record the position before a jump as the newest one, removing its older duplicate
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps.retain(|j| *j != jump);
+        self.jumps.push(jump);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// Go back to the previous position. The position jumped from is recorded first when
+    /// at the newest one, to come back to it with `CTRL-I`.
+    pub fn back(&mut self, from: Jump) -> Option<Jump> {
+        if self.index == self.jumps.len() {
+            self.push(from);
+            self.index = self.jumps.len() - 1;
+        }
+        self.index = self.index.checked_sub(1)?;
+        Some(self.jumps[self.index])
+    }
+
+    /// Go forward to the position gone back from
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.jumps[self.index])
+    }
+
+    /// Move the positions in a buffer along its changes
+    pub fn follow(&mut self, file: FileId, transaction: &Transaction) {
+        for jump in self.jumps.iter_mut().filter(|jump| jump.file == file) {
+            jump.index = transaction.map(jump.index);
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use super::Mode;
+
+/// Key bound to a command chain with `:map` and its variants
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    pub modes: Vec<Mode>,
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    /// Key as written in the mapping, for listing
+    pub lhs: String,
+    /// Command line executed when the key is pressed
+    pub command: String,
+    /// Script and line that defined the mapping, none when typed in the cmdline
+    pub source: Option<String>,
+}
+
+/// Builtin keybindings, as (modes, key, description), listed along the user mappings
+const DEFAULTS: &[(&[Mode], &str, &str)] = &[
+    (&[Mode::Normal, Mode::Visual], "h", "Move left"),
+    (&[Mode::Normal, Mode::Visual], "j", "Move down"),
+    (&[Mode::Normal, Mode::Visual], "k", "Move up"),
+    (&[Mode::Normal, Mode::Visual], "l", "Move right"),
+    (&[Mode::Normal, Mode::Visual], "0", "Move to the line start"),
+    (&[Mode::Normal, Mode::Visual], "$", "Move to the line end"),
+    (&[Mode::Normal, Mode::Visual], "gg", "Go to the first line"),
+    (&[Mode::Normal, Mode::Visual], "G", "Go to the last line"),
+    (&[Mode::Normal, Mode::Visual], "{count}G", "Go to a line"),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "<C-d>",
+        "Scroll down half a page",
+    ),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "<C-u>",
+        "Scroll up half a page",
+    ),
+    (&[Mode::Normal, Mode::Visual], "<C-f>", "Scroll down a page"),
+    (&[Mode::Normal, Mode::Visual], "<C-b>", "Scroll up a page"),
+    (&[Mode::Normal, Mode::Visual], "w", "Move to the next word"),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "b",
+        "Move back to the word start",
+    ),
+    (&[Mode::Normal, Mode::Visual], "e", "Move to the word end"),
+    (&[Mode::Normal, Mode::Visual], "W", "Move to the next WORD"),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "B",
+        "Move back to the WORD start",
+    ),
+    (&[Mode::Normal, Mode::Visual], "E", "Move to the WORD end"),
+    (&[Mode::Normal], "i", "Insert before the cursor"),
+    (&[Mode::Normal], "a", "Insert after the cursor"),
+    (&[Mode::Normal], "I", "Insert at the line start"),
+    (&[Mode::Normal], "A", "Insert at the line end"),
+    (&[Mode::Normal], "x", "Delete the char under the cursor"),
+    (&[Mode::Normal], "d{motion}", "Delete the text of a motion"),
+    (&[Mode::Normal], "c{motion}", "Change the text of a motion"),
+    (&[Mode::Normal], "y{motion}", "Yank the text of a motion"),
+    (&[Mode::Normal], "dd", "Delete the line"),
+    (&[Mode::Normal], "cc", "Change the line"),
+    (&[Mode::Normal], "yy", "Yank the line"),
+    (&[Mode::Normal], "p", "Put a register after the cursor"),
+    (&[Mode::Normal], "P", "Put a register before the cursor"),
+    (&[Mode::Normal], ">>", "Indent the line"),
+    (&[Mode::Normal], "<<", "Unindent the line"),
+    (&[Mode::Normal], "gcc", "Toggle the line comment"),
+    (&[Mode::Visual], ">", "Indent the selected lines"),
+    (&[Mode::Visual], "<", "Unindent the selected lines"),
+    (
+        &[Mode::Visual],
+        "gc",
+        "Toggle the comment of the selected lines",
+    ),
+    (&[Mode::Visual], "d", "Delete the selection"),
+    (&[Mode::Visual], "c", "Change the selection"),
+    (&[Mode::Visual], "y", "Yank the selection"),
+    (&[Mode::Visual], "I", "Insert before the selected block"),
+    (&[Mode::Visual], "A", "Append after the selected block"),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "\"{reg}",
+        "Use a register for the next delete, yank or put",
+    ),
+    (&[Mode::Normal], "u", "Undo the last change"),
+    (&[Mode::Normal], "<C-r>", "Redo the last undone change"),
+    (&[Mode::Normal], "g-", "Go to the previous text state"),
+    (&[Mode::Normal], "g+", "Go to the next text state"),
+    (&[Mode::Normal], ".", "Repeat the last change"),
+    (&[Mode::Normal], "v", "Enter visual mode"),
+    (&[Mode::Normal], "V", "Enter linewise visual mode"),
+    (&[Mode::Normal], "<C-v>", "Enter blockwise visual mode"),
+    (&[Mode::Normal, Mode::Visual], ":", "Open the cmdline"),
+    (&[Mode::Normal], "/", "Search forward"),
+    (&[Mode::Normal], "?", "Search backward"),
+    (&[Mode::Normal], "n", "Repeat the search forward"),
+    (&[Mode::Normal], "N", "Repeat the search backward"),
+    (&[Mode::Normal], "]c", "Jump to the next hunk of a diff"),
+    (&[Mode::Normal], "[c", "Jump to the previous hunk of a diff"),
+    (&[Mode::Normal], "m{a-z}", "Set a mark"),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "'{a-z}",
+        "Jump to the line of a mark",
+    ),
+    (&[Mode::Normal, Mode::Visual], "`{a-z}", "Jump to a mark"),
+    (&[Mode::Normal], "<C-o>", "Go back in the jump list"),
+    (&[Mode::Normal], "<C-i>", "Go forward in the jump list"),
+    (&[Mode::Normal], "zc", "Close the fold under the cursor"),
+    (&[Mode::Normal], "zo", "Open the fold under the cursor"),
+    (&[Mode::Normal], "za", "Toggle the fold under the cursor"),
+    (&[Mode::Normal], "zM", "Close all the folds"),
+    (&[Mode::Normal], "zR", "Open all the folds"),
+    (&[Mode::Normal], "q:", "Open the command history window"),
+    (&[Mode::Normal], "q/", "Open the search history window"),
+    (&[Mode::Normal], "q{reg}", "Record a macro into a register"),
+    (&[Mode::Normal], "@{reg}", "Replay the macro of a register"),
+    (&[Mode::Normal], "gqq", "Format the line"),
+    (&[Mode::Normal], "gqip", "Format the paragraph"),
+    (&[Mode::Visual], "gq", "Format the selected lines"),
+    (&[Mode::Visual], "gs", "Sort the selected lines"),
+    (&[Mode::Visual], "gr", "Reverse the selected lines"),
+    (&[Mode::Visual], "gu", "Remove the duplicate selected lines"),
+    (
+        &[Mode::Visual],
+        "ga",
+        "Align the selected lines on a delimiter",
+    ),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "<C-a>",
+        "Add 1 to the number",
+    ),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "<C-x>",
+        "Subtract 1 from the number",
+    ),
+    (
+        &[Mode::Visual],
+        "g<C-a>",
+        "Add an increasing sequence to the numbers",
+    ),
+    (
+        &[Mode::Visual],
+        "g<C-x>",
+        "Subtract an increasing sequence from the numbers",
+    ),
+    (
+        &[Mode::Visual],
+        "g#",
+        "Insert numbers from 1 down the block",
+    ),
+    (
+        &[Mode::Visual],
+        "p",
+        "Replace the selection with a register",
+    ),
+    (
+        &[Mode::Visual],
+        "P",
+        "Replace the selection, keeping the register",
+    ),
+    (
+        &[Mode::Normal, Mode::Visual],
+        "g<C-g>",
+        "Count the lines, words, chars and bytes",
+    ),
+    (
+        &[Mode::Normal],
+        "ga",
+        "Show the value of the char under the cursor",
+    ),
+    (&[Mode::Normal], "<C-]>", "Follow the link under the cursor"),
+    (&[Mode::Normal], "<C-h>", "Focus the filetree"),
+    (&[Mode::Normal], "<C-l>", "Focus the panes"),
+    (&[Mode::Normal], "<C-p>", "Open the command palette"),
+    (
+        &[Mode::Normal, Mode::Visual, Mode::Insert],
+        "<F2>",
+        "Toggle the mouse capture",
+    ),
+    (
+        &[Mode::Visual, Mode::Insert],
+        "<Esc>",
+        "Back to normal mode",
+    ),
+    (
+        &[Mode::Insert],
+        "<Tab>",
+        "Indent to the next shiftwidth column",
+    ),
+    (&[Mode::Insert], "<C-r>{reg}", "Insert a register"),
+    (&[Mode::Insert], "<C-v>{key}", "Insert a key literally"),
+    (&[Mode::Insert], "<C-g>u", "Start a new undoable change"),
+    (&[Mode::Insert], "<C-v>u{hex}", "Insert a unicode codepoint"),
+    (&[Mode::Insert], "<C-k>{char}{char}", "Insert a digraph"),
+    (&[Mode::Insert], "<C-x><C-f>", "Complete a file path"),
+];
+
+/// User defined key mappings
+#[derive(Debug, Default)]
+pub struct Keymaps {
+    pub maps: Vec<Keymap>,
+    /// Location of the script line being sourced, recorded in the new mappings
+    pub source: Option<String>,
+}
+
+impl Keymaps {
+    /// Define a mapping from the `:map` arguments (`{key} :{command}<CR>`)
+    pub fn define(&mut self, modes: &[Mode], args: &str) -> Result<(), String> {
+        let Some((lhs, rhs)) = args.split_once(char::is_whitespace) else {
+            return Err("E471: Argument required".to_string());
+        };
+        let (code, modifiers) =
+            parse_key(lhs).ok_or_else(|| format!("E474: Invalid argument: {}", lhs))?;
+
+        // Only command chains are supported as mapping targets
+        let rhs = rhs.trim();
+        let command = rhs
+            .strip_prefix(':')
+            .and_then(|rhs| rhs.strip_suffix("<CR>").or(rhs.strip_suffix("<cr>")))
+            .ok_or_else(|| format!("E474: Mappings must run a command (:cmd<CR>): {}", rhs))?;
+
+        self.maps
+            .retain(|m| !(m.code == code && m.modifiers == modifiers && m.modes == modes));
+        self.maps.push(Keymap {
+            modes: modes.to_vec(),
+            code,
+            modifiers,
+            lhs: lhs.to_string(),
+            command: command.to_string(),
+            source: self.source.clone(),
+        });
+        Ok(())
+    }
+
+    /// Table of the mappings and builtin keybindings of the modes whose key starts with `prefix`.
+    /// User mappings come first and hide the builtins they override.
+    pub fn listing(&self, modes: &[Mode], prefix: &str) -> String {
+        let mut lines = vec![];
+        for mode in modes {
+            let mut keys: Vec<(KeyCode, KeyModifiers)> = vec![];
+            let mut entries = vec![];
+            for map in self.maps.iter().rev().filter(|m| m.modes.contains(mode)) {
+                if keys.contains(&(map.code, map.modifiers)) {
+                    continue;
+                }
+                keys.push((map.code, map.modifiers));
+                let source = map.source.as_deref().unwrap_or("cmdline");
+                let rhs = format!(":{}<CR>", map.command);
+                entries.push((map.lhs.clone(), rhs, source.to_string()));
+            }
+            for (default_modes, lhs, description) in DEFAULTS {
+                let overridden = parse_key(lhs).is_some_and(|key| keys.contains(&key));
+                if default_modes.contains(mode) && !overridden {
+                    entries.push((lhs.to_string(), description.to_string(), "default".into()));
+                }
+            }
+            entries.retain(|(lhs, _, _)| lhs.starts_with(prefix));
+            if entries.is_empty() {
+                continue;
+            }
+
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push(format!("{:?} mode:", mode));
+            for (lhs, rhs, source) in entries {
+                lines.push(format!("  {:<10} {:<36} {}", lhs, rhs, source));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Builtin keybindings of a mode, with their description
+    pub fn defaults(mode: Mode) -> impl Iterator<Item = (&'static str, &'static str)> {
+        DEFAULTS
+            .iter()
+            .filter(move |(modes, _, _)| modes.contains(&mode))
+            .map(|(_, lhs, description)| (*lhs, *description))
+    }
+
+    /// Command bound to a key in the given mode
+    pub fn get(&self, mode: Mode, key_event: KeyEvent) -> Option<&str> {
+        self.maps
+            .iter()
+            .rev()
+            .find(|m| {
+                m.modes.contains(&mode)
+                    && m.code == key_event.code
+                    && m.modifiers == normalize(key_event.code, key_event.modifiers)
+            })
+            .map(|m| m.command.as_str())
+    }
+}
+
+/// Shift is already part of typed chars
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> KeyModifiers {
+    match code {
+        KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    }
+}
+
+/// Keys written in vim notation, like the `dd<Esc>` of a recorded macro. A line break is
+/// read as `<CR>`, and a `<` that does not start a key as itself.
+pub fn parse_keys(text: &str) -> Vec<KeyEvent> {
+    let mut keys = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let notation = match c {
+            '<' => rest.find('>').and_then(|end| {
+                let (code, modifiers) = parse_key(&rest[..=end])?;
+                Some((code, modifiers, end + 1))
+            }),
+            _ => None,
+        };
+        let (code, modifiers, len) = notation.unwrap_or(match c {
+            '\n' => (KeyCode::Enter, KeyModifiers::NONE, 1),
+            c => (KeyCode::Char(c), KeyModifiers::NONE, c.len_utf8()),
+        });
+        keys.push(KeyEvent::new(code, modifiers));
+        rest = &rest[len..];
+    }
+    keys
+}
+
+/// Write a key in vim notation, as parsed by `parse_key`
+pub fn key_notation(key_event: KeyEvent) -> String {
+    let modifiers = normalize(key_event.code, key_event.modifiers);
+    let name = match key_event.code {
+        KeyCode::Char('<') => "lt".to_string(),
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) if modifiers.is_empty() => return c.to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "CR".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::BackTab => return "<S-Tab>".to_string(),
+        KeyCode::Backspace => "BS".to_string(),
+        KeyCode::Delete => "Del".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => return String::new(),
+    };
+    let mut prefix = String::new();
+    for (modifier, letter) in [
+        (KeyModifiers::CONTROL, "C-"),
+        (KeyModifiers::ALT, "A-"),
+        (KeyModifiers::SHIFT, "S-"),
+    ] {
+        if modifiers.contains(modifier) {
+            prefix.push_str(letter);
+        }
+    }
+    format!("<{}{}>", prefix, name)
+}
+
+/// Parse a key in vim notation: `x`, `<C-s>`, `<A-x>`, `<F5>`, `<CR>`, `<S-Tab>`...
+pub fn parse_key(notation: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut chars = notation.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some((KeyCode::Char(c), KeyModifiers::NONE));
+    }
+
+    let inner = notation.strip_prefix('<')?.strip_suffix('>')?;
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = inner;
+    while let Some((modifier, rest)) = name.split_once('-')
+        && !rest.is_empty()
+    {
+        modifiers |= match modifier.to_ascii_uppercase().as_str() {
+            "C" => KeyModifiers::CONTROL,
+            "A" | "M" => KeyModifiers::ALT,
+            "S" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        name = rest;
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        // Ctrl chords are reported lowercase by terminals
+        (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+            KeyCode::Char(c.to_ascii_lowercase())
+        }
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_ascii_lowercase().as_str() {
+            "cr" | "enter" | "return" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "bs" => KeyCode::Backspace,
+            "del" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+        },
+    };
+    Some((code, normalize(code, modifiers)))
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+
+/// Text registers, named by a char like vim registers
+#[derive(Debug, Default)]
+pub struct Registers {
+    values: BTreeMap<char, String>,
+}
+
+/// Whether a char names a register: the unnamed `"`, the blackhole `_`, `a-z` (`A-Z` to append),
+/// `0-9`, the selections `+` `*`, and the read-only `/` `:`
+pub fn is_register(name: char) -> bool {
+    name.is_ascii_alphanumeric() || "\"_+*/:".contains(name)
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Option<&str> {
+        self.values.get(&name).map(String::as_str)
+    }
+
+    /// Set a register, or append to it when named by an uppercase letter
+    pub fn set(&mut self, name: char, text: String) {
+        match name.is_ascii_uppercase() {
+            true => {
+                let value = self.values.entry(name.to_ascii_lowercase()).or_default();
+                value.push_str(&text);
+            }
+            false => {
+                self.values.insert(name, text);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use tokio::sync::mpsc::Sender;
+
+use super::{EditorEvent, FileId, Jobs};
+
+/// Counter making the names of the local transfer files unique
+static TRANSFERS: AtomicUsize = AtomicUsize::new(0);
+
+/// File on another host, read and written over SFTP with the `sftp` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+    /// Host, as `[user@]host`
+    pub host: String,
+    pub port: Option<u16>,
+    /// Path on the host, relative to the home folder unless absolute
+    pub path: String,
+}
+
+impl Remote {
+    /// Parse a `ssh://[user@]host[:port]/path` or `sftp://` URI.
+    /// Paths starting with `/~/` are relative to the home folder.
+    pub fn parse(uri: &str) -> Option<Self> {
+        let rest = uri
+            .strip_prefix("ssh://")
+            .or_else(|| uri.strip_prefix("sftp://"))?;
+        let (authority, path) = rest.split_once('/')?;
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().ok()?)),
+            None => (authority, None),
+        };
+        if host.is_empty() || path.is_empty() {
+            return None;
+        }
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => relative.to_string(),
+            None => format!("/{}", path),
+        };
+        Some(Self {
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// URI of the file, used as the path of its buffer
+    pub fn uri(&self) -> String {
+        let port = self
+            .port
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        let path = match self.path.strip_prefix('/') {
+            Some(absolute) => absolute.to_string(),
+            None => format!("~/{}", self.path),
+        };
+        format!("ssh://{}{}/{}", self.host, port, path)
+    }
+
+    /// Run a batch of sftp commands on the host
+    fn sftp(&self, batch: &str) -> Result<(), String> {
+        let mut sftp = Command::new("sftp");
+        // Batch mode never prompts for passwords, which would garble the screen
+        sftp.args(["-q", "-b", "-", "-o", "ConnectTimeout=10"]);
+        if let Some(port) = self.port {
+            sftp.arg("-P").arg(port.to_string());
+        }
+        let mut child = sftp
+            .arg(&self.host)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Failed to run sftp: {}", err))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(batch.as_bytes())
+                .map_err(|err| err.to_string())?;
+        }
+        let output = child.wait_with_output().map_err(|err| err.to_string())?;
+        match output.status.success() {
+            true => Ok(()),
+            false => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let reason = stderr.lines().find(|line| !line.trim().is_empty());
+                Err(reason.unwrap_or("sftp failed").trim().to_string())
+            }
+        }
+    }
+}
+
+/// Download a remote file in the background, then send its contents.
+/// A missing file is read as empty, to be created on write.
+pub fn fetch(remote: Remote, sender: Sender<EditorEvent>, jobs: &Jobs, file: FileId) {
+    jobs.spawn_blocking(format!("sftp get {}", remote.uri()), move |_| {
+        let local = transfer_path();
+        let batch = format!("get {} {}\n", quote(&remote.path), quote_path(&local));
+        let text = match remote.sftp(&batch) {
+            Ok(()) => fs::read_to_string(&local).map_err(|err| err.to_string()),
+            Err(err) if err.contains("not found") => Ok(String::new()),
+            Err(err) => Err(err),
+        };
+        let _ = fs::remove_file(&local);
+        if let Err(err) = sender.blocking_send(EditorEvent::RemoteLoaded { file, text }) {
+            log::error!("Failed to send remote loaded event: {}", err);
+        }
+    });
+}
+
+/// Upload the text of a buffer to its remote file in the background, then send the result
+pub fn upload(
+    remote: Remote,
+    sender: Sender<EditorEvent>,
+    jobs: &Jobs,
+    file: FileId,
+    text: String,
+) {
+    jobs.spawn_blocking(format!("sftp put {}", remote.uri()), move |_| {
+        let local = transfer_path();
+        let batch = format!("put {} {}\n", quote_path(&local), quote(&remote.path));
+        let result = fs::write(&local, &text)
+            .map_err(|err| err.to_string())
+            .and_then(|()| remote.sftp(&batch));
+        let _ = fs::remove_file(&local);
+        let event = EditorEvent::RemoteWritten { file, text, result };
+        if let Err(err) = sender.blocking_send(event) {
+            log::error!("Failed to send remote written event: {}", err);
+        }
+    });
+}
+
+/// Unique local file for a transfer
+fn transfer_path() -> PathBuf {
+    let count = TRANSFERS.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("tui-editor-{}-{}", std::process::id(), count))
+}
+
+/// Quote a path for an sftp batch
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
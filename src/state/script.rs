@@ -0,0 +1,143 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use eval::Interpreter;
+use parser::{Expr, Function, Stmt, StmtKind};
+
+mod eval;
+mod lexer;
+mod parser;
+
+/// Value of a script variable
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Value>),
+    /// Script function, passed by name as a callback
+    Fn(String),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Unit => "()",
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Str(_) => "string",
+            Self::Array(_) => "array",
+            Self::Fn(_) => "function",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "()"),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Int(value) => write!(f, "{}", value),
+            Self::Str(text) => write!(f, "{}", text),
+            Self::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match item {
+                        Self::Str(text) => write!(f, "{:?}", text)?,
+                        item => write!(f, "{}", item)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Self::Fn(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Script error, located by script name and line
+#[derive(Debug)]
+pub struct Error {
+    source: Rc<str>,
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.source, self.line, self.message)
+    }
+}
+
+/// Editor functions available to the scripts
+pub trait Host {
+    /// Call an editor function. Returns `None` if there is no function with this name.
+    fn call(&mut self, name: &str, args: &[Value]) -> Option<Result<Value, String>>;
+}
+
+/// Functions defined by the loaded scripts, shared by all the editor callbacks
+#[derive(Debug, Default)]
+pub struct Scripts {
+    functions: Rc<HashMap<String, Rc<Function>>>,
+    /// Label and callback of the cmdline opened with `prompt()`
+    pub prompt: Option<(String, String)>,
+}
+
+impl Scripts {
+    /// Parse a script and define its functions, replacing the existing ones.
+    /// Returns its top level statements, to be run by the caller.
+    pub fn load(&mut self, name: &str, text: &str) -> Result<Script, String> {
+        let source: Rc<str> = Rc::from(name);
+        let tokens = lexer::tokenize(text, &source).map_err(|err| err.to_string())?;
+        let program = parser::parse(tokens, &source).map_err(|err| err.to_string())?;
+        if !program.functions.is_empty() {
+            let functions = Rc::make_mut(&mut self.functions);
+            for (name, function) in program.functions {
+                functions.insert(name, Rc::new(function));
+            }
+        }
+        Ok(Script {
+            source,
+            body: program.body,
+            functions: self.functions.clone(),
+        })
+    }
+
+    /// Whether a script defines a function with this name
+    pub fn defines(&self, function: &str) -> bool {
+        self.functions.contains_key(function)
+    }
+
+    /// Call of a function with literal arguments, such as an editor callback
+    pub fn call(&self, function: &str, args: Vec<Value>) -> Script {
+        let call = Expr::Call(
+            function.to_string(),
+            args.into_iter().map(Expr::Literal).collect(),
+        );
+        Script {
+            source: Rc::from(function),
+            body: vec![Stmt {
+                kind: StmtKind::Expr(call),
+                line: 1,
+            }],
+            functions: self.functions.clone(),
+        }
+    }
+}
+
+/// Statements ready to run, with the functions defined when they were loaded
+pub struct Script {
+    source: Rc<str>,
+    body: Vec<Stmt>,
+    functions: Rc<HashMap<String, Rc<Function>>>,
+}
+
+impl Script {
+    pub fn run(&self, host: &mut dyn Host) -> Result<Value, String> {
+        Interpreter::new(&self.functions, host, self.source.clone())
+            .run(&self.body)
+            .map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,443 @@
+use std::{collections::HashMap, mem, rc::Rc};
+
+use super::{
+    Error, Host, Value,
+    parser::{Expr, Function, Stmt, StmtKind},
+};
+
+/// Maximum number of statements and loop iterations of a script run,
+/// so that an endless loop does not freeze the editor
+const MAX_STEPS: usize = 1_000_000;
+/// Maximum nesting of function calls
+const MAX_DEPTH: usize = 64;
+
+/// How the execution goes on after a statement
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Value),
+}
+
+/// Tree walking interpreter, running the statements of a script against the editor
+pub struct Interpreter<'a> {
+    functions: &'a HashMap<String, Rc<Function>>,
+    host: &'a mut dyn Host,
+    /// Variables of the running function, by nested block
+    scopes: Vec<HashMap<String, Value>>,
+    steps: usize,
+    depth: usize,
+    /// Script and line of the running statement, for the error messages
+    source: Rc<str>,
+    line: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        functions: &'a HashMap<String, Rc<Function>>,
+        host: &'a mut dyn Host,
+        source: Rc<str>,
+    ) -> Self {
+        Self {
+            functions,
+            host,
+            scopes: vec![HashMap::new()],
+            steps: 0,
+            depth: 0,
+            source,
+            line: 1,
+        }
+    }
+
+    /// Run top level statements
+    pub fn run(&mut self, body: &[Stmt]) -> Result<Value, Error> {
+        let flow = self.statements(body)?;
+        self.returned(flow)
+    }
+
+    /// Call a script, builtin or editor function
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Error> {
+        if let Some(function) = self.functions.get(name) {
+            return self.call_script(name, function.clone(), args);
+        }
+        if let Some(result) = builtin(name, &args) {
+            return result.map_err(|message| self.error(&message));
+        }
+        match self.host.call(name, &args) {
+            Some(result) => result.map_err(|message| self.error(&message)),
+            None => Err(self.error(&format!("Unknown function: {}", name))),
+        }
+    }
+
+    fn call_script(
+        &mut self,
+        name: &str,
+        function: Rc<Function>,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        if args.len() != function.params.len() {
+            let text = format!(
+                "{} expects {} arguments, got {}",
+                name,
+                function.params.len(),
+                args.len()
+            );
+            return Err(self.error(&text));
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("Function calls nested too deeply"));
+        }
+        let scope = function.params.iter().cloned().zip(args).collect();
+        let scopes = mem::replace(&mut self.scopes, vec![scope]);
+        let source = mem::replace(&mut self.source, function.source.clone());
+        let line = self.line;
+        self.depth += 1;
+        let result = self.statements(&function.body);
+        self.depth -= 1;
+        self.scopes = scopes;
+        self.source = source;
+        self.line = line;
+        self.returned(result?)
+    }
+
+    /// Value returned by a function body
+    fn returned(&self, flow: Flow) -> Result<Value, Error> {
+        match flow {
+            Flow::Return(value) => Ok(value),
+            Flow::Next => Ok(Value::Unit),
+            Flow::Break | Flow::Continue => Err(self.error("break or continue outside of a loop")),
+        }
+    }
+
+    /// Execute statements in a new scope
+    fn block(&mut self, stmts: &[Stmt]) -> Result<Flow, Error> {
+        self.scopes.push(HashMap::new());
+        let flow = self.statements(stmts);
+        self.scopes.pop();
+        flow
+    }
+
+    fn statements(&mut self, stmts: &[Stmt]) -> Result<Flow, Error> {
+        for stmt in stmts {
+            match self.exec(stmt)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<Flow, Error> {
+        self.step(stmt.line)?;
+        match &stmt.kind {
+            StmtKind::Let(name, expr) => {
+                let value = self.eval(expr)?;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), value);
+                }
+            }
+            StmtKind::Assign(name, op, expr) => {
+                let mut value = self.eval(expr)?;
+                if let Some(op) = op {
+                    let current = self.eval(&Expr::Var(name.clone()))?;
+                    value = self.binary(op, current, value)?;
+                }
+                match self.scopes.iter_mut().rev().find_map(|s| s.get_mut(name)) {
+                    Some(variable) => *variable = value,
+                    None => return Err(self.error(&format!("Unknown variable: {}", name))),
+                }
+            }
+            StmtKind::If(branches, otherwise) => {
+                for (condition, body) in branches {
+                    if self.condition(condition)? {
+                        return self.block(body);
+                    }
+                }
+                if let Some(body) = otherwise {
+                    return self.block(body);
+                }
+            }
+            StmtKind::While(condition, body) => {
+                while self.condition(condition)? {
+                    match self.block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => self.step(stmt.line)?,
+                    }
+                }
+            }
+            StmtKind::For(name, iterable, body) => {
+                let items: Box<dyn Iterator<Item = Value>> = match iterable {
+                    Expr::Range(start, end) => {
+                        let start = self.int(start)?;
+                        let end = self.int(end)?;
+                        Box::new((start..end).map(Value::Int))
+                    }
+                    expr => match self.eval(expr)? {
+                        Value::Array(items) => Box::new(items.into_iter()),
+                        Value::Str(text) => Box::new(
+                            text.chars()
+                                .map(|c| Value::Str(c.to_string()))
+                                .collect::<Vec<_>>()
+                                .into_iter(),
+                        ),
+                        value => {
+                            let text = format!("Cannot iterate over {}", value.type_name());
+                            return Err(self.error(&text));
+                        }
+                    },
+                };
+                for item in items {
+                    self.scopes.push(HashMap::from([(name.clone(), item)]));
+                    let flow = self.statements(body);
+                    self.scopes.pop();
+                    match flow? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => self.step(stmt.line)?,
+                    }
+                }
+            }
+            StmtKind::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.eval(expr)?,
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::Expr(expr) => {
+                self.eval(expr)?;
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value, Error> {
+        match expr {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Var(name) => {
+                if let Some(value) = self.scopes.iter().rev().find_map(|s| s.get(name)) {
+                    return Ok(value.clone());
+                }
+                // Functions are passed by name, as callbacks
+                match self.functions.contains_key(name) {
+                    true => Ok(Value::Fn(name.clone())),
+                    false => Err(self.error(&format!("Unknown variable: {}", name))),
+                }
+            }
+            Expr::Array(items) => Ok(Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.eval(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Expr::Index(value, index) => {
+                let value = self.eval(value)?;
+                let index = self.int(index)?;
+                let item = match &value {
+                    Value::Array(items) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| items.get(index).cloned()),
+                    Value::Str(text) => usize::try_from(index)
+                        .ok()
+                        .and_then(|index| text.chars().nth(index))
+                        .map(|c| Value::Str(c.to_string())),
+                    value => {
+                        let text = format!("Cannot index {}", value.type_name());
+                        return Err(self.error(&text));
+                    }
+                };
+                item.ok_or_else(|| self.error(&format!("Index out of range: {}", index)))
+            }
+            Expr::Call(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // A variable holding a function is called by its name
+                let callee = self.scopes.iter().rev().find_map(|s| s.get(name));
+                match callee {
+                    Some(Value::Fn(function)) => {
+                        let function = function.clone();
+                        self.call(&function, args)
+                    }
+                    _ => self.call(name, args),
+                }
+            }
+            Expr::Unary(op, operand) => match (*op, self.eval(operand)?) {
+                ("-", Value::Int(value)) => value
+                    .checked_neg()
+                    .map(Value::Int)
+                    .ok_or_else(|| self.error("Integer overflow")),
+                ("!", Value::Bool(value)) => Ok(Value::Bool(!value)),
+                (op, value) => {
+                    let text = format!("Cannot apply {} to {}", op, value.type_name());
+                    Err(self.error(&text))
+                }
+            },
+            Expr::Binary("&&", left, right) => match self.condition(left)? {
+                true => Ok(Value::Bool(self.condition(right)?)),
+                false => Ok(Value::Bool(false)),
+            },
+            Expr::Binary("||", left, right) => match self.condition(left)? {
+                true => Ok(Value::Bool(true)),
+                false => Ok(Value::Bool(self.condition(right)?)),
+            },
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                self.binary(op, left, right)
+            }
+            Expr::Range(..) => Err(self.error("Ranges can only be iterated by for loops")),
+        }
+    }
+
+    fn binary(&self, op: &str, left: Value, right: Value) -> Result<Value, Error> {
+        let overflow = || self.error("Integer overflow");
+        let value = match (op, left, right) {
+            ("==", left, right) => Value::Bool(left == right),
+            ("!=", left, right) => Value::Bool(left != right),
+            ("+", Value::Int(a), Value::Int(b)) => {
+                Value::Int(a.checked_add(b).ok_or_else(overflow)?)
+            }
+            ("-", Value::Int(a), Value::Int(b)) => {
+                Value::Int(a.checked_sub(b).ok_or_else(overflow)?)
+            }
+            ("*", Value::Int(a), Value::Int(b)) => {
+                Value::Int(a.checked_mul(b).ok_or_else(overflow)?)
+            }
+            ("/" | "%", Value::Int(_), Value::Int(0)) => return Err(self.error("Division by zero")),
+            ("/", Value::Int(a), Value::Int(b)) => {
+                Value::Int(a.checked_div(b).ok_or_else(overflow)?)
+            }
+            ("%", Value::Int(a), Value::Int(b)) => {
+                Value::Int(a.checked_rem(b).ok_or_else(overflow)?)
+            }
+            ("+", Value::Array(mut a), Value::Array(b)) => {
+                a.extend(b);
+                Value::Array(a)
+            }
+            // Strings are concatenated with the text of any value
+            ("+", Value::Str(a), b) => Value::Str(format!("{}{}", a, b)),
+            ("+", a, Value::Str(b)) => Value::Str(format!("{}{}", a, b)),
+            ("<" | "<=" | ">" | ">=", Value::Int(a), Value::Int(b)) => {
+                Value::Bool(compare(op, a, b))
+            }
+            ("<" | "<=" | ">" | ">=", Value::Str(a), Value::Str(b)) => {
+                Value::Bool(compare(op, a, b))
+            }
+            (op, left, right) => {
+                let text = format!(
+                    "Cannot apply {} to {} and {}",
+                    op,
+                    left.type_name(),
+                    right.type_name()
+                );
+                return Err(self.error(&text));
+            }
+        };
+        Ok(value)
+    }
+
+    fn condition(&mut self, expr: &Expr) -> Result<bool, Error> {
+        match self.eval(expr)? {
+            Value::Bool(value) => Ok(value),
+            value => {
+                let text = format!("Expected a bool condition, got {}", value.type_name());
+                Err(self.error(&text))
+            }
+        }
+    }
+
+    fn int(&mut self, expr: &Expr) -> Result<i64, Error> {
+        match self.eval(expr)? {
+            Value::Int(value) => Ok(value),
+            value => Err(self.error(&format!("Expected an int, got {}", value.type_name()))),
+        }
+    }
+
+    /// Count an executed statement or loop iteration
+    fn step(&mut self, line: usize) -> Result<(), Error> {
+        self.line = line;
+        self.steps += 1;
+        match self.steps > MAX_STEPS {
+            true => Err(self.error("Script interrupted after too many steps")),
+            false => Ok(()),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error {
+            source: self.source.clone(),
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn compare<T: PartialOrd>(op: &str, a: T, b: T) -> bool {
+    match op {
+        "<" => a < b,
+        "<=" => a <= b,
+        ">" => a > b,
+        _ => a >= b,
+    }
+}
+
+/// Functions on values available to all scripts.
+/// Returns `None` if there is no builtin with this name.
+fn builtin(name: &str, args: &[Value]) -> Option<Result<Value, String>> {
+    let value = match (name, args) {
+        ("len", [Value::Str(text)]) => Value::Int(text.chars().count() as i64),
+        ("len", [Value::Array(items)]) => Value::Int(items.len() as i64),
+        ("str", [value]) => Value::Str(value.to_string()),
+        ("int", [Value::Int(value)]) => Value::Int(*value),
+        ("int", [Value::Str(text)]) => match text.trim().parse() {
+            Ok(value) => Value::Int(value),
+            Err(_) => return Some(Err(format!("Not a number: {}", text))),
+        },
+        ("trim", [Value::Str(text)]) => Value::Str(text.trim().to_string()),
+        ("upper", [Value::Str(text)]) => Value::Str(text.to_uppercase()),
+        ("lower", [Value::Str(text)]) => Value::Str(text.to_lowercase()),
+        ("split", [Value::Str(text), Value::Str(separator)]) => Value::Array(
+            text.split(separator.as_str())
+                .map(|part| Value::Str(part.to_string()))
+                .collect(),
+        ),
+        ("join", [Value::Array(items), Value::Str(separator)]) => Value::Str(
+            items
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<_>>()
+                .join(separator),
+        ),
+        ("contains", [Value::Str(text), Value::Str(part)]) => {
+            Value::Bool(text.contains(part.as_str()))
+        }
+        ("contains", [Value::Array(items), item]) => Value::Bool(items.contains(item)),
+        ("replace", [Value::Str(text), Value::Str(from), Value::Str(to)]) => {
+            Value::Str(text.replace(from.as_str(), to))
+        }
+        ("push", [Value::Array(items), item]) => {
+            let mut items = items.clone();
+            items.push(item.clone());
+            Value::Array(items)
+        }
+        (
+            "len" | "str" | "int" | "trim" | "upper" | "lower" | "split" | "join" | "contains"
+            | "replace" | "push",
+            args,
+        ) => {
+            let types = args.iter().map(Value::type_name).collect::<Vec<_>>();
+            return Some(Err(format!(
+                "Invalid arguments for {}: ({})",
+                name,
+                types.join(", ")
+            )));
+        }
+        _ => return None,
+    };
+    Some(Ok(value))
+}
@@ -0,0 +1,111 @@
+use std::rc::Rc;
+
+use super::Error;
+
+/// Token of a script, with the line it starts on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Int(i64),
+    Str(String),
+    /// Identifier or keyword
+    Ident(String),
+    /// Operator or delimiter
+    Punct(&'static str),
+    Eof,
+}
+
+/// Operators and delimiters, the longest ones first
+const PUNCTS: &[&str] = &[
+    "==", "!=", "<=", ">=", "&&", "||", "..", "+=", "-=", "(", ")", "{", "}", "[", "]", ",", ";",
+    "=", "<", ">", "+", "-", "*", "/", "%", "!",
+];
+
+/// Split a script into tokens. `//` starts a comment until the end of the line.
+pub fn tokenize(text: &str, source: &Rc<str>) -> Result<Vec<Token>, Error> {
+    let error = |line, message: String| Error {
+        source: source.clone(),
+        line,
+        message,
+    };
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '\n' {
+            line += 1;
+            rest = &rest[1..];
+        } else if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let value = rest[..end]
+                .parse()
+                .map_err(|_| error(line, format!("Number too large: {}", &rest[..end])))?;
+            tokens.push(Token {
+                kind: TokenKind::Int(value),
+                line,
+            });
+            rest = &rest[end..];
+        } else if c.is_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token {
+                kind: TokenKind::Ident(rest[..end].to_string()),
+                line,
+            });
+            rest = &rest[end..];
+        } else if c == '"' {
+            let (text, len) =
+                string(&rest[1..]).ok_or_else(|| error(line, "Unterminated string".to_string()))?;
+            tokens.push(Token {
+                kind: TokenKind::Str(text),
+                line,
+            });
+            line += rest[..len + 1].matches('\n').count();
+            rest = &rest[len + 1..];
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+            tokens.push(Token {
+                kind: TokenKind::Punct(punct),
+                line,
+            });
+            rest = &rest[punct.len()..];
+        } else {
+            return Err(error(line, format!("Unexpected character: {}", c)));
+        }
+    }
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        line,
+    });
+    Ok(tokens)
+}
+
+/// Read a string literal after its opening quote, with the `\n`, `\t`, `\"` and `\\`
+/// escapes. Returns its text and its length in bytes up to the closing quote included.
+fn string(source: &str) -> Option<(String, usize)> {
+    let mut text = String::new();
+    let mut chars = source.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((text, index + 1)),
+            '\\' => match chars.next()?.1 {
+                'n' => text.push('\n'),
+                't' => text.push('\t'),
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+    None
+}
@@ -0,0 +1,355 @@
+use std::rc::Rc;
+
+use super::{
+    Error, Value,
+    lexer::{Token, TokenKind},
+};
+
+/// Function defined with `fn name(params) { body }`
+#[derive(Debug)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+    /// Script defining the function
+    pub source: Rc<str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Let(String, Expr),
+    /// `name = value`, or `name += value` with the operator
+    Assign(String, Option<&'static str>, Expr),
+    /// Conditions with their branch, and the `else` branch
+    If(Vec<(Expr, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    While(Expr, Vec<Stmt>),
+    For(String, Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Literal(Value),
+    Var(String),
+    Array(Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// `start..end`, only iterated by `for` loops
+    Range(Box<Expr>, Box<Expr>),
+}
+
+/// Binary operators by increasing precedence
+const PRECEDENCE: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<", "<=", ">", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+/// Parsed script: its functions, and the statements run when it is loaded
+pub struct Program {
+    pub functions: Vec<(String, Function)>,
+    pub body: Vec<Stmt>,
+}
+
+pub fn parse(tokens: Vec<Token>, source: &Rc<str>) -> Result<Program, Error> {
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        source: source.clone(),
+    };
+    let mut program = Program {
+        functions: vec![],
+        body: vec![],
+    };
+    while !parser.at_end() {
+        // Empty statements, such as a semicolon after a block
+        if parser.eat(";") {
+            continue;
+        }
+        if parser.eat_keyword("fn") {
+            program.functions.push(parser.function()?);
+        } else {
+            program.body.push(parser.statement()?);
+        }
+    }
+    Ok(program)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    source: Rc<str>,
+}
+
+impl Parser {
+    fn function(&mut self) -> Result<(String, Function), Error> {
+        let name = self.ident()?;
+        self.expect("(")?;
+        let mut params = vec![];
+        while !self.eat(")") {
+            params.push(self.ident()?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        let body = self.block()?;
+        let source = self.source.clone();
+        Ok((
+            name,
+            Function {
+                params,
+                body,
+                source,
+            },
+        ))
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        self.expect("{")?;
+        let mut stmts = vec![];
+        while !self.eat("}") {
+            if self.at_end() {
+                return Err(self.error("Expected }"));
+            }
+            if !self.eat(";") {
+                stmts.push(self.statement()?);
+            }
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        let line = self.peek().line;
+        let kind = if self.eat_keyword("fn") {
+            return Err(self.error_at(line, "Functions are defined at the top level"));
+        } else if self.eat_keyword("if") {
+            return Ok(Stmt {
+                kind: self.if_statement()?,
+                line,
+            });
+        } else if self.eat_keyword("while") {
+            let condition = self.expression()?;
+            return Ok(Stmt {
+                kind: StmtKind::While(condition, self.block()?),
+                line,
+            });
+        } else if self.eat_keyword("for") {
+            let name = self.ident()?;
+            if !self.eat_keyword("in") {
+                return Err(self.error("Expected in"));
+            }
+            let iterable = self.expression()?;
+            return Ok(Stmt {
+                kind: StmtKind::For(name, iterable, self.block()?),
+                line,
+            });
+        } else if self.eat_keyword("let") {
+            let name = self.ident()?;
+            self.expect("=")?;
+            StmtKind::Let(name, self.expression()?)
+        } else if self.eat_keyword("return") {
+            match self.ends_statement() {
+                true => StmtKind::Return(None),
+                false => StmtKind::Return(Some(self.expression()?)),
+            }
+        } else if self.eat_keyword("break") {
+            StmtKind::Break
+        } else if self.eat_keyword("continue") {
+            StmtKind::Continue
+        } else {
+            let expr = self.expression()?;
+            let assign = match self.peek_punct() {
+                Some("=") => Some(None),
+                Some("+=") => Some(Some("+")),
+                Some("-=") => Some(Some("-")),
+                _ => None,
+            };
+            match (expr, assign) {
+                (Expr::Var(name), Some(op)) => {
+                    self.index += 1;
+                    StmtKind::Assign(name, op, self.expression()?)
+                }
+                (_, Some(_)) => return Err(self.error("Only variables can be assigned")),
+                (expr, None) => StmtKind::Expr(expr),
+            }
+        };
+        // The semicolon is optional before the end of a block
+        if !self.eat(";") && !self.ends_statement() {
+            return Err(self.error("Expected ;"));
+        }
+        Ok(Stmt { kind, line })
+    }
+
+    fn if_statement(&mut self) -> Result<StmtKind, Error> {
+        let mut branches = vec![];
+        loop {
+            let condition = self.expression()?;
+            branches.push((condition, self.block()?));
+            if !self.eat_keyword("else") {
+                return Ok(StmtKind::If(branches, None));
+            }
+            if !self.eat_keyword("if") {
+                return Ok(StmtKind::If(branches, Some(self.block()?)));
+            }
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, Error> {
+        let start = self.binary(0)?;
+        match self.eat("..") {
+            true => Ok(Expr::Range(Box::new(start), Box::new(self.binary(0)?))),
+            false => Ok(start),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, Error> {
+        let Some(ops) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.peek_punct().filter(|op| ops.contains(op)) {
+            self.index += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        match self.peek_punct() {
+            Some(op @ ("-" | "!")) => {
+                self.index += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.primary()?;
+        while self.eat("[") {
+            let index = self.expression()?;
+            self.expect("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let token = self.peek().clone();
+        self.index += 1;
+        match token.kind {
+            TokenKind::Int(value) => Ok(Expr::Literal(Value::Int(value))),
+            TokenKind::Str(text) => Ok(Expr::Literal(Value::Str(text))),
+            TokenKind::Ident(name) if name == "true" => Ok(Expr::Literal(Value::Bool(true))),
+            TokenKind::Ident(name) if name == "false" => Ok(Expr::Literal(Value::Bool(false))),
+            TokenKind::Ident(name) if self.eat("(") => Ok(Expr::Call(name, self.list(")")?)),
+            TokenKind::Ident(name) => Ok(Expr::Var(name)),
+            TokenKind::Punct("(") if self.eat(")") => Ok(Expr::Literal(Value::Unit)),
+            TokenKind::Punct("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            TokenKind::Punct("[") => Ok(Expr::Array(self.list("]")?)),
+            _ => {
+                self.index -= 1;
+                Err(self.error("Expected an expression"))
+            }
+        }
+    }
+
+    /// Comma separated expressions, until the closing delimiter
+    fn list(&mut self, close: &str) -> Result<Vec<Expr>, Error> {
+        let mut items = vec![];
+        while !self.eat(close) {
+            items.push(self.expression()?);
+            if !self.eat(",") {
+                self.expect(close)?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match &self.peek().kind {
+            TokenKind::Ident(name) => {
+                let name = name.clone();
+                self.index += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("Expected a name")),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.peek().kind {
+            TokenKind::Punct(punct) => Some(punct),
+            _ => None,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.peek().kind == TokenKind::Eof
+    }
+
+    fn ends_statement(&self) -> bool {
+        self.at_end() || matches!(self.peek_punct(), Some(";" | "}"))
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.peek_punct() == Some(punct);
+        self.index += found as usize;
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(&self.peek().kind, TokenKind::Ident(name) if name == keyword);
+        self.index += found as usize;
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), Error> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(self.error(&format!("Expected {}", punct))),
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        let found = match &self.peek().kind {
+            TokenKind::Int(value) => value.to_string(),
+            TokenKind::Str(text) => format!("\"{}\"", text),
+            TokenKind::Ident(name) => name.clone(),
+            TokenKind::Punct(punct) => punct.to_string(),
+            TokenKind::Eof => "end of script".to_string(),
+        };
+        self.error_at(self.peek().line, &format!("{}, found {}", message, found))
+    }
+
+    fn error_at(&self, line: usize, message: &str) -> Error {
+        Error {
+            source: self.source.clone(),
+            line,
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+
+use regex::{Regex, RegexBuilder};
+use ropey::{Rope, RopeSlice};
+
+use super::{Config, FileId};
+
+/// Compiled search pattern, with the options it was compiled with
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    /// Pattern as typed by the user
+    pub source: String,
+    pub regex: Regex,
+    pub ignorecase: bool,
+    pub literal: bool,
+}
+
+impl Pattern {
+    /// Compile a pattern with the `ignorecase`, `smartcase` and `magic` options.
+    /// The `\c` / `\C` flags force case insensitive / sensitive matching,
+    /// and the `\v` / `\V` flags force regex / literal matching.
+    pub fn new(source: &str, config: &Config) -> Result<Self, regex::Error> {
+        let mut body = String::with_capacity(source.len());
+        let mut case = None;
+        let mut literal = !config.magic;
+
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                body.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('c') => case = Some(true),
+                Some('C') => case = Some(false),
+                Some('v') => literal = false,
+                Some('V') => literal = true,
+                Some(c) => {
+                    body.push('\\');
+                    body.push(c);
+                }
+                None => body.push('\\'),
+            }
+        }
+
+        let ignorecase = case.unwrap_or_else(|| {
+            config.ignorecase && !(config.smartcase && body.chars().any(char::is_uppercase))
+        });
+        let regex = match literal {
+            true => regex::escape(&body),
+            false => body,
+        };
+        let regex = RegexBuilder::new(&regex)
+            .case_insensitive(ignorecase)
+            .build()?;
+
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+            ignorecase,
+            literal,
+        })
+    }
+
+    /// Char ranges of all matches in a line
+    pub fn line_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut chars = 0;
+        let mut last = 0;
+        let mut matches = vec![];
+        for m in self.regex.find_iter(line) {
+            // Empty matches would make the cursor stay in place
+            if m.is_empty() {
+                continue;
+            }
+            chars += line[last..m.start()].chars().count();
+            let len = m.as_str().chars().count();
+            matches.push((chars, chars + len));
+            chars += len;
+            last = m.end();
+        }
+        matches
+    }
+
+    /// Count the matches in the buffer.
+    /// Returns the 1-based index of the match at or before the `at` char index,
+    /// and the total number of matches.
+    pub fn count(&self, rope: &Rope, at: usize) -> (usize, usize) {
+        let mut current = 0;
+        let mut total = 0;
+        for (index, line) in rope.lines().enumerate() {
+            let start = rope.line_to_char(index);
+            for (s, _) in self.line_matches(&line_text(line)) {
+                total += 1;
+                if start + s <= at {
+                    current = total;
+                }
+            }
+        }
+        (current, total)
+    }
+
+    /// Find the next match after the `from` char index, wrapping around the buffer.
+    /// Lines are searched one by one, so that the rope is never copied as a whole.
+    /// Returns the char range of the match.
+    pub fn find(&self, rope: &Rope, from: usize, backward: bool) -> Option<(usize, usize)> {
+        let lines = rope.len_lines();
+        let current = rope.char_to_line(from.min(rope.len_chars()));
+        let line_matches = |index: usize| {
+            let start = rope.line_to_char(index);
+            self.line_matches(&line_text(rope.line(index)))
+                .into_iter()
+                .map(move |(s, e)| (start + s, start + e))
+        };
+
+        if backward {
+            if let Some(m) = line_matches(current).rfind(|(s, _)| *s < from) {
+                return Some(m);
+            }
+            for offset in 1..=lines {
+                let index = (current + lines - offset) % lines;
+                if let Some(m) = line_matches(index).next_back() {
+                    return Some(m);
+                }
+            }
+        } else {
+            if let Some(m) = line_matches(current).find(|(s, _)| *s > from) {
+                return Some(m);
+            }
+            for offset in 1..=lines {
+                let index = (current + offset) % lines;
+                if let Some(m) = line_matches(index).next() {
+                    return Some(m);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Text of a line, borrowed when it is stored in a single chunk of the rope
+fn line_text(line: RopeSlice) -> Cow<str> {
+    match line.as_str() {
+        Some(text) => Cow::Borrowed(text),
+        None => Cow::Owned(line.to_string()),
+    }
+}
+
+/// Last search, repeated with `n` / `N`
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub pattern: Pattern,
+    /// Whether the search was started with `?`
+    pub backward: bool,
+}
+
+/// Index of the match under the cursor among all matches of the last search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchCount {
+    pub file: FileId,
+    /// Char index of the cursor when the count was requested
+    pub at: usize,
+    /// Current match and total number of matches, None while counting in the background
+    pub result: Option<(usize, usize)>,
+}
@@ -0,0 +1,87 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// File written by `:mksession` and read by `-S` without argument, in the current folder
+pub const DEFAULT_FILE: &str = "Session.tui";
+
+/// Pane of a session, with its file and view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionPane {
+    pub path: PathBuf,
+    /// First displayed line
+    pub scroll_y: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Pane layout written by `:mksession` and restored at startup with `-S`.
+/// The panes share a single row with equal widths, from left to right.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session {
+    pub panes: Vec<SessionPane>,
+    /// Index of the focused pane
+    pub focused: usize,
+}
+
+impl Session {
+    /// Text of the session file. Each line holds one entry, identified by its first word:
+    /// `layout row` for the orientation of the panes, `pane {scroll} {line} {col} {path}`
+    /// for each pane with its positions counted from 0, and `focus {index}`.
+    pub fn format(&self) -> String {
+        let mut text = String::from("# tui-editor session, written by :mksession\n");
+        text.push_str("layout row\n");
+        for pane in &self.panes {
+            text.push_str(&format!(
+                "pane {} {} {} {}\n",
+                pane.scroll_y,
+                pane.line,
+                pane.col,
+                pane.path.display()
+            ));
+        }
+        text.push_str(&format!("focus {}\n", self.focused));
+        text
+    }
+
+    /// Parse the text of a session file, skipping the empty lines and `#` comments
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut session = Session::default();
+        for line in text.lines() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid session line: {}", line);
+            let (entry, args) = line.split_once(' ').unwrap_or((line, ""));
+            match entry {
+                "layout" if args == "row" => {}
+                "pane" => {
+                    let mut parts = args.splitn(4, ' ');
+                    let mut number = || parts.next().and_then(|part| part.parse().ok());
+                    let (Some(scroll_y), Some(line), Some(col)) = (number(), number(), number())
+                    else {
+                        return Err(invalid());
+                    };
+                    let path = parts.next().filter(|path| !path.is_empty());
+                    session.panes.push(SessionPane {
+                        path: PathBuf::from(path.ok_or_else(invalid)?),
+                        scroll_y,
+                        line,
+                        col,
+                    });
+                }
+                "focus" => session.focused = args.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(session)
+    }
+}
+
+/// Read a session file
+pub fn read(path: &Path) -> io::Result<Session> {
+    let text = fs::read_to_string(path)?;
+    Session::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,63 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+use super::History;
+
+/// Path of the shada-like state file persisting editor state across sessions,
+/// in the XDG state directory
+fn path() -> Option<PathBuf> {
+    let dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(dir.join("tui-editor").join("shada"))
+}
+
+/// Load the persisted history, or an empty one if there is no state file yet.
+/// Each line holds one entry, identified by its first char:
+/// `:` for command history entries, `/` for search history entries.
+pub fn load() -> History {
+    let mut history = History::default();
+    let Some(path) = path() else {
+        return history;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return history,
+        Err(err) => {
+            log::error!("Failed to read state file {}: {}", path.display(), err);
+            return history;
+        }
+    };
+
+    for line in text.lines() {
+        if let Some(entry) = line.strip_prefix(':') {
+            history.commands.push(entry.to_string());
+        } else if let Some(entry) = line.strip_prefix('/') {
+            history.searches.push(entry.to_string());
+        }
+    }
+    history
+}
+
+/// Persist the history to the state file
+pub fn save(history: &History) -> io::Result<()> {
+    let Some(path) = path() else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut text = String::from("# tui-editor state file, overwritten on exit\n");
+    for entry in &history.commands {
+        text.push_str(&format!(":{}\n", entry));
+    }
+    for entry in &history.searches {
+        text.push_str(&format!("/{}\n", entry));
+    }
+    fs::write(path, text)
+}
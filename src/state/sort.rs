@@ -0,0 +1,111 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::Edit;
+
+/// First number of a line, the key of `:sort n`
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-?\d+").unwrap());
+
+/// Flags of `:sort`, after the command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortFlags {
+    /// `!`: sort in reverse order
+    pub reverse: bool,
+    /// `u`: keep only the first of the equal lines
+    pub unique: bool,
+    /// `i`: ignore the case
+    pub ignorecase: bool,
+    /// `n`: sort by the first decimal number of the lines, those without one first
+    pub numeric: bool,
+}
+
+/// Reordering of the lines of a range, by `:sort`, `:uniq` and `:reverse` or their visual
+/// mode keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOperation {
+    Sort(SortFlags),
+    /// Remove the lines equal to the one before them, ignoring the case or not
+    Uniq {
+        ignorecase: bool,
+    },
+    Reverse,
+}
+
+impl SortFlags {
+    /// Parse the flags, like `u` or `in`, ignoring the spaces
+    pub fn parse(text: &str, reverse: bool) -> Result<Self, String> {
+        let mut flags = Self {
+            reverse,
+            ..Self::default()
+        };
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            match c {
+                'u' => flags.unique = true,
+                'i' => flags.ignorecase = true,
+                'n' => flags.numeric = true,
+                _ => return Err(format!("E488: Trailing characters: {}", text.trim())),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+impl LineOperation {
+    /// Reorder lines, without their line breaks
+    pub fn apply<'a>(&self, mut lines: Vec<&'a str>) -> Vec<&'a str> {
+        match *self {
+            Self::Sort(flags) => {
+                let key = |line: &str| match flags.numeric {
+                    true => SortKey::Number(
+                        NUMBER
+                            .find(line)
+                            .and_then(|m| m.as_str().parse::<i128>().ok()),
+                    ),
+                    false if flags.ignorecase => SortKey::Text(line.to_lowercase()),
+                    false => SortKey::Text(line.to_string()),
+                };
+                // The sort is stable, the equal lines keeping their order
+                let mut keyed = lines
+                    .into_iter()
+                    .map(|line| (key(line), line))
+                    .collect::<Vec<_>>();
+                keyed.sort_by(|(a, _), (b, _)| match flags.reverse {
+                    true => b.cmp(a),
+                    false => a.cmp(b),
+                });
+                if flags.unique {
+                    keyed.dedup_by(|(a, _), (b, _)| a == b);
+                }
+                keyed.into_iter().map(|(_, line)| line).collect()
+            }
+            Self::Uniq { ignorecase } => {
+                lines.dedup_by(|a, b| match ignorecase {
+                    true => a.to_lowercase() == b.to_lowercase(),
+                    false => a == b,
+                });
+                lines
+            }
+            Self::Reverse => {
+                lines.reverse();
+                lines
+            }
+        }
+    }
+
+    /// Reorder the lines from `start` to `end` included in a single change. Returns
+    /// whether they changed.
+    pub fn apply_lines(&self, rope: &mut Edit, start: usize, end: usize) -> bool {
+        rope.replace_lines(start, end, |lines| {
+            let lines = self.apply(lines.to_vec());
+            lines.into_iter().map(str::to_string).collect()
+        })
+    }
+}
+
+/// Key lines are sorted by, the lines without a number coming first with `:sort n`
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Text(String),
+    Number(Option<i128>),
+}
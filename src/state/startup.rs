@@ -0,0 +1,58 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// Phase ending with the first frame drawn
+pub const FIRST_RENDER: &str = "first render";
+/// Phase ending once the entries of the root folder are listed, in the background
+pub const ROOT_SCAN: &str = "scan the root folder";
+
+/// Timestamps of the startup phases, shown by `:startuptime`
+#[derive(Debug)]
+pub struct StartupTimes {
+    start: Instant,
+    /// Phases in the order they ended, with the time since the start
+    phases: Vec<(&'static str, Duration)>,
+    /// File of `--startuptime`, rewritten after each phase
+    file: Option<PathBuf>,
+}
+
+impl StartupTimes {
+    /// Start timing now, writing the phases to a file if given
+    pub fn new(file: Option<PathBuf>) -> Self {
+        Self {
+            start: Instant::now(),
+            phases: vec![],
+            file,
+        }
+    }
+
+    /// Record the end of a phase, only the first time for the repeated ones
+    pub fn mark(&mut self, phase: &'static str) {
+        if self.phases.iter().any(|(name, _)| *name == phase) {
+            return;
+        }
+        self.phases.push((phase, self.start.elapsed()));
+        if let Some(file) = &self.file
+            && let Err(err) = fs::write(file, self.report())
+        {
+            log::error!("Failed to write {}: {}", file.display(), err);
+        }
+    }
+
+    /// Report of the phases, with their end since the start and their own duration in
+    /// milliseconds
+    pub fn report(&self) -> String {
+        let mut report = String::from("times in msec\n   clock     self  phase\n\n");
+        let mut previous = Duration::ZERO;
+        for (phase, end) in &self.phases {
+            let clock = end.as_secs_f64() * 1000.0;
+            let own = end.saturating_sub(previous).as_secs_f64() * 1000.0;
+            report.push_str(&format!("{:>8.3} {:>8.3}  {}\n", clock, own, phase));
+            previous = *end;
+        }
+        report
+    }
+}
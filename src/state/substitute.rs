@@ -0,0 +1,160 @@
+use regex::Captures;
+
+/// Regex metacharacters, kept escaped when they delimit the pattern
+const META_CHARS: &str = r".+*?()|[]{}^$#&-~";
+
+/// Flags of a substitution, after its pattern and replacement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubstituteFlags {
+    /// `g`: replace all the matches of each line, instead of the first one
+    pub global: bool,
+    /// `c`: ask to confirm each replacement
+    pub confirm: bool,
+    /// `n`: only count the matches
+    pub count: bool,
+    /// `e`: no error when the pattern is not found
+    pub quiet: bool,
+    /// `i` / `I`: ignore or match the case, overriding `ignorecase`
+    pub ignorecase: Option<bool>,
+}
+
+/// `:s/pattern/replacement/flags` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitute {
+    /// Pattern as typed, empty for the last search
+    pub pattern: String,
+    pub replacement: String,
+    pub flags: SubstituteFlags,
+}
+
+impl Substitute {
+    /// Parse the arguments of `:s`, delimited by the first char, like `/foo/bar/g` or
+    /// `#/usr#/opt#`. A delimiter is escaped with a backslash, and trailing delimiters
+    /// can be left out.
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut chars = args.chars();
+        let delimiter = chars.next().ok_or("E35: No previous regular expression")?;
+        if delimiter.is_alphanumeric() || matches!(delimiter, '\\' | '"' | '|' | ' ') {
+            return Err("E146: Regular expressions can't be delimited by letters".to_string());
+        }
+        let rest = chars.as_str();
+        let (pattern, rest) = split_delimited(rest, delimiter, true);
+        let (replacement, rest) = match rest {
+            Some(rest) => split_delimited(rest, delimiter, false),
+            None => (String::new(), None),
+        };
+        let flags = SubstituteFlags::parse(rest.unwrap_or_default())?;
+        Ok(Self {
+            pattern,
+            replacement,
+            flags,
+        })
+    }
+
+    /// Source of a pattern with the case flag, which overrides the case options
+    pub fn pattern_source(&self, pattern: &str) -> String {
+        match self.flags.ignorecase {
+            Some(true) => format!("{}\\c", pattern),
+            Some(false) => format!("{}\\C", pattern),
+            None => pattern.to_string(),
+        }
+    }
+}
+
+impl SubstituteFlags {
+    /// Parse the flags, like `gc`, ignoring the spaces
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut flags = Self::default();
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            match c {
+                'g' => flags.global = true,
+                'c' => flags.confirm = true,
+                'n' => flags.count = true,
+                'e' => flags.quiet = true,
+                'i' => flags.ignorecase = Some(true),
+                'I' => flags.ignorecase = Some(false),
+                _ => return Err(format!("E488: Trailing characters: {}", text.trim())),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// Split the text up to an unescaped delimiter, unescaping it, and the text after it if
+/// the delimiter was found. In the pattern, the delimiters that are regex metacharacters
+/// stay escaped.
+fn split_delimited(text: &str, delimiter: char, pattern: bool) -> (String, Option<&str>) {
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) if c == delimiter && !(pattern && META_CHARS.contains(c)) => {
+                    part.push(c);
+                }
+                Some((_, c)) => {
+                    part.push('\\');
+                    part.push(c);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter => return (part, Some(&text[i + c.len_utf8()..])),
+            c => part.push(c),
+        }
+    }
+    (part, None)
+}
+
+/// Case change of the replaced text, with `\u`, `\l`, `\U` and `\L`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+impl Case {
+    fn apply(case: Option<Self>, c: char, out: &mut String) {
+        match case {
+            Some(Self::Upper) => out.extend(c.to_uppercase()),
+            Some(Self::Lower) => out.extend(c.to_lowercase()),
+            None => out.push(c),
+        }
+    }
+}
+
+/// Replacement text of a match: `&` (`\&` without `magic`) is the whole match, `\1` to
+/// `\9` the groups, `\r` and `\n` line breaks, `\t` a tab. `\u` / `\l` change the case of
+/// the next char, and `\U` / `\L` the case of the chars up to `\E` or `\e`.
+pub fn expand(replacement: &str, captures: &Captures, magic: bool) -> String {
+    let mut out = String::new();
+    let mut run = None;
+    let mut next = None;
+    let mut push = |text: &str, run: Option<Case>, next: &mut Option<Case>| {
+        for c in text.chars() {
+            Case::apply(next.take().or(run), c, &mut out);
+        }
+    };
+    let group = |i: usize| captures.get(i).map_or("", |m| m.as_str());
+
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' if magic => push(group(0), run, &mut next),
+            '\\' => match chars.next() {
+                Some('&') if !magic => push(group(0), run, &mut next),
+                Some(c @ '0'..='9') => push(group(c as usize - '0' as usize), run, &mut next),
+                Some('n' | 'r') => push("\n", run, &mut next),
+                Some('t') => push("\t", run, &mut next),
+                Some('u') => next = Some(Case::Upper),
+                Some('l') => next = Some(Case::Lower),
+                Some('U') => run = Some(Case::Upper),
+                Some('L') => run = Some(Case::Lower),
+                Some('E' | 'e') => run = None,
+                Some(c) => push(c.encode_utf8(&mut [0; 4]), run, &mut next),
+                None => push("\\", run, &mut next),
+            },
+            c => push(c.encode_utf8(&mut [0; 4]), run, &mut next),
+        }
+    }
+    out
+}
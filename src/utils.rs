@@ -1,3 +1,42 @@
+use std::{
+    borrow::Cow,
+    fs::Metadata,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ratatui::text::{Line, Span};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Marker of the end of a truncated text
+pub const ELLIPSIS: char = '…';
+
+/// Remove the terminal formatting of a program output: the backspace sequences used by
+/// man pages for bold and underlined text, and the escape sequences of colors
+pub fn plain_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{8}' => {
+                result.pop();
+            }
+            // CSI sequences end with a char from `@` to `~`
+            '\x1b' if chars.peek() == Some(&'[') => {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
 /// Returns the number of digits in a number,
 /// in order to compute the char width needed to
 /// display it
@@ -14,3 +53,195 @@ pub fn whitespace_padding(n: usize, width: usize) -> String {
     let remaining = width.saturating_sub(number_digits(n));
     " ".repeat(remaining)
 }
+
+/// Part of a text between two char indices, clamped to its end
+pub fn char_slice(text: &str, chars: Range<usize>) -> &str {
+    let byte = |index: usize| {
+        text.char_indices()
+            .nth(index)
+            .map_or(text.len(), |(byte, _)| byte)
+    };
+    let start = byte(chars.start);
+    &text[start..byte(chars.end).max(start)]
+}
+
+/// Longest start of a text that fits in `width` display cells
+pub fn fitting_prefix(text: &str, width: usize) -> &str {
+    let mut cells = 0;
+    let end = text
+        .char_indices()
+        .find(|(_, c)| {
+            cells += c.width().unwrap_or(0);
+            cells > width
+        })
+        .map_or(text.len(), |(byte, _)| byte);
+    &text[..end]
+}
+
+/// Truncate a text to `width` display cells, ending it with an ellipsis when it is cut
+pub fn truncate(text: &str, width: usize) -> Cow<'_, str> {
+    match text.width() <= width {
+        true => Cow::Borrowed(text),
+        false if width == 0 => Cow::Borrowed(""),
+        false => Cow::Owned(format!("{}{}", fitting_prefix(text, width - 1), ELLIPSIS)),
+    }
+}
+
+/// Truncate a styled line to `width` display cells, ending it with an ellipsis in the
+/// style of the cut span when it is cut
+pub fn truncate_line(line: Line<'_>, width: usize) -> Line<'_> {
+    if line.width() <= width {
+        return line;
+    }
+    let mut left = width.saturating_sub(1);
+    let mut spans = vec![];
+    for span in line.spans {
+        let cells = span.width();
+        if cells <= left {
+            left -= cells;
+            spans.push(span);
+            continue;
+        }
+        let prefix = fitting_prefix(&span.content, left).to_string();
+        spans.push(Span::styled(prefix, span.style));
+        if width > 0 {
+            spans.push(Span::styled(ELLIPSIS.to_string(), span.style));
+        }
+        break;
+    }
+    Line { spans, ..line }
+}
+
+/// Size in bytes with a binary unit, like `1.5 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// UTC date and time of a timestamp, like `2024-03-01 14:05 UTC`
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Civil date of a day count since 1970-01-01, in eras of 400 years
+    let days = days + 719_468;
+    let (era, day) = (days / 146_097, days % 146_097);
+    let year = (day - day / 1460 + day / 36_524 - day / 146_096) / 365;
+    let day = day - (365 * year + year / 4 - year / 100);
+    let month = (5 * day + 2) / 153;
+    let (day, month) = (day - (153 * month + 2) / 5 + 1, (month + 2) % 12 + 1);
+    let year = year + era * 400 + u64::from(month <= 2);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// Permissions of a file like `ls -l`, such as `rw-r--r--`
+pub fn format_permissions(metadata: &Metadata) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = metadata.permissions().mode();
+        (0..9)
+            .map(|bit| match mode & (0o400 >> bit) != 0 {
+                true => ['r', 'w', 'x'][bit % 3],
+                false => '-',
+            })
+            .collect()
+    }
+    #[cfg(not(unix))]
+    match metadata.permissions().readonly() {
+        true => "r--".to_string(),
+        false => "rw-".to_string(),
+    }
+}
+
+/// Returns an absolute version of the path, resolved from the current directory.
+/// Existing paths are canonicalized so that they can be compared with filetree paths.
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    // The file may not exist yet, but its parent folder may
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match parent.canonicalize() {
+            Ok(parent) => parent.join(name),
+            Err(_) => path,
+        },
+        _ => path,
+    }
+}
+
+/// Find an executable in the `PATH` directories
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Expand a leading `~` and the `$VAR`, `${VAR}` and `%VAR%` environment variables
+/// of a path typed by the user. Unset variables are kept as typed.
+pub fn expand_path(path: &str) -> PathBuf {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+    if let Some(after) = path.strip_prefix('~')
+        && (after.is_empty() || after.starts_with('/'))
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        expanded.push_str(&home.to_string_lossy());
+        rest = after;
+    }
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let (name, len) = variable_name(&rest[start..]);
+        match name.and_then(|name| std::env::var(name).ok()) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(&rest[start..start + len]),
+        }
+        rest = &rest[start + len..];
+    }
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
+}
+
+/// Name of the variable referenced at the start of the text, with the length of the reference
+fn variable_name(text: &str) -> (Option<&str>, usize) {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let (name, len) = if let Some(rest) = text.strip_prefix("${") {
+        match rest.find('}') {
+            Some(end) => (&rest[..end], end + 3),
+            None => ("", 1),
+        }
+    } else if let Some(rest) = text.strip_prefix('%') {
+        match rest.find('%') {
+            Some(end) => (&rest[..end], end + 2),
+            None => ("", 1),
+        }
+    } else {
+        let rest = &text[1..];
+        let end = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+        (&rest[..end], end + 1)
+    };
+    match !name.is_empty() && name.chars().all(is_name) {
+        true => (Some(name), len),
+        false => (None, 1),
+    }
+}
@@ -1,19 +1,55 @@
 use ratatui::prelude::*;
 
-use crate::State;
+use crate::{
+    State,
+    state::{Jump, Prompt},
+};
 pub use border::Border;
 pub use cmdline::Cmdline;
+pub use cmdwin::CmdlineWindow;
 pub use filetree::FileTree;
+pub use input::Input;
 pub use lualine::Lualine;
-pub use pane::Pane;
+pub use menu::{ContextMenu, MenuItem};
+pub use palette::{CommandPalette, PaletteAction};
+pub use pane::{Pane, READONLY_ERROR, UndoMotion};
 pub use panes::Panes;
+pub use preview::Preview;
+pub use replace::ProjectReplace;
 
 mod border;
 mod cmdline;
-mod filetree;
+pub mod cmdwin;
+pub mod filetree;
+mod input;
 mod lualine;
+mod menu;
+mod palette;
 mod pane;
 mod panes;
+mod preview;
+mod replace;
+
+/// Requests from a widget to the screen that owns it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Open the history editing window for a prompt kind
+    CmdlineWindow(Prompt),
+    /// Open the command cmdline with some text already typed
+    Cmdline(String),
+    /// Execute an ex command line
+    Command(String),
+    /// Open the help at a topic
+    Help(String),
+    /// Record the typed keys into a register
+    Record(char),
+    /// Stop recording the typed keys
+    StopRecording,
+    /// Replay the keys of a register, `@` for the last replayed one
+    Replay(char),
+    /// Go to a position of the jump list, opening its buffer in the pane
+    Jump(Jump),
+}
 
 /// Editor widget trait
 pub trait Widget {
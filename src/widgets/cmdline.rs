@@ -1,113 +1,276 @@
-use std::cell::Cell;
+use std::path::Path;
 
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::layout::Flex;
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, BorderType, Clear, Paragraph, Widget as RatatuiWidget};
-use ropey::Rope;
 
-use crate::state::Mode;
+use crate::state::{
+    CmdlineLayout, Mode, Prompt,
+    commands::{complete_path, completes_path, is_keyword},
+};
+use crate::utils::truncate_line;
+use crate::widgets::Input;
 use crate::{State, Widget};
 
 /// Command line input
 #[derive(Debug, Default)]
 pub struct Cmdline {
-    command: Rope,
-    text_cursor: usize,
-    tui_cursor: Cell<Position>,
+    input: Input,
+    /// Index of the displayed history entry, when browsing the history
+    history_index: Option<usize>,
+    /// Text typed before browsing the history, used as a prefix filter
+    draft: String,
+    /// Completed command lines cycled with Tab, and the index of the displayed one
+    completion: Option<(Vec<String>, usize)>,
+    /// Whether `Ctrl-r` waits for a register name
+    pending_register: bool,
 }
 
 impl Cmdline {
-    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) {
+    /// Handle a key event while the cmdline is open.
+    /// Returns the command to execute when it is submitted.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) -> Option<String> {
+        if self.pending_register {
+            self.pending_register = false;
+            if let KeyCode::Char(name) = key_event.code {
+                match state.register(name) {
+                    Ok(text) => self.paste(&text),
+                    Err(err) => state.error(err),
+                }
+            }
+            return None;
+        }
+        if key_event.code == KeyCode::Char('r')
+            && key_event.modifiers.contains(KeyModifiers::CONTROL)
+        {
+            self.pending_register = true;
+            return None;
+        }
+        if !matches!(key_event.code, KeyCode::Tab | KeyCode::BackTab) {
+            self.completion = None;
+        }
+        let ends_word = match key_event.code {
+            KeyCode::Enter => true,
+            KeyCode::Char(c) => !is_keyword(c),
+            _ => false,
+        };
+        if ends_word && state.prompt == Prompt::Command {
+            self.expand_abbreviation(state);
+        }
+
         match key_event.code {
             KeyCode::Esc => self.close(state),
-            KeyCode::Enter => self.execute(state),
-            KeyCode::Backspace => {
-                if self.text_cursor > 0 {
-                    self.remove_char(self.text_cursor - 1);
-                    self.text_cursor -= 1;
+            KeyCode::Enter => return Some(self.execute(state)),
+            KeyCode::Up => {
+                if self.history_index.is_none() {
+                    self.draft = self.input.text();
                 }
-            }
-            KeyCode::Left => {
-                if self.text_cursor > 0 {
-                    self.text_cursor -= 1;
+                let index = state
+                    .history
+                    .older(state.prompt, self.history_index, &self.draft);
+                if let Some(index) = index {
+                    self.history_index = Some(index);
+                    self.input
+                        .set_text(&state.history.entries(state.prompt)[index]);
                 }
             }
-            KeyCode::Right => {
-                if self.text_cursor < self.command.len_chars() {
-                    self.text_cursor += 1;
+            KeyCode::Down => {
+                let current = self.history_index?;
+                match state.history.newer(state.prompt, current, &self.draft) {
+                    Some(index) => {
+                        self.history_index = Some(index);
+                        self.input
+                            .set_text(&state.history.entries(state.prompt)[index]);
+                    }
+                    None => {
+                        self.history_index = None;
+                        self.input.set_text(&self.draft);
+                    }
                 }
             }
-            KeyCode::Delete => {
-                if self.text_cursor < self.command.len_chars() {
-                    self.remove_char(self.text_cursor);
+            KeyCode::Tab if state.prompt == Prompt::Command => self.complete(true, state),
+            KeyCode::BackTab if state.prompt == Prompt::Command => self.complete(false, state),
+            _ => {
+                if self.input.handle_key_event(key_event) {
+                    self.history_index = None;
                 }
             }
-            KeyCode::Home => self.text_cursor = 0,
-            KeyCode::End => self.text_cursor = self.command.len_chars(),
-            KeyCode::Char(c) => {
-                self.command.insert_char(self.text_cursor, c);
-                self.text_cursor += 1;
+        }
+        None
+    }
+
+    /// Replace the abbreviation typed right before the cursor
+    fn expand_abbreviation(&mut self, state: &State) {
+        let start = self.input.word_start(is_keyword);
+        let text = self.input.text();
+        let word = text.chars().skip(start).take(self.input.cursor() - start);
+        if let Some(rhs) = state.commands.abbreviations.get(&word.collect::<String>()) {
+            self.input.replace_before_cursor(start, rhs);
+        }
+    }
+
+    /// Complete the command name or path argument, or cycle through the completions
+    fn complete(&mut self, forward: bool, state: &mut State) {
+        if let Some((names, index)) = &mut self.completion {
+            *index = match forward {
+                true => (*index + 1) % names.len(),
+                false => (*index + names.len() - 1) % names.len(),
+            };
+            self.input.set_text(&names[*index]);
+            return;
+        }
+
+        let text = self.input.text();
+        let (head, names) = match text.split_once(char::is_whitespace) {
+            None => (String::new(), state.commands.complete(&text)),
+            Some((name, _)) if completes_path(name) => {
+                let (head, typed) = text.rsplit_once(char::is_whitespace).unwrap_or_default();
+                (format!("{} ", head), complete_path(typed, Path::new("")))
             }
-            _ => {}
+            Some(_) => return,
+        };
+        let Some(first) = names.first() else {
+            return;
+        };
+        self.input.set_text(&format!("{}{}", head, first));
+        if names.len() > 1 {
+            state.info(names.join("  "));
+            let names = names.iter().map(|name| format!("{}{}", head, name));
+            self.completion = Some((names.collect(), 0));
         }
     }
 
-    /// Draws the cursor
-    pub fn draw_cursor(&self, frame: &mut Frame) {
-        frame.set_cursor_position(self.tui_cursor.get());
+    /// Insert pasted text at the cursor, on a single line
+    pub fn paste(&mut self, text: &str) {
+        let text = text
+            .trim_end_matches(['\r', '\n'])
+            .replace(['\r', '\n'], " ");
+        self.input.insert_str(&text);
+        self.history_index = None;
+        self.completion = None;
+    }
+
+    /// Typed text
+    pub fn text(&self) -> String {
+        self.input.text()
+    }
+
+    /// Replace the typed text
+    pub fn set_text(&mut self, text: &str) {
+        self.input.set_text(text);
     }
 
     fn close(&mut self, state: &mut State) {
-        state.mode = Mode::Normal;
-        self.command = Rope::new();
-        self.text_cursor = 0;
+        state.close_cmdline();
+        self.input.clear();
+        self.history_index = None;
+        self.draft.clear();
+        self.completion = None;
+        self.pending_register = false;
     }
 
-    fn execute(&mut self, state: &mut State) {
+    fn execute(&mut self, state: &mut State) -> String {
+        let command = self.input.text();
+        state.history.push(state.prompt, &command);
         self.close(state);
+        command
     }
+}
 
-    fn remove_char(&mut self, idx: usize) {
-        self.command.remove(idx..idx + 1);
+impl Cmdline {
+    /// Render the classic single line layout, showing the last message when closed
+    fn render_bottom(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        if state.mode != Mode::Command {
+            if let Some(message) = &state.message {
+                truncate_line(Line::from(message.span()), area.width as usize).render(area, buf);
+            }
+            return;
+        }
+        let prompt = match state.prompt {
+            Prompt::Command => ":".to_string(),
+            Prompt::Search { backward: false } => "/".to_string(),
+            Prompt::Search { backward: true } => "?".to_string(),
+            Prompt::Input => {
+                let label = state
+                    .scripts
+                    .prompt
+                    .as_ref()
+                    .map_or("Input", |(label, _)| label);
+                format!("{}: ", label)
+            }
+        };
+        let width = prompt.chars().count();
+        let (text, cursor) = self.input.view((area.width as usize).saturating_sub(width));
+        state.cursor_pos.set(Position {
+            x: area.left() + width as u16 + cursor,
+            y: area.top(),
+        });
+        Line::from(vec![Span::raw(prompt), text]).render(area, buf);
     }
-}
 
-impl Widget for Cmdline {
-    fn render(&self, area: Rect, buf: &mut Buffer, _: &State) {
+    /// Render the floating box layout
+    fn render_float(&self, area: Rect, buf: &mut Buffer, state: &State) {
         let [middle_line] = Layout::vertical([Constraint::Length(3)])
             .flex(Flex::Center)
             .areas(area);
 
-        let width = 60.max(1 + 3 + self.command.chars().count() as u16 + 2);
+        let width = 60.max(1 + 3 + self.input.len() as u16 + 2).min(area.width);
 
         let [middle] = Layout::horizontal([Constraint::Length(width)])
             .flex(Flex::Center)
             .areas(middle_line);
+        // Inside the borders and after the icon
+        let (text, cursor) = self.input.view(width.saturating_sub(2 + 3) as usize);
 
         // Set cursor position from the computed layout
         let cursor_y = middle.top() + 1;
-        let cursor_x = middle.left() + 1 + 3 + self.text_cursor as u16;
-        self.tui_cursor.set(Position {
+        let cursor_x = middle.left() + 1 + 3 + cursor;
+        state.cursor_pos.set(Position {
             x: cursor_x,
             y: cursor_y,
         });
 
-        Clear::default().render(middle, buf);
+        Clear.render(middle, buf);
+
+        let (icon, title) = match state.prompt {
+            Prompt::Command => (state.config.icons.icons().cmdline, " Cmdline ".to_string()),
+            Prompt::Search { backward: false } => (" / ", " Search ".to_string()),
+            Prompt::Search { backward: true } => (" ? ", " Search backward ".to_string()),
+            Prompt::Input => {
+                let label = state
+                    .scripts
+                    .prompt
+                    .as_ref()
+                    .map_or("Input", |(label, _)| label);
+                (" > ", format!(" {} ", label))
+            }
+        };
 
         Paragraph::new(Text::from(Line::from(vec![
-            Span::styled("  ", Style::default().bold().cyan()),
-            Span::raw(&self.command),
+            Span::styled(icon, Style::default().bold().cyan()),
+            text,
         ])))
         .block(
             Block::bordered()
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().magenta())
                 .title_alignment(HorizontalAlignment::Center)
-                .title(" Cmdline "),
+                .title(title),
         )
         .render(middle, buf);
     }
+}
+
+impl Widget for Cmdline {
+    /// Render in the whole screen area for the floating layout,
+    /// or in the bottom line for the classic layout
+    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        match state.config.cmdline {
+            CmdlineLayout::Float => self.render_float(area, buf, state),
+            CmdlineLayout::Bottom => self.render_bottom(area, buf, state),
+        }
+    }
 
     /// CmdLine is a popup that is click-agnostic
     fn contains(&self, _: Position) -> bool {
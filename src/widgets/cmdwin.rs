@@ -0,0 +1,95 @@
+use crossterm::event::{KeyEvent, MouseEvent};
+use ratatui::prelude::*;
+
+use crate::{
+    State, Widget,
+    position::last_line,
+    state::{File, Prompt},
+    widgets::{Action, Border, Pane},
+};
+
+/// Number of text lines of the window
+pub const HEIGHT: u16 = 7;
+
+/// Command-line window (`q:`, `q/`), editing the history in a scratch buffer
+#[derive(Debug)]
+pub struct CmdlineWindow {
+    pub prompt: Prompt,
+    pane: Pane,
+    border: Border,
+}
+
+impl CmdlineWindow {
+    /// Open the window with the history of the given prompt kind,
+    /// and an empty line at the end to type a new entry
+    pub fn new(prompt: Prompt, state: &mut State) -> Self {
+        let mut text = state.history.entries(prompt).join("\n");
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        let file = state.filesystem.files.insert(File::scratch(&text));
+
+        let mut pane = Pane::new(file);
+        if let Some(rope) = &state.filesystem.files[file].buffer {
+            pane.cursor.set_position(0, last_line(rope), rope);
+        }
+
+        Self {
+            prompt,
+            pane,
+            border: Border::horizontal(),
+        }
+    }
+
+    /// Text of the line under the cursor
+    pub fn current_line(&self, state: &State) -> String {
+        let Some(rope) = &state.filesystem.files[self.pane.file].buffer else {
+            return String::new();
+        };
+        let line = rope.line(self.pane.cursor.y).to_string();
+        line.trim_end_matches(['\n', '\r']).to_string()
+    }
+
+    /// Delete the scratch buffer
+    pub fn close(self, state: &mut State) {
+        state.filesystem.files.remove(self.pane.file);
+    }
+
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) -> Option<Action> {
+        self.pane.handle_key_event(key_event, state)
+    }
+
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, state: &mut State) {
+        self.pane.handle_mouse_event(mouse_event, state);
+    }
+
+    pub fn cursor_position(&self) -> Position {
+        self.pane.cursor_position()
+    }
+}
+
+impl Widget for CmdlineWindow {
+    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        let [border, pane] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(area);
+
+        self.border.render(border, buf, state);
+        let title = match self.prompt {
+            Prompt::Command => " Command history ",
+            Prompt::Search { .. } => " Search history ",
+            Prompt::Input => " Input history ",
+        };
+        buf.set_string(
+            border.left() + 2,
+            border.top(),
+            title,
+            Style::default().magenta(),
+        );
+
+        self.pane.render(pane, buf, state);
+    }
+
+    fn contains(&self, pos: Position) -> bool {
+        self.pane.contains(pos)
+    }
+}
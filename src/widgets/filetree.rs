@@ -1,69 +1,340 @@
-use std::cell::Cell;
+use std::{cell::Cell, path::Path};
+
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
 
 use crate::{
     State, Widget,
-    state::{FileSystem, FolderId},
+    state::{FileId, FileSystem, FolderId, Prompt, clipboard, is_link_cycle},
+    utils::{format_permissions, format_size, format_time, truncate_line},
 };
 use ratatui::{prelude::*, widgets::Widget as RatatuiWidget};
 
+/// Visible filetree row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    File(FileId),
+    Folder(FolderId),
+}
+
 #[derive(Debug)]
 pub struct FileTree {
     area: Cell<Rect>,
+    /// Index of the selected row
+    selected: usize,
+    scroll_y: Cell<usize>,
+    /// Whether the details of the selected entry are shown below the tree
+    details: bool,
+    /// Whether the selected file is previewed
+    preview: bool,
+    /// Whether `g` was typed, to yank the absolute path with `gy`
+    pending_g: bool,
+    /// File whose entry is being dragged with the mouse
+    dragged: Option<FileId>,
+    /// Row of the folder under the dragged file
+    drop_row: Option<usize>,
+    /// File dropped onto a folder, until the move is confirmed
+    dropped: Option<(FileId, FolderId)>,
+}
+
+/// Part of the path of an entry that is yanked
+#[derive(Debug, Clone, Copy)]
+enum PathPart {
+    Name,
+    Relative,
+    Absolute,
 }
 
 impl FileTree {
     pub fn new() -> Self {
         Self {
             area: Cell::new(Rect::default()),
+            selected: 0,
+            scroll_y: Cell::new(0),
+            details: false,
+            preview: false,
+            pending_g: false,
+            dragged: None,
+            drop_row: None,
+            dropped: None,
+        }
+    }
+
+    /// Handle a key event while the filetree is focused.
+    /// Returns the file to open in a pane, if any.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) -> Option<FileId> {
+        let entries = self.entries(&state.filesystem);
+        let count = entries.len();
+        let pending_g = std::mem::take(&mut self.pending_g);
+
+        match key_event.code {
+            KeyCode::Char('y') if pending_g => self.yank_path(PathPart::Absolute, state),
+            KeyCode::Char('y') => self.yank_path(PathPart::Name, state),
+            KeyCode::Char('Y') => self.yank_path(PathPart::Relative, state),
+            KeyCode::Char('g') => self.pending_g = true,
+            KeyCode::Char('j') | KeyCode::Down if self.selected + 1 < count => self.selected += 1,
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char('l') | KeyCode::Char('o') => {
+                return self.activate(*entries.get(self.selected)?, state);
+            }
+            KeyCode::Char('h') => {
+                if let Some((Entry::Folder(id), _)) = entries.get(self.selected) {
+                    state.filesystem.folders[*id].open = false;
+                }
+            }
+            KeyCode::Char('i') => self.details = !self.details,
+            KeyCode::Char('P') => self.preview = !self.preview,
+            KeyCode::Char('R') => {
+                state
+                    .filesystem
+                    .refresh(state.events.editor_sender.clone(), &state.jobs);
+            }
+            KeyCode::Char(':') => state.open_cmdline(Prompt::Command),
+            _ => {}
+        }
+        None
+    }
+
+    /// Handle a mouse event over the filetree.
+    /// Returns the file to open in a pane, if any.
+    pub fn handle_mouse_event(
+        &mut self,
+        mouse_event: MouseEvent,
+        state: &mut State,
+    ) -> Option<FileId> {
+        let entries = self.entries(&state.filesystem);
+        let row = (mouse_event.row - self.area.get().top()) as usize + self.scroll_y.get();
+        match mouse_event.kind {
+            MouseEventKind::Down(button) => {
+                self.cancel_drag();
+                if row < entries.len() {
+                    self.selected = row;
+                    if button == MouseButton::Left
+                        && let (Entry::File(id), _) = entries[row]
+                    {
+                        self.dragged = Some(id);
+                    }
+                    // Right clicks only select the entry for the context menu
+                    if button != MouseButton::Right {
+                        return self.activate(entries[row], state);
+                    }
+                }
+            }
+            // Dragged files can be dropped onto folders
+            MouseEventKind::Drag(MouseButton::Left) => {
+                self.drop_row = self.dragged.and_then(|_| {
+                    let (entry, _) = entries.get(row)?;
+                    matches!(entry, Entry::Folder(_)).then_some(row)
+                });
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                if let Some(file) = self.dragged.take()
+                    && let Some(row) = self.drop_row.take()
+                    && let Some((Entry::Folder(folder), _)) = entries.get(row)
+                {
+                    self.dropped = Some((file, *folder));
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                self.selected = self.selected.saturating_sub(state.config.scroll_tick);
+            }
+            MouseEventKind::ScrollDown => {
+                let last = entries.len().saturating_sub(1);
+                self.selected = (self.selected + state.config.scroll_tick).min(last);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Path of the selected file when the preview is enabled
+    pub fn preview_path<'a>(&self, filesystem: &'a FileSystem) -> Option<&'a Path> {
+        match self.selected_entry(filesystem)? {
+            Entry::File(id) if self.preview => {
+                let file = &filesystem.files[id];
+                let local = file.remote.is_none() && !file.path.as_os_str().is_empty();
+                local.then_some(file.path.as_path())
+            }
+            _ => None,
+        }
+    }
+
+    /// File dropped onto a folder by the last mouse event, and that folder
+    pub fn take_drop(&mut self) -> Option<(FileId, FolderId)> {
+        self.dropped.take()
+    }
+
+    /// Stop dragging a file, when the mouse is released outside the filetree
+    pub fn cancel_drag(&mut self) {
+        self.dragged = None;
+        self.drop_row = None;
+    }
+
+    /// Terminal cursor position, on the selected row
+    pub fn cursor_position(&self) -> Position {
+        let area = self.area.get();
+        let row = self.selected.saturating_sub(self.scroll_y.get()) as u16;
+        Position::new(area.left(), area.top() + row)
+    }
+
+    /// Entry of the selected row
+    pub fn selected_entry(&self, filesystem: &FileSystem) -> Option<Entry> {
+        let entries = self.entries(filesystem);
+        entries.get(self.selected).map(|(entry, _)| *entry)
+    }
+
+    /// Copy the name or path of the selected entry to the unnamed register and the
+    /// clipboard
+    fn yank_path(&self, part: PathPart, state: &mut State) {
+        let filesystem = &state.filesystem;
+        let path = match self.selected_entry(filesystem) {
+            Some(Entry::File(id)) => &filesystem.files[id].path,
+            Some(Entry::Folder(id)) => &filesystem.folders[id].path,
+            None => return,
+        };
+        let text = match part {
+            PathPart::Name => path.file_name().unwrap_or_default().to_string_lossy(),
+            PathPart::Relative => filesystem.relative_path(path).to_string_lossy(),
+            PathPart::Absolute => path.to_string_lossy(),
+        }
+        .into_owned();
+        state.registers.set('"', text.clone());
+        match clipboard::copy(&text) {
+            Ok(()) => state.info(format!("Copied {}", text)),
+            Err(err) => state.error(err),
+        }
+    }
+
+    /// Move the selection to an entry, or keep it in bounds if the entry is gone
+    pub fn select(&mut self, entry: Entry, filesystem: &FileSystem) {
+        let entries = self.entries(filesystem);
+        match entries.iter().position(|(other, _)| *other == entry) {
+            Some(index) => self.selected = index,
+            None => self.selected = self.selected.min(entries.len().saturating_sub(1)),
+        }
+    }
+
+    /// Toggle the selected folder, or return the selected file to open
+    pub fn activate_selected(&self, state: &mut State) -> Option<FileId> {
+        let entries = self.entries(&state.filesystem);
+        self.activate(*entries.get(self.selected)?, state)
+    }
+
+    /// Toggle a folder, or return the file to open
+    fn activate(&self, (entry, _): (Entry, usize), state: &mut State) -> Option<FileId> {
+        match entry {
+            Entry::File(id) => Some(id),
+            Entry::Folder(id) => {
+                let folder = &mut state.filesystem.folders[id];
+                if !folder.open && is_link_cycle(&folder.path) {
+                    let message = format!("Symlink cycle: {} is one of its parents", folder.name);
+                    state.error(message);
+                    return None;
+                }
+                folder.open = !folder.open;
+                if folder.open && !folder.init {
+                    state.filesystem.load_folder(
+                        state.events.editor_sender.clone(),
+                        &state.jobs,
+                        id,
+                    );
+                }
+                None
+            }
+        }
+    }
+
+    /// Flatten the visible tree into a list of entries with their depth.
+    /// The extra roots of the workspace follow the root folder contents.
+    pub fn entries(&self, filesystem: &FileSystem) -> Vec<(Entry, usize)> {
+        let mut entries = vec![];
+        self.recurse_entries(filesystem.root, filesystem, &mut entries, 0);
+        for root in &filesystem.roots {
+            entries.push((Entry::Folder(*root), 0));
+            if filesystem.folders[*root].open {
+                self.recurse_entries(*root, filesystem, &mut entries, 1);
+            }
         }
+        entries
     }
 
-    /// Recursively display files, folders and their children
-    fn recurse_lines<'a>(
+    /// Recursively list files, folders and their open children
+    fn recurse_entries(
         &self,
         id: FolderId,
-        filesystem: &'a FileSystem,
-        lines: &mut Vec<Line<'a>>,
-        remaining: &mut u16,
+        filesystem: &FileSystem,
+        entries: &mut Vec<(Entry, usize)>,
         depth: usize,
     ) {
         let folder = &filesystem.folders[id];
         for folder_id in &folder.child_folders {
-            if *remaining == 0 {
-                return;
-            }
-
             let folder = &filesystem.folders[*folder_id];
             if folder.hidden() {
                 continue;
             }
-            lines.push(folder.line(depth));
+            entries.push((Entry::Folder(*folder_id), depth));
 
             if folder.open {
-                self.recurse_lines(*folder_id, filesystem, lines, remaining, depth + 1);
+                self.recurse_entries(*folder_id, filesystem, entries, depth + 1);
             }
-
-            *remaining = remaining.saturating_sub(1);
         }
 
         for file_id in &folder.child_files {
-            if *remaining == 0 {
-                return;
-            }
-
-            let file = &filesystem.files[*file_id];
-            lines.push(file.line(depth));
-            *remaining = remaining.saturating_sub(1);
+            entries.push((Entry::File(*file_id), depth));
         }
     }
 }
 
 impl Widget for FileTree {
     fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
-        let mut lines = vec![];
-        let mut remaining = area.height;
         let filesystem = &state.filesystem;
-        self.recurse_lines(filesystem.root, filesystem, &mut lines, &mut remaining, 0);
+        let icons = state.config.icons.icons();
+        let entries = self.entries(filesystem);
+
+        let area = match self.details && area.height > 8 {
+            true => {
+                let [tree, details] =
+                    Layout::vertical([Constraint::Fill(1), Constraint::Length(4)]).areas(area);
+                let path = entries.get(self.selected).map(|(entry, _)| match entry {
+                    Entry::File(id) => filesystem.files[*id].path.as_path(),
+                    Entry::Folder(id) => filesystem.folders[*id].path.as_path(),
+                });
+                render_details(path, details, buf);
+                tree
+            }
+            false => area,
+        };
+        let height = area.height as usize;
+
+        // Keep the selected row in view
+        if self.selected < self.scroll_y.get() {
+            self.scroll_y.set(self.selected);
+        } else if self.selected >= self.scroll_y.get() + height {
+            self.scroll_y.set(self.selected + 1 - height);
+        }
+
+        let lines = entries
+            .iter()
+            .enumerate()
+            .skip(self.scroll_y.get())
+            .take(height)
+            .map(|(i, (entry, depth))| {
+                let line = match entry {
+                    Entry::File(id) => {
+                        let open = filesystem.open_buffers.contains(id);
+                        filesystem.files[*id].line(*depth, open, icons)
+                    }
+                    Entry::Folder(id) => filesystem.folders[*id].line(*depth, icons),
+                };
+                let line = truncate_line(line, area.width as usize);
+                if self.drop_row == Some(i) {
+                    line.on_blue()
+                } else if i == self.selected {
+                    line.on_dark_gray()
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
 
         Text::from(lines).render(area, buf);
 
@@ -74,3 +345,41 @@ impl Widget for FileTree {
         self.area.get().contains(pos)
     }
 }
+
+/// Size, permissions and modification time of the selected entry, which is the only
+/// one whose metadata is read
+fn render_details(path: Option<&Path>, area: Rect, buf: &mut Buffer) {
+    let separator = Line::from("─".repeat(area.width as usize)).dark_gray();
+    let lines = std::iter::once(separator)
+        .chain(path.map(details).unwrap_or_default())
+        .map(|line| truncate_line(line, area.width as usize))
+        .collect::<Vec<_>>();
+    Text::from(lines).render(area, buf);
+}
+
+/// Size, permissions and modification time of a file or folder
+pub fn details(path: &Path) -> Vec<Line<'static>> {
+    let field = |name: &str, value: String| {
+        Line::from(vec![
+            Span::raw(format!(" {:<9}", name)).gray(),
+            Span::raw(value),
+        ])
+    };
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            let size = match metadata.is_dir() {
+                true => "directory".to_string(),
+                false => format_size(metadata.len()),
+            };
+            let modified = metadata
+                .modified()
+                .map_or("unknown".to_string(), format_time);
+            vec![
+                field("Size", size),
+                field("Mode", format_permissions(&metadata)),
+                field("Modified", modified),
+            ]
+        }
+        Err(err) => vec![field("Error", err.to_string())],
+    }
+}
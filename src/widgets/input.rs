@@ -0,0 +1,139 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ropey::Rope;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    state::commands::is_keyword,
+    utils::{ELLIPSIS, char_slice, truncate},
+};
+
+/// Single line text input, shared by the cmdline and the popup prompts
+#[derive(Debug, Default)]
+pub struct Input {
+    text: Rope,
+    cursor: usize,
+}
+
+impl Input {
+    /// Handle an editing key.
+    /// Returns false if the key is not an editing key (Enter, Esc, Tab...),
+    /// so that the owner can handle it.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) -> bool {
+        if key_event.modifiers.contains(KeyModifiers::CONTROL) {
+            match key_event.code {
+                // Delete the word before the cursor
+                KeyCode::Char('w') => {
+                    let end = self.word_start(char::is_whitespace);
+                    let start = match end.checked_sub(1).map(|i| self.text.char(i)) {
+                        Some(c) if is_keyword(c) => self.word_start_from(end, is_keyword),
+                        Some(_) => {
+                            self.word_start_from(end, |c| !is_keyword(c) && !c.is_whitespace())
+                        }
+                        None => end,
+                    };
+                    self.replace_before_cursor(start, "");
+                }
+                // Delete everything before the cursor
+                KeyCode::Char('u') => self.replace_before_cursor(0, ""),
+                _ => return false,
+            }
+            return true;
+        }
+        match key_event.code {
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.remove_char(self.cursor - 1);
+                self.cursor -= 1;
+            }
+            KeyCode::Left if self.cursor > 0 => self.cursor -= 1,
+            KeyCode::Right if self.cursor < self.text.len_chars() => self.cursor += 1,
+            KeyCode::Delete if self.cursor < self.text.len_chars() => self.remove_char(self.cursor),
+            KeyCode::Backspace | KeyCode::Left | KeyCode::Right | KeyCode::Delete => {}
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.len_chars(),
+            KeyCode::Char(c) => {
+                self.text.insert_char(self.cursor, c);
+                self.cursor += 1;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    /// Replace the whole text, moving the cursor to the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = Rope::from_str(text);
+        self.cursor = self.text.len_chars();
+    }
+
+    pub fn clear(&mut self) {
+        self.text = Rope::new();
+        self.cursor = 0;
+    }
+
+    /// Number of chars in the input
+    pub fn len(&self) -> usize {
+        self.text.len_chars()
+    }
+
+    /// Cursor offset in chars from the start of the input
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Start index of the run of chars matching `pred` right before the cursor
+    pub fn word_start(&self, pred: impl Fn(char) -> bool) -> usize {
+        self.word_start_from(self.cursor, pred)
+    }
+
+    fn word_start_from(&self, end: usize, pred: impl Fn(char) -> bool) -> usize {
+        let mut start = end;
+        while start > 0 && pred(self.text.char(start - 1)) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Replace the chars between `start` and the cursor, moving the cursor after the new text
+    pub fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        self.text.remove(start..self.cursor);
+        self.text.insert(start, text);
+        self.cursor = start + text.chars().count();
+    }
+
+    /// Insert text at the cursor, moving the cursor after it
+    pub fn insert_str(&mut self, text: &str) {
+        self.replace_before_cursor(self.cursor, text);
+    }
+
+    /// Part of the text that fits in `width` display cells, scrolled to keep the cursor in
+    /// view, and the cell of the cursor in it
+    pub fn view(&self, width: usize) -> (Span<'static>, u16) {
+        let text = self.text.to_string();
+        let before = char_slice(&text, 0..self.cursor);
+        // The cursor takes one more cell after the text
+        if before.width() < width {
+            return (
+                Span::raw(truncate(&text, width).into_owned()),
+                before.width() as u16,
+            );
+        }
+        // The start of the text is replaced by an ellipsis
+        let mut start = 0;
+        while start < self.cursor && 1 + char_slice(before, start..self.cursor).width() >= width {
+            start += 1;
+        }
+        let rest = char_slice(&text, start..usize::MAX);
+        let shown = format!("{}{}", ELLIPSIS, truncate(rest, width.saturating_sub(1)));
+        let cursor = 1 + char_slice(before, start..self.cursor).width();
+        (Span::raw(shown), cursor as u16)
+    }
+
+    fn remove_char(&mut self, idx: usize) {
+        self.text.remove(idx..idx + 1);
+    }
+}
@@ -2,9 +2,9 @@ use ratatui::prelude::*;
 use ratatui::{style::Color, widgets::Widget as RatatuiWidget};
 use ropey::Rope;
 
-use crate::cursor::Cursor;
-use crate::state::Mode;
-use crate::utils::whitespace_padding;
+use crate::position::{last_line, line_count};
+use crate::state::{ActiveCursor, CmdlineLayout, Icons, Mode, Prompt};
+use crate::utils::{truncate_line, whitespace_padding};
 use crate::{State, Widget};
 
 /// Lualine equivalent
@@ -16,40 +16,38 @@ impl Lualine {
         Self {}
     }
 
-    fn temp_render_from_cursor_and_rope(
+    /// Position of the cursor in the buffer of the focused pane
+    fn position(
         &self,
-        area: Rect,
-        buf: &mut Buffer,
         color: Color,
-        cursor: &Cursor,
+        icons: &Icons,
+        cursor: ActiveCursor,
         rope: &Rope,
-    ) {
-        let row = cursor.y + 1;
-        let col = cursor.x + 1;
-
-        // Right part
+    ) -> Line<'static> {
+        let row = cursor.line + 1;
+        let col = cursor.column + 1;
         let text = format!(
-            "  {}  {}{}:{}{} ",
-            if cursor.y == 0 {
+            " {} {} {} {}{}:{}{} ",
+            icons.position,
+            if cursor.line == 0 {
                 "Top".to_string()
-            } else if cursor.y == rope.len_lines() - 1 {
+            } else if cursor.line >= last_line(rope) {
                 "Bot".to_string()
             } else {
-                let percent = (cursor.y * 100) / rope.len_lines();
+                let percent = (cursor.line * 100) / line_count(rope);
                 let padding = if percent < 10 { " " } else { "" };
                 format!("{}{}%", padding, percent)
             },
+            icons.separator_thin,
             whitespace_padding(row, 3),
             row,
             col,
             whitespace_padding(col, 2),
         );
         Line::from(vec![
-            Span::from("").fg(color).on_black(),
+            Span::from(icons.separator_left).fg(color).on_black(),
             Span::from(text).black().bg(color),
         ])
-        .alignment(HorizontalAlignment::Right)
-        .render(area, buf);
     }
 }
 
@@ -57,16 +55,77 @@ impl Widget for Lualine {
     fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
         let text = state.mode.text();
         let color = state.mode.color();
+        let icons = state.config.icons.icons();
 
         // Left part
-        Line::from(vec![
+        let mut left = vec![
             Span::from(text).black().bg(color),
-            Span::from("").fg(color).on_black(),
+            Span::from(icons.separator_right).fg(color).on_black(),
+        ];
+        if let Some(register) = state.recording {
+            left.push(Span::from(format!(" recording @{}", register)).red());
+        }
+        if state.config.paste {
+            left.push(Span::from(" [paste]").yellow());
+        }
+        if state
+            .active_cursor
+            .get()
+            .and_then(|cursor| state.filesystem.files.get(cursor.file))
+            .is_some_and(|file| file.readonly)
+        {
+            left.push(Span::from(" [RO]").dark_gray());
+        }
+        if let Some(git) = &state.git {
+            let mut text = format!(" {}{}", icons.branch, git.branch);
+            if git.ahead > 0 {
+                text.push_str(&format!(" {}{}", icons.ahead, git.ahead));
+            }
+            if git.behind > 0 {
+                text.push_str(&format!(" {}{}", icons.behind, git.behind));
+            }
+            left.push(Span::from(text));
+        }
+        left.extend(diagnostics(state, icons));
+        if let Some(text) = search_options(state) {
+            left.push(Span::from(text).dark_gray());
+        }
+        match state.search_count.map(|count| count.result) {
+            Some(Some((current, total))) => {
+                left.push(Span::from(format!(" match {} of {}", current, total)).cyan())
+            }
+            Some(None) => left.push(Span::from(" counting matches...").dark_gray()),
+            None => {}
+        }
+        // The bottom cmdline displays the messages instead
+        if let Some(message) = &state.message
+            && state.config.cmdline == CmdlineLayout::Float
+        {
+            left.push(Span::from(" "));
+            left.push(message.span());
+        }
+        // The pending keys are kept visible over the end of the messages
+        let pending = match state.config.showcmd && !state.pending_keys.is_empty() {
+            true => Line::from(format!(" {} ", state.pending_keys)),
+            false => Line::default(),
+        };
+        // Right part, following the focused pane
+        let position = state
+            .active_buffer()
+            .map(|(rope, cursor)| self.position(color, icons, cursor, rope))
+            .unwrap_or_default();
+        let [area, right] = Layout::horizontal([
+            Constraint::Fill(1),
+            Constraint::Length(position.width() as u16),
         ])
-        .render(area, buf);
+        .areas(area);
+        position.render(right, buf);
 
-        // Right part (TODO)
-        // self.temp_render_from_cursor_and_rope(area, buf, color, &state.cursor, &state.rope);
+        let width = (area.width as usize).saturating_sub(pending.width());
+        truncate_line(Line::from(left), width).render(area, buf);
+        pending
+            .alignment(HorizontalAlignment::Right)
+            .render(area, buf);
     }
 
     /// Lualine is not click-sensitive
@@ -75,12 +134,63 @@ impl Widget for Lualine {
     }
 }
 
+/// Diagnostic counts of the buffer of the focused pane in the severity colors, followed
+/// by the workspace total when other files have diagnostics
+fn diagnostics(state: &State, icons: &Icons) -> Vec<Span<'static>> {
+    let buffer = state
+        .active_cursor
+        .get()
+        .and_then(|cursor| state.filesystem.files.get(cursor.file))
+        .map(|file| state.diagnostics.counts(&file.path))
+        .unwrap_or_default();
+    let workspace = state.diagnostics.workspace_counts();
+    let mut spans = [
+        (buffer.errors, icons.error, Color::Red),
+        (buffer.warnings, icons.warning, Color::Yellow),
+        (buffer.infos, icons.info, Color::Blue),
+    ]
+    .into_iter()
+    .filter(|(count, _, _)| *count > 0)
+    .map(|(count, icon, color)| Span::from(format!(" {}{}", icon, count)).fg(color))
+    .collect::<Vec<_>>();
+    if workspace.total() > buffer.total() {
+        let text = format!(" ({} in workspace)", workspace.total());
+        spans.push(Span::from(text).dark_gray());
+    }
+    spans
+}
+
+/// Search options indicator, while typing a search or when a search is active
+fn search_options(state: &State) -> Option<String> {
+    let config = &state.config;
+    let (case, literal) = match (state.mode, state.prompt, &state.search) {
+        (Mode::Command, Prompt::Search { .. }, _) => {
+            let case = match (config.ignorecase, config.smartcase) {
+                (true, true) => "smartcase",
+                (true, false) => "ignorecase",
+                (false, _) => "matchcase",
+            };
+            (case, !config.magic)
+        }
+        (_, _, Some(search)) => {
+            let case = match search.pattern.ignorecase {
+                true => "ignorecase",
+                false => "matchcase",
+            };
+            (case, search.pattern.literal)
+        }
+        _ => return None,
+    };
+    let kind = if literal { "literal" } else { "regex" };
+    Some(format!(" [{} {}]", case, kind))
+}
+
 impl Mode {
     fn color(&self) -> Color {
         match self {
             Mode::Normal => Color::Blue,
             Mode::Insert => Color::Green,
-            Mode::Visual => Color::Magenta,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => Color::Magenta,
             Mode::Command => Color::Yellow,
         }
     }
@@ -90,6 +200,8 @@ impl Mode {
             Mode::Normal => " NORMAL ",
             Mode::Insert => " INSERT ",
             Mode::Visual => " VISUAL ",
+            Mode::VisualLine => " V-LINE ",
+            Mode::VisualBlock => " V-BLOCK ",
             Mode::Command => " COMMAND ",
         }
     }
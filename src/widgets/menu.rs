@@ -0,0 +1,136 @@
+use std::cell::Cell;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Clear, Widget as RatatuiWidget};
+
+use crate::{State, Widget};
+
+/// Entry of the context menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuItem {
+    Copy,
+    Paste,
+    GotoDefinition,
+    Open,
+    Rename,
+    Delete,
+}
+
+impl MenuItem {
+    /// Entries for a right click in a text pane
+    pub const PANE: &[Self] = &[Self::Copy, Self::Paste, Self::GotoDefinition];
+    /// Entries for a right click in the filetree
+    pub const TREE: &[Self] = &[Self::Open, Self::Rename, Self::Delete];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Copy => "Copy",
+            Self::Paste => "Paste",
+            Self::GotoDefinition => "Go to definition",
+            Self::Open => "Open",
+            Self::Rename => "Rename",
+            Self::Delete => "Delete",
+        }
+    }
+}
+
+/// Floating menu opened at the mouse position on right click
+#[derive(Debug)]
+pub struct ContextMenu {
+    items: &'static [MenuItem],
+    /// Clicked position, where the menu is anchored
+    position: Position,
+    selected: usize,
+
+    // Memoized values from the rendering pass
+    area: Cell<Rect>,
+}
+
+impl ContextMenu {
+    pub fn new(items: &'static [MenuItem], position: Position) -> Self {
+        Self {
+            items,
+            position,
+            selected: 0,
+            area: Cell::new(Rect::default()),
+        }
+    }
+
+    pub fn selected(&self) -> MenuItem {
+        self.items[self.selected]
+    }
+
+    /// Move the selection with the navigation keys
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Tab => {
+                self.selected = (self.selected + 1) % self.items.len();
+            }
+            KeyCode::Char('k') | KeyCode::Up | KeyCode::BackTab => {
+                self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+            }
+            _ => {}
+        }
+    }
+
+    /// Entry under the mouse, which becomes selected
+    pub fn hover(&mut self, pos: Position) -> Option<MenuItem> {
+        let area = self.area.get();
+        if !self.contains(pos) || pos.y == area.top() || pos.y + 1 >= area.bottom() {
+            return None;
+        }
+        self.selected = (pos.y - area.top() - 1) as usize;
+        Some(self.selected())
+    }
+}
+
+impl Widget for ContextMenu {
+    fn render(&self, area: Rect, buf: &mut Buffer, _: &State) {
+        let width = self
+            .items
+            .iter()
+            .map(|item| item.label().len() as u16 + 4)
+            .max()
+            .unwrap_or_default();
+        let height = self.items.len() as u16 + 2;
+
+        // Open towards the inside of the screen
+        let x = match self.position.x + width > area.right() {
+            true => area.right().saturating_sub(width),
+            false => self.position.x,
+        };
+        let y = match self.position.y + height > area.bottom() {
+            true => self.position.y.saturating_sub(height - 1),
+            false => self.position.y,
+        };
+        let popup = Rect::new(x, y, width, height).intersection(area);
+
+        Clear.render(popup, buf);
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().magenta());
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+
+        let lines = self
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let line = Line::from(format!(" {} ", item.label()));
+                match i == self.selected {
+                    true => line.on_dark_gray(),
+                    false => line,
+                }
+            })
+            .collect::<Vec<_>>();
+        Text::from(lines).render(inner, buf);
+
+        self.area.set(popup);
+    }
+
+    fn contains(&self, pos: Position) -> bool {
+        self.area.get().contains(pos)
+    }
+}
@@ -0,0 +1,244 @@
+use std::cell::Cell;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::Flex;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Clear, Paragraph, Widget as RatatuiWidget};
+
+use crate::state::{Keymaps, Mode, commands, help};
+use crate::utils::truncate;
+use crate::widgets::Input;
+use crate::{State, Widget};
+
+/// What the chosen palette entry does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteAction {
+    /// Execute an ex command line
+    Command(String),
+    /// Type keys in normal mode
+    Keys(String),
+    /// Open the cmdline with a command typed, to add its arguments
+    Edit(String),
+}
+
+/// Keybinding or ex command listed in the palette
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Description of a keybinding, or `:name` of a command
+    name: String,
+    /// Summary of a command from the help
+    detail: String,
+    /// Keys bound to the entry
+    keys: String,
+    action: PaletteAction,
+}
+
+/// Floating picker of every keybinding and ex command, filtered by fuzzy matching the
+/// typed text, opened with `CTRL-P`
+#[derive(Debug, Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    input: Input,
+    entries: Vec<Entry>,
+    /// Indexes of the entries matching the input, best first
+    matches: Vec<usize>,
+    selected: usize,
+    scroll_y: Cell<usize>,
+}
+
+impl CommandPalette {
+    /// Open the palette, listing the current mappings and user commands
+    pub fn open(&mut self, state: &State) {
+        let mut entries = vec![];
+        for map in state.keymaps.maps.iter().rev() {
+            if map.modes.contains(&Mode::Normal) {
+                entries.push(Entry {
+                    name: format!(":{}", map.command),
+                    detail: "mapping".to_string(),
+                    keys: map.lhs.clone(),
+                    action: PaletteAction::Command(map.command.clone()),
+                });
+            }
+        }
+        for (lhs, description) in Keymaps::defaults(Mode::Normal) {
+            // Keys waiting for an argument, like `"{reg}`, are typed up to it
+            let keys = lhs.split('{').next().unwrap_or(lhs);
+            entries.push(Entry {
+                name: description.to_string(),
+                detail: String::new(),
+                keys: lhs.to_string(),
+                action: PaletteAction::Keys(keys.to_string()),
+            });
+        }
+        let user = state.commands.user.keys().cloned();
+        for name in commands::builtin_names().map(str::to_string).chain(user) {
+            let mapped = state
+                .keymaps
+                .maps
+                .iter()
+                .find(|map| map.modes.contains(&Mode::Normal) && map.command == name);
+            entries.push(Entry {
+                name: format!(":{}", name),
+                detail: help::command_summary(&name).unwrap_or_default(),
+                keys: mapped.map(|map| map.lhs.clone()).unwrap_or_default(),
+                action: PaletteAction::Command(name),
+            });
+        }
+        self.entries = entries;
+        self.input.clear();
+        self.open = true;
+        self.filter();
+    }
+
+    /// Handle a key event while the palette is open.
+    /// Returns the action of the chosen entry, if any.
+    pub fn handle_key_event(&mut self, key_event: KeyEvent) -> Option<PaletteAction> {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let count = self.matches.len().max(1);
+        match key_event.code {
+            KeyCode::Esc => self.open = false,
+            KeyCode::Down => self.selected = (self.selected + 1) % count,
+            KeyCode::Char('n') if ctrl => self.selected = (self.selected + 1) % count,
+            KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Char('p') if ctrl => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Enter | KeyCode::Tab => {
+                let entry = &self.entries[*self.matches.get(self.selected)?];
+                self.open = false;
+                return match (&entry.action, key_event.code) {
+                    (PaletteAction::Command(command), KeyCode::Tab) => {
+                        Some(PaletteAction::Edit(format!("{} ", command)))
+                    }
+                    (action, _) => Some(action.clone()),
+                };
+            }
+            _ => {
+                if self.input.handle_key_event(key_event) {
+                    self.filter();
+                }
+            }
+        }
+        None
+    }
+
+    /// Keep the entries matching the input, sorted by score
+    fn filter(&mut self) {
+        let query = self.input.text().to_lowercase();
+        let mut scored = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| score(entry, &query).map(|score| (score, i)))
+            .collect::<Vec<_>>();
+        scored.sort();
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+}
+
+/// Score of an entry for a query, lower being better: entries whose keys are the
+/// query come first, then those whose text contains it, then fuzzy matches
+fn score(entry: &Entry, query: &str) -> Option<(usize, usize)> {
+    let text = format!("{} {} {}", entry.name, entry.keys, entry.detail).to_lowercase();
+    if !query.is_empty() && entry.keys.to_lowercase() == query {
+        Some((0, 0))
+    } else if let Some(start) = text.find(query) {
+        Some((1, start))
+    } else {
+        fuzzy_score(&text, query).map(|gaps| (2, gaps))
+    }
+}
+
+/// Fuzzy match of a query in a text: the chars of the query must appear in order, and
+/// each gap between them costs its length
+fn fuzzy_score(text: &str, query: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = text.chars().enumerate();
+    let mut last = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let (i, _) = chars.find(|(_, c)| *c == q)?;
+        if let Some(last) = last {
+            score += i - last - 1;
+        }
+        last = Some(i);
+    }
+    Some(score)
+}
+
+impl Widget for CommandPalette {
+    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        let [popup] = Layout::vertical([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().magenta())
+            .title_alignment(HorizontalAlignment::Center)
+            .title(" Command palette ")
+            .title_bottom(" <Enter> run  <Tab> edit command  <Esc> close ");
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+
+        let [prompt, status, list] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+
+        // The prompt takes 3 cells, and the cursor one more after the text
+        let (text, cursor) = self
+            .input
+            .view((prompt.width as usize).saturating_sub(3 + 1));
+        Line::from(vec![Span::from(" > ").cyan().bold(), text]).render(prompt, buf);
+        let count = format!(" {} of {}", self.matches.len(), self.entries.len());
+        Span::raw(count).dark_gray().render(status, buf);
+
+        // Keep the selected entry in view
+        let height = list.height as usize;
+        if self.selected < self.scroll_y.get() {
+            self.scroll_y.set(self.selected);
+        } else if self.selected >= self.scroll_y.get() + height {
+            self.scroll_y.set(self.selected + 1 - height);
+        }
+
+        let width = list.width as usize;
+        let keys_width = 12;
+        let lines = self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(self.scroll_y.get())
+            .take(height)
+            .map(|(i, entry)| {
+                let entry = &self.entries[*entry];
+                let name = truncate(&entry.name, 30);
+                let keys = truncate(&entry.keys, keys_width);
+                let detail_width = width.saturating_sub(1 + 30 + 1 + keys_width + 1);
+                let line = Line::from(vec![
+                    Span::raw(format!(" {:<30} ", name)),
+                    Span::raw(format!("{:<1$} ", keys, keys_width)).cyan(),
+                    Span::raw(truncate(&entry.detail, detail_width)).dark_gray(),
+                ]);
+                match i == self.selected {
+                    true => line.on_dark_gray(),
+                    false => line,
+                }
+            })
+            .collect::<Vec<_>>();
+        Paragraph::new(lines).render(list, buf);
+
+        state
+            .cursor_pos
+            .set(Position::new(prompt.left() + 3 + cursor, prompt.top()));
+    }
+
+    /// The palette is a popup that is click-agnostic
+    fn contains(&self, _: Position) -> bool {
+        false
+    }
+}
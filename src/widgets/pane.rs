@@ -1,31 +1,159 @@
-use std::cell::Cell;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    ops::Range,
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
-use crate::{State, Widget, cursor::Cursor, state::FileId, utils::number_digits};
+use crate::{
+    State, Widget,
+    cursor::Cursor,
+    format::{DEFAULT_TEXTWIDTH, align, is_prose, line_prefix, reflow},
+    numbers::{add_to_numbers, find_number, insert_sequence},
+    position::{cell_to_col, col_to_cell, last_line, line_count, line_len},
+    state::{
+        BufferOptions, Edit, FileId, Jump, KeyPreset, LineOperation, Mode, NON_TEXT, Pattern,
+        Prompt, Search, SignColumn, SortFlags, WindowOptions,
+        clipboard::{self, Selection},
+        commands::{complete_path, is_keyword},
+        diff, digraphs, is_register,
+    },
+    utils::{number_digits, truncate},
+    widgets::Action,
+};
 
 use ratatui::{
     prelude::*,
-    widgets::{Paragraph, Widget as RatatuiWidget},
+    widgets::{Clear, Paragraph, Widget as RatatuiWidget},
 };
+use regex::Regex;
+use ropey::Rope;
+
+use block::{Block, BlockInsert, Side};
+use fold::fold_text;
+use highlight::{Layers, LineCache, guides};
+use operator::{Motion, Operator};
+
+mod basic;
+mod block;
+mod fold;
+mod highlight;
+mod operator;
+
+/// Move through the undo tree of a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoMotion {
+    /// `u`: revert the last change
+    Undo,
+    /// `CTRL-R`: reapply the last undone change
+    Redo,
+    /// `g-`: go to the previous text state in time, across branches
+    Earlier,
+    /// `g+`: go to the next text state in time
+    Later,
+    /// `:undo {N}`: go to the text after a numbered change
+    To(usize),
+}
+
+/// Error displayed when editing a read-only buffer
+pub const READONLY_ERROR: &str = "E21: Cannot make changes, 'modifiable' is off";
+
+/// Pending keys of `CTRL-R` in insert mode, waiting for a register name
+const INSERT_REGISTER: &str = "<C-r>";
+/// Pending keys of `CTRL-V` in insert mode, waiting for a literal key or codepoint digits
+const INSERT_LITERAL: &str = "<C-v>";
+/// Pending keys of `CTRL-K` in insert mode, waiting for the two chars of a digraph
+const INSERT_DIGRAPH: &str = "<C-k>";
+/// Pending keys of `CTRL-X` in insert mode, waiting for the kind of completion
+const INSERT_COMPLETE: &str = "<C-x>";
+/// Pending keys of `CTRL-G` in insert mode, waiting for `u` to break the undo group
+const INSERT_UNDO: &str = "<C-g>";
+/// Background of the cursor line, with `cursorline`
+const CURSORLINE_STYLE: Style = Style::new().bg(Color::Rgb(48, 48, 48));
+/// Background of the guide columns of the filetype
+const GUIDE_STYLE: Style = Style::new().bg(Color::Rgb(48, 48, 48));
+/// Line standing for the lines of a closed fold
+const FOLD_STYLE: Style = Style::new().fg(Color::Cyan).bg(Color::Rgb(48, 48, 48));
+/// Maximum number of rows of the completion popup
+const COMPLETION_HEIGHT: usize = 10;
+/// Cells of the sign column
+const SIGN_WIDTH: usize = 2;
+
+/// Path completion started with `CTRL-X CTRL-F` in insert mode
+#[derive(Debug)]
+struct PathCompletion {
+    /// Char index where the completed path starts
+    start: usize,
+    /// Path typed before completing, restored by `CTRL-E`
+    typed: String,
+    /// Byte length of the folder part of the completed paths, hidden in the popup
+    folder: usize,
+    /// Matching paths, and the index of the inserted one
+    items: Vec<String>,
+    selected: usize,
+}
 
 /// Single pane widget, linked to a single file
+#[derive(Debug)]
 pub struct Pane {
-    file: FileId,
-    cursor: Cursor,
+    pub file: FileId,
+    pub cursor: Cursor,
     scroll_y: Cell<usize>,
+    /// Keys typed so far of a pending multi-key command
+    pending: String,
+    /// Char index where the visual selection started
+    visual_anchor: usize,
+    /// Register selected with `"` for the next command
+    register: Option<char>,
+    /// Count typed before `G` or `gg`, like `12G`
+    count: Option<usize>,
+    /// Whether the visual selection was started by the keys of the `basic` preset
+    selecting: bool,
+    /// Path completion in progress, shown in a popup
+    completion: Option<PathCompletion>,
+    /// Window options set with `:setlocal`, overriding the global ones
+    pub local: WindowOptions<Option<bool>>,
+    /// Closed folds, as ranges of lines shown as a single one
+    folds: Vec<(usize, usize)>,
+    /// Insertion on the lines of a visual block, repeated when leaving insert mode
+    block_insert: Option<BlockInsert>,
 
     // Memoized values from the rendering pass
     area: Cell<Rect>,
+    /// Whether the pane is focused, which shows its visual selection
+    pub focused: Cell<bool>,
+    /// Styled lines of the last frame
+    lines: LineCache,
+    /// Columns before the text: the line numbers and the space after them
     gutter_width: Cell<u16>,
+    /// Buffer line and first display cell of each row of the text
+    rows: RefCell<Vec<(usize, usize)>>,
+    /// Display width of the text before the cursor on its line, with wide chars and tabs
+    cursor_column: Cell<usize>,
 }
 
 impl Pane {
     pub fn new(file: FileId) -> Self {
         Self {
             area: Cell::new(Rect::default()),
+            focused: Cell::new(true),
+            lines: LineCache::default(),
             gutter_width: Cell::new(0),
+            rows: RefCell::new(vec![]),
+            cursor_column: Cell::new(0),
             cursor: Cursor::default(),
             file,
             scroll_y: Cell::new(0),
+            pending: String::new(),
+            visual_anchor: 0,
+            register: None,
+            count: None,
+            selecting: false,
+            completion: None,
+            local: WindowOptions::default(),
+            folds: vec![],
+            block_insert: None,
         }
     }
 
@@ -33,9 +161,1421 @@ impl Pane {
     /// on the screen from the inner relative cursor position.
     pub fn cursor_position(&self) -> Position {
         let area = self.area.get();
-        let x = (self.gutter_width.get() + 1 + area.left()).saturating_add(self.cursor.x as u16);
-        let y = (self.cursor.y - self.scroll_y.get()) as u16 + area.top();
-        Position::new(x, y)
+        let cell = self.cursor_column.get();
+        // Last row of the cursor line starting before the cursor, when wrapped
+        let (row, start) = self
+            .rows
+            .borrow()
+            .iter()
+            .enumerate()
+            .rfind(|(_, (line, start))| *line == self.cursor.y && *start <= cell)
+            .map_or(
+                (self.cursor.y.saturating_sub(self.scroll_y.get()), 0),
+                |(row, (_, start))| (row, *start),
+            );
+        let x = (self.gutter_width.get() + area.left()).saturating_add((cell - start) as u16);
+        Position::new(x, row as u16 + area.top())
+    }
+
+    /// Scroll so that the cursor line is in the middle of the view
+    pub fn center_cursor(&self, rope: &Rope, state: &State) {
+        let height = self.area.get().height as usize;
+        self.scroll_y.set(
+            self.cursor
+                .y
+                .saturating_sub(height / 2)
+                .min(self.max_scroll(rope, state)),
+        );
+    }
+
+    /// Display cells between two tab stops in the buffer
+    fn tabstop(&self, state: &State) -> usize {
+        state
+            .filesystem
+            .files
+            .get(self.file)
+            .and_then(|file| file.options.as_ref())
+            .unwrap_or(&state.config.buffer)
+            .tabstop
+    }
+
+    /// Scroll a page forward or backward, keeping two lines of the previous one, the
+    /// cursor moving along when it leaves the view. Half pages move the cursor by as many
+    /// lines, like `CTRL-D` and `CTRL-U`.
+    fn scroll_page(&mut self, forward: bool, half: bool, state: &State) -> Option<Action> {
+        let rope = state.filesystem.files.get(self.file)?.buffer.as_ref()?;
+        let height = self.area.get().height as usize;
+        let lines = match half {
+            true => (height / 2).max(1),
+            false => height.saturating_sub(2).max(1),
+        };
+        let scroll = match forward {
+            true => (self.scroll_y.get() + lines).min(self.max_scroll(rope, state)),
+            false => self.scroll_y.get().saturating_sub(lines),
+        };
+        self.scroll_y.set(scroll);
+        match (half, forward) {
+            (true, true) => self.cursor.move_down_n(rope, lines),
+            (true, false) => self.cursor.move_up_n(rope, lines),
+            (false, _) => {}
+        }
+
+        let margin = state
+            .config
+            .cursor_margin_y
+            .min(height.saturating_sub(1) / 2);
+        if self.cursor.y < scroll + margin {
+            self.cursor
+                .move_down_n(rope, scroll + margin - self.cursor.y);
+        } else if self.cursor.y + margin >= scroll + height {
+            self.cursor
+                .move_up_n(rope, self.cursor.y + margin + 1 - scroll - height);
+        }
+        None
+    }
+
+    /// Last scroll offset: the last line at the bottom of the view,
+    /// or at its top when scrolling past the end of the buffer
+    fn max_scroll(&self, rope: &Rope, state: &State) -> usize {
+        match state.config.scrollpasteof {
+            true => last_line(rope),
+            false => line_count(rope).saturating_sub(self.area.get().height as usize),
+        }
+    }
+
+    /// Whether the next key is inserted literally or as part of a digraph,
+    /// bypassing the mappings
+    pub fn inserting_literal(&self) -> bool {
+        self.pending.starts_with(INSERT_LITERAL) || self.pending.starts_with(INSERT_DIGRAPH)
+    }
+
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) -> Option<Action> {
+        // The match count is only shown until the next non-search command
+        if state.mode != Mode::Normal || !matches!(key_event.code, KeyCode::Char('n' | 'N')) {
+            state.search_count = None;
+        }
+
+        // Cycle through the path completions, other keys accepting the inserted one
+        if state.mode != Mode::Insert {
+            self.completion = None;
+        }
+        if let Some(completion) = &self.completion {
+            let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+            let count = completion.items.len();
+            let next = (completion.selected + 1) % count;
+            let previous = (completion.selected + count - 1) % count;
+            match key_event.code {
+                KeyCode::Char('n') if ctrl => return self.select_completion(next, state),
+                KeyCode::Down => return self.select_completion(next, state),
+                KeyCode::Char('p') if ctrl => return self.select_completion(previous, state),
+                KeyCode::Up => return self.select_completion(previous, state),
+                KeyCode::Char('e') if ctrl => {
+                    let typed = completion.typed.clone();
+                    self.replace_completed(&typed, state);
+                    self.completion = None;
+                    return None;
+                }
+                KeyCode::Char('y') if ctrl => {
+                    self.completion = None;
+                    return None;
+                }
+                _ => self.completion = None,
+            }
+        }
+
+        // Complete the path before the cursor after CTRL-X CTRL-F
+        if state.mode == Mode::Insert && self.pending == INSERT_COMPLETE {
+            self.pending.clear();
+            if key_event.code == KeyCode::Char('f')
+                && key_event.modifiers.contains(KeyModifiers::CONTROL)
+            {
+                self.complete_path(state);
+            }
+            return None;
+        }
+
+        // Insert a key literally after CTRL-V,
+        // or a codepoint from up to 4 hex digits after CTRL-V u, 8 after CTRL-V U
+        if state.mode == Mode::Insert && self.pending.starts_with(INSERT_LITERAL) {
+            let digits = self.pending[INSERT_LITERAL.len()..].to_string();
+            let mut chars = digits.chars();
+            match (chars.next(), key_event.code) {
+                (None, KeyCode::Char(c @ ('u' | 'U')))
+                    if !key_event.modifiers.contains(KeyModifiers::CONTROL) =>
+                {
+                    self.pending.push(c);
+                    return None;
+                }
+                (None, _) => {
+                    self.pending.clear();
+                    if let Some(c) = literal_char(key_event) {
+                        self.insert_text(&c.to_string(), state);
+                    }
+                    return None;
+                }
+                (Some(kind), KeyCode::Char(c)) if c.is_ascii_hexdigit() => {
+                    self.pending.push(c);
+                    let max = if kind == 'u' { 4 } else { 8 };
+                    if digits.len() == max {
+                        let hex = self.pending[INSERT_LITERAL.len() + 1..].to_string();
+                        self.pending.clear();
+                        self.insert_codepoint(&hex, state);
+                    }
+                    return None;
+                }
+                // Other keys end the digits, and are then handled as usual
+                (Some(_), _) => {
+                    self.pending.clear();
+                    self.insert_codepoint(chars.as_str(), state);
+                }
+            }
+        }
+
+        // Insert a digraph after CTRL-K, or the second char when there is none
+        if state.mode == Mode::Insert && self.pending.starts_with(INSERT_DIGRAPH) {
+            let KeyCode::Char(c) = key_event.code else {
+                self.pending.clear();
+                return None;
+            };
+            match self.pending[INSERT_DIGRAPH.len()..].chars().next() {
+                None => self.pending.push(c),
+                Some(first) => {
+                    self.pending.clear();
+                    let c = digraphs::lookup(first, c).unwrap_or(c);
+                    self.insert_text(&c.to_string(), state);
+                }
+            }
+            return None;
+        }
+
+        // Insert a register after CTRL-R
+        if state.mode == Mode::Insert && self.pending == INSERT_REGISTER {
+            self.pending.clear();
+            if let KeyCode::Char(c) = key_event.code {
+                match state.register(c) {
+                    Ok(text) => self.insert_text(&text, state),
+                    Err(err) => state.error(err),
+                }
+            }
+            return None;
+        }
+
+        if state.config.keypreset == KeyPreset::Basic
+            && self.pending.is_empty()
+            && let Some(action) = self.handle_basic_key(key_event, state)
+        {
+            return action;
+        }
+
+        // Pager keys of `-R`, like less
+        if state.pager
+            && state.mode == Mode::Normal
+            && self.pending.is_empty()
+            && key_event.modifiers.is_empty()
+        {
+            match key_event.code {
+                KeyCode::Char(' ') => return self.scroll_page(true, false, state),
+                KeyCode::Char('b') => return self.scroll_page(false, false, state),
+                KeyCode::Char('q') => return Some(Action::Command("qall".to_string())),
+                _ => {}
+            }
+        }
+
+        // Scroll by full or half pages, forward with `CTRL-F` and `CTRL-D`
+        if state.mode != Mode::Insert
+            && self.pending.is_empty()
+            && key_event.modifiers == KeyModifiers::CONTROL
+        {
+            let page = match key_event.code {
+                KeyCode::Char('f') => Some((true, false)),
+                KeyCode::Char('b') => Some((false, false)),
+                KeyCode::Char('d') => Some((true, true)),
+                KeyCode::Char('u') => Some((false, true)),
+                _ => None,
+            };
+            if let Some((forward, half)) = page {
+                self.count = None;
+                return self.scroll_page(forward, half, state);
+            }
+        }
+
+        let register = self.register.take();
+        // Registers are read before borrowing the buffer, as the selections are read from the state
+        let paste = match (state.mode, self.pending.as_str(), key_event.code) {
+            (
+                Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                "",
+                KeyCode::Char('p' | 'P'),
+            ) => Some(state.register(register.unwrap_or('"'))),
+            _ => None,
+        };
+
+        let file = state.filesystem.files.get_mut(self.file)?;
+        let options = file
+            .options
+            .clone()
+            .unwrap_or_else(|| state.config.buffer.clone());
+        let rope = &mut Edit::new(file.buffer.as_mut()?, &mut file.changes);
+        let cursor = &mut self.cursor;
+
+        // Normal mode commands are undone one by one, even when typed in a burst
+        if state.mode != Mode::Insert && self.pending.is_empty() {
+            rope.break_undo();
+        }
+        // Start a new undo group within the insert session after CTRL-G u
+        if state.mode == Mode::Insert && self.pending == INSERT_UNDO {
+            self.pending.clear();
+            if key_event.code == KeyCode::Char('u') {
+                rope.break_undo();
+            }
+            return None;
+        }
+
+        if file.readonly && modifies(state.mode, &self.pending, key_event) {
+            self.pending.clear();
+            state.error(READONLY_ERROR);
+            return None;
+        }
+        // Follow the links of help pages
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let follow = match key_event.code {
+            KeyCode::Char(']' | '5') => ctrl,
+            KeyCode::Enter => file.readonly,
+            _ => false,
+        };
+        if state.mode == Mode::Normal && self.pending.is_empty() && follow {
+            let line = rope.line(cursor.y).to_string();
+            return tag_at(&line, cursor.x).map(Action::Help);
+        }
+
+        let count = self.count.take();
+        if matches!(
+            state.mode,
+            Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock
+        ) && !self.pending.is_empty()
+        {
+            let pending = std::mem::take(&mut self.pending);
+            let textwidth = match state.config.textwidth {
+                0 => DEFAULT_TEXTWIDTH,
+                width => width,
+            };
+            // Operator-pending mode, the operator waiting for a motion or text object
+            if state.mode == Mode::Normal
+                && let Some(operator) = pending.chars().next().and_then(Operator::from_key)
+            {
+                let key = operator::motion_key(key_event.code)?;
+                let keys = format!("{}{}", &pending[1..], key);
+                let target = match operator::parse(operator, &keys, rope, cursor) {
+                    Motion::Pending => {
+                        self.pending = format!("{}{}", pending, key);
+                        self.register = register;
+                        return None;
+                    }
+                    Motion::Invalid => return None,
+                    Motion::Target(target) => target,
+                };
+                let text = operator::apply(operator, target, rope, cursor);
+                if operator == Operator::Change {
+                    state.mode = Mode::Insert;
+                }
+                if !text.is_empty() {
+                    if operator != Operator::Yank {
+                        file.modified = !file.scratch;
+                    }
+                    state.set_register(register.unwrap_or(operator.default_register()), text);
+                }
+                return None;
+            }
+            match (state.mode, pending.as_str(), key_event.code) {
+                (Mode::Normal, "q", KeyCode::Char(':')) => {
+                    return Some(Action::CmdlineWindow(Prompt::Command));
+                }
+                (Mode::Normal, "q", KeyCode::Char('/')) => {
+                    return Some(Action::CmdlineWindow(Prompt::Search { backward: false }));
+                }
+                (Mode::Normal, "q", KeyCode::Char('?')) => {
+                    return Some(Action::CmdlineWindow(Prompt::Search { backward: true }));
+                }
+                (Mode::Normal, "q", KeyCode::Char(c)) if c.is_ascii_alphanumeric() || c == '"' => {
+                    return Some(Action::Record(c));
+                }
+                (Mode::Normal, "@", KeyCode::Char(c)) if is_register(c) || c == '@' => {
+                    return Some(Action::Replay(c));
+                }
+                (
+                    Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "\"",
+                    KeyCode::Char(c),
+                ) if is_register(c) => {
+                    self.register = Some(c);
+                }
+                (Mode::Normal, "g", KeyCode::Char(c @ ('-' | '+'))) => {
+                    let motion = match c {
+                        '+' => UndoMotion::Later,
+                        _ => UndoMotion::Earlier,
+                    };
+                    self.undo(motion, state);
+                    return None;
+                }
+                // Jump to the next or previous hunk of a diff
+                (Mode::Normal, "]" | "[", KeyCode::Char('c')) => {
+                    match diff::next_hunk(rope, cursor.y, pending == "]") {
+                        Some(line) => cursor.move_to_char(rope, rope.line_to_char(line)),
+                        None => state.error("No more hunks"),
+                    }
+                }
+                (Mode::Normal, "z", KeyCode::Char(c @ ('c' | 'o' | 'a' | 'M' | 'R'))) => {
+                    let filetype = file.filetype;
+                    let folds = &mut self.folds;
+                    if let Err(err) =
+                        fold::fold_command(folds, c, cursor.y, rope, filetype, options.tabstop)
+                    {
+                        state.error(err);
+                    }
+                }
+                (Mode::Normal, "g", KeyCode::Char('q')) => self.pending = "gq".to_string(),
+                (Mode::Normal, "g", KeyCode::Char('c')) => self.pending = "gc".to_string(),
+                (Mode::Normal, ">", KeyCode::Char('>'))
+                | (Mode::Normal, "<", KeyCode::Char('<')) => {
+                    let right = pending == ">";
+                    shift_lines(rope, cursor.y, cursor.y, &options, right);
+                    file.modified = !file.scratch;
+                    cursor.move_first_non_blank(rope);
+                }
+                (Mode::Normal, "gc", KeyCode::Char('c')) => {
+                    let result = toggle_comment(rope, cursor.y, cursor.y, &options);
+                    if result.is_ok() {
+                        file.modified = !file.scratch;
+                    }
+                    cursor.clamp(rope);
+                    if let Err(err) = result {
+                        state.error(err);
+                    }
+                }
+                (Mode::Visual | Mode::VisualLine | Mode::VisualBlock, "g", KeyCode::Char('c')) => {
+                    let anchor = rope.char_to_line(self.visual_anchor.min(rope.len_chars()));
+                    let (start, end) = (anchor.min(cursor.y), anchor.max(cursor.y));
+                    let result = toggle_comment(rope, start, end, &options);
+                    if result.is_ok() {
+                        file.modified = !file.scratch;
+                    }
+                    cursor.clamp(rope);
+                    if let Err(err) = result {
+                        state.error(err);
+                    }
+                    state.mode = Mode::Normal;
+                }
+                (
+                    Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char('g'),
+                ) if ctrl => {
+                    let index = cursor.cursor_char_index(rope);
+                    let info = match state.mode {
+                        Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                            selection_counts(rope, self.visual_anchor, index)
+                        }
+                        _ => buffer_counts(rope, cursor, index),
+                    };
+                    state.info(info);
+                }
+                (
+                    Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char('g'),
+                ) => {
+                    let height = self.area.get().height as usize;
+                    let line = count.map_or(0, |count| count - 1);
+                    state.jumps.push(Jump {
+                        file: self.file,
+                        index: cursor.cursor_char_index(rope),
+                    });
+                    jump_to_line(cursor, &self.scroll_y, height, rope, line);
+                }
+                (Mode::Normal, "m", KeyCode::Char(c)) if c.is_ascii_lowercase() => {
+                    file.marks.insert(c, cursor.cursor_char_index(rope));
+                }
+                // Jump to the line of a mark, or to its exact position with a backtick
+                (
+                    Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "'" | "`",
+                    KeyCode::Char(c),
+                ) => match file.marks.get(&c) {
+                    Some(&index) => {
+                        let height = self.area.get().height as usize;
+                        let index = index.min(rope.len_chars());
+                        state.jumps.push(Jump {
+                            file: self.file,
+                            index: cursor.cursor_char_index(rope),
+                        });
+                        match pending.as_str() {
+                            "'" => {
+                                let line = rope.char_to_line(index);
+                                jump_to_line(cursor, &self.scroll_y, height, rope, line);
+                            }
+                            _ => {
+                                cursor.move_to_char(rope, index);
+                                cursor.clamp(rope);
+                                reveal_cursor(cursor, &self.scroll_y, height);
+                            }
+                        }
+                    }
+                    None => state.error("E20: Mark not set"),
+                },
+                (Mode::Normal, "g", KeyCode::Char('a')) => {
+                    let info = char_info(rope, cursor.cursor_char_index(rope));
+                    state.info(info);
+                }
+                // Add more to the numbers of each selected line than to the line above
+                (
+                    Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char(c @ ('a' | 'x')),
+                ) if ctrl => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let delta = if c == 'a' { 1 } else { -1 };
+                    let (mode, anchor, tabstop) = (state.mode, self.visual_anchor, options.tabstop);
+                    if add_to_selected_numbers(rope, mode, anchor, cursor, tabstop, delta, true) {
+                        file.modified = !file.scratch;
+                    }
+                    state.mode = Mode::Normal;
+                }
+                (Mode::Visual | Mode::VisualLine | Mode::VisualBlock, "g", KeyCode::Char('a')) => {
+                    self.pending = "ga".to_string();
+                }
+                // Insert the numbers from 1 down the block
+                (Mode::VisualBlock, "g", KeyCode::Char('#')) => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let block = Block::new(rope, self.visual_anchor, cursor, options.tabstop);
+                    let columns = (block.start..=block.end)
+                        .map(|index| {
+                            let line = rope.line(index);
+                            let width = col_to_cell(line, line_len(line), options.tabstop);
+                            (width >= block.left)
+                                .then(|| cell_to_col(line, block.left, options.tabstop))
+                        })
+                        .collect::<Vec<_>>();
+                    if rope.replace_lines(block.start, block.end, |lines| {
+                        insert_sequence(lines, &columns)
+                    }) {
+                        file.modified = !file.scratch;
+                    }
+                    let column = columns[0].unwrap_or(0);
+                    cursor.set_position(column, block.start, rope);
+                    state.mode = Mode::Normal;
+                }
+                // Align a delimiter on the selected lines, like `:Align`
+                (Mode::Visual | Mode::VisualLine | Mode::VisualBlock, "ga", KeyCode::Char(c)) => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    let delimiter = c.to_string();
+                    if rope.replace_lines(start, end, |lines| align(lines, &delimiter)) {
+                        file.modified = !file.scratch;
+                    }
+                    cursor.set_position(0, start, rope);
+                    cursor.move_first_non_blank(rope);
+                    state.mode = Mode::Normal;
+                }
+                (Mode::Normal, "gq", KeyCode::Char(c @ ('i' | 'a'))) => {
+                    self.pending = format!("gq{}", c);
+                }
+                // Format lines
+                (Mode::Normal, "gq", KeyCode::Char('q')) => {
+                    format_lines(rope, cursor, cursor.y, cursor.y, textwidth, &options);
+                    file.modified = !file.scratch;
+                }
+                (Mode::Normal, "gq", KeyCode::Char('j')) => {
+                    format_lines(rope, cursor, cursor.y, cursor.y + 1, textwidth, &options);
+                    file.modified = !file.scratch;
+                }
+                (Mode::Normal, "gq", KeyCode::Char('k')) => {
+                    let start = cursor.y.saturating_sub(1);
+                    format_lines(rope, cursor, start, cursor.y, textwidth, &options);
+                    file.modified = !file.scratch;
+                }
+                (Mode::Normal, "gqi" | "gqa", KeyCode::Char('p')) => {
+                    if let Some((start, end)) = paragraph(rope, cursor.y) {
+                        format_lines(rope, cursor, start, end, textwidth, &options);
+                        file.modified = !file.scratch;
+                    }
+                }
+                (Mode::Visual | Mode::VisualLine | Mode::VisualBlock, "g", KeyCode::Char('q')) => {
+                    let anchor = rope.char_to_line(self.visual_anchor.min(rope.len_chars()));
+                    let (start, end) = (anchor.min(cursor.y), anchor.max(cursor.y));
+                    format_lines(rope, cursor, start, end, textwidth, &options);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Normal;
+                }
+                // Sort, reverse or deduplicate the selected lines, like `:sort`, `:reverse`
+                // and `:uniq`
+                (
+                    Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char(c @ ('s' | 'r' | 'u')),
+                ) => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    let operation = match c {
+                        's' => LineOperation::Sort(SortFlags::default()),
+                        'r' => LineOperation::Reverse,
+                        _ => LineOperation::Uniq { ignorecase: false },
+                    };
+                    if operation.apply_lines(rope, start, end) {
+                        file.modified = !file.scratch;
+                    }
+                    cursor.set_position(0, start, rope);
+                    cursor.move_first_non_blank(rope);
+                    state.mode = Mode::Normal;
+                }
+                _ => {}
+            }
+            return None;
+        }
+
+        match state.mode {
+            Mode::Normal => match key_event.code {
+                KeyCode::Char('q') if state.recording.is_some() => {
+                    return Some(Action::StopRecording);
+                }
+                KeyCode::Char('q') => self.pending = "q".to_string(),
+                KeyCode::Char('@') => self.pending = "@".to_string(),
+                KeyCode::Char('m') => self.pending = "m".to_string(),
+                KeyCode::Char('o') if ctrl => {
+                    let from = Jump {
+                        file: self.file,
+                        index: cursor.cursor_char_index(rope),
+                    };
+                    return state.jumps.back(from).map(Action::Jump);
+                }
+                KeyCode::Char('i') if ctrl => return state.jumps.forward().map(Action::Jump),
+                KeyCode::Tab => return state.jumps.forward().map(Action::Jump),
+                KeyCode::Char('g') => {
+                    self.pending = "g".to_string();
+                    self.count = count;
+                }
+                KeyCode::Char(c @ '0'..='9') if c != '0' || count.is_some() => {
+                    self.count = Some(append_digit(count, c));
+                }
+                KeyCode::Char('G') => {
+                    let height = self.area.get().height as usize;
+                    let line = count.map_or(last_line(rope), |count| count - 1);
+                    state.jumps.push(Jump {
+                        file: self.file,
+                        index: cursor.cursor_char_index(rope),
+                    });
+                    jump_to_line(cursor, &self.scroll_y, height, rope, line);
+                }
+                KeyCode::Char(c @ ('\'' | '`')) => self.pending = c.to_string(),
+                KeyCode::Char('z') if !ctrl => self.pending = "z".to_string(),
+                KeyCode::Char('"') => self.pending = "\"".to_string(),
+                KeyCode::Char(c @ (']' | '[')) if !ctrl => self.pending = c.to_string(),
+                KeyCode::Char(c @ ('>' | '<')) => self.pending = c.to_string(),
+                KeyCode::Char(c @ ('d' | 'c' | 'y')) => {
+                    self.pending = c.to_string();
+                    self.register = register;
+                }
+                KeyCode::Char('i') => state.mode = Mode::Insert,
+                KeyCode::Char('u') => {
+                    self.undo(UndoMotion::Undo, state);
+                    return None;
+                }
+                KeyCode::Char('r') if ctrl => {
+                    self.undo(UndoMotion::Redo, state);
+                    return None;
+                }
+                KeyCode::Char('h') | KeyCode::Left => cursor.move_left(rope),
+                KeyCode::Char('j') | KeyCode::Down => cursor.move_down(rope),
+                KeyCode::Char('k') | KeyCode::Up => cursor.move_up(rope),
+                KeyCode::Char('l') | KeyCode::Right => cursor.move_right(rope),
+                KeyCode::Char('0') | KeyCode::Home => cursor.move_line_start(rope),
+                KeyCode::Char('$') | KeyCode::End => cursor.move_line_end(rope),
+                KeyCode::Char(c @ ('w' | 'W')) => cursor.move_word_start(rope, c == 'W'),
+                KeyCode::Char(c @ ('e' | 'E')) => cursor.move_word_end(rope, c == 'E'),
+                KeyCode::Char(c @ ('b' | 'B')) => cursor.move_word_back(rope, c == 'B'),
+                KeyCode::Char(c @ ('v' | 'V')) => {
+                    self.visual_anchor = cursor.cursor_char_index(rope);
+                    state.mode = match c {
+                        'V' => Mode::VisualLine,
+                        _ if ctrl => Mode::VisualBlock,
+                        _ => Mode::Visual,
+                    };
+                }
+                // Add to the number under or after the cursor, leaving the cursor on its
+                // last digit
+                KeyCode::Char(c @ ('a' | 'x')) if ctrl => {
+                    let delta = if c == 'a' { 1 } else { -1 };
+                    let (y, x) = (cursor.y, cursor.x);
+                    let number = find_number(&rope.line(y).to_string(), x)?;
+                    rope.replace_lines(y, y, |lines| add_to_numbers(lines, &[x], delta, false));
+                    file.modified = !file.scratch;
+                    if let Some(number) = find_number(&rope.line(y).to_string(), number.start) {
+                        cursor.set_position(number.end - 1, y, rope);
+                    }
+                }
+                KeyCode::Char('a') => {
+                    cursor.move_right(rope);
+                    state.mode = Mode::Insert;
+                }
+                KeyCode::Char('A') => {
+                    cursor.move_line_end(rope);
+                    state.mode = Mode::Insert;
+                }
+                KeyCode::Char('I') => {
+                    cursor.move_line_start(rope);
+                    state.mode = Mode::Insert;
+                }
+                KeyCode::Char('x') => {
+                    let deleted = rope.get_char(cursor.cursor_char_index(rope));
+                    cursor.delete_next_char(rope);
+                    file.modified = !file.scratch;
+                    if let Some(c) = deleted {
+                        state.set_register(register.unwrap_or('"'), c.to_string());
+                    }
+                }
+                KeyCode::Char(c @ ('p' | 'P')) => match paste? {
+                    Ok(text) => {
+                        put(rope, cursor, &text, c == 'p');
+                        file.modified = !file.scratch;
+                    }
+                    Err(err) => state.error(err),
+                },
+                KeyCode::Char(':') => state.open_cmdline(Prompt::Command),
+                KeyCode::Char('/') => state.open_cmdline(Prompt::Search { backward: false }),
+                KeyCode::Char('?') => state.open_cmdline(Prompt::Search { backward: true }),
+                KeyCode::Char('n') | KeyCode::Char('N') => {
+                    let reverse = key_event.code == KeyCode::Char('N');
+                    let from = cursor.cursor_char_index(rope);
+                    if search_next(cursor, rope, state.search.as_ref(), reverse) {
+                        state.jumps.push(Jump {
+                            file: self.file,
+                            index: from,
+                        });
+                        let at = cursor.cursor_char_index(rope);
+                        state.count_search(self.file, at);
+                    } else {
+                        not_found(state);
+                    }
+                }
+                _ => {}
+            },
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => match key_event.code {
+                KeyCode::Esc => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    state.mode = Mode::Normal;
+                }
+                // Switch between the charwise, linewise and blockwise selections, or leave
+                // them
+                KeyCode::Char(c @ ('v' | 'V')) => {
+                    let mode = match c {
+                        'V' => Mode::VisualLine,
+                        _ if ctrl => Mode::VisualBlock,
+                        _ => Mode::Visual,
+                    };
+                    if state.mode == mode {
+                        set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                        state.mode = Mode::Normal;
+                    } else {
+                        state.mode = mode;
+                    }
+                }
+                KeyCode::Char('i') => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    state.mode = Mode::Insert;
+                }
+                KeyCode::Char(c @ '0'..='9') if c != '0' || count.is_some() => {
+                    self.count = Some(append_digit(count, c));
+                }
+                KeyCode::Char('h') | KeyCode::Left => cursor.move_left(rope),
+                KeyCode::Char('j') | KeyCode::Down => cursor.move_down(rope),
+                KeyCode::Char('k') | KeyCode::Up => cursor.move_up(rope),
+                KeyCode::Char('l') | KeyCode::Right => cursor.move_right(rope),
+                KeyCode::Char('0') | KeyCode::Home => cursor.move_line_start(rope),
+                KeyCode::Char('$') | KeyCode::End => cursor.move_line_end(rope),
+                KeyCode::Char(c @ ('w' | 'W')) => cursor.move_word_start(rope, c == 'W'),
+                KeyCode::Char(c @ ('e' | 'E')) => cursor.move_word_end(rope, c == 'E'),
+                KeyCode::Char(c @ ('b' | 'B')) => cursor.move_word_back(rope, c == 'B'),
+                KeyCode::Char(':') => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    state.mode = Mode::Normal;
+                    return Some(Action::Cmdline("'<,'>".to_string()));
+                }
+                KeyCode::Char('g') => {
+                    self.pending = "g".to_string();
+                    self.count = count;
+                }
+                KeyCode::Char('G') => {
+                    let height = self.area.get().height as usize;
+                    let line = count.map_or(last_line(rope), |count| count - 1);
+                    state.jumps.push(Jump {
+                        file: self.file,
+                        index: cursor.cursor_char_index(rope),
+                    });
+                    jump_to_line(cursor, &self.scroll_y, height, rope, line);
+                }
+                KeyCode::Char(c @ ('\'' | '`')) => self.pending = c.to_string(),
+                KeyCode::Char('"') => self.pending = "\"".to_string(),
+                KeyCode::Char(c @ ('a' | 'x')) if ctrl => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let delta = if c == 'a' { 1 } else { -1 };
+                    let (mode, anchor, tabstop) = (state.mode, self.visual_anchor, options.tabstop);
+                    if add_to_selected_numbers(rope, mode, anchor, cursor, tabstop, delta, false) {
+                        file.modified = !file.scratch;
+                    }
+                    state.mode = Mode::Normal;
+                }
+                KeyCode::Char('y') if state.mode == Mode::VisualBlock => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let block = Block::new(rope, self.visual_anchor, cursor, options.tabstop);
+                    let text = block.text(rope, options.tabstop);
+                    let line = rope.line(block.start);
+                    let column = cell_to_col(line, block.left, options.tabstop);
+                    cursor.move_to_char(rope, rope.line_to_char(block.start) + column);
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('0'), text);
+                }
+                KeyCode::Char('d' | 'x') if state.mode == Mode::VisualBlock => {
+                    let block = Block::new(rope, self.visual_anchor, cursor, options.tabstop);
+                    let text = block.delete(rope, options.tabstop);
+                    let line = rope.line(block.start);
+                    let column = cell_to_col(line, block.left, options.tabstop);
+                    cursor.move_to_char(rope, rope.line_to_char(block.start) + column);
+                    cursor.clamp(rope);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('"'), text);
+                }
+                // Insert before or after the block on each of its lines, `c` replacing it
+                KeyCode::Char(c @ ('I' | 'A' | 'c')) if state.mode == Mode::VisualBlock => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let mut block = Block::new(rope, self.visual_anchor, cursor, options.tabstop);
+                    let deleted = (c == 'c').then(|| block.delete(rope, options.tabstop));
+                    if deleted.is_some() {
+                        block.right = block.left;
+                        file.modified = !file.scratch;
+                    }
+                    let side = match c {
+                        'I' => Side::Before,
+                        'A' => Side::After,
+                        _ => Side::Replaced,
+                    };
+                    self.block_insert = Some(BlockInsert::start(
+                        block,
+                        side,
+                        rope,
+                        cursor,
+                        options.tabstop,
+                    ));
+                    state.mode = Mode::Insert;
+                    if let Some(text) = deleted {
+                        state.set_register(register.unwrap_or('"'), text);
+                    }
+                }
+                // Replace the block with a register, put as text at its start
+                KeyCode::Char(c @ ('p' | 'P')) if state.mode == Mode::VisualBlock => match paste? {
+                    Ok(text) => {
+                        let block = Block::new(rope, self.visual_anchor, cursor, options.tabstop);
+                        let replaced = block.delete(rope, options.tabstop);
+                        let line = rope.line(block.start);
+                        let column = cell_to_col(line, block.left, options.tabstop);
+                        let at = rope.line_to_char(block.start) + column;
+                        rope.insert(at, &text);
+                        cursor.move_to_char(rope, at);
+                        file.modified = !file.scratch;
+                        state.mode = Mode::Normal;
+                        if c == 'p' {
+                            state.set_register('"', replaced);
+                        }
+                    }
+                    Err(err) => state.error(err),
+                },
+                KeyCode::Char('y') if state.mode == Mode::VisualLine => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    let text = line_text(rope, start, end);
+                    cursor.move_up_n(rope, cursor.y - start);
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('0'), text);
+                }
+                KeyCode::Char('d' | 'x') if state.mode == Mode::VisualLine => {
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    let text = cursor.delete_lines(rope, start, end);
+                    cursor.move_first_non_blank(rope);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('"'), text);
+                }
+                // The lines are replaced by an empty one to insert on
+                KeyCode::Char('c') if state.mode == Mode::VisualLine => {
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    let text = line_text(rope, start, end);
+                    let last = rope.line(end);
+                    let from = rope.line_to_char(start);
+                    rope.remove(from..rope.line_to_char(end) + line_len(last));
+                    cursor.move_to_char(rope, from);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Insert;
+                    state.set_register(register.unwrap_or('"'), text);
+                }
+                KeyCode::Char(c @ ('p' | 'P')) if state.mode == Mode::VisualLine => match paste? {
+                    Ok(mut text) => {
+                        let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                        let replaced = cursor.delete_lines(rope, start, end);
+                        if !text.ends_with('\n') {
+                            text.push('\n');
+                        }
+                        // The lines replaced at the end of the buffer are put after the
+                        // line left above them
+                        put(rope, cursor, &text, start >= line_count(rope));
+                        file.modified = !file.scratch;
+                        state.mode = Mode::Normal;
+                        if c == 'p' {
+                            state.set_register('"', replaced);
+                        }
+                    }
+                    Err(err) => state.error(err),
+                },
+                KeyCode::Char('y') => {
+                    set_visual_marks(&mut file.marks, rope, self.visual_anchor, cursor);
+                    let index = cursor.cursor_char_index(rope);
+                    let start = self.visual_anchor.min(index);
+                    let end = (self.visual_anchor.max(index) + 1).min(rope.len_chars());
+                    let text = rope.slice(start..end).to_string();
+                    cursor.move_to_char(rope, start);
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('0'), text);
+                }
+                KeyCode::Char('d' | 'x') => {
+                    let text = delete_selection(rope, cursor, self.visual_anchor);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Normal;
+                    state.set_register(register.unwrap_or('"'), text);
+                }
+                KeyCode::Char(c @ ('>' | '<')) => {
+                    let (start, end) = selected_lines(rope, self.visual_anchor, cursor);
+                    shift_lines(rope, start, end, &options, c == '>');
+                    file.modified = !file.scratch;
+                    cursor.set_position(0, start, rope);
+                    cursor.move_first_non_blank(rope);
+                    state.mode = Mode::Normal;
+                }
+                KeyCode::Char('c') => {
+                    let text = delete_selection(rope, cursor, self.visual_anchor);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Insert;
+                    state.set_register(register.unwrap_or('"'), text);
+                }
+                // Replace the selection with a register, the unnamed one receiving the
+                // selection with `p` but not with `P`
+                KeyCode::Char(c @ ('p' | 'P')) => match paste? {
+                    Ok(text) => {
+                        let replaced = delete_selection(rope, cursor, self.visual_anchor);
+                        let at = cursor.cursor_char_index(rope);
+                        rope.insert(at, &text);
+                        cursor.move_to_char(
+                            rope,
+                            (at + text.chars().count()).saturating_sub(1).max(at),
+                        );
+                        file.modified = !file.scratch;
+                        state.mode = Mode::Normal;
+                        if c == 'p' {
+                            state.set_register('"', replaced);
+                        }
+                    }
+                    Err(err) => state.error(err),
+                },
+                _ => {}
+            },
+            Mode::Insert => match key_event.code {
+                KeyCode::Esc => {
+                    if let Some(insert) = self.block_insert.take() {
+                        insert.finish(rope, cursor, options.tabstop);
+                    }
+                    cursor.clamp_to_last_line(rope);
+                    state.mode = Mode::Normal;
+                }
+                KeyCode::Char('r') if ctrl => self.pending = INSERT_REGISTER.to_string(),
+                KeyCode::Char('v') if ctrl => self.pending = INSERT_LITERAL.to_string(),
+                KeyCode::Char('k') if ctrl => self.pending = INSERT_DIGRAPH.to_string(),
+                KeyCode::Char('x') if ctrl => self.pending = INSERT_COMPLETE.to_string(),
+                KeyCode::Char('g') if ctrl => self.pending = INSERT_UNDO.to_string(),
+                KeyCode::Char(c) => {
+                    cursor.insert_char(rope, c);
+                    file.modified = !file.scratch;
+                    if state.config.textwidth > 0 && !state.config.paste && !c.is_whitespace() {
+                        let prose = is_prose(&file.path);
+                        let comment = options.line_comment();
+                        auto_wrap(rope, cursor, state.config.textwidth, prose, comment);
+                    }
+                }
+                KeyCode::Enter => {
+                    // Each line of an insert session is undone on its own
+                    rope.break_undo();
+                    let indent = match state.config.autoindent && !state.config.paste {
+                        true => new_line_indent(rope, cursor, &options),
+                        false => String::new(),
+                    };
+                    let at = cursor.cursor_char_index(rope);
+                    rope.insert(at, &format!("\n{}", indent));
+                    cursor.move_to_char(rope, at + 1 + indent.chars().count());
+                    file.modified = !file.scratch;
+                }
+                KeyCode::Tab => {
+                    let indent = match options.expandtab && !state.config.paste {
+                        true => " ".repeat(options.shiftwidth - cursor.x % options.shiftwidth),
+                        false => "\t".to_string(),
+                    };
+                    let at = cursor.cursor_char_index(rope);
+                    rope.insert(at, &indent);
+                    cursor.move_to_char(rope, at + indent.chars().count());
+                    file.modified = !file.scratch;
+                }
+                KeyCode::Backspace => {
+                    cursor.delete_prev_char(rope);
+                    file.modified = !file.scratch;
+                }
+                KeyCode::Delete => {
+                    cursor.delete_next_char(rope);
+                    file.modified = !file.scratch;
+                }
+                KeyCode::Right => cursor.move_right(rope),
+                KeyCode::Left => cursor.move_left(rope),
+                KeyCode::Up => cursor.move_up(rope),
+                KeyCode::Down => cursor.move_down(rope),
+                KeyCode::Home => cursor.move_line_start(rope),
+                KeyCode::End => cursor.move_line_end(rope),
+                _ => {}
+            },
+            Mode::Command => {}
+        }
+        None
+    }
+
+    /// Jump to the next match of a new search.
+    /// Returns false if the pattern was not found.
+    pub fn search(&mut self, state: &mut State) -> bool {
+        let Some(rope) = state
+            .filesystem
+            .files
+            .get(self.file)
+            .and_then(|file| file.buffer.as_ref())
+        else {
+            return false;
+        };
+        let from = self.cursor.cursor_char_index(rope);
+        if !search_next(&mut self.cursor, rope, state.search.as_ref(), false) {
+            return false;
+        }
+        state.jumps.push(Jump {
+            file: self.file,
+            index: from,
+        });
+        let at = self.cursor.cursor_char_index(rope);
+        state.count_search(self.file, at);
+        true
+    }
+
+    /// Move the cursor to the first match of a pattern being typed with `incsearch`
+    pub fn preview_search(&mut self, pattern: &Pattern, backward: bool, state: &State) {
+        let Some(rope) = state
+            .filesystem
+            .files
+            .get(self.file)
+            .and_then(|file| file.buffer.as_ref())
+        else {
+            return;
+        };
+        let from = self.cursor.cursor_char_index(rope);
+        if let Some((start, _)) = pattern.find(rope, from, backward) {
+            self.cursor.move_to_char(rope, start);
+        }
+    }
+
+    /// Cursor position, recorded in the jump list before jumping away from it
+    pub fn jump(&self, state: &State) -> Option<Jump> {
+        let rope = state.filesystem.files.get(self.file)?.buffer.as_ref()?;
+        Some(Jump {
+            file: self.file,
+            index: self.cursor.cursor_char_index(rope),
+        })
+    }
+
+    /// Cursor and first displayed line, to go back to after a preview
+    pub fn view(&self) -> (Cursor, usize) {
+        (self.cursor.clone(), self.scroll_y.get())
+    }
+
+    pub fn restore_view(&mut self, (cursor, scroll_y): (Cursor, usize)) {
+        self.cursor = cursor;
+        self.scroll_y.set(scroll_y);
+    }
+
+    /// Move through the undo tree of the buffer, the cursor going to the changed text
+    pub fn undo(&mut self, motion: UndoMotion, state: &mut State) {
+        let Some(file) = state.filesystem.files.get_mut(self.file) else {
+            return;
+        };
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        let tree = &mut file.undo;
+        let result = match motion {
+            UndoMotion::Undo => rope.undo(tree),
+            UndoMotion::Redo => rope.redo(tree),
+            UndoMotion::Earlier => rope.undo_in_time(tree, false),
+            UndoMotion::Later => rope.undo_in_time(tree, true),
+            UndoMotion::To(revision) => rope.goto(tree, revision),
+        };
+        file.modified = !file.scratch && !file.undo.is_saved();
+        match result {
+            Ok((at, message)) => {
+                self.cursor.move_to_char(rope, at);
+                self.cursor.clamp_to_last_line(rope);
+                state.info(message);
+            }
+            Err(err) => state.error(err),
+        }
+    }
+
+    /// Insert text at the cursor, moving the cursor after it
+    pub fn insert_text(&mut self, text: &str, state: &mut State) {
+        let Some(file) = state.filesystem.files.get_mut(self.file) else {
+            return;
+        };
+        if file.readonly {
+            state.error(READONLY_ERROR);
+            return;
+        }
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        let at = self.cursor.cursor_char_index(rope);
+        rope.insert(at, text);
+        file.modified = !file.scratch;
+        self.cursor.move_to_char(rope, at + text.chars().count());
+        self.cursor.clamp(rope);
+    }
+
+    /// Start completing the path before the cursor, relative to the folder of the file.
+    /// The first match is inserted, and a popup lists the others.
+    fn complete_path(&mut self, state: &mut State) {
+        let Some(file) = state.filesystem.files.get(self.file) else {
+            return;
+        };
+        let Some(rope) = &file.buffer else {
+            return;
+        };
+        let before = rope
+            .line(self.cursor.y)
+            .chars()
+            .take(self.cursor.x)
+            .collect::<Vec<_>>();
+        let len = before
+            .iter()
+            .rev()
+            .take_while(|c| !is_path_delimiter(**c))
+            .count();
+        let typed = before[before.len() - len..].iter().collect::<String>();
+        let base = match file.path.parent() {
+            Some(parent) if file.remote.is_none() && !parent.as_os_str().is_empty() => {
+                parent.to_path_buf()
+            }
+            _ => state.filesystem.folders[state.filesystem.root].path.clone(),
+        };
+        let items = complete_path(&typed, &base);
+        if items.is_empty() {
+            state.error("No matching path");
+            return;
+        }
+        let single = items.len() == 1;
+        self.completion = Some(PathCompletion {
+            start: self.cursor.cursor_char_index(rope) - len,
+            folder: typed.rfind('/').map_or(0, |index| index + 1),
+            typed,
+            items,
+            selected: 0,
+        });
+        self.select_completion(0, state);
+        if single {
+            self.completion = None;
+        }
+    }
+
+    /// Insert a path completion in place of the completed text
+    fn select_completion(&mut self, selected: usize, state: &mut State) -> Option<Action> {
+        let completion = self.completion.as_mut()?;
+        completion.selected = selected;
+        let item = completion.items[selected].clone();
+        let count = completion.items.len();
+        self.replace_completed(&item, state);
+        if count > 1 {
+            state.info(format!(
+                "-- File name completion (^N^P) match {} of {}",
+                selected + 1,
+                count
+            ));
+        }
+        None
+    }
+
+    /// Replace the text between the start of the completion and the cursor
+    fn replace_completed(&mut self, text: &str, state: &mut State) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let Some(file) = state.filesystem.files.get_mut(self.file) else {
+            return;
+        };
+        let Some(rope) = file.buffer.as_mut() else {
+            return;
+        };
+        let rope = &mut Edit::new(rope, &mut file.changes);
+        let at = self.cursor.cursor_char_index(rope);
+        rope.remove(completion.start..at);
+        rope.insert(completion.start, text);
+        file.modified = !file.scratch;
+        self.cursor
+            .move_to_char(rope, completion.start + text.chars().count());
+    }
+
+    /// Render the path completions below the cursor, or above it without room below
+    fn render_completion(&self, area: Rect, buf: &mut Buffer) {
+        let Some(completion) = &self.completion else {
+            return;
+        };
+        let labels = completion
+            .items
+            .iter()
+            .map(|item| &item[completion.folder..])
+            .collect::<Vec<_>>();
+        let width = (labels
+            .iter()
+            .map(|label| Span::raw(*label).width())
+            .max()
+            .unwrap_or(0)
+            + 2)
+        .min(area.width as usize);
+        let height = labels.len().min(COMPLETION_HEIGHT);
+        let cursor = self.cursor_position();
+        let x = cursor
+            .x
+            .saturating_sub(Span::raw(labels[completion.selected]).width() as u16 + 1);
+        let y = match cursor.y as usize + 1 + height > area.bottom() as usize {
+            true => cursor.y.saturating_sub(height as u16),
+            false => cursor.y + 1,
+        };
+        let popup = Rect::new(x, y, width as u16, height as u16).intersection(area);
+
+        let scroll = (completion.selected + 1).saturating_sub(height);
+        let lines = labels
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(height)
+            .map(|(i, label)| {
+                let label = truncate(label, width.saturating_sub(2));
+                let line = Line::from(format!(" {:<1$} ", label, width.saturating_sub(2)));
+                match i == completion.selected {
+                    true => line.black().on_magenta(),
+                    false => line.on_dark_gray(),
+                }
+            })
+            .collect::<Vec<_>>();
+        Clear.render(popup, buf);
+        Text::from(lines).render(popup, buf);
+    }
+
+    /// Insert the char of a hex codepoint
+    fn insert_codepoint(&mut self, hex: &str, state: &mut State) {
+        if hex.is_empty() {
+            return;
+        }
+        match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+            Some(c) => self.insert_text(&c.to_string(), state),
+            None => state.error(format!("Invalid codepoint: U+{}", hex.to_uppercase())),
+        }
+    }
+
+    /// Insert text typed at once, like an IME commit, as a single edit
+    pub fn type_text(&mut self, text: &str, state: &mut State) {
+        self.insert_text(text, state);
+        let Some(file) = state.filesystem.files.get_mut(self.file) else {
+            return;
+        };
+        if let Some(rope) = file.buffer.as_mut()
+            && state.config.textwidth > 0
+            && !state.config.paste
+            && !file.readonly
+            && !text.ends_with(char::is_whitespace)
+        {
+            let prose = is_prose(&file.path);
+            let comment = file
+                .options
+                .as_ref()
+                .unwrap_or(&state.config.buffer)
+                .line_comment();
+            auto_wrap(
+                &mut Edit::new(rope, &mut file.changes),
+                &mut self.cursor,
+                state.config.textwidth,
+                prose,
+                comment,
+            );
+        }
+    }
+
+    /// Whether keys of a multi-key command were typed
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Keys typed so far of the pending command, after its register, with the control
+    /// keys written like `^V`
+    pub fn pending_keys(&self) -> String {
+        let register = self.register.map(|register| format!("\"{}", register));
+        let keys = match self
+            .pending
+            .strip_prefix("<C-")
+            .and_then(|rest| rest.split_once('>'))
+        {
+            Some((key, rest)) => format!("^{}{}", key.to_uppercase(), rest),
+            None => self.pending.clone(),
+        };
+        register.unwrap_or_default() + &keys
+    }
+
+    /// Chars of the visual selection, through the cursor char, or up to the cursor for
+    /// the selections of the `basic` preset
+    fn selection(&self, rope: &Rope) -> Range<usize> {
+        let index = self.cursor.cursor_char_index(rope);
+        let start = self.visual_anchor.min(index);
+        let end = match self.selecting {
+            true => self.visual_anchor.max(index),
+            false => self.visual_anchor.max(index) + 1,
+        };
+        start..end.min(rope.len_chars())
+    }
+
+    /// Text of the visual selection, or of the current line
+    pub fn copy_text(&self, state: &State) -> Option<String> {
+        let rope = state.filesystem.files.get(self.file)?.buffer.as_ref()?;
+        let text = match state.mode {
+            Mode::Visual => rope.slice(self.selection(rope)).to_string(),
+            Mode::VisualLine => {
+                let (start, end) = selected_lines(rope, self.visual_anchor, &self.cursor);
+                line_text(rope, start, end)
+            }
+            Mode::VisualBlock => {
+                let block = Block::new(rope, self.visual_anchor, &self.cursor, self.tabstop(state));
+                block.text(rope, self.tabstop(state))
+            }
+            _ => rope.line(self.cursor.y).to_string(),
+        };
+        Some(text)
+    }
+
+    /// Jump to the declaration of the keyword under the cursor in the buffer:
+    /// the first line defining it, or else its first occurrence
+    pub fn goto_definition(&mut self, state: &State) -> Result<(), String> {
+        let rope = state
+            .filesystem
+            .files
+            .get(self.file)
+            .and_then(|file| file.buffer.as_ref())
+            .ok_or("E348: No string under cursor")?;
+        let line = rope.line(self.cursor.y).to_string();
+        let word = keyword_at(&line, self.cursor.x).ok_or("E348: No string under cursor")?;
+        let word = regex::escape(&word);
+
+        let text = rope.to_string();
+        let definition = Regex::new(&format!(
+            r"\b(fn|struct|enum|trait|type|impl|mod|const|static|let|def|class|function|var)\s+(mut\s+)?({})\b",
+            word
+        ))
+        .ok()
+        .and_then(|regex| regex.captures(&text))
+        .and_then(|captures| captures.get(3));
+        let found = definition.or_else(|| Regex::new(&format!(r"\b{}\b", word)).ok()?.find(&text));
+        if let Some(found) = found {
+            self.cursor
+                .move_to_char(rope, rope.byte_to_char(found.start()));
+        }
+        Ok(())
+    }
+
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, state: &mut State) {
+        let Some(rope) = state
+            .filesystem
+            .files
+            .get(self.file)
+            .and_then(|file| file.buffer.as_ref())
+        else {
+            return;
+        };
+        let area = self.area.get();
+        let cursor_margin_y = state.config.cursor_margin_y;
+        let scroll_tick = state.config.scroll_tick;
+        let tabstop = self.tabstop(state);
+
+        match mouse_event.kind {
+            // Right clicks keep the visual selection for the context menu
+            MouseEventKind::Down(MouseButton::Right) if state.mode.is_visual() => {}
+            MouseEventKind::Down(button) => {
+                let cell = (mouse_event.column as usize)
+                    .saturating_sub(area.left() as usize + self.gutter_width.get() as usize);
+                let row = (mouse_event.row - area.top()) as usize;
+                // Rows after the end of the buffer select its last line
+                let (y, start) = self
+                    .rows
+                    .borrow()
+                    .get(row)
+                    .copied()
+                    .unwrap_or((last_line(rope), 0));
+                let x = cell_to_col(rope.line(y), start + cell, tabstop);
+                let gutter = mouse_event.column < area.left() + self.gutter_width.get();
+
+                // Shift clicks extend a visual selection from the cursor
+                let extend = button == MouseButton::Left
+                    && mouse_event.modifiers.contains(KeyModifiers::SHIFT);
+                if extend && !state.mode.is_visual() {
+                    self.visual_anchor = self.cursor.cursor_char_index(rope);
+                    state.mode = Mode::Visual;
+                }
+                self.cursor.set_position(x, y, rope);
+                // Gutter clicks select whole lines when extending downwards
+                if extend && gutter && self.cursor.cursor_char_index(rope) >= self.visual_anchor {
+                    self.cursor.move_line_end(rope);
+                }
+
+                if button == MouseButton::Middle && state.config.middlepaste {
+                    let at = self.cursor.cursor_char_index(rope);
+                    let sender = state.events.editor_sender.clone();
+                    clipboard::paste(Selection::Primary, sender, &state.jobs, self.file, at);
+                }
+            }
+            MouseEventKind::ScrollUp => {
+                self.scroll_y
+                    .set(self.scroll_y.get().saturating_sub(scroll_tick));
+
+                let bottom = self.scroll_y.get() + area.height as usize;
+                if self.cursor.y + cursor_margin_y >= bottom {
+                    let n = self.cursor.y + cursor_margin_y + 1 - bottom;
+                    self.cursor.move_up_n(rope, n);
+                }
+            }
+            MouseEventKind::ScrollDown => {
+                let max_scroll = self.max_scroll(rope, state);
+                self.scroll_y
+                    .set((self.scroll_y.get() + scroll_tick).min(max_scroll));
+
+                if self.cursor.y < self.scroll_y.get() + cursor_margin_y {
+                    let n = self.scroll_y.get() + cursor_margin_y - self.cursor.y;
+                    self.cursor.move_down_n(rope, n);
+                }
+            }
+            _ => {}
+        }
     }
 }
 
@@ -43,82 +1583,730 @@ impl Widget for Pane {
     fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
         // If the file does not exist or has no buffer, silently render nothing
         // (this should not happen)
-        let buffer = match state.filesystem.files.get(self.file) {
+        let (buffer, long_lines) = match state.filesystem.files.get(self.file) {
             Some(file) => match &file.buffer {
-                Some(buffer) => buffer,
+                Some(buffer) => (buffer, file.long_lines),
                 None => return,
             },
             None => return,
         };
 
+        let mut options = state.config.window.with(&self.local);
+        options.wrap &= !long_lines;
+        let tabstop = self.tabstop(state);
         let cursor_margin_y = state.config.cursor_margin_y;
-        let line_length = area.width as usize;
-        let line_count = area.height as usize;
+        let height = area.height as usize;
+        // The empty line after a final line break is only drawn while inserting on it
+        let lines = line_count(buffer).max(self.cursor.y + 1);
+        let number_width = match options.number || options.relativenumber {
+            true => state.config.numberwidth.max(number_digits(lines) + 1),
+            false => 0,
+        };
+        // No signs are placed yet, so `auto` hides the sign column
+        let sign_width = match state.config.signcolumn {
+            SignColumn::Yes => SIGN_WIDTH,
+            SignColumn::Auto | SignColumn::No => 0,
+        };
+        let fold_width = state.config.foldcolumn;
+        let gutter_width = fold_width + sign_width + number_width;
+        self.gutter_width.set(gutter_width as u16);
+        let text_width = (area.width as usize).saturating_sub(gutter_width);
 
         // Autoscroll at rendering time, depending on the cursor position
         if self.cursor.y < self.scroll_y.get() + cursor_margin_y {
             self.scroll_y
                 .set(self.cursor.y.saturating_sub(cursor_margin_y));
-        } else if self.cursor.y + cursor_margin_y >= self.scroll_y.get() + line_count {
+        } else if self.cursor.y + cursor_margin_y >= self.scroll_y.get() + height {
+            let previous = self.scroll_y.get();
             self.scroll_y
-                .set(self.cursor.y + 1 + cursor_margin_y - line_count);
+                .set(self.cursor.y + 1 + cursor_margin_y - height);
+            // Closed folds leave room for more lines above the cursor
+            let bottom = (self.cursor.y + cursor_margin_y).min(lines - 1);
+            while !self.folds.is_empty()
+                && self.scroll_y.get() > previous
+                && self.visible_lines(self.scroll_y.get() - 1, bottom) <= height
+            {
+                self.scroll_y.set(self.scroll_y.get() - 1);
+            }
+        }
+        if !state.config.scrollpasteof {
+            let max_scroll = lines.saturating_sub(height);
+            self.scroll_y.set(self.scroll_y.get().min(max_scroll));
+        }
+        if let Some((start, _)) = self.closed_fold(self.scroll_y.get()) {
+            self.scroll_y.set(start);
         }
 
-        let gutter_width = 4.max(number_digits(buffer.len_lines()));
-        self.gutter_width.set(gutter_width as u16);
-        let [gutter_area, _, buffer_area] = Layout::horizontal([
-            Constraint::Length(gutter_width as u16),
-            Constraint::Length(1),
+        // The terminal places the IME popups at the cursor, which must account for wide chars
+        let line = buffer.line(self.cursor.y);
+        self.cursor_column
+            .set(col_to_cell(line, self.cursor.x, tabstop));
+        // Wrapped lines above the cursor may push it below the view
+        if options.wrap && text_width > 0 {
+            let rows = |index: usize| {
+                let line = buffer.line(index);
+                col_to_cell(line, line_len(line), tabstop)
+                    .max(1)
+                    .div_ceil(text_width)
+            };
+            let cursor_row = self.cursor_column.get() / text_width;
+            while self.scroll_y.get() < self.cursor.y
+                && (self.scroll_y.get()..self.cursor.y)
+                    .map(rows)
+                    .sum::<usize>()
+                    + cursor_row
+                    >= height
+            {
+                self.scroll_y.set(self.scroll_y.get() + 1);
+            }
+        }
+        let [_, number_area, buffer_area] = Layout::horizontal([
+            Constraint::Length((fold_width + sign_width) as u16),
+            Constraint::Length(number_width as u16),
             Constraint::Fill(1),
         ])
         .areas(area);
 
-        // Render the text area
-        Paragraph::new(Text::from(
-            (self.scroll_y.get()..buffer.len_lines().min(line_count + self.scroll_y.get()))
-                .map(|line| {
-                    let mut remaining = line_length;
-                    let line = buffer.line(line);
-                    Line::from_iter(line.chunks().map_while(|chunk| {
-                        if remaining == 0 {
-                            return None;
-                        }
-
-                        let n = chunk.chars().count().min(remaining);
-                        remaining -= n;
+        // Lay out the text rows, with the search matches and the selection
+        let filetype = state.filesystem.files[self.file].filetype;
+        let layers = Layers {
+            filetype,
+            search: match (&state.incsearch, &state.search) {
+                _ if long_lines => None,
+                (Some(pattern), _) => Some(pattern),
+                (None, Some(search)) if state.config.hlsearch => Some(&search.pattern),
+                _ => None,
+            },
+            selection: match state.mode {
+                _ if !self.focused.get() => None,
+                Mode::Visual => Some(self.selection(buffer)),
+                Mode::VisualLine => {
+                    let (start, end) = selected_lines(buffer, self.visual_anchor, &self.cursor);
+                    Some(buffer.line_to_char(start)..buffer.line_to_char(end + 1))
+                }
+                _ => None,
+            },
+            block: (state.mode == Mode::VisualBlock && self.focused.get())
+                .then(|| Block::new(buffer, self.visual_anchor, &self.cursor, tabstop)),
+        };
+        let mut rows = vec![];
+        let mut text = vec![];
+        let mut line = self.scroll_y.get();
+        while rows.len() < height && line < lines {
+            if let Some(fold) = self.closed_fold(line) {
+                let label = fold_text(buffer, fold);
+                let fill = text_width.saturating_sub(label.chars().count());
+                rows.push((line, 0));
+                text.push(Line::from(label + &" ".repeat(fill)).style(FOLD_STYLE));
+                line = fold.1 + 1;
+                continue;
+            }
+            match options.wrap && text_width > 0 {
+                true => {
+                    for (start, row) in self
+                        .lines
+                        .wrapped(buffer, line, text_width, tabstop, &layers)
+                    {
+                        rows.push((line, start));
+                        text.push(row);
+                    }
+                }
+                false => {
+                    rows.push((line, 0));
+                    text.push(self.lines.line(buffer, line, text_width, tabstop, &layers));
+                }
+            }
+            line += 1;
+        }
+        rows.truncate(height);
+        text.truncate(height);
+        self.lines.retain(self.scroll_y.get()..line);
 
-                        Some(&chunk[..n])
-                    }))
-                })
-                .collect::<Vec<_>>(),
-        ))
-        .render(buffer_area, buf);
-
-        // Render the gutter
-        Text::from_iter(
-            (self.scroll_y.get()..buffer.len_lines().min(line_count + self.scroll_y.get())).map(
-                |line| {
-                    if line == self.cursor.y {
-                        return Line::from(Span::raw((line + 1).to_string()).cyan())
-                            .alignment(HorizontalAlignment::Right);
-                    }
-                    let relative = if line < self.cursor.y {
-                        self.cursor.y - line
-                    } else {
-                        line - self.cursor.y
-                    };
+        // Render the text area, over the highlight of the cursor line
+        if options.cursorline {
+            for (i, (line, _)) in rows.iter().enumerate() {
+                if *line == self.cursor.y {
+                    let row = Rect::new(
+                        buffer_area.x,
+                        buffer_area.y + i as u16,
+                        buffer_area.width,
+                        1,
+                    );
+                    buf.set_style(row.intersection(buffer_area), CURSORLINE_STYLE);
+                }
+            }
+        }
+        Paragraph::new(Text::from(text)).render(buffer_area, buf);
+        for &column in guides(filetype) {
+            for (i, &(_, start)) in rows.iter().enumerate() {
+                if let Some(x) = column.checked_sub(start)
+                    && x < buffer_area.width as usize
+                {
+                    let position = (buffer_area.x + x as u16, buffer_area.y + i as u16);
+                    if let Some(cell) = buf.cell_mut(position) {
+                        cell.set_style(GUIDE_STYLE);
+                    }
+                }
+            }
+        }
 
-                    Line::from(Span::raw(relative.to_string()).dark_gray())
-                        .alignment(HorizontalAlignment::Right)
-                },
-            ),
-        )
-        .render(gutter_area, buf);
+        // Render the gutter, with filler rows after the end of the buffer
+        Text::from_iter((0..height).map(|i| {
+            let Some(&(line, start)) = rows.get(i) else {
+                let fill = state.config.eob.map(String::from).unwrap_or_default();
+                return Line::from(Span::raw(fill).fg(NON_TEXT));
+            };
+            let number = match line == self.cursor.y {
+                true if options.number => Span::raw((line + 1).to_string()).cyan(),
+                true => Span::raw("0").cyan(),
+                false if options.relativenumber => {
+                    Span::raw(self.cursor.y.abs_diff(line).to_string()).dark_gray()
+                }
+                false => Span::raw((line + 1).to_string()).dark_gray(),
+            };
+            match start {
+                // Wrapped rows continue the line above
+                0 => Line::from(vec![number, Span::raw(" ")]),
+                _ => Line::default(),
+            }
+            .alignment(HorizontalAlignment::Right)
+        }))
+        .render(number_area, buf);
 
+        *self.rows.borrow_mut() = rows;
         self.area.set(area);
+        self.render_completion(area, buf);
     }
 
     fn contains(&self, pos: Position) -> bool {
         self.area.get().contains(pos)
     }
 }
+
+/// Position of the cursor and size of the buffer, in lines, words, chars and bytes
+fn buffer_counts(rope: &Rope, cursor: &Cursor, index: usize) -> String {
+    let before = rope.slice(..index);
+    format!(
+        "Col {} of {}; Line {} of {}; Word {} of {}; Char {} of {}; Byte {} of {}",
+        cursor.x + 1,
+        line_len(rope.line(cursor.y)),
+        cursor.y + 1,
+        line_count(rope),
+        // The word under the cursor is counted
+        word_count(&rope.slice(..(index + 1).min(rope.len_chars())).to_string()),
+        word_count(&rope.to_string()),
+        index + 1,
+        rope.len_chars(),
+        before.len_bytes() + 1,
+        rope.len_bytes(),
+    )
+}
+
+/// Size of the visual selection and of the buffer, in lines, words, chars and bytes
+fn selection_counts(rope: &Rope, anchor: usize, index: usize) -> String {
+    let start = anchor.min(index);
+    let end = (anchor.max(index) + 1).min(rope.len_chars());
+    let selection = rope.slice(start..end);
+    let lines = rope.char_to_line(end.saturating_sub(1)) + 1 - rope.char_to_line(start);
+    format!(
+        "Selected {} of {} Lines; {} of {} Words; {} of {} Chars; {} of {} Bytes",
+        lines,
+        line_count(rope),
+        word_count(&selection.to_string()),
+        word_count(&rope.to_string()),
+        selection.len_chars(),
+        rope.len_chars(),
+        selection.len_bytes(),
+        rope.len_bytes(),
+    )
+}
+
+/// Value of the char at an index: decimal, hex and octal codepoint, and UTF-8 bytes
+fn char_info(rope: &Rope, index: usize) -> String {
+    let Some(c) = rope.get_char(index).filter(|c| *c != '\n') else {
+        return "NUL".to_string();
+    };
+    let mut bytes = [0; 4];
+    let bytes = c
+        .encode_utf8(&mut bytes)
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>();
+    let shown = match c.is_control() {
+        true => c.escape_default().to_string(),
+        false => c.to_string(),
+    };
+    let code = c as u32;
+    format!(
+        "<{}> {}, Hex {:04x}, Oct {:o}, U+{:04X}, UTF-8 {}",
+        shown,
+        code,
+        code,
+        code,
+        code,
+        bytes.join(" ")
+    )
+}
+
+/// Char inserted by a key after `CTRL-V`: control chars for `CTRL` chords, and raw special keys
+fn literal_char(key_event: KeyEvent) -> Option<char> {
+    match key_event.code {
+        KeyCode::Char(c) if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+            char::from_u32(c.to_ascii_uppercase() as u32 ^ 0x40)
+        }
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Enter => Some('\n'),
+        KeyCode::Esc => Some('\u{1b}'),
+        KeyCode::Backspace => Some('\u{8}'),
+        KeyCode::Delete => Some('\u{7f}'),
+        _ => None,
+    }
+}
+
+/// Insert a register after the cursor (`p`) or before it (`P`): below or above the cursor
+/// line when the text ends with a line break, leaving the cursor on its first non-blank,
+/// else next to the cursor char, leaving the cursor on the last inserted char.
+fn put(rope: &mut Edit, cursor: &mut Cursor, text: &str, after: bool) {
+    if let Some(lines) = text.strip_suffix('\n') {
+        let line = match after {
+            true => (cursor.y + 1).min(rope.len_lines()),
+            false => cursor.y,
+        };
+        let at = rope.line_to_char(line);
+        // The last line may have no line break to insert after
+        match at == rope.len_chars() && at > 0 && rope.char(at - 1) != '\n' {
+            true => rope.insert(at, &format!("\n{}", lines)),
+            false => rope.insert(at, text),
+        }
+        cursor.set_position(0, line, rope);
+        cursor.move_first_non_blank(rope);
+        return;
+    }
+    let index = cursor.cursor_char_index(rope);
+    let line_end = rope.line_to_char(cursor.y) + line_len(rope.line(cursor.y));
+    let at = match after && index < line_end {
+        true => index + 1,
+        false => index,
+    };
+    rope.insert(at, text);
+    cursor.move_to_char(rope, (at + text.chars().count()).saturating_sub(1).max(at));
+}
+
+/// First and last lines of a linewise visual selection
+fn selected_lines(rope: &Rope, anchor: usize, cursor: &Cursor) -> (usize, usize) {
+    let anchor = rope.char_to_line(anchor.min(rope.len_chars()));
+    (anchor.min(cursor.y), anchor.max(cursor.y))
+}
+
+/// Text of whole lines, ending with a line break to be put linewise
+fn line_text(rope: &Rope, start: usize, end: usize) -> String {
+    let to = rope.line_to_char((end + 1).min(rope.len_lines()));
+    let mut text = rope.slice(rope.line_to_char(start)..to).to_string();
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// Delete the visual selection, leaving the cursor at its start. Returns the deleted text.
+fn delete_selection(rope: &mut Edit, cursor: &mut Cursor, anchor: usize) -> String {
+    let index = cursor.cursor_char_index(rope);
+    let start = anchor.min(index);
+    let end = (anchor.max(index) + 1).min(rope.len_chars());
+    let deleted = rope.slice(start..end).to_string();
+    rope.remove(start..end);
+    cursor.move_to_char(rope, start);
+    deleted
+}
+
+fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Move the cursor to the next match of the last search,
+/// in the opposite direction if `reverse` is set.
+/// Returns false if there is no match.
+fn search_next(cursor: &mut Cursor, rope: &Rope, search: Option<&Search>, reverse: bool) -> bool {
+    let Some(search) = search else {
+        return false;
+    };
+    let from = cursor.cursor_char_index(rope);
+    match search.pattern.find(rope, from, search.backward != reverse) {
+        Some((start, _)) => {
+            cursor.move_to_char(rope, start);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Add to the first number of each selected line after the start of the selection, or in
+/// the block, leaving the cursor at the start of the selection. Returns whether a number
+/// changed.
+fn add_to_selected_numbers(
+    rope: &mut Edit,
+    mode: Mode,
+    anchor: usize,
+    cursor: &mut Cursor,
+    tabstop: usize,
+    delta: i64,
+    progressive: bool,
+) -> bool {
+    let (start, end) = selected_lines(rope, anchor, cursor);
+    let columns = match mode {
+        Mode::VisualBlock => {
+            let block = Block::new(rope, anchor, cursor, tabstop);
+            (start..=end)
+                .map(|index| block.columns(rope.line(index), tabstop).start)
+                .collect()
+        }
+        Mode::Visual => {
+            let first = anchor.min(cursor.cursor_char_index(rope)) - rope.line_to_char(start);
+            let mut columns = vec![0; end - start + 1];
+            columns[0] = first;
+            columns
+        }
+        _ => vec![0; end - start + 1],
+    };
+    let changed = rope.replace_lines(start, end, |lines| {
+        add_to_numbers(lines, &columns, delta, progressive)
+    });
+    cursor.set_position(columns[0], start, rope);
+    changed
+}
+
+/// Count with one more digit typed after it
+fn append_digit(count: Option<usize>, digit: char) -> usize {
+    let digit = digit.to_digit(10).unwrap_or(0) as usize;
+    count.unwrap_or(0).saturating_mul(10).saturating_add(digit)
+}
+
+/// Move the cursor to the first non-blank of a line, the last one past the end of the
+/// buffer
+fn jump_to_line(
+    cursor: &mut Cursor,
+    scroll_y: &Cell<usize>,
+    height: usize,
+    rope: &Rope,
+    line: usize,
+) {
+    cursor.set_position(0, line.min(last_line(rope)), rope);
+    cursor.move_first_non_blank(rope);
+    reveal_cursor(cursor, scroll_y, height);
+}
+
+/// Center the cursor line in the view after a jump, when it is out of it
+fn reveal_cursor(cursor: &Cursor, scroll_y: &Cell<usize>, height: usize) {
+    let scroll = scroll_y.get();
+    if cursor.y < scroll || cursor.y >= scroll + height {
+        scroll_y.set(cursor.y.saturating_sub(height / 2));
+    }
+}
+
+/// Set the `'<` and `'>` marks to the bounds of the visual selection
+fn set_visual_marks(
+    marks: &mut BTreeMap<char, usize>,
+    rope: &Rope,
+    anchor: usize,
+    cursor: &Cursor,
+) {
+    let index = cursor.cursor_char_index(rope);
+    marks.insert('<', anchor.min(index));
+    marks.insert('>', anchor.max(index));
+}
+
+/// Whether a key edits the buffer in a mode
+fn modifies(mode: Mode, pending: &str, key_event: KeyEvent) -> bool {
+    let deleting =
+        mode == Mode::Normal && pending.starts_with(['d', 'c']) && key_event.code != KeyCode::Esc;
+    // `CTRL-A` and `CTRL-X` add to numbers out of insert mode
+    let adding = mode != Mode::Insert
+        && key_event.modifiers.contains(KeyModifiers::CONTROL)
+        && matches!(key_event.code, KeyCode::Char('a' | 'x'));
+    deleting
+        || adding
+        || matches!(
+            (mode, pending, key_event.code),
+            (
+                Mode::Insert,
+                _,
+                KeyCode::Char(_)
+                    | KeyCode::Enter
+                    | KeyCode::Tab
+                    | KeyCode::Backspace
+                    | KeyCode::Delete
+            ) | (
+                Mode::Normal,
+                "",
+                KeyCode::Char('i' | 'a' | 'A' | 'I' | 'x' | 'p' | 'P')
+            ) | (
+                Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                "",
+                KeyCode::Char('i' | 'p' | 'P' | 'd' | 'x' | 'c' | '>' | '<')
+            ) | (Mode::VisualBlock, "", KeyCode::Char('I' | 'A'))
+                | (Mode::VisualBlock, "g", KeyCode::Char('#'))
+                | (
+                    Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "ga",
+                    KeyCode::Char(_)
+                )
+                | (Mode::Normal, ">", KeyCode::Char('>'))
+                | (Mode::Normal, "<", KeyCode::Char('<'))
+                | (Mode::Normal, "gc", KeyCode::Char('c'))
+                | (
+                    Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char('q')
+                )
+                | (
+                    Mode::Visual | Mode::VisualLine | Mode::VisualBlock,
+                    "g",
+                    KeyCode::Char('c' | 's' | 'r' | 'u')
+                )
+        )
+}
+
+/// Whether a char ends the path completed before the cursor, like quotes and brackets
+fn is_path_delimiter(c: char) -> bool {
+    c.is_whitespace() || "\"'`<>()[]{},;=|".contains(c)
+}
+
+/// Help tag of the `|tag|` link at a column, or else the keyword there
+fn tag_at(line: &str, x: usize) -> Option<String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut start = None;
+    for (i, c) in chars.iter().enumerate() {
+        match (c, start) {
+            ('|', None) => start = Some(i),
+            ('|', Some(s)) if s + 1 < i && (s..=i).contains(&x) => {
+                return Some(chars[s + 1..i].iter().collect());
+            }
+            ('|', Some(_)) => start = Some(i),
+            (c, Some(_)) if c.is_whitespace() => start = None,
+            _ => {}
+        }
+    }
+    keyword_at(line, x)
+}
+
+/// Keyword at a column
+fn keyword_at(line: &str, x: usize) -> Option<String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let x = x.min(chars.len());
+    let start = chars[..x]
+        .iter()
+        .rposition(|c| !is_keyword(*c))
+        .map_or(0, |i| i + 1);
+    let end = chars[x..]
+        .iter()
+        .position(|c| !is_keyword(*c))
+        .map_or(chars.len(), |i| x + i);
+    (start < end).then(|| chars[start..end].iter().collect())
+}
+
+/// Break the cursor line at the last blank before the text width, when typing past it.
+/// Code files only wrap their comments, and the new line keeps the comment leader.
+fn auto_wrap(
+    rope: &mut Edit,
+    cursor: &mut Cursor,
+    width: usize,
+    prose: bool,
+    comment: Option<&str>,
+) {
+    let line = rope.line(cursor.y).to_string();
+    let chars = line
+        .trim_end_matches(['\r', '\n'])
+        .chars()
+        .collect::<Vec<_>>();
+    if chars.len() <= width {
+        return;
+    }
+    let line = chars.iter().collect::<String>();
+    let prefix = line_prefix(&line, comment);
+    if !prose && prefix.trim().is_empty() {
+        return;
+    }
+    let prefix_len = prefix.chars().count();
+    let Some(blank) = (prefix_len..=width.min(chars.len() - 1))
+        .rev()
+        .find(|i| chars[*i].is_whitespace())
+    else {
+        return;
+    };
+    let start = (prefix_len..blank)
+        .rev()
+        .find(|i| !chars[*i].is_whitespace())
+        .map_or(prefix_len, |i| i + 1);
+    let end = (blank..chars.len())
+        .find(|i| !chars[*i].is_whitespace())
+        .unwrap_or(chars.len());
+    // Only wrap the text typed before the cursor
+    if start == prefix_len || cursor.x < end {
+        return;
+    }
+
+    let line_start = rope.line_to_char(cursor.y);
+    let x = cursor.x;
+    rope.remove(line_start + start..line_start + end);
+    rope.insert(line_start + start, &format!("\n{}", prefix));
+    let index = line_start + start + 1 + prefix_len + (x - end);
+    cursor.move_to_char(rope, index);
+}
+
+/// Non blank lines around a line
+fn paragraph(rope: &Rope, line: usize) -> Option<(usize, usize)> {
+    let blank = |i: usize| rope.line(i).chars().all(char::is_whitespace);
+    if blank(line) {
+        return None;
+    }
+    let start = (0..line).rev().find(|i| blank(*i)).map_or(0, |i| i + 1);
+    let end = (line + 1..rope.len_lines())
+        .find(|i| blank(*i))
+        .map_or(rope.len_lines() - 1, |i| i - 1);
+    Some((start, end))
+}
+
+/// Re-wrap lines to the text width, leaving the cursor on the last formatted line
+fn format_lines(
+    rope: &mut Edit,
+    cursor: &mut Cursor,
+    start: usize,
+    end: usize,
+    width: usize,
+    options: &BufferOptions,
+) {
+    let end = end.min(rope.len_lines() - 1);
+    let lines = (start..=end)
+        .map(|i| rope.line(i).to_string())
+        .collect::<Vec<_>>();
+    let eol = match lines[0].ends_with("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+    let lines = lines
+        .iter()
+        .map(|line| line.trim_end_matches(['\r', '\n']))
+        .collect::<Vec<_>>();
+    let formatted = reflow(&lines, width, options.line_comment());
+
+    let from = rope.line_to_char(start);
+    let to = rope.line_to_char(end) + lines[lines.len() - 1].chars().count();
+    rope.remove(from..to);
+    rope.insert(from, &formatted.join(eol));
+
+    let last = start + formatted.len().saturating_sub(1);
+    cursor.move_to_char(rope, rope.line_to_char(last));
+}
+
+/// Indentation of a line opened at the cursor: the one of the text before the cursor,
+/// with an extra level after an opening bracket
+fn new_line_indent(rope: &Rope, cursor: &Cursor, options: &BufferOptions) -> String {
+    let before = rope
+        .line(cursor.y)
+        .chars()
+        .take(cursor.x)
+        .collect::<String>();
+    let mut indent =
+        before[..before.len() - before.trim_start_matches([' ', '\t']).len()].to_string();
+    if before.trim_end().ends_with(['{', '(', '[']) {
+        indent.push_str(&options.indent_unit());
+    }
+    indent
+}
+
+/// Add or remove an indentation level on the lines from `start` to `end` included.
+/// Blank lines are not indented.
+fn shift_lines(rope: &mut Edit, start: usize, end: usize, options: &BufferOptions, right: bool) {
+    let unit = options.indent_unit();
+    for line in start..=end.min(rope.len_lines() - 1) {
+        let from = rope.line_to_char(line);
+        if right {
+            if !rope.line(line).chars().all(char::is_whitespace) {
+                rope.insert(from, &unit);
+            }
+            continue;
+        }
+        let blanks = match rope.get_char(from) {
+            Some('\t') => 1,
+            _ => rope
+                .line(line)
+                .chars()
+                .take(options.shiftwidth)
+                .take_while(|c| *c == ' ')
+                .count(),
+        };
+        rope.remove(from..from + blanks);
+    }
+}
+
+/// Comment the lines from `start` to `end` included with the comment string, at their
+/// smallest indentation, or uncomment them if they all are. Blank lines are left as is.
+fn toggle_comment(
+    rope: &mut Edit,
+    start: usize,
+    end: usize,
+    options: &BufferOptions,
+) -> Result<(), String> {
+    let (leader, trailer) = options
+        .comment_parts()
+        .ok_or("Option 'commentstring' is empty")?;
+    let end = end.min(rope.len_lines() - 1);
+    let lines = (start..=end)
+        .map(|i| rope.line(i).to_string())
+        .collect::<Vec<_>>();
+    let indent_len = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let filled = lines.iter().filter(|line| !line.trim().is_empty());
+    let commented = filled.clone().all(|line| {
+        let text = line.trim();
+        text.starts_with(leader) && text.ends_with(trailer)
+    });
+    let indent = filled.map(|line| indent_len(line)).min().unwrap_or(0);
+
+    let mut text = String::new();
+    for line in &lines {
+        let content = line.trim_end_matches(['\r', '\n']);
+        let eol = &line[content.len()..];
+        if content.trim().is_empty() {
+            text.push_str(line);
+            continue;
+        }
+        match commented {
+            true => {
+                let (indent, rest) = content.split_at(indent_len(content));
+                let rest = rest.strip_prefix(leader).unwrap_or(rest);
+                let mut rest = rest.strip_prefix(' ').unwrap_or(rest);
+                if !trailer.is_empty() {
+                    rest = rest.trim_end().strip_suffix(trailer).unwrap_or(rest);
+                    rest = rest.strip_suffix(' ').unwrap_or(rest);
+                }
+                text.push_str(indent);
+                text.push_str(rest);
+            }
+            false => {
+                let (indent, rest) = content.split_at(indent);
+                text.push_str(&format!("{}{} {}", indent, leader, rest));
+                if !trailer.is_empty() {
+                    text.push_str(&format!(" {}", trailer));
+                }
+            }
+        }
+        text.push_str(eol);
+    }
+
+    let from = rope.line_to_char(start);
+    let to = from + lines.iter().map(|line| line.chars().count()).sum::<usize>();
+    rope.remove(from..to);
+    rope.insert(from, &text);
+    Ok(())
+}
+
+fn not_found(state: &mut State) {
+    match &state.search {
+        Some(search) => {
+            let text = format!("E486: Pattern not found: {}", search.pattern.source);
+            state.error(text);
+        }
+        None => state.error("E35: No previous regular expression"),
+    }
+}
@@ -0,0 +1,168 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{
+    State,
+    state::{Edit, Mode},
+    widgets::{Action, READONLY_ERROR},
+};
+
+use super::{Pane, UndoMotion};
+
+impl Pane {
+    /// Handle the keys of the `basic` preset, before the modal ones: the save, quit, undo
+    /// and clipboard shortcuts of common editors, and selections made with Shift and the
+    /// arrows, which typed text replaces.
+    /// Returns None for the keys left to the modal handling.
+    pub(super) fn handle_basic_key(
+        &mut self,
+        key_event: KeyEvent,
+        state: &mut State,
+    ) -> Option<Option<Action>> {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key_event.modifiers.contains(KeyModifiers::SHIFT);
+        if state.mode != Mode::Visual {
+            self.selecting = false;
+        }
+        match key_event.code {
+            KeyCode::Char('s') if ctrl => return Some(Some(Action::Command("write".into()))),
+            KeyCode::Char('q') if ctrl => return Some(Some(Action::Command("quit".into()))),
+            KeyCode::Char(c @ ('z' | 'y')) if ctrl => {
+                self.leave_selection(state);
+                let motion = match c {
+                    'z' => UndoMotion::Undo,
+                    _ => UndoMotion::Redo,
+                };
+                self.undo(motion, state);
+                return Some(None);
+            }
+            _ => {}
+        }
+        // The clipboard is read before borrowing the buffer, falling back to the unnamed
+        // register without clipboard tool
+        let paste = match key_event.code {
+            KeyCode::Char('v') if ctrl => {
+                Some(state.register('+').or_else(|_| state.register('"')))
+            }
+            _ => None,
+        };
+
+        let file = state.filesystem.files.get_mut(self.file)?;
+        let readonly = file.readonly;
+        let rope = &mut Edit::new(file.buffer.as_mut()?, &mut file.changes);
+        let selecting = state.mode == Mode::Visual && self.selecting;
+        let edits = match key_event.code {
+            KeyCode::Char('x') => ctrl && selecting,
+            KeyCode::Char('v') => ctrl,
+            KeyCode::Char(_) | KeyCode::Enter | KeyCode::Tab => selecting && !ctrl,
+            KeyCode::Backspace | KeyCode::Delete => selecting,
+            _ => false,
+        };
+        if edits && readonly {
+            state.error(READONLY_ERROR);
+            return Some(None);
+        }
+
+        match key_event.code {
+            KeyCode::Char('a') if ctrl => {
+                self.visual_anchor = 0;
+                self.cursor.move_to_char(rope, rope.len_chars());
+                state.mode = Mode::Visual;
+                self.selecting = true;
+            }
+            KeyCode::Char('c') if ctrl => {
+                if selecting {
+                    let text = rope.slice(self.selection(rope)).to_string();
+                    state.set_register('+', text);
+                }
+            }
+            KeyCode::Char('x') if ctrl => {
+                if selecting {
+                    let text = self.delete_selected(rope);
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Insert;
+                    state.set_register('+', text);
+                }
+            }
+            KeyCode::Char('v') if ctrl => match paste? {
+                Ok(text) => {
+                    if selecting {
+                        self.delete_selected(rope);
+                    }
+                    let at = self.cursor.cursor_char_index(rope);
+                    rope.insert(at, &text);
+                    self.cursor.move_to_char(rope, at + text.chars().count());
+                    file.modified = !file.scratch;
+                    state.mode = Mode::Insert;
+                }
+                Err(err) => state.error(err),
+            },
+            // Shift and a move start a selection or extend it
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Home
+            | KeyCode::End
+                if shift && !ctrl =>
+            {
+                if !selecting {
+                    self.visual_anchor = self.cursor.cursor_char_index(rope);
+                    state.mode = Mode::Visual;
+                    self.selecting = true;
+                }
+                match key_event.code {
+                    KeyCode::Left => self.cursor.move_left(rope),
+                    KeyCode::Right => self.cursor.move_right(rope),
+                    KeyCode::Up => self.cursor.move_up(rope),
+                    KeyCode::Down => self.cursor.move_down(rope),
+                    KeyCode::Home => self.cursor.move_line_start(rope),
+                    _ => self.cursor.move_line_end(rope),
+                }
+            }
+            KeyCode::Esc if selecting => state.mode = Mode::Insert,
+            KeyCode::Backspace | KeyCode::Delete if selecting => {
+                self.delete_selected(rope);
+                file.modified = !file.scratch;
+                state.mode = Mode::Insert;
+            }
+            // Typed text replaces the selection, and moves leave it
+            KeyCode::Char(_) | KeyCode::Enter | KeyCode::Tab if selecting && !ctrl => {
+                self.delete_selected(rope);
+                file.modified = !file.scratch;
+                state.mode = Mode::Insert;
+                return None;
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Home
+            | KeyCode::End
+                if selecting =>
+            {
+                state.mode = Mode::Insert;
+                return None;
+            }
+            _ => return None,
+        }
+        Some(None)
+    }
+
+    /// Delete the chars of the selection, up to the cursor.
+    /// Returns the deleted text.
+    fn delete_selected(&mut self, rope: &mut Edit) -> String {
+        let range = self.selection(rope);
+        let deleted = rope.slice(range.clone()).to_string();
+        rope.remove(range.clone());
+        self.cursor.move_to_char(rope, range.start);
+        deleted
+    }
+
+    /// Leave a selection of the `basic` preset, back to insert mode
+    fn leave_selection(&mut self, state: &mut State) {
+        if state.mode == Mode::Visual && self.selecting {
+            state.mode = Mode::Insert;
+        }
+        self.selecting = false;
+    }
+}
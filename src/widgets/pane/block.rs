@@ -0,0 +1,155 @@
+use std::ops::Range;
+
+use ropey::{Rope, RopeSlice};
+
+use crate::{
+    cursor::Cursor,
+    position::{cell_to_col, col_to_cell, line_len, to_line_col},
+    state::Edit,
+};
+
+/// Rectangular selection of the blockwise visual mode, as lines and display cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// First and last lines
+    pub start: usize,
+    pub end: usize,
+    /// First and last display cells, included
+    pub left: usize,
+    pub right: usize,
+}
+
+/// Where the text inserted on a block goes on each of its lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Side {
+    /// `I`: before the block, on the lines reaching it
+    Before,
+    /// `c`: in place of the deleted block, on the lines reaching its start
+    Replaced,
+    /// `A`: after the block, padding the shorter lines with spaces
+    After,
+}
+
+/// Insertion started with `I`, `A` or `c` on a block, repeated on its other lines when
+/// leaving insert mode
+#[derive(Debug, Clone, Copy)]
+pub(super) struct BlockInsert {
+    block: Block,
+    /// Char index of the first line where the text is typed
+    at: usize,
+    side: Side,
+}
+
+impl Block {
+    /// Block between the char the selection started at and the cursor
+    pub fn new(rope: &Rope, anchor: usize, cursor: &Cursor, tabstop: usize) -> Self {
+        let (line, column) = to_line_col(rope, anchor);
+        let anchor_cell = col_to_cell(rope.line(line), column, tabstop);
+        let cursor_cell = col_to_cell(rope.line(cursor.y), cursor.x, tabstop);
+        Self {
+            start: line.min(cursor.y),
+            end: line.max(cursor.y),
+            left: anchor_cell.min(cursor_cell),
+            right: anchor_cell.max(cursor_cell),
+        }
+    }
+
+    /// Char columns of a line in the block, empty when the line ends before it
+    pub fn columns(&self, line: RopeSlice, tabstop: usize) -> Range<usize> {
+        let start = cell_to_col(line, self.left, tabstop);
+        let end = match cell_to_col(line, self.right, tabstop) {
+            end if end < line_len(line) => end + 1,
+            end => end,
+        };
+        start..end
+    }
+
+    /// Text of the block, one line of it per line
+    pub fn text(&self, rope: &Rope, tabstop: usize) -> String {
+        (self.start..=self.end)
+            .map(|index| {
+                let line = rope.line(index);
+                line.slice(self.columns(line, tabstop)).to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Delete the text of the block. Returns the deleted text.
+    pub fn delete(&self, rope: &mut Edit, tabstop: usize) -> String {
+        let text = self.text(rope, tabstop);
+        for index in self.start..=self.end {
+            let columns = self.columns(rope.line(index), tabstop);
+            let from = rope.line_to_char(index);
+            rope.remove(from + columns.start..from + columns.end);
+        }
+        text
+    }
+
+    /// Insert text on the lines of the block after the first one
+    fn insert(&self, rope: &mut Edit, text: &str, side: Side, tabstop: usize) {
+        for index in self.start + 1..=self.end.min(rope.len_lines() - 1) {
+            let line = rope.line(index);
+            let len = line_len(line);
+            let width = col_to_cell(line, len, tabstop);
+            let from = rope.line_to_char(index);
+            match side {
+                Side::After if width <= self.right => {
+                    let padding = " ".repeat(self.right + 1 - width);
+                    rope.insert(from + len, &format!("{}{}", padding, text));
+                }
+                Side::After => {
+                    let column = self.columns(line, tabstop).end;
+                    rope.insert(from + column, text);
+                }
+                Side::Before if width <= self.left => {}
+                Side::Replaced if width < self.left => {}
+                Side::Before | Side::Replaced => {
+                    let column = cell_to_col(line, self.left, tabstop);
+                    rope.insert(from + column, text);
+                }
+            }
+        }
+    }
+}
+
+impl BlockInsert {
+    /// Start inserting on the first line of a block, moving the cursor there
+    pub fn start(
+        block: Block,
+        side: Side,
+        rope: &mut Edit,
+        cursor: &mut Cursor,
+        tabstop: usize,
+    ) -> Self {
+        let line = rope.line(block.start);
+        let len = line_len(line);
+        let width = col_to_cell(line, len, tabstop);
+        let from = rope.line_to_char(block.start);
+        let column = match side {
+            Side::After if width <= block.right => {
+                rope.insert(from + len, &" ".repeat(block.right + 1 - width));
+                len + block.right + 1 - width
+            }
+            Side::After => block.columns(line, tabstop).end,
+            Side::Before | Side::Replaced => cell_to_col(line, block.left, tabstop),
+        };
+        cursor.move_to_char(rope, from + column);
+        Self {
+            block,
+            at: from + column,
+            side,
+        }
+    }
+
+    /// Repeat the text typed on the first line on the other lines, when it is still on
+    /// a single line
+    pub fn finish(&self, rope: &mut Edit, cursor: &Cursor, tabstop: usize) {
+        let index = cursor.cursor_char_index(rope);
+        if cursor.y != self.block.start || index <= self.at {
+            return;
+        }
+        let text = rope.slice(self.at..index).to_string();
+        self.block.insert(rope, &text, self.side, tabstop);
+    }
+}
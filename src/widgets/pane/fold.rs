@@ -0,0 +1,133 @@
+use ropey::Rope;
+
+use crate::{
+    State,
+    position::{last_line, line_len},
+    state::{Mode, Transaction, folds::folds as buffer_folds},
+};
+
+use super::Pane;
+
+/// Error of the fold commands without fold under the cursor
+const NO_FOLD: &str = "E490: No fold found";
+
+impl Pane {
+    /// Outermost closed fold containing a line
+    pub(super) fn closed_fold(&self, line: usize) -> Option<(usize, usize)> {
+        closed_fold(&self.folds, line)
+    }
+
+    /// Lines shown from one to another, a closed fold taking a single row
+    pub(super) fn visible_lines(&self, from: usize, to: usize) -> usize {
+        let mut count = 0;
+        let mut line = from;
+        while line <= to {
+            count += 1;
+            line = self.closed_fold(line).map_or(line, |(_, end)| end) + 1;
+        }
+        count
+    }
+
+    /// Move the cursor out of the closed folds after a key moved it from a line: to the
+    /// first line of the fold, or past it when moving down from the fold line.
+    /// Inserting in a fold opens it.
+    pub fn leave_folds(&mut self, from: usize, state: &State) {
+        let Some((start, end)) = self.closed_fold(self.cursor.y) else {
+            return;
+        };
+        if state.mode == Mode::Insert {
+            let line = self.cursor.y;
+            self.folds
+                .retain(|(start, end)| !(*start..=*end).contains(&line));
+            return;
+        }
+        let Some(rope) = state.filesystem.files[self.file].buffer.as_ref() else {
+            return;
+        };
+        let target = match self.cursor.y > from && from >= start && end < last_line(rope) {
+            true => end + 1,
+            false => start,
+        };
+        match target > self.cursor.y {
+            true => self.cursor.move_down_n(rope, target - self.cursor.y),
+            false => self.cursor.move_up_n(rope, self.cursor.y - target),
+        }
+    }
+
+    /// Move the closed folds with the lines changed by a transaction, from the text
+    /// before it
+    pub fn follow_folds(&mut self, transaction: &Transaction, before: &Rope, rope: &Rope) {
+        let map = |line: usize| {
+            let index = before.line_to_char(line.min(before.len_lines() - 1));
+            rope.char_to_line(transaction.map(index).min(rope.len_chars()))
+        };
+        self.folds = self
+            .folds
+            .iter()
+            .map(|&(start, end)| (map(start), map(end)))
+            .filter(|(start, end)| end > start)
+            .collect();
+    }
+
+    /// Open all the folds at the cursor, to show a line jumped to
+    pub fn open_folds(&mut self) {
+        let line = self.cursor.y;
+        self.folds
+            .retain(|(start, end)| !(*start..=*end).contains(&line));
+    }
+}
+
+/// Outermost fold of a list containing a line
+fn closed_fold(folds: &[(usize, usize)], line: usize) -> Option<(usize, usize)> {
+    folds
+        .iter()
+        .filter(|(start, end)| (*start..=*end).contains(&line))
+        .min_by_key(|(start, end)| (*start, usize::MAX - end))
+        .copied()
+}
+
+/// Fold commands after `z` on the closed folds of a pane, at a line: `zc` closes the
+/// innermost open fold, `zo` opens the outermost closed one, `za` toggles it, `zM`
+/// closes all the folds and `zR` opens them
+pub(super) fn fold_command(
+    folds: &mut Vec<(usize, usize)>,
+    key: char,
+    line: usize,
+    rope: &Rope,
+    filetype: Option<&str>,
+    tabstop: usize,
+) -> Result<(), &'static str> {
+    let closed = closed_fold(folds, line);
+    match key {
+        'R' => folds.clear(),
+        'M' => *folds = buffer_folds(rope, filetype, tabstop),
+        'o' | 'a' if closed.is_some() || key == 'o' => {
+            let closed = closed.ok_or(NO_FOLD)?;
+            folds.retain(|fold| *fold != closed);
+        }
+        _ => {
+            // The next fold around the closed ones at the line
+            let fold = buffer_folds(rope, filetype, tabstop)
+                .into_iter()
+                .filter(|&(start, end)| {
+                    (start..=end).contains(&line)
+                        && closed.is_none_or(|closed| {
+                            start <= closed.0 && closed.1 <= end && (start, end) != closed
+                        })
+                })
+                .min_by_key(|(start, end)| end - start)
+                .ok_or(NO_FOLD)?;
+            if !folds.contains(&fold) {
+                folds.push(fold);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Text of a closed fold, like `+--  4 lines: [dependencies]`
+pub(super) fn fold_text(rope: &Rope, (start, end): (usize, usize)) -> String {
+    let line = rope.line(start);
+    let text = line.slice(..line_len(line)).to_string();
+    format!("+--{:>3} lines: {}", end - start + 1, text.trim())
+}
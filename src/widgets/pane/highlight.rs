@@ -0,0 +1,246 @@
+use std::{cell::RefCell, collections::HashMap, ops::Range};
+
+use ratatui::prelude::*;
+use ropey::Rope;
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    position::{LONG_LINE, char_width, line_len},
+    state::Pattern,
+};
+
+pub use syntax::guides;
+
+use super::block::Block;
+
+mod syntax;
+
+/// Style of the matches of the last search
+const SEARCH_STYLE: Style = Style::new().fg(Color::Black).bg(Color::Yellow);
+/// Style of the visual selection
+const SELECTION_STYLE: Style = Style::new().bg(Color::DarkGray);
+
+/// Decoration layers drawn over the text, by increasing priority
+#[derive(Debug, Clone)]
+pub struct Layers<'a> {
+    /// Filetype whose syntax is highlighted
+    pub filetype: Option<&'static str>,
+    /// Pattern whose matches are highlighted
+    pub search: Option<&'a Pattern>,
+    /// Char range of the visual selection in the buffer
+    pub selection: Option<Range<usize>>,
+    /// Blockwise visual selection, instead of the char range
+    pub block: Option<Block>,
+}
+
+/// Styled char range of a line
+#[derive(Debug, Clone, Copy)]
+struct Decoration {
+    start: usize,
+    end: usize,
+    style: Style,
+}
+
+/// Inputs the styles of a line are computed from: its text, and the state of each layer
+/// over it
+#[derive(Debug, PartialEq, Eq)]
+struct LineKey {
+    text: String,
+    width: usize,
+    tabstop: usize,
+    filetype: Option<&'static str>,
+    /// Source and options of the highlighted pattern
+    search: Option<(String, bool, bool)>,
+    /// Char range of the selection on the line
+    selection: Option<Range<usize>>,
+}
+
+/// Styled lines of the last frame, reused while their key is unchanged so that the
+/// layers only scan the lines whose text or decorations changed
+#[derive(Debug, Default)]
+pub struct LineCache {
+    lines: RefCell<HashMap<usize, (LineKey, Line<'static>)>>,
+}
+
+impl LineCache {
+    /// Styled line of the rope, truncated to `width` display cells
+    pub fn line(
+        &self,
+        rope: &Rope,
+        index: usize,
+        width: usize,
+        tabstop: usize,
+        layers: &Layers,
+    ) -> Line<'static> {
+        let line = rope.line(index);
+        let len = line_len(line);
+        let text = match len >= LONG_LINE {
+            // Long lines are only read up to the edge of the view
+            true => {
+                let mut cell = 0;
+                line.chars()
+                    .take(len)
+                    .take_while(|&c| {
+                        let visible = cell < width;
+                        cell += char_width(c, cell, tabstop);
+                        visible
+                    })
+                    .collect()
+            }
+            false => line.slice(..len).to_string(),
+        };
+
+        let start = rope.line_to_char(index);
+        let selection = match layers.block {
+            Some(block) if (block.start..=block.end).contains(&index) => {
+                Some(block.columns(line, tabstop)).filter(|columns| !columns.is_empty())
+            }
+            Some(_) => None,
+            None => layers.selection.clone().and_then(|selection| {
+                // The newline is selected as one more column
+                let end = start + len + 1;
+                let range = selection.start.max(start)..selection.end.min(end);
+                (!range.is_empty()).then(|| range.start - start..range.end - start)
+            }),
+        };
+        let key = LineKey {
+            search: layers
+                .search
+                .map(|pattern| (pattern.source.clone(), pattern.ignorecase, pattern.literal)),
+            selection,
+            width,
+            tabstop,
+            filetype: layers.filetype,
+            text,
+        };
+
+        let mut lines = self.lines.borrow_mut();
+        if let Some((cached, line)) = lines.get(&index)
+            && *cached == key
+        {
+            return line.clone();
+        }
+        let line = compose(&key.text, &decorations(&key, index, layers), width, tabstop);
+        lines.insert(index, (key, line.clone()));
+        line
+    }
+
+    /// Styled line of the rope split into rows of `width` display cells, with the cell
+    /// each row starts at
+    pub fn wrapped(
+        &self,
+        rope: &Rope,
+        index: usize,
+        width: usize,
+        tabstop: usize,
+        layers: &Layers,
+    ) -> Vec<(usize, Line<'static>)> {
+        let line = self.line(rope, index, usize::MAX, tabstop, layers);
+        let mut rows = vec![(0, vec![])];
+        let (mut start, mut cell) = (0, 0);
+        for span in line.spans {
+            let mut text = String::new();
+            for c in span.content.chars() {
+                let cells = c.width().unwrap_or(0);
+                // Wide chars that do not fit at the end of a row start the next one
+                if cell > start && cell - start + cells > width {
+                    if let Some((_, spans)) = rows.last_mut() {
+                        spans.push(Span::styled(std::mem::take(&mut text), span.style));
+                    }
+                    start = cell;
+                    rows.push((start, vec![]));
+                }
+                text.push(c);
+                cell += cells;
+            }
+            if let Some((_, spans)) = rows.last_mut() {
+                spans.push(Span::styled(text, span.style));
+            }
+        }
+        rows.into_iter()
+            .map(|(start, spans)| (start, Line::from(spans)))
+            .collect()
+    }
+
+    /// Drop the lines that are no longer visible
+    pub fn retain(&self, visible: Range<usize>) {
+        self.lines
+            .borrow_mut()
+            .retain(|index, _| visible.contains(index));
+    }
+}
+
+/// Decorations of every layer over a line, by increasing priority
+fn decorations(key: &LineKey, index: usize, layers: &Layers) -> Vec<Decoration> {
+    let mut decorations = syntax::decorations(key.filetype, index, &key.text);
+    if let Some(pattern) = layers.search {
+        decorations.extend(
+            pattern
+                .line_matches(&key.text)
+                .into_iter()
+                .map(|(start, end)| Decoration {
+                    start,
+                    end,
+                    style: SEARCH_STYLE,
+                }),
+        );
+    }
+    if let Some(selection) = &key.selection {
+        decorations.push(Decoration {
+            start: selection.start,
+            end: selection.end,
+            style: SELECTION_STYLE,
+        });
+    }
+    decorations
+}
+
+/// Split a line into spans at the decoration bounds, each span patched with the styles
+/// of the decorations covering it, with the tabs expanded to spaces. Decorations past the
+/// end of the text are drawn on one more blank cell.
+fn compose(text: &str, decorations: &[Decoration], width: usize, tabstop: usize) -> Line<'static> {
+    let mut cell = 0;
+    let chars = text
+        .chars()
+        .map_while(|c| {
+            let cells = char_width(c, cell, tabstop);
+            cell += cells;
+            (cell <= width).then_some((c, cells))
+        })
+        .collect::<Vec<_>>();
+    let len = chars.len();
+    let mut bounds = vec![0, len];
+    for decoration in decorations {
+        bounds.push(decoration.start.min(len));
+        bounds.push(decoration.end.min(len));
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let style_at = |start: usize, end: usize| {
+        decorations
+            .iter()
+            .filter(|decoration| decoration.start <= start && end <= decoration.end)
+            .fold(Style::default(), |style, decoration| {
+                style.patch(decoration.style)
+            })
+    };
+    let mut spans = bounds
+        .windows(2)
+        .map(|bound| {
+            let text = chars[bound[0]..bound[1]]
+                .iter()
+                .map(|(c, cells)| match c {
+                    '\t' => " ".repeat(*cells),
+                    c => c.to_string(),
+                })
+                .collect::<String>();
+            Span::styled(text, style_at(bound[0], bound[1]))
+        })
+        .collect::<Vec<_>>();
+    let cells = chars.iter().map(|(_, cells)| cells).sum::<usize>();
+    if cells < width && decorations.iter().any(|decoration| decoration.end > len) {
+        spans.push(Span::styled(" ", style_at(len, len + 1)));
+    }
+    Line::from(spans)
+}
@@ -0,0 +1,76 @@
+use ratatui::prelude::*;
+
+use super::Decoration;
+
+/// Style of the comment lines
+const COMMENT_STYLE: Style = Style::new().fg(Color::DarkGray);
+/// Style of the summary line of a commit message
+const SUMMARY_STYLE: Style = Style::new().add_modifier(Modifier::BOLD);
+/// Style of the text past the recommended line length
+const OVERFLOW_STYLE: Style = Style::new().fg(Color::Red);
+/// Styles of the lines of a diff
+const ADDED_STYLE: Style = Style::new().fg(Color::Green);
+const REMOVED_STYLE: Style = Style::new().fg(Color::Red);
+const HUNK_STYLE: Style = Style::new().fg(Color::Cyan);
+const FILE_STYLE: Style = Style::new().add_modifier(Modifier::BOLD);
+
+/// Summary length of a commit message
+const SUMMARY_WIDTH: usize = 50;
+/// Body line length of a commit message
+const BODY_WIDTH: usize = 72;
+
+/// Columns drawn as guides in the panes of a filetype
+pub fn guides(filetype: Option<&str>) -> &'static [usize] {
+    match filetype {
+        Some("gitcommit") => &[SUMMARY_WIDTH, BODY_WIDTH],
+        _ => &[],
+    }
+}
+
+/// Decorations of the syntax of a filetype over a line, its first one at index 0
+pub(super) fn decorations(filetype: Option<&str>, index: usize, text: &str) -> Vec<Decoration> {
+    match filetype {
+        Some("gitcommit") => gitcommit(index, text),
+        Some("diff") => diff(text),
+        _ => vec![],
+    }
+}
+
+/// Comments of a commit message, and its summary, with the text past 50 columns on the
+/// summary and past 72 on the body. The line after the summary should be blank.
+fn gitcommit(index: usize, text: &str) -> Vec<Decoration> {
+    let len = text.chars().count();
+    let decoration = |start: usize, style: Style| Decoration {
+        start,
+        end: len,
+        style,
+    };
+    if text.starts_with('#') {
+        return vec![decoration(0, COMMENT_STYLE)];
+    }
+    match index {
+        0 => vec![
+            decoration(0, SUMMARY_STYLE),
+            decoration(SUMMARY_WIDTH.min(len), OVERFLOW_STYLE),
+        ],
+        1 => vec![decoration(0, OVERFLOW_STYLE)],
+        _ => vec![decoration(BODY_WIDTH.min(len), OVERFLOW_STYLE)],
+    }
+}
+
+/// Added and removed lines of a diff, its hunk headers, and the headers of its files
+fn diff(text: &str) -> Vec<Decoration> {
+    let style = match text.chars().next() {
+        _ if text.starts_with("+++ ") || text.starts_with("--- ") => FILE_STYLE,
+        _ if text.starts_with("diff ") || text.starts_with("index ") => FILE_STYLE,
+        Some('+') => ADDED_STYLE,
+        Some('-') => REMOVED_STYLE,
+        Some('@') if text.starts_with("@@") => HUNK_STYLE,
+        _ => return vec![],
+    };
+    vec![Decoration {
+        start: 0,
+        end: text.chars().count(),
+        style,
+    }]
+}
@@ -0,0 +1,355 @@
+use std::ops::Range;
+
+use crossterm::event::KeyCode;
+use ropey::Rope;
+
+use crate::{
+    cursor::{CharClass, Cursor, char_class, next_word_start, prev_word_start, word_end},
+    position::{last_line, line_len},
+    state::Edit,
+};
+
+use super::paragraph;
+
+/// Operator waiting in operator-pending mode for the motion or text object giving the
+/// text it acts on, like the `d` of `dw` and `di(`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+impl Operator {
+    pub fn from_key(c: char) -> Option<Self> {
+        match c {
+            'd' => Some(Self::Delete),
+            'c' => Some(Self::Change),
+            'y' => Some(Self::Yank),
+            _ => None,
+        }
+    }
+
+    fn key(self) -> char {
+        match self {
+            Self::Delete => 'd',
+            Self::Change => 'c',
+            Self::Yank => 'y',
+        }
+    }
+
+    /// Register receiving the text without `"{reg}`, yanks leaving `""` to deletes
+    pub fn default_register(self) -> char {
+        match self {
+            Self::Yank => '0',
+            _ => '"',
+        }
+    }
+}
+
+/// Text an operator acts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Chars(Range<usize>),
+    /// First and last lines, included
+    Lines(usize, usize),
+}
+
+/// Keys typed after an operator, read one at a time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Motion {
+    /// The keys start a motion, like the `i` of `di(`
+    Pending,
+    /// The keys are no motion, or it does not apply at the cursor
+    Invalid,
+    Target(Target),
+}
+
+/// Char typed after an operator, arrows and Home/End being their motion keys
+pub fn motion_key(code: KeyCode) -> Option<char> {
+    match code {
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Left => Some('h'),
+        KeyCode::Right => Some('l'),
+        KeyCode::Up => Some('k'),
+        KeyCode::Down => Some('j'),
+        KeyCode::Home => Some('0'),
+        KeyCode::End => Some('$'),
+        _ => None,
+    }
+}
+
+/// Read the keys typed after an operator: a motion from the cursor, a text object
+/// around it, or the operator key again for the cursor line
+pub fn parse(operator: Operator, keys: &str, rope: &Rope, cursor: &Cursor) -> Motion {
+    let at = cursor.cursor_char_index(rope);
+    let line_start = rope.line_to_char(cursor.y);
+    let line_end = line_start + line_len(rope.line(cursor.y));
+    let chars = |range: Range<usize>| Motion::Target(Target::Chars(range));
+    let mut keys = keys.chars();
+    let (Some(key), object) = (keys.next(), keys.next()) else {
+        return Motion::Invalid;
+    };
+    if keys.next().is_some() {
+        return Motion::Invalid;
+    }
+    match (key, object) {
+        (key, None) if key == operator.key() => Motion::Target(Target::Lines(cursor.y, cursor.y)),
+        ('h', None) => chars(at.saturating_sub(1).max(line_start)..at),
+        ('l' | ' ', None) => chars(at..(at + 1).min(line_end)),
+        ('0', None) => chars(line_start..at),
+        ('^', None) => {
+            let blanks = rope
+                .line(cursor.y)
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count();
+            let first = line_start + blanks.min(line_end - line_start);
+            chars(first.min(at)..first.max(at))
+        }
+        ('$', None) => chars(at..line_end),
+        ('j', None) if cursor.y < last_line(rope) => {
+            Motion::Target(Target::Lines(cursor.y, cursor.y + 1))
+        }
+        ('k', None) if cursor.y > 0 => Motion::Target(Target::Lines(cursor.y - 1, cursor.y)),
+        ('G', None) => Motion::Target(Target::Lines(cursor.y, last_line(rope))),
+        ('g', None) => Motion::Pending,
+        ('g', Some('g')) => Motion::Target(Target::Lines(0, cursor.y)),
+        // Like vim, `cw` on a word only changes up to its end
+        ('w' | 'W', None)
+            if operator == Operator::Change
+                && rope
+                    .get_char(at)
+                    .is_some_and(|c| char_class(c, key == 'W') != CharClass::Blank) =>
+        {
+            let end = end_of_word(rope, at, key == 'W');
+            chars(at..(end + 1).min(rope.len_chars()))
+        }
+        // The last word of a line stops at its end rather than at the next line
+        ('w' | 'W', None) => {
+            let end = next_word_start(rope, at, key == 'W');
+            match end > line_end && at < line_end {
+                true => chars(at..line_end),
+                false => chars(at..end),
+            }
+        }
+        ('e' | 'E', None) => {
+            let end = word_end(rope, at, key == 'E');
+            chars(at..(end + 1).min(rope.len_chars()))
+        }
+        ('b' | 'B', None) => chars(prev_word_start(rope, at, key == 'B')..at),
+        ('i' | 'a', None) => Motion::Pending,
+        ('i' | 'a', Some(object)) => match text_object(rope, cursor, at, object, key == 'a') {
+            Some(target) => Motion::Target(target),
+            None => Motion::Invalid,
+        },
+        _ => Motion::Invalid,
+    }
+}
+
+/// Apply an operator to its target, leaving the cursor at its start.
+/// Returns the deleted or yanked text, ending with a line break for lines.
+pub fn apply(operator: Operator, target: Target, rope: &mut Edit, cursor: &mut Cursor) -> String {
+    match (operator, target) {
+        (Operator::Yank, Target::Chars(range)) => {
+            let text = rope.slice(range.clone()).to_string();
+            cursor.move_to_char(rope, range.start);
+            text
+        }
+        (Operator::Delete | Operator::Change, Target::Chars(range)) => {
+            let text = rope.slice(range.clone()).to_string();
+            rope.remove(range.clone());
+            cursor.move_to_char(rope, range.start);
+            text
+        }
+        (Operator::Yank, Target::Lines(start, end)) => {
+            let from = rope.line_to_char(start);
+            let to = rope.line_to_char((end + 1).min(rope.len_lines()));
+            let mut text = rope.slice(from..to).to_string();
+            if !text.ends_with('\n') {
+                text.push('\n');
+            }
+            if start != cursor.y {
+                cursor.set_position(cursor.x, start, rope);
+            }
+            text
+        }
+        (Operator::Delete, Target::Lines(start, end)) => {
+            let text = cursor.delete_lines(rope, start, end);
+            cursor.move_first_non_blank(rope);
+            text
+        }
+        // The lines are replaced by an empty one to insert into
+        (Operator::Change, Target::Lines(start, end)) => {
+            let from = rope.line_to_char(start);
+            let to = rope.line_to_char(end) + line_len(rope.line(end));
+            let text = format!("{}\n", rope.slice(from..to));
+            rope.remove(from..to);
+            cursor.move_to_char(rope, from);
+            text
+        }
+    }
+}
+
+/// End of the word under the cursor, unlike `e` which moves on from its last char
+fn end_of_word(rope: &Rope, at: usize, big: bool) -> usize {
+    let class = char_class(rope.char(at), big);
+    match rope
+        .get_char(at + 1)
+        .is_some_and(|c| char_class(c, big) == class)
+    {
+        true => word_end(rope, at, big),
+        false => at,
+    }
+}
+
+/// Text object after `i` (inner) or `a` (around): words, quoted strings, blocks
+/// between brackets and paragraphs
+fn text_object(
+    rope: &Rope,
+    cursor: &Cursor,
+    at: usize,
+    object: char,
+    around: bool,
+) -> Option<Target> {
+    let range = match object {
+        'w' | 'W' => word_object(rope, cursor, object == 'W', around)?,
+        '"' | '\'' | '`' => quote_object(rope, cursor, object, around)?,
+        '(' | ')' | 'b' => bracket_object(rope, at, '(', ')', around)?,
+        '[' | ']' => bracket_object(rope, at, '[', ']', around)?,
+        '{' | '}' | 'B' => bracket_object(rope, at, '{', '}', around)?,
+        '<' | '>' => bracket_object(rope, at, '<', '>', around)?,
+        'p' => return paragraph_object(rope, cursor.y, around),
+        _ => return None,
+    };
+    Some(Target::Chars(range))
+}
+
+/// Word under the cursor, or blanks between words, with the blanks after it when
+/// `around`, or else before it
+fn word_object(rope: &Rope, cursor: &Cursor, big: bool, around: bool) -> Option<Range<usize>> {
+    let line = rope.line(cursor.y);
+    let chars = line.chars().take(line_len(line)).collect::<Vec<_>>();
+    if chars.is_empty() {
+        return None;
+    }
+    let class = |i: usize| char_class(chars[i], big);
+    let run = |i: usize| {
+        let start = (0..i)
+            .rev()
+            .find(|j| class(*j) != class(i))
+            .map_or(0, |j| j + 1);
+        let end = (i..chars.len())
+            .find(|j| class(*j) != class(i))
+            .unwrap_or(chars.len());
+        (start, end)
+    };
+    let x = cursor.x.min(chars.len() - 1);
+    let (mut start, mut end) = run(x);
+    if around {
+        if class(x) == CharClass::Blank || (end < chars.len() && class(end) == CharClass::Blank) {
+            if end < chars.len() {
+                end = run(end).1;
+            }
+        } else if start > 0 && class(start - 1) == CharClass::Blank {
+            start = run(start - 1).0;
+        }
+    }
+    let offset = rope.line_to_char(cursor.y);
+    Some(offset + start..offset + end)
+}
+
+/// String between quotes of the cursor line, around the cursor or else after it, with
+/// the quotes and the blanks after them when `around`
+fn quote_object(rope: &Rope, cursor: &Cursor, quote: char, around: bool) -> Option<Range<usize>> {
+    let line = rope.line(cursor.y);
+    let chars = line.chars().take(line_len(line)).collect::<Vec<_>>();
+    let quotes = (0..chars.len())
+        .filter(|i| chars[*i] == quote && (*i == 0 || chars[*i - 1] != '\\'))
+        .collect::<Vec<_>>();
+    let &[open, close] = quotes.chunks_exact(2).find(|pair| cursor.x <= pair[1])? else {
+        return None;
+    };
+    let range = match around {
+        true => {
+            let blanks = chars[close + 1..]
+                .iter()
+                .take_while(|c| c.is_whitespace())
+                .count();
+            open..close + 1 + blanks
+        }
+        false => open + 1..close,
+    };
+    let offset = rope.line_to_char(cursor.y);
+    Some(offset + range.start..offset + range.end)
+}
+
+/// Block between the brackets enclosing the cursor, which may be on one of them, with
+/// the brackets when `around`
+fn bracket_object(
+    rope: &Rope,
+    at: usize,
+    open: char,
+    close: char,
+    around: bool,
+) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut depth = 0;
+    let mut chars = rope.chars_at((at + 1).min(rope.len_chars()));
+    let mut i = at + 1;
+    while let Some(c) = chars.prev() {
+        i -= 1;
+        if c == close && i != at {
+            depth += 1;
+        } else if c == open {
+            if depth == 0 {
+                start = Some(i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let start = start?;
+    let mut depth = 0;
+    let mut end = None;
+    for (i, c) in rope.chars_at(start + 1).enumerate() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            if depth == 0 {
+                end = Some(start + 1 + i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let end = end?;
+    match around {
+        true => Some(start..end + 1),
+        false => Some(start + 1..end),
+    }
+}
+
+/// Paragraph of the cursor line, or the blank lines around it, with the blank lines
+/// after it when `around`
+fn paragraph_object(rope: &Rope, line: usize, around: bool) -> Option<Target> {
+    let blank = |i: usize| rope.line(i).chars().all(char::is_whitespace);
+    let last = last_line(rope);
+    let (start, mut end) = match paragraph(rope, line) {
+        Some((start, end)) => (start, end.min(last)),
+        None => {
+            let start = (0..line).rev().find(|i| !blank(*i)).map_or(0, |i| i + 1);
+            let end = (line + 1..=last)
+                .find(|i| !blank(*i))
+                .map_or(last, |i| i - 1);
+            (start, end)
+        }
+    };
+    if around {
+        end = (end + 1..=last)
+            .find(|i| blank(*i) != blank(end + 1))
+            .map_or(last, |i| i - 1)
+            .max(end);
+    }
+    Some(Target::Lines(start, end))
+}
@@ -1,25 +1,217 @@
 use std::cell::Cell;
 
-use crate::{State, Widget};
+use crossterm::event::{KeyEvent, MouseEvent, MouseEventKind};
+
+use crate::{
+    State, Widget,
+    state::{
+        ActiveCursor, FileId, Transaction,
+        session::{Session, SessionPane},
+    },
+    widgets::{Action, Pane},
+};
 
 use ratatui::prelude::*;
+use ropey::Rope;
 
 /// Group of editor panes
 #[derive(Debug)]
 pub struct Panes {
     area: Cell<Rect>,
+    panes: Vec<Pane>,
+    /// Index of the focused pane
+    focused: usize,
 }
 
 impl Panes {
     pub fn new() -> Self {
         Self {
             area: Cell::new(Rect::default()),
+            panes: vec![],
+            focused: 0,
+        }
+    }
+
+    /// Open a file in the focused pane
+    pub fn open(&mut self, file: FileId) {
+        match self.panes.get_mut(self.focused) {
+            Some(pane) if pane.file == file => {}
+            Some(pane) => *pane = Pane::new(file),
+            None => {
+                self.panes.push(Pane::new(file));
+                self.focused = self.panes.len() - 1;
+            }
+        }
+    }
+
+    /// Open a file in a new pane to the right of the focused one,
+    /// or the file of the focused pane if none is given
+    pub fn split(&mut self, file: Option<FileId>) {
+        let Some(focused) = self.focused() else {
+            if let Some(file) = file {
+                self.open(file);
+            }
+            return;
+        };
+        let mut pane = Pane::new(file.unwrap_or(focused.file));
+        // The new pane keeps the local options of the focused one
+        pane.local = focused.local;
+        if file.is_none() {
+            pane.cursor = focused.cursor.clone();
+        }
+        self.focused += 1;
+        self.panes.insert(self.focused, pane);
+    }
+
+    /// Open a file in a new pane to the right of the focused one, which keeps the focus
+    pub fn split_behind(&mut self, file: FileId) {
+        self.split(Some(file));
+        self.focused = self.focused.saturating_sub(1);
+    }
+
+    /// Close the focused pane, focusing the one before it
+    pub fn close_focused(&mut self) {
+        if self.focused >= self.panes.len() {
+            return;
+        }
+        self.panes.remove(self.focused);
+        self.focused = self.focused.saturating_sub(1);
+    }
+
+    pub fn len(&self) -> usize {
+        self.panes.len()
+    }
+
+    /// Number of panes displaying a file
+    pub fn count_file(&self, file: FileId) -> usize {
+        self.panes.iter().filter(|pane| pane.file == file).count()
+    }
+
+    pub fn focused(&self) -> Option<&Pane> {
+        self.panes.get(self.focused)
+    }
+
+    pub fn focused_mut(&mut self) -> Option<&mut Pane> {
+        self.panes.get_mut(self.focused)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.panes.is_empty()
+    }
+
+    /// Index of the focused pane, from the left
+    pub fn focused_index(&self) -> usize {
+        self.focused
+    }
+
+    /// Focus a pane by index, from the left
+    pub fn focus(&mut self, index: usize) {
+        self.focused = index.min(self.panes.len().saturating_sub(1));
+    }
+
+    /// Session of the panes showing files, with their view. The buffers without a file,
+    /// like pages and scratch buffers, are left out.
+    pub fn session(&self, state: &State) -> Session {
+        let mut session = Session::default();
+        for (i, pane) in self.panes.iter().enumerate() {
+            let Some(file) = state.filesystem.files.get(pane.file) else {
+                continue;
+            };
+            if file.path.as_os_str().is_empty() {
+                continue;
+            }
+            if i == self.focused {
+                session.focused = session.panes.len();
+            }
+            let (cursor, scroll_y) = pane.view();
+            session.panes.push(SessionPane {
+                path: file.path.clone(),
+                scroll_y,
+                line: cursor.y,
+                col: cursor.x,
+            });
+        }
+        session
+    }
+
+    /// Clamp the cursors of all panes, after buffers have been modified externally
+    pub fn clamp_cursors(&mut self, state: &State) {
+        for pane in &mut self.panes {
+            if let Some(rope) = state
+                .filesystem
+                .files
+                .get(pane.file)
+                .and_then(|file| file.buffer.as_ref())
+            {
+                pane.cursor.clamp(rope);
+            }
+        }
+    }
+
+    /// Keep the cursors of the other panes showing a buffer on the same text after it changed,
+    /// and the closed folds of all of them. The focused pane moves its own cursor while editing.
+    pub fn follow_changes(&mut self, file: FileId, transaction: &Transaction, rope: &Rope) {
+        let mut before = rope.clone();
+        transaction.revert(&mut before);
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            if pane.file != file {
+                continue;
+            }
+            pane.follow_folds(transaction, &before, rope);
+            if i != self.focused {
+                let index = transaction.map(pane.cursor.cursor_char_index(&before));
+                pane.cursor.move_to_char(rope, index);
+                pane.cursor.clamp(rope);
+            }
+        }
+    }
+
+    pub fn handle_key_event(&mut self, key_event: KeyEvent, state: &mut State) -> Option<Action> {
+        let pane = self.focused_mut()?;
+        let line = pane.cursor.y;
+        let action = pane.handle_key_event(key_event, state);
+        pane.leave_folds(line, state);
+        action
+    }
+
+    /// Clicks focus the pane under the mouse, and scrolling scrolls it without focusing it
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, state: &mut State) {
+        let position = Position::new(mouse_event.column, mouse_event.row);
+        let hovered = self.panes.iter().position(|pane| pane.contains(position));
+        let index = match (mouse_event.kind, hovered) {
+            (MouseEventKind::Down(_), Some(index)) => {
+                self.focused = index;
+                index
+            }
+            (MouseEventKind::ScrollUp | MouseEventKind::ScrollDown, Some(index)) => index,
+            _ => self.focused,
+        };
+        if let Some(pane) = self.panes.get_mut(index) {
+            pane.handle_mouse_event(mouse_event, state);
         }
     }
 }
 
 impl Widget for Panes {
-    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {}
+    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        self.area.set(area);
+        state
+            .active_cursor
+            .set(self.focused().map(|pane| ActiveCursor {
+                file: pane.file,
+                line: pane.cursor.y,
+                column: pane.cursor.x,
+            }));
+        if self.panes.is_empty() {
+            return;
+        }
+
+        let areas = Layout::horizontal(vec![Constraint::Fill(1); self.panes.len()]).split(area);
+        for (i, (pane, area)) in self.panes.iter().zip(areas.iter()).enumerate() {
+            pane.focused.set(i == self.focused);
+            pane.render(*area, buf, state);
+        }
+    }
 
     fn contains(&self, pos: Position) -> bool {
         self.area.get().contains(pos)
@@ -0,0 +1,252 @@
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, Read, Write, stdout},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Widget as RatatuiWidget},
+};
+
+use crate::{
+    position::char_width,
+    utils::{number_digits, truncate_line},
+    widgets::filetree,
+};
+
+/// Bytes read from the start of a previewed file
+const HEAD_SIZE: u64 = 64 * 1024;
+/// Largest image sent to the terminal
+const MAX_IMAGE_SIZE: u64 = 8 * 1024 * 1024;
+/// Signature at the start of PNG files
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Assumed size of a terminal cell in pixels, to keep the aspect ratio of images
+const CELL_PIXELS: (u32, u32) = (8, 16);
+
+/// Contents of a previewed file
+#[derive(Debug)]
+enum Content {
+    /// First lines of a text file
+    Text(Vec<String>),
+    /// PNG image with its width and height in pixels
+    Image(u32, u32),
+    /// File that cannot be displayed, described by its metadata
+    Binary,
+    Error(String),
+}
+
+impl Content {
+    fn read(path: &Path) -> Self {
+        let mut head = vec![];
+        if let Err(err) =
+            fs::File::open(path).and_then(|file| file.take(HEAD_SIZE).read_to_end(&mut head))
+        {
+            return Self::Error(err.to_string());
+        }
+        if head.starts_with(PNG_SIGNATURE) && head.len() >= 24 {
+            let be = |at: usize| {
+                u32::from_be_bytes([head[at], head[at + 1], head[at + 2], head[at + 3]])
+            };
+            return Self::Image(be(16), be(20));
+        }
+        let text = match std::str::from_utf8(&head) {
+            Ok(text) => text,
+            // The head may end in the middle of a char
+            Err(err) if err.error_len().is_none() => {
+                std::str::from_utf8(&head[..err.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return Self::Binary,
+        };
+        match text.contains('\0') {
+            true => Self::Binary,
+            false => Self::Text(text.lines().map(str::to_string).collect()),
+        }
+    }
+}
+
+/// Preview of the file selected in the filetree: the head of text files, PNG images on
+/// terminals supporting the kitty graphics protocol, and the metadata of other files.
+/// Files are only read when the selection or their modification time changes.
+#[derive(Debug, Default)]
+pub struct Preview {
+    /// Previewed file and its modification time, with its contents
+    cache: RefCell<Option<(PathBuf, Option<SystemTime>, Content)>>,
+    /// Image displayed by the terminal, and the cells it covers
+    image: RefCell<Option<(PathBuf, Rect)>>,
+}
+
+impl Preview {
+    pub fn render(&self, path: &Path, area: Rect, buf: &mut Buffer, tabstop: usize) {
+        Clear.render(area, buf);
+        let block = Block::new()
+            .borders(Borders::LEFT)
+            .border_style(Style::new().dark_gray());
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [header, body] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(inner);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        Line::from(format!(" {}", name)).bold().render(header, buf);
+
+        let mtime = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let mut cache = self.cache.borrow_mut();
+        if cache
+            .as_ref()
+            .is_none_or(|(cached, time, _)| cached != path || *time != mtime)
+        {
+            *cache = Some((path.to_path_buf(), mtime, Content::read(path)));
+        }
+        let Some((_, _, content)) = cache.as_ref() else {
+            return;
+        };
+
+        let width = body.width as usize;
+        let lines = match content {
+            Content::Text(lines) => {
+                let digits = number_digits(lines.len());
+                lines
+                    .iter()
+                    .take(body.height as usize)
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let number = Span::raw(format!(" {:>1$} ", i + 1, digits)).dark_gray();
+                        Line::from(vec![number, Span::raw(expand_tabs(line, tabstop))])
+                    })
+                    .collect()
+            }
+            Content::Image(width, height)
+                if kitty_graphics()
+                    && fs::metadata(path)
+                        .is_ok_and(|metadata| metadata.len() <= MAX_IMAGE_SIZE) =>
+            {
+                self.show_image(path, fit_image(*width, *height, body));
+                return;
+            }
+            Content::Image(width, height) => {
+                let mut lines = filetree::details(path);
+                lines.push(Line::from(format!(" {}x{} PNG image", width, height)));
+                lines.push(
+                    Line::from(" Image previews need the kitty graphics protocol").dark_gray(),
+                );
+                lines
+            }
+            Content::Binary => {
+                let mut lines = filetree::details(path);
+                lines.push(Line::from(" Binary file").dark_gray());
+                lines
+            }
+            Content::Error(err) => vec![Line::from(format!(" {}", err)).red()],
+        };
+        self.hide();
+        let lines = lines
+            .into_iter()
+            .map(|line| truncate_line(line, width))
+            .collect::<Vec<_>>();
+        Text::from(lines).render(body, buf);
+    }
+
+    /// Remove the image displayed by the terminal, if any
+    pub fn hide(&self) {
+        if self.image.borrow_mut().take().is_some()
+            && let Err(err) =
+                write!(stdout(), "\x1b_Ga=d,q=2\x1b\\").and_then(|()| stdout().flush())
+        {
+            log::error!("Failed to remove the preview image: {}", err);
+        }
+    }
+
+    /// Display an image in a cell area, unless it is already displayed there
+    fn show_image(&self, path: &Path, area: Rect) {
+        let shown = Some((path.to_path_buf(), area));
+        if *self.image.borrow() == shown {
+            return;
+        }
+        self.hide();
+        match transmit(path, area) {
+            Ok(()) => *self.image.borrow_mut() = shown,
+            Err(err) => log::error!("Failed to display {}: {}", path.display(), err),
+        }
+    }
+}
+
+/// Whether the terminal likely supports the kitty graphics protocol.
+/// Querying the terminal would race with the input event stream.
+fn kitty_graphics() -> bool {
+    let term = env::var("TERM").unwrap_or_default();
+    env::var_os("KITTY_WINDOW_ID").is_some()
+        || ["kitty", "ghostty"].iter().any(|name| term.contains(name))
+        || env::var("TERM_PROGRAM").is_ok_and(|program| program == "WezTerm")
+}
+
+/// Cells covered by an image of some pixel size, shrunk to fit in an area
+fn fit_image(width: u32, height: u32, area: Rect) -> Rect {
+    let cols = width.div_ceil(CELL_PIXELS.0).max(1) as f64;
+    let rows = height.div_ceil(CELL_PIXELS.1).max(1) as f64;
+    let scale = (area.width as f64 / cols)
+        .min(area.height as f64 / rows)
+        .min(1.0);
+    let cols = ((cols * scale) as u16).clamp(1, area.width.max(1));
+    let rows = ((rows * scale) as u16).clamp(1, area.height.max(1));
+    Rect::new(area.x, area.y, cols, rows)
+}
+
+/// Send a PNG file to the terminal with the kitty graphics protocol, scaled to an area.
+/// Its data is split in chunks of 4096 bytes.
+fn transmit(path: &Path, area: Rect) -> io::Result<()> {
+    let data = base64(&fs::read(path)?);
+    let chunks = data.as_bytes().chunks(4096).collect::<Vec<_>>();
+    let mut out = stdout().lock();
+    write!(out, "\x1b[{};{}H", area.y + 1, area.x + 1)?;
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        match i {
+            // Responses are suppressed so that they are not read as typed keys
+            0 => write!(
+                out,
+                "\x1b_Gf=100,a=T,q=2,C=1,c={},r={},m={};",
+                area.width, area.height, more
+            )?,
+            _ => write!(out, "\x1b_Gm={};", more)?,
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    out.flush()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+fn expand_tabs(line: &str, tabstop: usize) -> String {
+    let mut cell = 0;
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        let cells = char_width(c, cell, tabstop);
+        match c {
+            '\t' => expanded.push_str(&" ".repeat(cells)),
+            c => expanded.push(c),
+        }
+        cell += cells;
+    }
+    expanded
+}
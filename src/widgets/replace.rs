@@ -0,0 +1,352 @@
+use std::{cell::Cell, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent, MouseEventKind};
+use ratatui::layout::Flex;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, BorderType, Clear, Paragraph, Widget as RatatuiWidget};
+
+use crate::state::grep::{self, FileMatches};
+use crate::widgets::Input;
+use crate::{State, Widget};
+
+/// Focused part of the replace panel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    #[default]
+    Find,
+    Replace,
+    Results,
+}
+
+/// Row of the results list
+#[derive(Debug, Clone, Copy)]
+enum Row {
+    /// File header, with the file index
+    File(usize),
+    /// Single match, with the file and match indexes
+    Match(usize, usize),
+}
+
+/// Project-wide find & replace panel
+#[derive(Debug, Default)]
+pub struct ProjectReplace {
+    pub open: bool,
+    find: Input,
+    replace: Input,
+    field: Field,
+
+    /// Pattern of the current results
+    pattern: String,
+    results: Vec<FileMatches>,
+    /// Id of the latest search, to discard outdated background results
+    search_id: usize,
+    searching: bool,
+
+    /// Selected row in the results list
+    selected: usize,
+    scroll_y: Cell<usize>,
+}
+
+impl ProjectReplace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open the panel, optionally starting a search right away
+    pub fn open(&mut self, pattern: &str, state: &mut State) {
+        self.open = true;
+        if pattern.is_empty() {
+            self.field = Field::Find;
+        } else {
+            self.find.set_text(pattern);
+            self.search(state);
+        }
+    }
+
+    /// Handle a key event while the panel is open.
+    /// Returns a match location to jump to, if any.
+    pub fn handle_key_event(
+        &mut self,
+        key_event: KeyEvent,
+        state: &mut State,
+    ) -> Option<(PathBuf, usize, usize)> {
+        match (key_event.code, self.field) {
+            (KeyCode::Esc, _) => self.open = false,
+            (KeyCode::Tab, _) => {
+                self.field = match self.field {
+                    Field::Find => Field::Replace,
+                    Field::Replace => Field::Results,
+                    Field::Results => Field::Find,
+                }
+            }
+            (KeyCode::BackTab, _) => {
+                self.field = match self.field {
+                    Field::Find => Field::Results,
+                    Field::Replace => Field::Find,
+                    Field::Results => Field::Replace,
+                }
+            }
+            (KeyCode::Enter, Field::Find | Field::Replace) => self.search(state),
+            (_, Field::Find) => {
+                self.find.handle_key_event(key_event);
+            }
+            (_, Field::Replace) => {
+                self.replace.handle_key_event(key_event);
+            }
+            (KeyCode::Char('j') | KeyCode::Down, Field::Results)
+                if self.selected + 1 < self.rows().len() =>
+            {
+                self.selected += 1
+            }
+            (KeyCode::Char('k') | KeyCode::Up, Field::Results) => {
+                self.selected = self.selected.saturating_sub(1)
+            }
+            (KeyCode::Char(' '), Field::Results) => self.toggle(),
+            (KeyCode::Char('r'), Field::Results) => self.apply(state),
+            (KeyCode::Enter, Field::Results) => {
+                return match self.rows().get(self.selected)? {
+                    Row::File(file) => Some((self.results[*file].path.clone(), 0, 0)),
+                    Row::Match(file, index) => {
+                        let m = &self.results[*file].matches[*index];
+                        Some((self.results[*file].path.clone(), m.line, m.start))
+                    }
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Scroll the results with the mouse wheel
+    pub fn handle_mouse_event(&mut self, mouse_event: MouseEvent, state: &State) {
+        let tick = state.config.scroll_tick;
+        match mouse_event.kind {
+            MouseEventKind::ScrollUp => self.selected = self.selected.saturating_sub(tick),
+            MouseEventKind::ScrollDown => {
+                let last = self.rows().len().saturating_sub(1);
+                self.selected = (self.selected + tick).min(last);
+            }
+            _ => return,
+        }
+        self.field = Field::Results;
+    }
+
+    /// Receive the background search results
+    pub fn add_results(&mut self, id: usize, results: Vec<FileMatches>) {
+        if id != self.search_id {
+            return;
+        }
+        self.results.extend(results);
+        self.results.sort_by(|a, b| a.path.cmp(&b.path));
+        self.searching = false;
+    }
+
+    /// Start a new search from the find input
+    fn search(&mut self, state: &mut State) {
+        self.search_id += 1;
+        self.pattern = self.find.text();
+        self.selected = 0;
+        self.field = Field::Results;
+        if self.pattern.is_empty() {
+            self.results.clear();
+            self.searching = false;
+            return;
+        }
+        self.results = grep::search_project(
+            &state.filesystem,
+            state.events.editor_sender.clone(),
+            &state.jobs,
+            &self.pattern,
+            self.search_id,
+        );
+        self.searching = true;
+    }
+
+    /// Toggle the selected match, or all matches of the selected file
+    fn toggle(&mut self) {
+        match self.rows().get(self.selected) {
+            Some(Row::File(file)) => {
+                let matches = &mut self.results[*file].matches;
+                let selected = !matches.iter().all(|m| m.selected);
+                matches.iter_mut().for_each(|m| m.selected = selected);
+            }
+            Some(Row::Match(file, index)) => {
+                let m = &mut self.results[*file].matches[*index];
+                m.selected = !m.selected;
+            }
+            None => {}
+        }
+    }
+
+    /// Apply all selected replacements
+    fn apply(&mut self, state: &mut State) {
+        if self.searching {
+            state.error("Project search still running");
+            return;
+        }
+        let replacement = self.replace.text();
+        let (files, count, failed) = grep::replace_project(
+            &mut state.filesystem,
+            &self.results,
+            &self.pattern,
+            &replacement,
+        );
+        let text = format!("Replaced {} matches in {} files", count, files);
+        if failed.is_empty() {
+            state.info(text);
+            self.results.clear();
+            self.open = false;
+        } else {
+            // The failed files stay listed, to retry them
+            let paths = failed
+                .iter()
+                .map(|path| state.filesystem.relative_path(path).display().to_string())
+                .collect::<Vec<_>>();
+            state.error(format!("{}, failed to write {}", text, paths.join(", ")));
+            self.results.retain(|file| failed.contains(&file.path));
+        }
+        self.selected = 0;
+    }
+
+    /// Flatten the results into rows
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for (file, matches) in self.results.iter().enumerate() {
+            rows.push(Row::File(file));
+            rows.extend((0..matches.matches.len()).map(|index| Row::Match(file, index)));
+        }
+        rows
+    }
+
+    fn row_line(&self, row: Row, state: &State) -> Line<'_> {
+        match row {
+            Row::File(file) => {
+                let file = &self.results[file];
+                let path = state.filesystem.relative_path(&file.path);
+                Line::from(vec![
+                    Span::raw(path.display().to_string()).blue().bold(),
+                    Span::raw(format!(" ({})", file.matches.len())).dark_gray(),
+                ])
+            }
+            Row::Match(file, index) => {
+                let m = &self.results[file].matches[index];
+                let chars = m.text.chars().collect::<Vec<_>>();
+                let before = chars[..m.start].iter().collect::<String>();
+                let found = chars[m.start..m.end].iter().collect::<String>();
+                let after = chars[m.end..].iter().collect::<String>();
+
+                let mut spans = vec![
+                    Span::raw(if m.selected { "  [x] " } else { "  [ ] " }),
+                    Span::raw(format!("{}: ", m.line + 1)).dark_gray(),
+                    Span::raw(before.trim_start().to_string()),
+                ];
+                if m.selected {
+                    spans.push(Span::raw(found).red().crossed_out());
+                    spans.push(Span::raw(self.replace.text()).green());
+                } else {
+                    spans.push(Span::raw(found).yellow());
+                }
+                spans.push(Span::raw(after));
+                Line::from(spans)
+            }
+        }
+    }
+}
+
+impl Widget for ProjectReplace {
+    fn render(&self, area: Rect, buf: &mut Buffer, state: &State) {
+        let [popup] = Layout::vertical([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Constraint::Percentage(80)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().magenta())
+            .title_alignment(HorizontalAlignment::Center)
+            .title(" Replace in project ")
+            .title_bottom(" <Tab> switch  <Space> toggle  <r> replace  <Enter> open ");
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+
+        let [find, replace, status, results] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+
+        let label = |text, field| {
+            let style = if self.field == field {
+                Style::default().cyan().bold()
+            } else {
+                Style::default().dark_gray()
+            };
+            Span::styled(text, style)
+        };
+        // The labels take 10 cells, and the cursor one more after the text
+        let width = (find.width as usize).saturating_sub(10 + 1);
+        let (find_text, find_cursor) = self.find.view(width);
+        let (replace_text, replace_cursor) = self.replace.view(width);
+        Line::from(vec![label(" Find:    ", Field::Find), find_text]).render(find, buf);
+        Line::from(vec![label(" Replace: ", Field::Replace), replace_text]).render(replace, buf);
+
+        let count = self
+            .results
+            .iter()
+            .map(|file| file.matches.len())
+            .sum::<usize>();
+        let status_text = if self.searching {
+            format!(" Searching... {} matches so far", count)
+        } else {
+            format!(" {} matches in {} files", count, self.results.len())
+        };
+        Span::raw(status_text).dark_gray().render(status, buf);
+
+        // Keep the selected row in view
+        let height = results.height as usize;
+        if self.selected < self.scroll_y.get() {
+            self.scroll_y.set(self.selected);
+        } else if self.selected >= self.scroll_y.get() + height {
+            self.scroll_y.set(self.selected + 1 - height);
+        }
+
+        let lines = self
+            .rows()
+            .into_iter()
+            .enumerate()
+            .skip(self.scroll_y.get())
+            .take(height)
+            .map(|(i, row)| {
+                let line = self.row_line(row, state);
+                if i == self.selected && self.field == Field::Results {
+                    line.on_dark_gray()
+                } else {
+                    line
+                }
+            })
+            .collect::<Vec<_>>();
+        Paragraph::new(lines).render(results, buf);
+
+        // The cursor is placed in the focused input
+        let input = match self.field {
+            Field::Find => Some((find_cursor, find)),
+            Field::Replace => Some((replace_cursor, replace)),
+            Field::Results => None,
+        };
+        if let Some((cursor, area)) = input {
+            state
+                .cursor_pos
+                .set(Position::new(area.left() + 10 + cursor, area.top()));
+        }
+    }
+
+    /// The panel is a popup that is click-agnostic
+    fn contains(&self, _: Position) -> bool {
+        false
+    }
+}